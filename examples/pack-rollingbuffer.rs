@@ -33,6 +33,7 @@ fn main() {
         encoding: MSDataEncoding::Steim2,
         rec_len: 256,
         extra_headers: None,
+        byte_order: None,
     };
 
     // A simple record handler callback function that parses and prints records
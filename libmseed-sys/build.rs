@@ -67,15 +67,19 @@ fn main() {
         .allowlist_var("HPT.*")
         .allowlist_var("DE_.*")
         .allowlist_var("MSF_.*")
+        .allowlist_var("MSSWAP_.*")
         .allowlist_var("NSTERROR")
         .allowlist_var("NSTMODULUS")
+        .allowlist_var("NSTUNSET")
         .allowlist_var("LM_SIDLEN")
+        .allowlist_var("libmseed_memory")
         .allowlist_function("ms_.*")
         .allowlist_function("msr_.*")
         .allowlist_function("ms3_.*")
         .allowlist_function("mst_.*")
         .allowlist_function("mstl3_.*")
         .allowlist_function("msr3_.*")
+        .allowlist_function("mseh_.*")
         .generate()
         .expect("Unable to generate bindings");
 
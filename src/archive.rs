@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use std::ffi::c_int;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{MSControlFlags, MSError, MSRecord, MSResult, MSWriter};
+
+/// Default maximum number of files kept open by a [`MSArchiveWriter`].
+const DEFAULT_MAX_OPEN_FILES: usize = 50;
+/// Default duration after which idle files are closed by a [`MSArchiveWriter`].
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Element of a parsed archive path template.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Literal text.
+    Literal(String),
+    /// `%n`: network code.
+    Network,
+    /// `%s`: station code.
+    Station,
+    /// `%l`: location code.
+    Location,
+    /// `%c`: channel code.
+    Channel,
+    /// `%Y`: year, four digits.
+    Year,
+    /// `%y`: year, two digits.
+    YearShort,
+    /// `%j`: day of year, three digits.
+    DayOfYear,
+    /// `%H`: hour, two digits.
+    Hour,
+    /// `%M`: minute, two digits.
+    Minute,
+    /// `%S`: second, two digits.
+    Second,
+    /// `%F`: fractional seconds, four digits.
+    Fraction,
+    /// `%v`: publication version.
+    PubVersion,
+    /// `%q`: SEED 2.x data quality indicator.
+    Quality,
+    /// `%L`: record length.
+    RecordLength,
+    /// `%r`: sample rate rounded to an integer.
+    SampleRate,
+    /// `%R`: sample rate with six decimal places.
+    SampleRateFull,
+}
+
+/// Parses an archive path template into tokens.
+fn parse_template(template: &str) -> MSResult<Vec<Token>> {
+    let mut rv = Vec::new();
+    let mut literal = String::new();
+
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            literal.push(c);
+            continue;
+        }
+
+        let token = match chars.next() {
+            Some('%') => {
+                literal.push('%');
+                continue;
+            }
+            Some('n') => Token::Network,
+            Some('s') => Token::Station,
+            Some('l') => Token::Location,
+            Some('c') => Token::Channel,
+            Some('Y') => Token::Year,
+            Some('y') => Token::YearShort,
+            Some('j') => Token::DayOfYear,
+            Some('H') => Token::Hour,
+            Some('M') => Token::Minute,
+            Some('S') => Token::Second,
+            Some('F') => Token::Fraction,
+            Some('v') => Token::PubVersion,
+            Some('q') => Token::Quality,
+            Some('L') => Token::RecordLength,
+            Some('r') => Token::SampleRate,
+            Some('R') => Token::SampleRateFull,
+            Some(other) => {
+                return Err(MSError::from_str(&format!(
+                    "invalid archive template token: %{}",
+                    other
+                )))
+            }
+            None => return Err(MSError::from_str("incomplete archive template token")),
+        };
+
+        if !literal.is_empty() {
+            rv.push(Token::Literal(mem::take(&mut literal)));
+        }
+        rv.push(token);
+    }
+
+    if !literal.is_empty() {
+        rv.push(Token::Literal(literal));
+    }
+
+    Ok(rv)
+}
+
+/// An open file of a [`MSArchiveWriter`].
+#[derive(Debug)]
+struct OpenFile {
+    writer: MSWriter<BufWriter<File>>,
+    last_write: Instant,
+}
+
+/// A miniSEED archive writer routing records to files selected by a path template.
+///
+/// The path template may contain the following tokens (as known from libmseed's `dataselect`
+/// and `msrouter`) which are replaced by the corresponding values of the record written:
+///
+/// - `%n`: network code
+/// - `%s`: station code
+/// - `%l`: location code
+/// - `%c`: channel code
+/// - `%Y`: year, four digits
+/// - `%y`: year, two digits
+/// - `%j`: day of year, three digits
+/// - `%H`: hour, two digits
+/// - `%M`: minute, two digits
+/// - `%S`: second, two digits
+/// - `%F`: fractional seconds, four digits
+/// - `%v`: publication version
+/// - `%q`: SEED 2.x data quality indicator (i.e. `R`, `D`, `Q` or `M`), derived from the
+///   publication version
+/// - `%L`: record length
+/// - `%r`: sample rate rounded to an integer
+/// - `%R`: sample rate with six decimal places
+/// - `%%`: a literal `%`
+///
+/// Files are opened in append mode and directories are created as required. The writer keeps a
+/// bounded pool of open files: if the pool is exhausted, the least recently written file is
+/// closed. Files which were not written to for longer than the idle timeout are closed, as well.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use mseed::{MSArchiveWriter, MSControlFlags, MSReader};
+///
+/// let mut writer = MSArchiveWriter::new("archive/%Y/%j/%n.%s.%l.%c.%Y.%j").unwrap();
+/// writer.set_max_open_files(10);
+/// writer.set_idle_timeout(Duration::from_secs(60));
+///
+/// let mut reader = MSReader::new_with_flags("path/to/data.mseed", MSControlFlags::MSF_UNPACKDATA).unwrap();
+/// while let Some(msr) = reader.next() {
+///     let msr = msr.unwrap();
+///     writer.write_record(&msr, MSControlFlags::MSF_FLUSHDATA).unwrap();
+/// }
+///
+/// writer.close_all().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MSArchiveWriter {
+    template: Vec<Token>,
+    max_open_files: usize,
+    idle_timeout: Duration,
+    files: HashMap<PathBuf, OpenFile>,
+}
+
+impl MSArchiveWriter {
+    /// Creates a new `MSArchiveWriter` from the path template `template`.
+    pub fn new(template: &str) -> MSResult<Self> {
+        Ok(Self {
+            template: parse_template(template)?,
+            max_open_files: DEFAULT_MAX_OPEN_FILES,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            files: HashMap::new(),
+        })
+    }
+
+    /// Sets the maximum number of files kept open.
+    ///
+    /// At least one file is kept open.
+    pub fn set_max_open_files(&mut self, max_open_files: usize) {
+        self.max_open_files = max_open_files.max(1);
+    }
+
+    /// Sets the duration after which idle files are closed.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    /// Returns the number of files currently open.
+    pub fn num_open_files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns the path of the file `msr` is written to.
+    pub fn path(&self, msr: &MSRecord) -> MSResult<PathBuf> {
+        let start_time = msr.start_time()?;
+
+        let mut rv = String::new();
+        for token in &self.template {
+            let _ = match token {
+                Token::Literal(s) => write!(rv, "{}", s),
+                Token::Network => write!(rv, "{}", msr.network()?),
+                Token::Station => write!(rv, "{}", msr.station()?),
+                Token::Location => write!(rv, "{}", msr.location()?),
+                Token::Channel => write!(rv, "{}", msr.channel()?),
+                Token::Year => write!(rv, "{:04}", start_time.year()),
+                Token::YearShort => write!(rv, "{:02}", start_time.year().rem_euclid(100)),
+                Token::DayOfYear => write!(rv, "{:03}", start_time.ordinal()),
+                Token::Hour => write!(rv, "{:02}", start_time.hour()),
+                Token::Minute => write!(rv, "{:02}", start_time.minute()),
+                Token::Second => write!(rv, "{:02}", start_time.second()),
+                Token::Fraction => write!(rv, "{:04}", start_time.nanosecond() / 100_000),
+                Token::PubVersion => write!(rv, "{}", msr.pub_version()),
+                Token::Quality => write!(rv, "{}", pub_version_to_quality(msr.pub_version())),
+                Token::RecordLength => write!(rv, "{}", msr.raw().map_or(0, |rec| rec.len())),
+                Token::SampleRate => write!(rv, "{}", msr.sample_rate_hz().round() as i64),
+                Token::SampleRateFull => write!(rv, "{:.6}", msr.sample_rate_hz()),
+            };
+        }
+
+        Ok(PathBuf::from(rv))
+    }
+
+    /// Writes the miniSEED record `msr` to the file selected by the path template.
+    ///
+    /// Records are written by means of [`MSWriter::write_record()`], i.e. see there for the
+    /// `flags` honored.
+    pub fn write_record(&mut self, msr: &MSRecord, flags: MSControlFlags) -> MSResult<c_int> {
+        let path = self.path(msr)?;
+        self.close_idle()?;
+
+        if !self.files.contains_key(&path) {
+            if self.files.len() >= self.max_open_files {
+                self.close_least_recently_written()?;
+            }
+
+            let file = open(&path)?;
+            self.files.insert(
+                path.clone(),
+                OpenFile {
+                    writer: MSWriter::new(BufWriter::new(file)),
+                    last_write: Instant::now(),
+                },
+            );
+        }
+
+        let file = self.files.get_mut(&path).unwrap();
+        file.last_write = Instant::now();
+        file.writer.write_record(msr, flags)
+    }
+
+    /// Closes the files which were not written to for longer than the idle timeout.
+    pub fn close_idle(&mut self) -> MSResult<()> {
+        let idle: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|(_, f)| f.last_write.elapsed() >= self.idle_timeout)
+            .map(|(p, _)| p.clone())
+            .collect();
+
+        for p in idle {
+            self.close(&p)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes all open files.
+    pub fn flush(&mut self) -> MSResult<()> {
+        for f in self.files.values_mut() {
+            f.writer
+                .get_mut()
+                .flush()
+                .map_err(|e| MSError::from_str(&e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes and closes all open files.
+    pub fn close_all(&mut self) -> MSResult<()> {
+        let rv = self.flush();
+        self.files.clear();
+
+        rv
+    }
+
+    fn close_least_recently_written(&mut self) -> MSResult<()> {
+        let lru = self
+            .files
+            .iter()
+            .min_by_key(|(_, f)| f.last_write)
+            .map(|(p, _)| p.clone());
+
+        match lru {
+            Some(p) => self.close(&p),
+            None => Ok(()),
+        }
+    }
+
+    fn close(&mut self, path: &Path) -> MSResult<()> {
+        match self.files.remove(path) {
+            Some(f) => f
+                .writer
+                .into_inner()
+                .flush()
+                .map_err(|e| MSError::from_str(&e.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MSArchiveWriter {
+    fn drop(&mut self) {
+        let _ = self.close_all();
+    }
+}
+
+/// Opens the file at `path` for appending, creating parent directories as required.
+fn open(path: &Path) -> MSResult<File> {
+    if let Some(dir) = path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir).map_err(|e| MSError::from_str(&e.to_string()))?;
+        }
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| MSError::from_str(&e.to_string()))
+}
+
+/// Maps a publication version to the SEED 2.x data quality indicator.
+fn pub_version_to_quality(pub_version: u8) -> char {
+    match pub_version {
+        1 => 'R',
+        2 => 'D',
+        3 => 'Q',
+        4 => 'M',
+        _ => 'D',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{test, MSReader};
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            parse_template("%n/%s/%n.%s.%l.%c.%Y.%j").unwrap(),
+            vec![
+                Token::Network,
+                Token::Literal("/".to_string()),
+                Token::Station,
+                Token::Literal("/".to_string()),
+                Token::Network,
+                Token::Literal(".".to_string()),
+                Token::Station,
+                Token::Literal(".".to_string()),
+                Token::Location,
+                Token::Literal(".".to_string()),
+                Token::Channel,
+                Token::Literal(".".to_string()),
+                Token::Year,
+                Token::Literal(".".to_string()),
+                Token::DayOfYear,
+            ]
+        );
+        assert_eq!(
+            parse_template("data_%%_%q.mseed").unwrap(),
+            vec![
+                Token::Literal("data_%_".to_string()),
+                Token::Quality,
+                Token::Literal(".mseed".to_string()),
+            ]
+        );
+
+        assert!(parse_template("%n.%x").is_err());
+        assert!(parse_template("%n.%").is_err());
+    }
+
+    #[test]
+    fn test_archive_writer() {
+        let root = test::scratch_dir("archive-writer");
+
+        let template = format!("{}/%Y/%n/%s/%n.%s.%l.%c.%q.%Y.%j", root.to_str().unwrap());
+        let mut writer = MSArchiveWriter::new(&template).unwrap();
+        writer.set_max_open_files(1);
+
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut expected: HashMap<PathBuf, i64> = HashMap::new();
+        let reader = MSReader::new_with_flags(p, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        for msr in reader {
+            let msr = msr.unwrap();
+            *expected.entry(writer.path(&msr).unwrap()).or_default() += msr.sample_cnt();
+
+            writer
+                .write_record(&msr, MSControlFlags::MSF_FLUSHDATA)
+                .unwrap();
+            assert_eq!(writer.num_open_files(), 1);
+        }
+        writer.close_all().unwrap();
+        assert_eq!(writer.num_open_files(), 0);
+
+        assert_eq!(expected.len(), 3);
+        assert!(expected.contains_key(&root.join("2010/IU/COLA/IU.COLA.00.LH1.M.2010.058")));
+
+        for (path, sample_cnt) in expected {
+            let written: i64 = MSReader::new(path)
+                .unwrap()
+                .map(|msr| msr.unwrap().sample_cnt())
+                .sum();
+            assert_eq!(written, sample_cnt);
+        }
+    }
+}
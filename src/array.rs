@@ -0,0 +1,380 @@
+use ndarray::{Array1, Array2, ArrayView1};
+use time::OffsetDateTime;
+
+use crate::{
+    pack_raw, raw, util, DataSampleType, MSControlFlags, MSDataEncoding, MSError, MSResult,
+    MSSampleType, MSTraceId, MSTraceList, MSTraceSegment, PackInfo,
+};
+
+impl MSTraceSegment<'_> {
+    /// Returns a view of the data samples of the trace segment.
+    ///
+    /// The data samples are converted and unpacked on demand, see
+    /// [`data_samples()`](Self::data_samples).
+    pub fn as_array<T: DataSampleType>(&mut self) -> MSResult<ArrayView1<'_, T>> {
+        self.data_samples().map(ArrayView1::from)
+    }
+
+    /// Returns the time axis of the trace segment, i.e. the time of each sample as nanosecond
+    /// epoch.
+    pub fn time_axis(&self) -> MSResult<Array1<i64>> {
+        let start_time = util::time_to_nstime(&self.start_time()?);
+        let sample_rate = self.sample_rate_hz();
+
+        Ok(Array1::from_iter((0..self.sample_cnt()).map(|offset| {
+            util::sample_time(start_time, offset, sample_rate)
+        })))
+    }
+}
+
+impl MSTraceList {
+    /// Returns the data samples of the trace identifiers `sids` aligned on the time window from
+    /// `start` (inclusive) to `end` (exclusive).
+    ///
+    /// Each row of the array corresponds to a source identifier of `sids` and each column to a
+    /// sample of the window, i.e. the sample of column `i` is at `start + i / sample_rate`. Data
+    /// samples are aligned to the nearest sample of the window. Samples not covered by data,
+    /// including the rows of source identifiers not part of the trace list, are set to `fill`.
+    ///
+    /// All trace segments within the window are required to share a common sample rate. The data
+    /// samples are copied, i.e. the trace segments keep their sample type.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use time::format_description::well_known::Iso8601;
+    /// use time::OffsetDateTime;
+    ///
+    /// use mseed::{MSControlFlags, MSTraceList};
+    ///
+    /// let mstl = MSTraceList::read("path/to/data.mseed", MSControlFlags::MSF_UNPACKDATA).unwrap();
+    ///
+    /// let start = OffsetDateTime::parse("2010-02-27T06:50:00Z", &Iso8601::DEFAULT).unwrap();
+    /// let end = OffsetDateTime::parse("2010-02-27T07:50:00Z", &Iso8601::DEFAULT).unwrap();
+    /// let data = mstl
+    ///     .to_array2(
+    ///         &["FDSN:IU_COLA_00_L_H_1", "FDSN:IU_COLA_00_L_H_2", "FDSN:IU_COLA_00_L_H_Z"],
+    ///         start,
+    ///         end,
+    ///         f64::NAN,
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(data.nrows(), 3);
+    /// ```
+    pub fn to_array2<T>(
+        &self,
+        sids: &[&str],
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+        fill: T,
+    ) -> MSResult<Array2<T>>
+    where
+        T: DataSampleType + Copy,
+    {
+        let (window_start, window_end) = (util::time_to_nstime(&start), util::time_to_nstime(&end));
+        if window_start >= window_end {
+            return Err(MSError::from_str("empty time window"));
+        }
+
+        let in_window = |seg: &MSTraceSegment| -> MSResult<bool> {
+            Ok(util::time_to_nstime(&seg.end_time()?) >= window_start
+                && util::time_to_nstime(&seg.start_time()?) < window_end)
+        };
+
+        // determine the common sample rate
+        let mut sample_rate = None;
+        for (_, tid) in self.trace_ids(sids)? {
+            for seg in tid.iter() {
+                if !in_window(&seg)? {
+                    continue;
+                }
+
+                match sample_rate {
+                    None => sample_rate = Some(seg.sample_rate_hz()),
+                    Some(rate) if (1.0 - rate / seg.sample_rate_hz()).abs() < 0.0001 => {}
+                    Some(rate) => {
+                        return Err(MSError::from_str(&format!(
+                            "sample rates differ: {} != {}",
+                            rate,
+                            seg.sample_rate_hz()
+                        )))
+                    }
+                }
+            }
+        }
+        let sample_rate = match sample_rate {
+            Some(rate) if rate > 0.0 => rate,
+            Some(_) => return Err(MSError::from_str("invalid sample rate")),
+            None => return Err(MSError::from_str("no data within time window")),
+        };
+
+        let to_index =
+            |t: i64| ((t - window_start) as f64 * sample_rate / raw::NSTMODULUS as f64).round();
+        let num_samples = ((window_end - window_start) as f64 * sample_rate
+            / raw::NSTMODULUS as f64)
+            .ceil() as usize;
+        let sample_period = raw::NSTMODULUS as f64 / sample_rate;
+
+        let mut rv = Array2::from_elem((sids.len(), num_samples), fill);
+        for (row, tid) in self.trace_ids(sids)? {
+            for mut seg in tid.iter() {
+                if !in_window(&seg)? {
+                    continue;
+                }
+
+                let start_time = util::time_to_nstime(&seg.start_time()?);
+                let seg_sample_rate = seg.sample_rate_hz();
+                // copy only the samples within the window, including a sample of slack on either
+                // side which is rounded off the columns below
+                let (first, last) = seg.window(
+                    window_start - sample_period as i64,
+                    window_start + (num_samples as f64 * sample_period) as i64,
+                );
+                let samples = seg
+                    .samples()?
+                    .get(first as usize..last as usize)
+                    .ok_or_else(|| MSError::from_str("data samples out of range"))?;
+                let samples = T::copy_from(&samples).ok_or_else(|| {
+                    MSError::from_str(&format!(
+                        "cannot convert {:?} data samples into {:?}",
+                        seg.sample_type(),
+                        T::SAMPLE_TYPE
+                    ))
+                })?;
+                for (offset, sample) in samples.iter().enumerate() {
+                    let offset = first + offset as i64;
+                    let idx = to_index(util::sample_time(start_time, offset, seg_sample_rate));
+                    if idx >= 0.0 && (idx as usize) < num_samples {
+                        rv[[row, idx as usize]] = *sample;
+                    }
+                }
+            }
+        }
+
+        Ok(rv)
+    }
+
+    /// Returns the trace identifiers matching `sids` together with the index of the matching
+    /// source identifier.
+    fn trace_ids(&self, sids: &[&str]) -> MSResult<Vec<(usize, MSTraceId)>> {
+        let mut rv = vec![];
+        for tid in self.iter() {
+            let sid = tid.sid()?;
+            if let Some(row) = sids.iter().position(|s| *s == sid) {
+                rv.push((row, tid));
+            }
+        }
+
+        Ok(rv)
+    }
+}
+
+/// Packs the data samples of `data` into miniSEED records.
+///
+/// In contrast to [`pack_raw()`], the data samples are copied such that `data` may be
+/// non-contiguous. The sample rate is taken from [`PackInfo::sample_rate`]. The sample type of
+/// `T` is required to match the data encoding of `info`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ndarray::Array1;
+/// use time::OffsetDateTime;
+///
+/// use mseed::{self, MSControlFlags, PackInfo};
+///
+/// let mut pack_info = PackInfo::new("FDSN:XX_TEST__X_Y_Z").unwrap();
+/// pack_info.sample_rate = 100.0;
+///
+/// let data: Array1<i32> = (0..1000).collect();
+///
+/// let mut buf = vec![];
+/// mseed::pack_array(
+///     data.view(),
+///     &OffsetDateTime::now_utc(),
+///     |rec: &[u8]| buf.extend_from_slice(rec),
+///     &pack_info,
+///     MSControlFlags::MSF_FLUSHDATA,
+/// )
+/// .unwrap();
+/// ```
+pub fn pack_array<T, F>(
+    data: ArrayView1<'_, T>,
+    start_time: &OffsetDateTime,
+    record_handler: F,
+    info: &PackInfo,
+    flags: MSControlFlags,
+) -> MSResult<(usize, usize)>
+where
+    T: DataSampleType + Clone,
+    F: FnMut(&[u8]),
+{
+    let sample_type = {
+        use MSDataEncoding::*;
+        match info.encoding {
+            Text => MSSampleType::Text,
+            Integer16 | Integer32 | Steim1 | Steim2 => MSSampleType::Integer32,
+            Float32 => MSSampleType::Float32,
+            Float64 => MSSampleType::Float64,
+            _ => MSSampleType::Unknown,
+        }
+    };
+    if sample_type != T::SAMPLE_TYPE {
+        return Err(MSError::from_str(&format!(
+            "invalid data encoding for {:?} data samples: {}",
+            T::SAMPLE_TYPE,
+            info.encoding
+        )));
+    }
+
+    let mut data_samples = data.to_vec();
+    pack_raw(&mut data_samples, start_time, record_handler, info, flags)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use time::Duration;
+
+    use crate::test;
+
+    fn read_3channel() -> MSTraceList {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        MSTraceList::read(p, MSControlFlags::MSF_UNPACKDATA).unwrap()
+    }
+
+    #[test]
+    fn test_as_array() {
+        let mstl = read_3channel();
+        let tid = mstl.iter().next().unwrap();
+        let mut tseg = tid.iter().next().unwrap();
+
+        let data_samples = tseg.data_samples::<i32>().unwrap().to_vec();
+        let arr = tseg.as_array::<i32>().unwrap();
+        assert_eq!(arr.len(), 4200);
+        assert_eq!(arr.to_vec(), data_samples);
+
+        let time_axis = tseg.time_axis().unwrap();
+        assert_eq!(time_axis.len(), 4200);
+        assert_eq!(
+            time_axis[0],
+            util::time_to_nstime(&tseg.start_time().unwrap())
+        );
+        assert_eq!(
+            time_axis[4199],
+            util::time_to_nstime(&tseg.end_time().unwrap())
+        );
+        assert_eq!(time_axis[1] - time_axis[0], raw::NSTMODULUS as i64);
+    }
+
+    #[test]
+    fn test_to_array2() {
+        let mstl = read_3channel();
+        let mut samples = vec![];
+        for tid in mstl.iter() {
+            let mut tseg = tid.iter().next().unwrap();
+            samples.push(tseg.data_samples::<i32>().unwrap().to_vec());
+        }
+        let tid = mstl.iter().next().unwrap();
+        let start = tid.start_time().unwrap();
+
+        let sids = [
+            "FDSN:IU_COLA_00_L_H_Z",
+            "FDSN:IU_COLA_00_L_H_1",
+            "FDSN:XX_TEST_00_L_H_Z",
+        ];
+        let arr = mstl
+            .to_array2(&sids, start, start + Duration::seconds(4200), -1)
+            .unwrap();
+        assert_eq!(arr.dim(), (3, 4200));
+        assert_eq!(arr.row(0).to_vec(), samples[2]);
+        assert_eq!(arr.row(1).to_vec(), samples[0]);
+        assert!(arr.row(2).iter().all(|s| *s == -1));
+
+        // the window exceeds the data
+        let arr = mstl
+            .to_array2(
+                &sids[..1],
+                start - Duration::seconds(10),
+                start + Duration::seconds(100),
+                -1,
+            )
+            .unwrap();
+        assert_eq!(arr.dim(), (1, 110));
+        assert!(arr.row(0).iter().take(10).all(|s| *s == -1));
+        assert_eq!(
+            arr.row(0).iter().skip(10).copied().collect::<Vec<_>>(),
+            samples[2][..100]
+        );
+
+        assert!(mstl
+            .to_array2(&sids[2..], start, start + Duration::seconds(10), -1)
+            .is_err());
+
+        // the data samples are converted without touching the trace segments
+        let arr = mstl
+            .to_array2(&sids[..1], start, start + Duration::seconds(100), f64::NAN)
+            .unwrap();
+        assert_eq!(
+            arr.row(0).to_vec(),
+            samples[2][..100]
+                .iter()
+                .map(|&s| s as f64)
+                .collect::<Vec<_>>()
+        );
+        for tid in mstl.iter() {
+            let tseg = tid.iter().next().unwrap();
+            assert_eq!(tseg.sample_type(), MSSampleType::Integer32);
+        }
+    }
+
+    #[test]
+    fn test_pack_array() {
+        let mstl = read_3channel();
+        let tid = mstl.iter().next().unwrap();
+        let mut tseg = tid.iter().next().unwrap();
+        let start_time = tseg.start_time().unwrap();
+        let data_samples = tseg.data_samples::<i32>().unwrap().to_vec();
+
+        let mut pack_info = PackInfo::new("FDSN:XX_TEST__L_H_Z").unwrap();
+        pack_info.sample_rate = 1.0;
+        pack_info.rec_len = 512;
+
+        let data = Array1::from(data_samples.clone());
+        let mut buf = vec![];
+        let (_, cnt_samples) = pack_array(
+            data.view(),
+            &start_time,
+            |rec: &[u8]| buf.extend_from_slice(rec),
+            &pack_info,
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .unwrap();
+        assert_eq!(cnt_samples, 4200);
+
+        let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        let tid = mstl.iter().next().unwrap();
+        assert_eq!(tid.sid().unwrap(), "FDSN:XX_TEST__L_H_Z");
+        let mut tseg = tid.iter().next().unwrap();
+        assert_eq!(tseg.start_time().unwrap(), start_time);
+        assert_eq!(tseg.data_samples::<i32>().unwrap(), &data_samples[..]);
+
+        // the sample type does not match the data encoding
+        let data = Array1::from(vec![0.0f64; 10]);
+        assert!(pack_array(
+            data.view(),
+            &start_time,
+            |_: &[u8]| {},
+            &pack_info,
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,667 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::ptr;
+use std::sync::Arc;
+
+use arrow_array::{
+    Array, ArrayRef, Float32Array, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    TimestampNanosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::reader::ChunkReader;
+
+use crate::{
+    raw, util, MSControlFlags, MSError, MSRecordBuilder, MSResult, MSSampleType, MSTraceList,
+    MSTraceSegment,
+};
+
+/// Column name of the FDSN source identifiers.
+const SID: &str = "sid";
+/// Column name of the sample times in nanoseconds.
+const TIME_NS: &str = "time_ns";
+/// Column name of the data samples.
+const VALUE: &str = "value";
+/// Column name of the sample rates.
+const SAMPLE_RATE: &str = "sample_rate";
+
+/// Schema metadata key of the publication version.
+const PUB_VERSION: &str = "pub_version";
+/// Schema metadata key of the start time of a trace segment.
+const START_TIME: &str = "start_time";
+/// Schema metadata key of the end time of a trace segment.
+const END_TIME: &str = "end_time";
+/// Schema metadata key of the number of samples of a trace segment.
+const SAMPLE_CNT: &str = "sample_cnt";
+
+impl MSTraceSegment<'_> {
+    /// Converts the trace segment into an Arrow [`RecordBatch`].
+    ///
+    /// The record batch is made of the columns `time_ns`, i.e. the sample times as nanosecond
+    /// timestamps, and `value`, i.e. the data samples typed according to the trace segment's
+    /// [`sample_type()`](Self::sample_type). The segment metadata (`sid`, `pub_version`,
+    /// `sample_rate`, `start_time`, `end_time` and `sample_cnt`) is part of the schema metadata.
+    /// Data samples are copied by means of [`samples()`](Self::samples), i.e. the trace segment
+    /// keeps its sample type.
+    ///
+    /// Segments of text samples are not supported.
+    pub fn to_record_batch(&mut self) -> MSResult<RecordBatch> {
+        let data_type = value_type(self.sample_type())?;
+        let schema = Schema::new(vec![
+            time_field(),
+            Field::new(VALUE, data_type.clone(), false),
+        ])
+        .with_metadata(self.metadata()?);
+
+        let columns = vec![self.time_array()?, self.value_array(&data_type)?];
+        RecordBatch::try_new(Arc::new(schema), columns)
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+
+    /// Returns the segment metadata as schema metadata.
+    fn metadata(&self) -> MSResult<HashMap<String, String>> {
+        Ok(HashMap::from([
+            (SID.to_string(), self.trace_id().sid()?),
+            (
+                PUB_VERSION.to_string(),
+                self.trace_id().pub_version().to_string(),
+            ),
+            (SAMPLE_RATE.to_string(), self.sample_rate_hz().to_string()),
+            (
+                START_TIME.to_string(),
+                util::time_to_nstime(&self.start_time()?).to_string(),
+            ),
+            (
+                END_TIME.to_string(),
+                util::time_to_nstime(&self.end_time()?).to_string(),
+            ),
+            (SAMPLE_CNT.to_string(), self.sample_cnt().to_string()),
+        ]))
+    }
+
+    fn time_array(&self) -> MSResult<ArrayRef> {
+        let start_time = util::time_to_nstime(&self.start_time()?);
+        let sample_rate = self.sample_rate_hz();
+
+        let times: Vec<i64> = (0..self.sample_cnt())
+            .map(|offset| util::sample_time(start_time, offset, sample_rate))
+            .collect();
+        Ok(Arc::new(
+            TimestampNanosecondArray::from(times).with_timezone_utc(),
+        ))
+    }
+
+    /// Returns the data samples converted into `data_type`.
+    ///
+    /// The data samples are copied, i.e. they are unpacked on demand but not converted in place.
+    fn value_array(&mut self, data_type: &DataType) -> MSResult<ArrayRef> {
+        let sample_cnt = self.sample_cnt();
+        let samples = self.samples()?;
+        if samples.len() as i64 != sample_cnt {
+            return Err(MSError::from_str("data samples must be unpacked"));
+        }
+
+        let unsupported = || MSError::from_str("text data samples are not supported");
+        let rv: ArrayRef = match data_type {
+            DataType::Int32 => Arc::new(Int32Array::from(
+                samples.to_i32_vec().ok_or_else(unsupported)?,
+            )),
+            DataType::Float32 => Arc::new(Float32Array::from(
+                samples.to_f32_vec().ok_or_else(unsupported)?,
+            )),
+            DataType::Float64 => Arc::new(Float64Array::from(
+                samples.to_f64_vec().ok_or_else(unsupported)?,
+            )),
+            _ => {
+                return Err(MSError::from_str(&format!(
+                    "invalid data type: {}",
+                    data_type
+                )))
+            }
+        };
+
+        Ok(rv)
+    }
+}
+
+impl MSTraceList {
+    /// Converts the trace list into Arrow [`RecordBatch`]es, one per trace segment.
+    ///
+    /// See also [`MSTraceSegment::to_record_batch()`]. Segments of text samples, e.g. log
+    /// records, are skipped.
+    pub fn to_record_batches(&self) -> MSResult<Vec<RecordBatch>> {
+        let mut rv = vec![];
+        for tid in self.iter() {
+            for mut seg in tid.iter() {
+                if seg.sample_type() == MSSampleType::Text {
+                    continue;
+                }
+
+                rv.push(seg.to_record_batch()?);
+            }
+        }
+
+        Ok(rv)
+    }
+
+    /// Converts the trace list into a long table of Arrow [`RecordBatch`]es sharing common
+    /// columns.
+    ///
+    /// The table is made of the columns `sid`, `time_ns`, `value` and `sample_rate`. If all
+    /// trace segments share a common sample type, values are typed accordingly, otherwise
+    /// values are converted into `f64`. Segments of text samples, e.g. log records, are skipped.
+    ///
+    /// Each record batch corresponds to a trace segment, such that the schema metadata of a
+    /// record batch provides the segment metadata (see
+    /// [`MSTraceSegment::to_record_batch()`]). The publication version is additionally part of
+    /// the common schema metadata if shared by all trace identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mseed::{MSControlFlags, MSTraceList};
+    ///
+    /// let mstl = MSTraceList::read("path/to/data.mseed", MSControlFlags::MSF_UNPACKDATA).unwrap();
+    ///
+    /// let batches = mstl.to_long_record_batches().unwrap();
+    /// let num_rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    /// ```
+    pub fn to_long_record_batches(&self) -> MSResult<Vec<RecordBatch>> {
+        let schema = self.long_schema()?;
+        let data_type = schema.field_with_name(VALUE).unwrap().data_type().clone();
+
+        let mut rv = vec![];
+        for tid in self.iter() {
+            let sid = tid.sid()?;
+            for mut seg in tid.iter() {
+                if seg.sample_type() == MSSampleType::Text {
+                    continue;
+                }
+
+                let mut metadata = schema.metadata().clone();
+                metadata.extend(seg.metadata()?);
+                let seg_schema = Schema::new(schema.fields().clone()).with_metadata(metadata);
+
+                let num_rows = seg.sample_cnt() as usize;
+                let columns = vec![
+                    Arc::new(StringArray::from(vec![sid.as_str(); num_rows])) as ArrayRef,
+                    seg.time_array()?,
+                    seg.value_array(&data_type)?,
+                    Arc::new(Float64Array::from(vec![seg.sample_rate_hz(); num_rows])),
+                ];
+                rv.push(
+                    RecordBatch::try_new(Arc::new(seg_schema), columns)
+                        .map_err(|e| MSError::from_str(&e.to_string()))?,
+                );
+            }
+        }
+
+        Ok(rv)
+    }
+
+    /// Writes the trace list as long table (see [`MSTraceList::to_long_record_batches()`]) in
+    /// Parquet format to `writer`.
+    ///
+    /// Only the common schema metadata is written, i.e. the segment metadata is not.
+    pub fn write_parquet<W: Write + Send>(&self, writer: W) -> MSResult<()> {
+        let batches = self.to_long_record_batches()?;
+
+        let mut writer = ArrowWriter::try_new(writer, self.long_schema()?, None)
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        for batch in &batches {
+            writer
+                .write(batch)
+                .map_err(|e| MSError::from_str(&e.to_string()))?;
+        }
+        writer
+            .close()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Creates a new [`MSTraceList`] from Parquet data read from `reader`.
+    ///
+    /// See also [`MSTraceList::from_record_batches()`].
+    pub fn read_parquet<R: ChunkReader + 'static>(reader: R) -> MSResult<Self> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(reader)
+            .and_then(|builder| builder.build())
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        let batches = reader
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        Self::from_record_batches(&batches)
+    }
+
+    /// Creates a new [`MSTraceList`] from Arrow [`RecordBatch`]es.
+    ///
+    /// Both, record batches of single trace segments (see
+    /// [`MSTraceList::to_record_batches()`]) and long tables (see
+    /// [`MSTraceList::to_long_record_batches()`]) are supported. I.e. each record batch is
+    /// required to provide the columns `time_ns` and `value`, while `sid` and `sample_rate` are
+    /// either columns or part of the schema metadata. The time column is either of nanosecond
+    /// timestamp or `i64` type, while values are either of `i32`, `f32` or `f64` type.
+    ///
+    /// Consecutive rows are merged into trace segments as long as the sample times are
+    /// continuous, such that the trace list may be packed by means of
+    /// [`pack_trace_list()`](crate::pack_trace_list).
+    pub fn from_record_batches(batches: &[RecordBatch]) -> MSResult<Self> {
+        let mut rv = Self::new()?;
+        for batch in batches {
+            rv.insert_record_batch(batch)?;
+        }
+
+        Ok(rv)
+    }
+
+    fn insert_record_batch(&mut self, batch: &RecordBatch) -> MSResult<()> {
+        let schema = batch.schema();
+        let metadata = schema.metadata();
+        let num_rows = batch.num_rows();
+
+        let sids: Vec<&str> = match batch.column_by_name(SID) {
+            Some(col) => {
+                let col = downcast::<StringArray>(col, SID)?;
+                col.iter()
+                    .map(|sid| sid.ok_or_else(|| MSError::from_str("missing source identifier")))
+                    .collect::<MSResult<_>>()?
+            }
+            None => vec![metadata_value(metadata, SID)?; num_rows],
+        };
+        let sample_rates: Vec<f64> = match batch.column_by_name(SAMPLE_RATE) {
+            Some(col) => downcast::<Float64Array>(non_null(col, SAMPLE_RATE)?, SAMPLE_RATE)?
+                .values()
+                .to_vec(),
+            None => {
+                let sample_rate = metadata_value(metadata, SAMPLE_RATE)?
+                    .parse()
+                    .map_err(|_| MSError::from_str("invalid sample rate"))?;
+                vec![sample_rate; num_rows]
+            }
+        };
+        let pub_version = match metadata.get(PUB_VERSION) {
+            Some(v) => v
+                .parse()
+                .map_err(|_| MSError::from_str("invalid publication version"))?,
+            None => 1,
+        };
+
+        let times = non_null(column(batch, TIME_NS)?, TIME_NS)?;
+        let times = match times.data_type() {
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                downcast::<TimestampNanosecondArray>(times, TIME_NS)?.values()
+            }
+            _ => downcast::<Int64Array>(times, TIME_NS)?.values(),
+        };
+        let values = non_null(column(batch, VALUE)?, VALUE)?;
+
+        let mut first = 0;
+        for i in 1..=num_rows {
+            if i < num_rows
+                && sids[i] == sids[first]
+                && sample_rates[i] == sample_rates[first]
+                && is_continuous(times[first], times[i], i - first, sample_rates[first])
+            {
+                continue;
+            }
+
+            let builder = MSRecordBuilder::new()
+                .sid(sids[first])
+                .pub_version(pub_version)
+                .sample_rate(sample_rates[first])
+                .start_time(util::nstime_to_time(times[first])?);
+            let builder = match values.data_type() {
+                DataType::Int32 => builder.samples_i32(
+                    downcast::<Int32Array>(values, VALUE)?.values()[first..i].to_vec(),
+                ),
+                DataType::Float32 => builder.samples_f32(
+                    downcast::<Float32Array>(values, VALUE)?.values()[first..i].to_vec(),
+                ),
+                DataType::Float64 => builder.samples_f64(
+                    downcast::<Float64Array>(values, VALUE)?.values()[first..i].to_vec(),
+                ),
+                data_type => {
+                    return Err(MSError::from_str(&format!(
+                        "invalid data type: {}",
+                        data_type
+                    )))
+                }
+            };
+
+            let mut msr = builder.build()?;
+            let seg = unsafe {
+                raw::mstl3_addmsr_recordptr(
+                    self.get_raw_mut(),
+                    msr.get_raw_mut(),
+                    ptr::null_mut(),
+                    0,
+                    1,
+                    MSControlFlags::empty().bits(),
+                    ptr::null_mut(),
+                )
+            };
+            if seg.is_null() {
+                return Err(MSError::from_str("failed to insert trace segment"));
+            }
+
+            first = i;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the schema of the long table representation of the trace list.
+    ///
+    /// The schema metadata provides the publication version if shared by all trace identifiers.
+    fn long_schema(&self) -> MSResult<SchemaRef> {
+        let mut sample_type = None;
+        let mut pub_version = None;
+        for tid in self.iter() {
+            for seg in tid.iter() {
+                match (seg.sample_type(), sample_type) {
+                    (MSSampleType::Text, _) => continue,
+                    (t, None) => sample_type = Some(t),
+                    (t, Some(prev)) if t != prev => sample_type = Some(MSSampleType::Float64),
+                    _ => {}
+                }
+
+                pub_version = match pub_version {
+                    None => Some(Some(tid.pub_version())),
+                    Some(Some(v)) if v != tid.pub_version() => Some(None),
+                    v => v,
+                };
+            }
+        }
+        let data_type = value_type(sample_type.unwrap_or(MSSampleType::Float64))?;
+
+        let mut metadata = HashMap::new();
+        if let Some(Some(pub_version)) = pub_version {
+            metadata.insert(PUB_VERSION.to_string(), pub_version.to_string());
+        }
+
+        Ok(Arc::new(
+            Schema::new(vec![
+                Field::new(SID, DataType::Utf8, false),
+                time_field(),
+                Field::new(VALUE, data_type, false),
+                Field::new(SAMPLE_RATE, DataType::Float64, false),
+            ])
+            .with_metadata(metadata),
+        ))
+    }
+}
+
+fn time_field() -> Field {
+    Field::new(
+        TIME_NS,
+        DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+        false,
+    )
+}
+
+/// Returns the Arrow data type corresponding to `sample_type`.
+fn value_type(sample_type: MSSampleType) -> MSResult<DataType> {
+    match sample_type {
+        MSSampleType::Integer32 => Ok(DataType::Int32),
+        MSSampleType::Float32 => Ok(DataType::Float32),
+        MSSampleType::Float64 => Ok(DataType::Float64),
+        _ => Err(MSError::from_str(&format!(
+            "unsupported sample type: {:?}",
+            sample_type
+        ))),
+    }
+}
+
+/// Checks whether the sample at `offset` relative to the sample at `start` is at the time
+/// expected, within a tolerance of half a sample period.
+fn is_continuous(start: i64, time: i64, offset: usize, sample_rate: f64) -> bool {
+    if sample_rate <= 0.0 {
+        return false;
+    }
+
+    let expected = util::sample_time(start, offset as _, sample_rate);
+    ((time - expected).abs() as f64) <= 0.5 * raw::NSTMODULUS as f64 / sample_rate
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> MSResult<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| MSError::from_str(&format!("missing column: {}", name)))
+}
+
+fn non_null<'a>(col: &'a ArrayRef, name: &str) -> MSResult<&'a ArrayRef> {
+    if col.null_count() > 0 {
+        return Err(MSError::from_str(&format!(
+            "null values in column: {}",
+            name
+        )));
+    }
+
+    Ok(col)
+}
+
+fn downcast<'a, T: 'static>(col: &'a ArrayRef, name: &str) -> MSResult<&'a T> {
+    col.as_any().downcast_ref::<T>().ok_or_else(|| {
+        MSError::from_str(&format!(
+            "invalid data type of column {}: {}",
+            name,
+            col.data_type()
+        ))
+    })
+}
+
+fn metadata_value<'a>(metadata: &'a HashMap<String, String>, key: &str) -> MSResult<&'a str> {
+    metadata
+        .get(key)
+        .map(|v| v.as_str())
+        .ok_or_else(|| MSError::from_str(&format!("missing column or metadata: {}", key)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs::File;
+
+    use pretty_assertions::assert_eq;
+    use time::OffsetDateTime;
+
+    use crate::test;
+
+    fn read_mstl(f: &str) -> MSTraceList {
+        read_mstl_with_flags(f, MSControlFlags::MSF_UNPACKDATA)
+    }
+
+    fn read_mstl_with_flags(f: &str, flags: MSControlFlags) -> MSTraceList {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push(f);
+
+        MSTraceList::read(p, flags).unwrap()
+    }
+
+    /// Asserts that the trace lists `a` and `b` are made of the same trace segments.
+    fn assert_same_segments(a: &MSTraceList, b: &MSTraceList) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.sid().unwrap(), b.sid().unwrap());
+            assert_eq!(a.len(), b.len());
+            for (mut a, mut b) in a.iter().zip(b.iter()) {
+                assert_eq!(a.start_time().unwrap(), b.start_time().unwrap());
+                assert_eq!(a.sample_rate_hz(), b.sample_rate_hz());
+                assert_eq!(
+                    a.data_samples::<i32>().unwrap(),
+                    b.data_samples::<i32>().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_record_batches() {
+        let mstl = read_mstl("testdata-3channel-signal.mseed3");
+
+        let batches = mstl.to_record_batches().unwrap();
+        assert_eq!(batches.len(), 3);
+        for (batch, tid) in batches.iter().zip(mstl.iter()) {
+            let mut seg = tid.iter().next().unwrap();
+            let metadata = batch.schema().metadata().clone();
+            assert_eq!(metadata[SID], tid.sid().unwrap());
+            assert_eq!(metadata[SAMPLE_RATE], "1");
+            assert_eq!(metadata[SAMPLE_CNT], "4200");
+
+            assert_eq!(batch.num_rows(), 4200);
+            let times = downcast::<TimestampNanosecondArray>(batch.column(0), TIME_NS).unwrap();
+            assert_eq!(
+                times.value(0),
+                util::time_to_nstime(&seg.start_time().unwrap())
+            );
+            assert_eq!(
+                times.value(4199),
+                util::time_to_nstime(&seg.end_time().unwrap())
+            );
+            let values = downcast::<Int32Array>(batch.column(1), VALUE).unwrap();
+            assert_eq!(values.values(), seg.data_samples::<i32>().unwrap());
+        }
+
+        let imported = MSTraceList::from_record_batches(&batches).unwrap();
+        assert_same_segments(&mstl, &imported);
+    }
+
+    #[test]
+    fn test_long_record_batches() {
+        let mstl = read_mstl("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let batches = mstl.to_long_record_batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 3952);
+        assert_eq!(batch.schema().field(2).data_type(), &DataType::Int32);
+        let sids = downcast::<StringArray>(batch.column(0), SID).unwrap();
+        assert!(sids.iter().all(|sid| sid == Some("FDSN:XX_TEST_00_L_H_Z")));
+
+        let tid = mstl.iter().next().unwrap();
+        let seg = tid.iter().next().unwrap();
+        let metadata = batch.schema().metadata().clone();
+        assert_eq!(metadata[SID], "FDSN:XX_TEST_00_L_H_Z");
+        assert_eq!(metadata[PUB_VERSION], tid.pub_version().to_string());
+        assert_eq!(
+            metadata[START_TIME],
+            util::time_to_nstime(&seg.start_time().unwrap()).to_string()
+        );
+        assert_eq!(metadata[SAMPLE_CNT], "3952");
+        assert_eq!(
+            mstl.long_schema().unwrap().metadata()[PUB_VERSION],
+            tid.pub_version().to_string()
+        );
+
+        let imported = MSTraceList::from_record_batches(&batches).unwrap();
+        assert_same_segments(&mstl, &imported);
+
+        // discontinuous sample times split trace segments
+        let head = batch.slice(0, 1000);
+        let tail = batch.slice(1500, 2452);
+        let imported = MSTraceList::from_record_batches(&[head, tail]).unwrap();
+        assert_eq!(imported.len(), 1);
+        let tid = imported.iter().next().unwrap();
+        assert_eq!(tid.len(), 2);
+        assert_eq!(
+            tid.iter().map(|seg| seg.sample_cnt()).collect::<Vec<_>>(),
+            vec![1000, 2452]
+        );
+    }
+
+    #[test]
+    fn test_record_batches_record_list() {
+        let f = "testdata-oneseries-mixedlengths-mixedorder.mseed3";
+        let unpacked = read_mstl(f);
+        let mstl = read_mstl_with_flags(f, MSControlFlags::MSF_RECORDLIST);
+
+        let batches = mstl.to_long_record_batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 3952);
+        assert_eq!(batches, unpacked.to_long_record_batches().unwrap());
+
+        let batches = mstl.to_record_batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 3952);
+        assert_eq!(batches, unpacked.to_record_batches().unwrap());
+    }
+
+    #[test]
+    fn test_long_record_batches_mixed_types() {
+        let start = util::time_to_nstime(&OffsetDateTime::UNIX_EPOCH);
+        let schema = |data_type: DataType| {
+            Arc::new(Schema::new(vec![
+                Field::new(SID, DataType::Utf8, false),
+                Field::new(TIME_NS, DataType::Int64, false),
+                Field::new(VALUE, data_type, false),
+                Field::new(SAMPLE_RATE, DataType::Float64, false),
+            ]))
+        };
+        let int_batch = RecordBatch::try_new(
+            schema(DataType::Int32),
+            vec![
+                Arc::new(StringArray::from(vec!["FDSN:XX_TEST__L_H_Z"; 3])),
+                Arc::new(Int64Array::from(vec![
+                    start,
+                    start + 1_000_000_000,
+                    start + 2_000_000_000,
+                ])),
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(Float64Array::from(vec![1.0; 3])),
+            ],
+        )
+        .unwrap();
+        let float_batch = RecordBatch::try_new(
+            schema(DataType::Float32),
+            vec![
+                Arc::new(StringArray::from(vec!["FDSN:XX_TEST__L_H_N"; 2])),
+                Arc::new(Int64Array::from(vec![start, start + 1_000_000_000])),
+                Arc::new(Float32Array::from(vec![0.5, 1.5])),
+                Arc::new(Float64Array::from(vec![1.0; 2])),
+            ],
+        )
+        .unwrap();
+        let mstl = MSTraceList::from_record_batches(&[int_batch, float_batch]).unwrap();
+
+        let batches = mstl.to_long_record_batches().unwrap();
+        assert_eq!(batches.len(), 2);
+        let values: Vec<f64> = batches
+            .iter()
+            .flat_map(|batch| {
+                downcast::<Float64Array>(batch.column(2), VALUE)
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        assert_eq!(values, vec![0.5, 1.5, 1.0, 2.0, 3.0]);
+
+        // the trace segments keep their sample types
+        let sample_types: Vec<MSSampleType> = mstl
+            .iter()
+            .map(|tid| tid.iter().next().unwrap().sample_type())
+            .collect();
+        assert_eq!(
+            sample_types,
+            vec![MSSampleType::Float32, MSSampleType::Integer32]
+        );
+    }
+
+    #[test]
+    fn test_parquet() {
+        let mstl = read_mstl("testdata-3channel-signal.mseed3");
+
+        let dir = test::scratch_dir("arrow-parquet");
+        let p = dir.join("data.parquet");
+
+        mstl.write_parquet(File::create(&p).unwrap()).unwrap();
+        let imported = MSTraceList::read_parquet(File::open(&p).unwrap()).unwrap();
+
+        assert_same_segments(&mstl, &imported);
+    }
+}
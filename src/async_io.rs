@@ -0,0 +1,310 @@
+use std::ffi::c_int;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::io::{FramerStep, RecordFramer};
+use crate::{
+    pack_record, pack_trace_list, MSControlFlags, MSDataEncoding, MSError, MSRecord, MSResult,
+    MSTraceList, TlPackInfo,
+};
+
+/// An asynchronous reader for miniSEED records from a source implementing [`AsyncRead`].
+///
+/// Asynchronous counterpart of [`MSStreamReader`]. Records are framed by means of [`detect()`],
+/// i.e. records spanning across multiple reads are handled, and are provided as a [`Stream`].
+///
+/// Each record owns a copy of its raw record (see [`MSRecord::raw()`]), i.e. records remain valid
+/// while subsequent records are read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use tokio::fs::File;
+///
+/// use mseed::{MSAsyncReader, MSControlFlags};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let file = File::open("path/to/data.mseed").await.unwrap();
+/// let mut reader = MSAsyncReader::new_with_flags(file, MSControlFlags::MSF_UNPACKDATA);
+///
+/// while let Some(msr) = reader.next().await {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// # }
+/// ```
+///
+/// [`MSStreamReader`]: crate::MSStreamReader
+/// [`detect()`]: crate::detect
+#[derive(Debug)]
+pub struct MSAsyncReader<R> {
+    reader: R,
+    framer: RecordFramer,
+}
+
+impl<R: AsyncRead + Unpin> MSAsyncReader<R> {
+    /// Creates a new `MSAsyncReader` reading records from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::new_with_flags(inner, MSControlFlags::empty())
+    }
+
+    /// Creates a new `MSAsyncReader` reading records from `inner` with control flags `flags`.
+    ///
+    /// See [`MSStreamReader`](crate::MSStreamReader) for the control flags honored.
+    pub fn new_with_flags(inner: R, flags: MSControlFlags) -> Self {
+        Self {
+            reader: inner,
+            framer: RecordFramer::new(flags),
+        }
+    }
+
+    /// Consumes this `MSAsyncReader`, returning the underlying reader.
+    ///
+    /// Note that any leftover data in the internal buffer is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MSAsyncReader<R> {
+    type Item = MSResult<MSRecord>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.framer.step() {
+                FramerStep::Read(len) => {
+                    let mut read_buf = ReadBuf::new(this.framer.spare(len));
+                    match Pin::new(&mut this.reader).poll_read(cx, &mut read_buf) {
+                        Poll::Ready(Ok(())) => {
+                            let n = read_buf.filled().len();
+                            this.framer.commit(n);
+                        }
+                        Poll::Ready(Err(e)) => {
+                            this.framer.discard();
+                            this.framer.finish();
+                            return Poll::Ready(Some(Err(MSError::from_str(&e.to_string()))));
+                        }
+                        Poll::Pending => {
+                            this.framer.discard();
+                            return Poll::Pending;
+                        }
+                    }
+                }
+                FramerStep::Record(rv) => return Poll::Ready(Some(rv)),
+                FramerStep::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Asynchronous miniSEED record writer.
+///
+/// Asynchronous counterpart of [`MSWriter`](crate::MSWriter). Records are packed in memory
+/// before being written to the underlying writer. The futures returned do not borrow the
+/// records or trace lists written.
+#[derive(Debug)]
+pub struct MSAsyncWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> MSAsyncWriter<W> {
+    /// Creates a new `MSAsyncWriter`.
+    pub fn new(inner: W) -> MSAsyncWriter<W> {
+        Self { writer: inner }
+    }
+
+    /// Consumes this `MSAsyncWriter`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Writes the miniSEED record `msr` to the underlying writer.
+    ///
+    ///  If `flags` has [`MSControlFlags::MSF_FLUSHDATA`] set, all of the data will be packed into
+    ///  data records even though the last one will probably be smaller than requested or, in the
+    ///  case of miniSEED 2, unfilled.
+    ///  If `flags` has [`MSControlFlags::MSF_PACKVER2`] set `msr` is packed as miniSEED v2
+    ///  regardless of msr's [`MSRecord::format_version`].
+    pub fn write_record(
+        &mut self,
+        msr: &MSRecord,
+        flags: MSControlFlags,
+    ) -> impl Future<Output = MSResult<c_int>> + '_ {
+        let mut buf = Vec::new();
+        let rv = pack_record(msr, |rec: &[u8]| buf.extend_from_slice(rec), flags);
+
+        async move {
+            let (cnt_records, _) = rv?;
+            self.write_all(&buf).await?;
+
+            Ok(cnt_records as c_int)
+        }
+    }
+
+    /// Writes `mstl` to the underlying writer.
+    pub fn write_trace_list(
+        &mut self,
+        mstl: &mut MSTraceList,
+        flags: MSControlFlags,
+        encoding: MSDataEncoding,
+        max_rec_len: c_int,
+    ) -> impl Future<Output = MSResult<i64>> + '_ {
+        let mut flags = flags;
+        flags |= MSControlFlags::MSF_MAINTAINMSTL;
+        flags |= MSControlFlags::MSF_FLUSHDATA;
+
+        let info = TlPackInfo {
+            encoding,
+            rec_len: max_rec_len,
+            extra_headers: None,
+            byte_order: None,
+        };
+
+        let mut buf = Vec::new();
+        let rv = pack_trace_list(mstl, |rec: &[u8]| buf.extend_from_slice(rec), &info, flags);
+
+        async move {
+            let (cnt_records, _) = rv?;
+            self.write_all(&buf).await?;
+
+            Ok(cnt_records as i64)
+        }
+    }
+
+    async fn write_all(&mut self, buf: &[u8]) -> MSResult<()> {
+        self.writer
+            .write_all(buf)
+            .await
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use futures_util::StreamExt;
+    use pretty_assertions::assert_eq;
+    use tokio::fs::File;
+
+    use crate::{test, MSReader};
+
+    #[tokio::test]
+    async fn test_async_read_write_msr() {
+        let test_data = vec![
+            "reference-testdata-text.mseed2",
+            "reference-testdata-text.mseed3",
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-steim2.mseed3",
+        ];
+
+        for f in &test_data {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let file = File::open(&p).await.unwrap();
+            let mut reader = MSAsyncReader::new_with_flags(file, MSControlFlags::MSF_UNPACKDATA);
+            let mut writer = MSAsyncWriter::new(Vec::new());
+            while let Some(msr) = reader.next().await {
+                let msr = msr.unwrap();
+                writer
+                    .write_record(&msr, MSControlFlags::MSF_FLUSHDATA)
+                    .await
+                    .unwrap();
+            }
+
+            let written = writer.into_inner();
+            let expected = fs::read(&p).unwrap();
+
+            assert_eq!(written.len(), expected.len());
+            assert_eq!(written, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_read_partial() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let expected: Vec<(String, i64)> = MSReader::new(p.clone())
+            .unwrap()
+            .map(|msr| {
+                let msr = msr.unwrap();
+                (msr.sid_lossy(), msr.sample_cnt())
+            })
+            .collect();
+
+        // read in chunks which do not align with record boundaries
+        let mut file = File::open(&p).await.unwrap();
+        let (rx, mut tx) = tokio::io::duplex(7);
+        let copy = tokio::spawn(async move {
+            tokio::io::copy(&mut file, &mut tx).await.unwrap();
+        });
+
+        let read: Vec<(String, i64)> = MSAsyncReader::new(rx)
+            .map(|msr| {
+                let msr = msr.unwrap();
+                (msr.sid_lossy(), msr.sample_cnt())
+            })
+            .collect()
+            .await;
+        copy.await.unwrap();
+
+        assert_eq!(read, expected);
+    }
+
+    #[tokio::test]
+    async fn test_async_read_owned_records() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let buf = fs::read(&p).unwrap();
+        let records: Vec<MSRecord> = MSAsyncReader::new(File::open(&p).await.unwrap())
+            .map(|msr| msr.unwrap())
+            .collect()
+            .await;
+
+        // the raw records remain valid while subsequent records are read
+        let mut pos = 0;
+        for msr in &records {
+            let raw = msr.raw().unwrap();
+            assert_eq!(raw, &buf[pos..pos + raw.len()]);
+            pos += raw.len();
+        }
+        assert_eq!(pos, buf.len());
+    }
+}
@@ -0,0 +1,1130 @@
+use std::ffi::c_char;
+use std::slice;
+
+use time::OffsetDateTime;
+
+use crate::error::check;
+use crate::record_ref::MS2_FSDH_LEN;
+use crate::steim::{encode_steim1, encode_steim2, STEIM_FRAME_LEN};
+use crate::{
+    raw, util, MSByteOrder, MSControlFlags, MSDataEncoding, MSError, MSRecord, MSRecordRef,
+    MSResult, MSSampleType,
+};
+use raw::MS3Record;
+
+/// Blockette 100: sample rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette100 {
+    /// Actual sample rate (`Hz`).
+    pub sample_rate: f32,
+    /// Flags (reserved).
+    pub flags: u8,
+}
+
+/// Blockette 200: generic event detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette200 {
+    /// Signal amplitude.
+    pub signal_amplitude: f32,
+    /// Signal period.
+    pub signal_period: f32,
+    /// Background estimate.
+    pub background_estimate: f32,
+    /// Event detection flags.
+    pub flags: u8,
+    /// Signal onset time.
+    pub onset_time: OffsetDateTime,
+    /// Detector name.
+    pub detector: String,
+}
+
+/// Blockette 201: Murdock event detection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette201 {
+    /// Signal amplitude.
+    pub signal_amplitude: f32,
+    /// Signal period.
+    pub signal_period: f32,
+    /// Background estimate.
+    pub background_estimate: f32,
+    /// Event detection flags.
+    pub flags: u8,
+    /// Signal onset time.
+    pub onset_time: OffsetDateTime,
+    /// Signal-to-noise ratio values.
+    pub snr_values: [u8; 6],
+    /// Lookback value.
+    pub lookback: u8,
+    /// Pick algorithm.
+    pub pick_algorithm: u8,
+    /// Detector name.
+    pub detector: String,
+}
+
+/// Blockette 300: step calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette300 {
+    /// Beginning of calibration time.
+    pub start_time: OffsetDateTime,
+    /// Number of step calibrations.
+    pub step_cnt: u8,
+    /// Calibration flags.
+    pub flags: u8,
+    /// Step duration in units of `0.0001` seconds.
+    pub step_duration: u32,
+    /// Interval duration in units of `0.0001` seconds.
+    pub interval_duration: u32,
+    /// Calibration signal amplitude.
+    pub amplitude: f32,
+    /// Channel with calibration input.
+    pub input_channel: String,
+    /// Reference amplitude.
+    pub reference_amplitude: u32,
+    /// Coupling.
+    pub coupling: String,
+    /// Rolloff.
+    pub rolloff: String,
+}
+
+/// Blockette 310: sine calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette310 {
+    /// Beginning of calibration time.
+    pub start_time: OffsetDateTime,
+    /// Calibration flags.
+    pub flags: u8,
+    /// Calibration duration in units of `0.0001` seconds.
+    pub duration: u32,
+    /// Period of signal (seconds).
+    pub period: f32,
+    /// Amplitude of signal.
+    pub amplitude: f32,
+    /// Channel with calibration input.
+    pub input_channel: String,
+    /// Reference amplitude.
+    pub reference_amplitude: u32,
+    /// Coupling.
+    pub coupling: String,
+    /// Rolloff.
+    pub rolloff: String,
+}
+
+/// Blockette 320: pseudo-random calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette320 {
+    /// Beginning of calibration time.
+    pub start_time: OffsetDateTime,
+    /// Calibration flags.
+    pub flags: u8,
+    /// Calibration duration in units of `0.0001` seconds.
+    pub duration: u32,
+    /// Peak-to-peak amplitude of steps.
+    pub amplitude: f32,
+    /// Channel with calibration input.
+    pub input_channel: String,
+    /// Reference amplitude.
+    pub reference_amplitude: u32,
+    /// Coupling.
+    pub coupling: String,
+    /// Rolloff.
+    pub rolloff: String,
+    /// Noise type.
+    pub noise_type: String,
+}
+
+/// Blockette 390: generic calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette390 {
+    /// Beginning of calibration time.
+    pub start_time: OffsetDateTime,
+    /// Calibration flags.
+    pub flags: u8,
+    /// Calibration duration in units of `0.0001` seconds.
+    pub duration: u32,
+    /// Amplitude of signal.
+    pub amplitude: f32,
+    /// Channel with calibration input.
+    pub input_channel: String,
+}
+
+/// Blockette 395: calibration abort.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette395 {
+    /// End of calibration time.
+    pub end_time: OffsetDateTime,
+}
+
+/// Blockette 500: timing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette500 {
+    /// VCO correction (percent).
+    pub vco_correction: f32,
+    /// Time of exception, including the microsecond offset.
+    pub time: OffsetDateTime,
+    /// Reception quality (percent).
+    pub reception_quality: u8,
+    /// Exception count.
+    pub count: u32,
+    /// Exception type.
+    pub exception_type: String,
+    /// Clock model.
+    pub clock_model: String,
+    /// Clock status.
+    pub clock_status: String,
+}
+
+/// Blockette 1000: data only SEED.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette1000 {
+    /// Data encoding format (see [`MSDataEncoding`](crate::MSDataEncoding)).
+    pub encoding: u8,
+    /// Word order, i.e. `0` for little-endian and `1` for big-endian.
+    pub word_order: u8,
+    /// Record length as exponent of a power of two.
+    pub rec_len_exp: u8,
+}
+
+/// Blockette 1001: data extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette1001 {
+    /// Timing quality (percent).
+    pub timing_quality: u8,
+    /// Microsecond offset.
+    pub microsecond: i8,
+    /// Number of frames.
+    pub frame_cnt: u8,
+}
+
+/// Blockette 2000: variable length opaque data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette2000 {
+    /// Record number.
+    pub record_number: u32,
+    /// Word order of the opaque data, i.e. `0` for little-endian and `1` for big-endian.
+    pub word_order: u8,
+    /// Data flags.
+    pub flags: u8,
+    /// Opaque data header fields.
+    pub header_fields: Vec<String>,
+    /// Opaque data.
+    pub data: Vec<u8>,
+}
+
+/// A blockette not known to the crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBlockette {
+    /// Blockette type.
+    pub blockette_type: u16,
+    /// Blockette body, i.e. the bytes following the blockette type and the offset of the next
+    /// blockette.
+    ///
+    /// Note that the body is stored in the byte order of the record.
+    pub body: Vec<u8>,
+    /// Byte order of the blockette body.
+    ///
+    /// Since the layout of the body is unknown, it cannot be swapped. Thus, only big-endian
+    /// blockettes may be encoded.
+    pub byte_order: MSByteOrder,
+}
+
+/// A SEED 2.x blockette of a miniSEED v2 record.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{Blockette, MSControlFlags, MSReader};
+///
+/// let reader = MSReader::new_with_flags("path/to/data.mseed2", MSControlFlags::empty()).unwrap();
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     for blkt in msr.blockettes().unwrap() {
+///         if let Blockette::Timing(b500) = blkt {
+///             println!("timing exception: {} at {}", b500.exception_type, b500.time);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Blockette {
+    /// Blockette 100.
+    SampleRate(Blockette100),
+    /// Blockette 200.
+    GenericEvent(Blockette200),
+    /// Blockette 201.
+    MurdockEvent(Blockette201),
+    /// Blockette 300.
+    StepCalibration(Blockette300),
+    /// Blockette 310.
+    SineCalibration(Blockette310),
+    /// Blockette 320.
+    PseudoRandomCalibration(Blockette320),
+    /// Blockette 390.
+    GenericCalibration(Blockette390),
+    /// Blockette 395.
+    CalibrationAbort(Blockette395),
+    /// Blockette 500.
+    Timing(Blockette500),
+    /// Blockette 1000.
+    DataOnly(Blockette1000),
+    /// Blockette 1001.
+    DataExtension(Blockette1001),
+    /// Blockette 2000.
+    Opaque(Blockette2000),
+    /// Any other blockette.
+    Unknown(RawBlockette),
+}
+
+impl Blockette {
+    /// Returns the blockette type.
+    pub fn blockette_type(&self) -> u16 {
+        match self {
+            Self::SampleRate(_) => 100,
+            Self::GenericEvent(_) => 200,
+            Self::MurdockEvent(_) => 201,
+            Self::StepCalibration(_) => 300,
+            Self::SineCalibration(_) => 310,
+            Self::PseudoRandomCalibration(_) => 320,
+            Self::GenericCalibration(_) => 390,
+            Self::CalibrationAbort(_) => 395,
+            Self::Timing(_) => 500,
+            Self::DataOnly(_) => 1000,
+            Self::DataExtension(_) => 1001,
+            Self::Opaque(_) => 2000,
+            Self::Unknown(b) => b.blockette_type,
+        }
+    }
+
+    /// Encodes the blockette in big-endian byte order, including the blockette type. The offset
+    /// of the next blockette is set to zero.
+    ///
+    /// Only blockettes 100, 500 and 2000 as well as unknown big-endian blockettes may be encoded. Blockettes
+    /// 1000 and 1001 are managed by libmseed while the event detection and calibration blockettes
+    /// are created from extra headers (see [`MSExtraHeaders`](crate::MSExtraHeaders)).
+    pub(crate) fn to_bytes(&self) -> MSResult<Vec<u8>> {
+        let mut w = Writer(Vec::new());
+        w.u16(self.blockette_type());
+        w.u16(0);
+
+        match self {
+            Self::SampleRate(b) => {
+                w.f32(b.sample_rate);
+                w.u8(b.flags);
+                w.zeros(3);
+            }
+            Self::Timing(b) => {
+                w.f32(b.vco_correction);
+                w.btime(&b.time);
+                w.u8(((b.time.nanosecond() / 1000) % 100) as u8);
+                w.u8(b.reception_quality);
+                w.u32(b.count);
+                w.text(&b.exception_type, 16);
+                w.text(&b.clock_model, 32);
+                w.text(&b.clock_status, 128);
+            }
+            Self::Opaque(b) => {
+                let header: Vec<u8> = b
+                    .header_fields
+                    .iter()
+                    .flat_map(|f| f.bytes().chain(Some(b'~')))
+                    .collect();
+                let data_offset = 15 + header.len();
+                let total_len = u16::try_from(data_offset + b.data.len())
+                    .map_err(|_| MSError::from_str("blockette 2000 too large"))?;
+
+                w.u16(total_len);
+                w.u16(data_offset as u16);
+                w.u32(b.record_number);
+                w.u8(b.word_order);
+                w.u8(b.flags);
+                w.u8(u8::try_from(b.header_fields.len())
+                    .map_err(|_| MSError::from_str("too many blockette 2000 header fields"))?);
+                w.0.extend_from_slice(&header);
+                w.0.extend_from_slice(&b.data);
+            }
+            Self::Unknown(b) if b.byte_order == MSByteOrder::BigEndian => {
+                w.0.extend_from_slice(&b.body)
+            }
+            Self::Unknown(b) => {
+                return Err(MSError::from_str(&format!(
+                    "encoding little-endian blockette {} is not supported",
+                    b.blockette_type
+                )))
+            }
+            other => {
+                return Err(MSError::from_str(&format!(
+                    "encoding blockette {} is not supported",
+                    other.blockette_type()
+                )))
+            }
+        }
+
+        Ok(w.0)
+    }
+}
+
+impl MSRecord {
+    /// Returns the SEED 2.x blockettes of the miniSEED v2 record.
+    ///
+    /// Returns an error if the record is not a miniSEED v2 record or if the raw record is not
+    /// available. See also [`MSRecordRef::blockettes()`].
+    pub fn blockettes(&self) -> MSResult<Vec<Blockette>> {
+        let raw = self
+            .raw()
+            .ok_or_else(|| MSError::from_str("raw record not available"))?;
+
+        MSRecordRef::parse(raw)?.blockettes()
+    }
+}
+
+impl<'a> MSRecordRef<'a> {
+    /// Returns the SEED 2.x blockettes of the miniSEED v2 record in the order of the blockette
+    /// chain.
+    ///
+    /// Returns an error if the record is not a miniSEED v2 record or a blockette is truncated.
+    pub fn blockettes(&self) -> MSResult<Vec<Blockette>> {
+        if self.format_version() != 2 {
+            return Err(MSError::from_str("not a miniSEED v2 record"));
+        }
+
+        let buf = self.raw();
+        let data_offset = self.data_offset();
+        let mut rv = vec![];
+
+        let mut offset = self.u16_at(46) as usize;
+        while offset >= MS2_FSDH_LEN && offset + 4 <= buf.len() && rv.len() < buf[39] as usize {
+            let next = self.u16_at(offset + 2) as usize;
+            let end = if next > offset {
+                next
+            } else if data_offset > offset {
+                data_offset
+            } else {
+                buf.len()
+            };
+
+            let r = Reader {
+                rec: self,
+                offset,
+                end: end.min(buf.len()),
+            };
+            rv.push(r.blockette()?);
+
+            if next <= offset {
+                break;
+            }
+            offset = next;
+        }
+
+        Ok(rv)
+    }
+}
+
+/// Borrowed data samples to be packed.
+enum PackData<'a> {
+    Integer32(&'a [i32]),
+    Float32(&'a [f32]),
+    Float64(&'a [f64]),
+    Text(&'a [u8]),
+}
+
+impl PackData<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Self::Integer32(s) => s.len(),
+            Self::Float32(s) => s.len(),
+            Self::Float64(s) => s.len(),
+            Self::Text(s) => s.len(),
+        }
+    }
+}
+
+/// Packs the data samples of `msr` into miniSEED v2 records which additionally contain
+/// `blockettes`.
+///
+/// The fixed section of data header as well as the blockettes managed by libmseed (i.e.
+/// blockettes 1000, 1001 and blockettes created from extra headers) are packed by means of
+/// [`raw::msr3_pack_header2`]. Subsequently, `blockettes` are appended to the blockette chain and
+/// the data samples are encoded in big-endian byte order. Returns the number of records and the
+/// number of samples packed.
+///
+/// # Safety
+///
+/// `msr` must be a valid record pointing to `numsamples` data samples of its sample type.
+pub(crate) unsafe fn pack_mseed2<F>(
+    msr: *mut MS3Record,
+    blockettes: &[Blockette],
+    record_handler: &mut F,
+    flags: MSControlFlags,
+) -> MSResult<(usize, usize)>
+where
+    F: FnMut(&[u8]),
+{
+    let encoded = blockettes
+        .iter()
+        .map(Blockette::to_bytes)
+        .collect::<MSResult<Vec<_>>>()?;
+
+    let rec_len = mseed2_rec_len((*msr).reclen)?;
+
+    let encoding = MSDataEncoding::from_char((*msr).encoding as u8)?;
+    let num_samples = usize::try_from((*msr).numsamples).unwrap_or(0);
+    if num_samples == 0 || (*msr).datasamples.is_null() {
+        return Ok((0, 0));
+    }
+    let data_ptr = (*msr).datasamples as *const u8;
+    let data = match MSSampleType::from_char((*msr).sampletype as u8) {
+        MSSampleType::Integer32 => {
+            PackData::Integer32(slice::from_raw_parts(data_ptr as *const i32, num_samples))
+        }
+        MSSampleType::Float32 => {
+            PackData::Float32(slice::from_raw_parts(data_ptr as *const f32, num_samples))
+        }
+        MSSampleType::Float64 => {
+            PackData::Float64(slice::from_raw_parts(data_ptr as *const f64, num_samples))
+        }
+        MSSampleType::Text => PackData::Text(slice::from_raw_parts(data_ptr, num_samples)),
+        MSSampleType::Unknown => return Err(MSError::from_str("unknown sample type")),
+    };
+
+    let start_time = (*msr).starttime;
+    let sample_cnt = (*msr).samplecnt;
+
+    let rv = pack_mseed2_records(
+        msr,
+        &encoded,
+        rec_len,
+        encoding,
+        &data,
+        record_handler,
+        flags.contains(MSControlFlags::MSF_FLUSHDATA),
+    );
+
+    (*msr).starttime = start_time;
+    (*msr).samplecnt = sample_cnt;
+
+    rv
+}
+
+/// Validates the miniSEED v2 record length `rec_len`, where a negative value selects the default
+/// record length.
+pub(crate) fn mseed2_rec_len(rec_len: i32) -> MSResult<usize> {
+    let rec_len = match rec_len {
+        rec_len if rec_len < 0 => 4096,
+        rec_len => rec_len as usize,
+    };
+    if !rec_len.is_power_of_two() || !(128..=65536).contains(&rec_len) {
+        return Err(MSError::from_str(&format!(
+            "invalid miniSEED v2 record length: {}",
+            rec_len
+        )));
+    }
+
+    Ok(rec_len)
+}
+
+/// Appends the encoded `blockettes` to the blockette chain of the big-endian miniSEED v2 header
+/// of `header_len` bytes at the beginning of `buf`. Returns the offset following the last
+/// blockette appended.
+pub(crate) fn append_blockettes(
+    buf: &mut [u8],
+    header_len: usize,
+    blockettes: &[Vec<u8>],
+) -> MSResult<usize> {
+    let mut last = None;
+    let mut offset = u16::from_be_bytes([buf[46], buf[47]]) as usize;
+    for _ in 0..buf[39] {
+        if offset < MS2_FSDH_LEN || offset + 4 > header_len {
+            break;
+        }
+        last = Some(offset);
+        offset = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+    }
+
+    let mut end = header_len;
+    for b in blockettes {
+        if end + b.len() > buf.len() {
+            return Err(MSError::from_str("blockettes exceed record length"));
+        }
+        let pos = match last {
+            Some(last) => last + 2,
+            None => 46,
+        };
+        buf[pos..pos + 2].copy_from_slice(&(end as u16).to_be_bytes());
+        buf[end..end + b.len()].copy_from_slice(b);
+        buf[39] += 1;
+        last = Some(end);
+        end += b.len();
+    }
+
+    Ok(end)
+}
+
+/// Packs `data` into miniSEED v2 records of `rec_len` bytes, starting at the start time of `msr`.
+///
+/// # Safety
+///
+/// `msr` must be a valid record.
+unsafe fn pack_mseed2_records<F>(
+    msr: *mut MS3Record,
+    blockettes: &[Vec<u8>],
+    rec_len: usize,
+    encoding: MSDataEncoding,
+    data: &PackData,
+    record_handler: &mut F,
+    flush: bool,
+) -> MSResult<(usize, usize)>
+where
+    F: FnMut(&[u8]),
+{
+    let start_time = (*msr).starttime;
+
+    let mut buf = vec![0u8; rec_len];
+    let mut cnt_records = 0;
+    let mut idx = 0;
+    while idx < data.len() {
+        buf.fill(0);
+        (*msr).starttime = util::sample_time(start_time, idx as i64, (*msr).samprate);
+        (*msr).samplecnt = 0;
+        let header_len = check(raw::msr3_pack_header2(
+            msr,
+            buf.as_mut_ptr() as *mut c_char,
+            rec_len as u32,
+            0,
+        ))? as usize;
+
+        let end = append_blockettes(&mut buf, header_len, blockettes)?;
+
+        let data_offset = match encoding {
+            MSDataEncoding::Steim1 | MSDataEncoding::Steim2 => {
+                end.div_ceil(STEIM_FRAME_LEN) * STEIM_FRAME_LEN
+            }
+            _ => end,
+        };
+        if data_offset >= rec_len {
+            return Err(MSError::from_str("blockettes exceed record length"));
+        }
+        buf[44..46].copy_from_slice(&(data_offset as u16).to_be_bytes());
+
+        let room = rec_len - data_offset;
+        let (payload, n) = match (encoding, data) {
+            (MSDataEncoding::Steim1, PackData::Integer32(s)) => {
+                let d0 = if idx > 0 {
+                    s[idx].wrapping_sub(s[idx - 1])
+                } else {
+                    0
+                };
+                encode_steim1(&s[idx..], d0, room / STEIM_FRAME_LEN, true)?
+            }
+            (MSDataEncoding::Steim2, PackData::Integer32(s)) => {
+                let d0 = if idx > 0 {
+                    s[idx].wrapping_sub(s[idx - 1])
+                } else {
+                    0
+                };
+                encode_steim2(&s[idx..], d0, room / STEIM_FRAME_LEN, true)?
+            }
+            (MSDataEncoding::Integer32, PackData::Integer32(s)) => {
+                let s = &s[idx..(idx + room / 4).min(s.len())];
+                (s.iter().flat_map(|v| v.to_be_bytes()).collect(), s.len())
+            }
+            (MSDataEncoding::Integer16, PackData::Integer32(s)) => {
+                let s = &s[idx..(idx + room / 2).min(s.len())];
+                let mut payload = Vec::with_capacity(s.len() * 2);
+                for &v in s {
+                    let v = i16::try_from(v).map_err(|e| MSError::from_str(&e.to_string()))?;
+                    payload.extend_from_slice(&v.to_be_bytes());
+                }
+                (payload, s.len())
+            }
+            (MSDataEncoding::Float32, PackData::Float32(s)) => {
+                let s = &s[idx..(idx + room / 4).min(s.len())];
+                (s.iter().flat_map(|v| v.to_be_bytes()).collect(), s.len())
+            }
+            (MSDataEncoding::Float64, PackData::Float64(s)) => {
+                let s = &s[idx..(idx + room / 8).min(s.len())];
+                (s.iter().flat_map(|v| v.to_be_bytes()).collect(), s.len())
+            }
+            (MSDataEncoding::Text, PackData::Text(s)) => {
+                let s = &s[idx..(idx + room).min(s.len())];
+                (s.to_vec(), s.len())
+            }
+            _ => {
+                return Err(MSError::from_str(&format!(
+                    "invalid data encoding for sample type: {}",
+                    encoding
+                )))
+            }
+        };
+        if n == 0 {
+            return Err(MSError::from_str("no data samples fit into record"));
+        }
+
+        // unless flushing, only full records are packed
+        if idx + n == data.len() && !flush {
+            return Ok((cnt_records, idx));
+        }
+
+        buf[data_offset..data_offset + payload.len()].copy_from_slice(&payload);
+        let n_u16 = u16::try_from(n).map_err(|e| MSError::from_str(&e.to_string()))?;
+        buf[30..32].copy_from_slice(&n_u16.to_be_bytes());
+
+        record_handler(&buf);
+        cnt_records += 1;
+        idx += n;
+    }
+
+    Ok((cnt_records, idx))
+}
+
+/// Reads the fields of a single blockette.
+struct Reader<'r, 'a> {
+    rec: &'r MSRecordRef<'a>,
+    /// Offset of the blockette within the record.
+    offset: usize,
+    /// Offset of the end of the blockette within the record.
+    end: usize,
+}
+
+impl Reader<'_, '_> {
+    fn blockette(&self) -> MSResult<Blockette> {
+        let blockette_type = self.rec.u16_at(self.offset);
+        let rv = match blockette_type {
+            100 => {
+                self.require(12)?;
+                Blockette::SampleRate(Blockette100 {
+                    sample_rate: self.f32(4),
+                    flags: self.u8(8),
+                })
+            }
+            200 => {
+                self.require(52)?;
+                Blockette::GenericEvent(Blockette200 {
+                    signal_amplitude: self.f32(4),
+                    signal_period: self.f32(8),
+                    background_estimate: self.f32(12),
+                    flags: self.u8(16),
+                    onset_time: self.btime(18)?,
+                    detector: self.text(28, 24),
+                })
+            }
+            201 => {
+                self.require(60)?;
+                let mut snr_values = [0; 6];
+                snr_values.copy_from_slice(self.bytes(28, 6));
+                Blockette::MurdockEvent(Blockette201 {
+                    signal_amplitude: self.f32(4),
+                    signal_period: self.f32(8),
+                    background_estimate: self.f32(12),
+                    flags: self.u8(16),
+                    onset_time: self.btime(18)?,
+                    snr_values,
+                    lookback: self.u8(34),
+                    pick_algorithm: self.u8(35),
+                    detector: self.text(36, 24),
+                })
+            }
+            300 => {
+                self.require(60)?;
+                Blockette::StepCalibration(Blockette300 {
+                    start_time: self.btime(4)?,
+                    step_cnt: self.u8(14),
+                    flags: self.u8(15),
+                    step_duration: self.u32(16),
+                    interval_duration: self.u32(20),
+                    amplitude: self.f32(24),
+                    input_channel: self.text(28, 3),
+                    reference_amplitude: self.u32(32),
+                    coupling: self.text(36, 12),
+                    rolloff: self.text(48, 12),
+                })
+            }
+            310 => {
+                self.require(60)?;
+                Blockette::SineCalibration(Blockette310 {
+                    start_time: self.btime(4)?,
+                    flags: self.u8(15),
+                    duration: self.u32(16),
+                    period: self.f32(20),
+                    amplitude: self.f32(24),
+                    input_channel: self.text(28, 3),
+                    reference_amplitude: self.u32(32),
+                    coupling: self.text(36, 12),
+                    rolloff: self.text(48, 12),
+                })
+            }
+            320 => {
+                self.require(64)?;
+                Blockette::PseudoRandomCalibration(Blockette320 {
+                    start_time: self.btime(4)?,
+                    flags: self.u8(15),
+                    duration: self.u32(16),
+                    amplitude: self.f32(20),
+                    input_channel: self.text(24, 3),
+                    reference_amplitude: self.u32(28),
+                    coupling: self.text(32, 12),
+                    rolloff: self.text(44, 12),
+                    noise_type: self.text(56, 8),
+                })
+            }
+            390 => {
+                self.require(28)?;
+                Blockette::GenericCalibration(Blockette390 {
+                    start_time: self.btime(4)?,
+                    flags: self.u8(15),
+                    duration: self.u32(16),
+                    amplitude: self.f32(20),
+                    input_channel: self.text(24, 3),
+                })
+            }
+            395 => {
+                self.require(16)?;
+                Blockette::CalibrationAbort(Blockette395 {
+                    end_time: self.btime(4)?,
+                })
+            }
+            500 => {
+                self.require(200)?;
+                let time = self.btime(8)? + time::Duration::microseconds(self.u8(18) as i8 as i64);
+                Blockette::Timing(Blockette500 {
+                    vco_correction: self.f32(4),
+                    time,
+                    reception_quality: self.u8(19),
+                    count: self.u32(20),
+                    exception_type: self.text(24, 16),
+                    clock_model: self.text(40, 32),
+                    clock_status: self.text(72, 128),
+                })
+            }
+            1000 => {
+                self.require(8)?;
+                Blockette::DataOnly(Blockette1000 {
+                    encoding: self.u8(4),
+                    word_order: self.u8(5),
+                    rec_len_exp: self.u8(6),
+                })
+            }
+            1001 => {
+                self.require(8)?;
+                Blockette::DataExtension(Blockette1001 {
+                    timing_quality: self.u8(4),
+                    microsecond: self.u8(5) as i8,
+                    frame_cnt: self.u8(7),
+                })
+            }
+            2000 => {
+                self.require(15)?;
+                let total_len = self.u16(4) as usize;
+                let data_offset = self.u16(6) as usize;
+                if data_offset < 15 || data_offset > total_len {
+                    return Err(MSError::from_str("invalid blockette 2000 data offset"));
+                }
+                self.require(total_len)?;
+
+                let header = self.bytes(15, data_offset - 15);
+                let header_fields = header
+                    .split(|&c| c == b'~')
+                    .take(self.u8(14) as usize)
+                    .map(|f| String::from_utf8_lossy(f).into_owned())
+                    .collect();
+
+                Blockette::Opaque(Blockette2000 {
+                    record_number: self.u32(8),
+                    word_order: self.u8(12),
+                    flags: self.u8(13),
+                    header_fields,
+                    data: self.bytes(data_offset, total_len - data_offset).to_vec(),
+                })
+            }
+            _ => Blockette::Unknown(RawBlockette {
+                blockette_type,
+                body: self
+                    .bytes(4, self.end.saturating_sub(self.offset + 4))
+                    .to_vec(),
+                byte_order: self.rec.byte_order().header,
+            }),
+        };
+
+        Ok(rv)
+    }
+
+    /// Checks that the blockette provides at least `len` bytes.
+    fn require(&self, len: usize) -> MSResult<()> {
+        if self.offset + len > self.rec.raw().len() {
+            return Err(MSError::from_str(&format!(
+                "truncated blockette {} at offset {}",
+                self.rec.u16_at(self.offset),
+                self.offset
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn bytes(&self, pos: usize, len: usize) -> &[u8] {
+        let start = self.offset + pos;
+        &self.rec.raw()[start..start + len]
+    }
+
+    fn u8(&self, pos: usize) -> u8 {
+        self.rec.raw()[self.offset + pos]
+    }
+
+    fn u16(&self, pos: usize) -> u16 {
+        self.rec.u16_at(self.offset + pos)
+    }
+
+    fn u32(&self, pos: usize) -> u32 {
+        self.rec.u32_at(self.offset + pos)
+    }
+
+    fn f32(&self, pos: usize) -> f32 {
+        f32::from_bits(self.u32(pos))
+    }
+
+    fn text(&self, pos: usize, len: usize) -> String {
+        let text = self.bytes(pos, len);
+        String::from_utf8_lossy(text)
+            .trim_matches(|c| c == ' ' || c == '\0')
+            .to_string()
+    }
+
+    fn btime(&self, pos: usize) -> MSResult<OffsetDateTime> {
+        let nst = util::time_fields_to_nstime(
+            self.u16(pos).into(),
+            self.u16(pos + 2).into(),
+            self.u8(pos + 4).into(),
+            self.u8(pos + 5).into(),
+            self.u8(pos + 6).into(),
+            u32::from(self.u16(pos + 8)) * 100_000,
+        )
+        .ok_or_else(|| MSError::from_str("time out of range"))?;
+
+        OffsetDateTime::from_unix_timestamp_nanos(nst.into())
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+}
+
+/// Writes blockette fields in big-endian byte order.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.u32(v.to_bits());
+    }
+
+    fn zeros(&mut self, len: usize) {
+        self.0.resize(self.0.len() + len, 0);
+    }
+
+    /// Writes `text` truncated or padded with spaces to `len` bytes.
+    fn text(&mut self, text: &str, len: usize) {
+        let bytes = text.as_bytes();
+        let n = bytes.len().min(len);
+        self.0.extend_from_slice(&bytes[..n]);
+        self.0.resize(self.0.len() + len - n, b' ');
+    }
+
+    fn btime(&mut self, t: &OffsetDateTime) {
+        let t = t.to_offset(time::UtcOffset::UTC);
+        self.u16(t.year() as u16);
+        self.u16(t.ordinal());
+        self.u8(t.hour());
+        self.u8(t.minute());
+        self.u8(t.second());
+        self.u8(0);
+        self.u16((t.nanosecond() / 100_000) as u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::test;
+
+    #[test]
+    fn test_blockettes_detection() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-detection.record.mseed2");
+
+        let buf = fs::read(p).unwrap();
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        let blockettes = rec.blockettes().unwrap();
+        let types: Vec<u16> = blockettes.iter().map(|b| b.blockette_type()).collect();
+        assert!(types.contains(&1000));
+
+        let b201 = blockettes
+            .iter()
+            .find_map(|b| match b {
+                Blockette::MurdockEvent(b201) => Some(b201),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(b201.signal_amplitude, 80.0);
+        assert!((b201.signal_period - 0.4).abs() < 1e-6);
+        assert_eq!(b201.background_estimate, 18.0);
+        assert_eq!(b201.snr_values, [1, 3, 2, 1, 4, 0]);
+        assert_eq!(b201.lookback, 2);
+        assert_eq!(b201.detector, "Z_SPWWSS");
+    }
+
+    #[test]
+    fn test_blockettes_not_mseed2() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        assert!(rec.blockettes().is_err());
+    }
+
+    #[test]
+    fn test_blockettes_encode() {
+        let time = OffsetDateTime::parse("2012-01-01T12:30:45.123456Z", &Iso8601::DEFAULT).unwrap();
+        let blockettes = vec![
+            Blockette::SampleRate(Blockette100 {
+                sample_rate: 33.3,
+                flags: 0,
+            }),
+            Blockette::Timing(Blockette500 {
+                vco_correction: 50.0,
+                time,
+                reception_quality: 90,
+                count: 3,
+                exception_type: "MISSING".to_string(),
+                clock_model: "Quanterra Q330".to_string(),
+                clock_status: "Locked".to_string(),
+            }),
+            Blockette::Opaque(Blockette2000 {
+                record_number: 7,
+                word_order: 1,
+                flags: 0b0001_0000,
+                header_fields: vec!["LOG".to_string(), "v1".to_string()],
+                data: b"opaque payload".to_vec(),
+            }),
+            Blockette::Unknown(RawBlockette {
+                blockette_type: 405,
+                body: vec![0, 1, 0, 2],
+                byte_order: MSByteOrder::BigEndian,
+            }),
+        ];
+
+        // assemble a blockette chain following a zeroed fixed header
+        let mut buf = vec![0u8; MS2_FSDH_LEN];
+        buf[..8].copy_from_slice(b"000001D ");
+        buf[20..22].copy_from_slice(&2012u16.to_be_bytes());
+        buf[22..24].copy_from_slice(&1u16.to_be_bytes());
+        buf[39] = blockettes.len() as u8;
+        buf[46..48].copy_from_slice(&(MS2_FSDH_LEN as u16).to_be_bytes());
+        let mut prev: Option<usize> = None;
+        for b in &blockettes {
+            let offset = buf.len();
+            if let Some(prev) = prev {
+                buf[prev + 2..prev + 4].copy_from_slice(&(offset as u16).to_be_bytes());
+            }
+            buf.extend_from_slice(&b.to_bytes().unwrap());
+            prev = Some(offset);
+        }
+        let data_offset = buf.len() as u16;
+        buf[44..46].copy_from_slice(&data_offset.to_be_bytes());
+
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        assert_eq!(rec.blockettes().unwrap(), blockettes);
+
+        assert!(Blockette::DataOnly(Blockette1000 {
+            encoding: 11,
+            word_order: 1,
+            rec_len_exp: 9,
+        })
+        .to_bytes()
+        .is_err());
+
+        // the body of little-endian blockettes cannot be swapped
+        assert!(Blockette::Unknown(RawBlockette {
+            blockette_type: 405,
+            body: vec![1, 0, 2, 0],
+            byte_order: MSByteOrder::LittleEndian,
+        })
+        .to_bytes()
+        .is_err());
+    }
+
+    #[test]
+    fn test_pack_raw_with_blockettes() {
+        let mut info = crate::PackInfo::with_sample_rate("FDSN:XX_TEST__L_H_Z", 1.0).unwrap();
+        info.rec_len = 512;
+        info.blockettes = vec![
+            Blockette::SampleRate(Blockette100 {
+                sample_rate: 1.0,
+                flags: 0,
+            }),
+            Blockette::Opaque(Blockette2000 {
+                record_number: 1,
+                word_order: 1,
+                flags: 0,
+                header_fields: vec!["STATE".to_string()],
+                data: b"ok".to_vec(),
+            }),
+        ];
+
+        let start_time = OffsetDateTime::parse("2012-01-01T00:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let mut data_samples: Vec<i32> = (0..1000).map(|i| (i % 50) * 1000 - 25000).collect();
+
+        let mut records = vec![];
+        let record_handler = |rec: &[u8]| records.push(rec.to_vec());
+
+        let (cnt_records, cnt_samples) = crate::pack_raw(
+            &mut data_samples,
+            &start_time,
+            record_handler,
+            &info,
+            MSControlFlags::MSF_FLUSHDATA | MSControlFlags::MSF_PACKVER2,
+        )
+        .unwrap();
+        assert_eq!(cnt_samples, data_samples.len());
+        assert_eq!(cnt_records, records.len());
+
+        let mut unpacked = vec![];
+        for rec in records {
+            assert_eq!(rec.len(), 512);
+            let blockettes = MSRecordRef::parse(&rec).unwrap().blockettes().unwrap();
+            assert_eq!(blockettes[0].blockette_type(), 1000);
+            assert_eq!(&blockettes[blockettes.len() - 2..], &info.blockettes[..]);
+
+            let msr = MSRecord::parse(&rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+            assert_eq!(msr.format_version(), 2);
+            unpacked.extend_from_slice(msr.data_samples::<i32>().unwrap());
+        }
+        assert_eq!(unpacked, data_samples);
+
+        info.format_version = 3;
+        assert!(crate::pack_raw(
+            &mut data_samples,
+            &start_time,
+            |_: &[u8]| {},
+            &info,
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .is_err());
+    }
+}
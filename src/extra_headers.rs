@@ -0,0 +1,1053 @@
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+use std::str;
+
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+
+use crate::error::check;
+use crate::{raw, util, MSError, MSRecord, MSResult, MSSubSeconds, MSTimeFormat};
+
+const TIME_QUALITY: &str = "/FDSN/Time/Quality";
+const TIME_EXCEPTION: &str = "/FDSN/Time/Exception";
+const EVENT_DETECTION: &str = "/FDSN/Event/Detection";
+const CALIBRATION_SEQUENCE: &str = "/FDSN/Calibration/Sequence";
+const CLOCK: &str = "/FDSN/Clock";
+const RECENTER_SEQUENCE: &str = "/FDSN/Recenter/Sequence";
+
+/// Parsed miniSEED 3 extra headers.
+///
+/// Extra headers are a JSON object. Values are addressed by [JSON
+/// Pointer](https://www.rfc-editor.org/rfc/rfc6901), e.g. `/FDSN/Time/Quality`. Typed accessors
+/// are provided for the [FDSN reserved extra
+/// headers](https://docs.fdsn.org/projects/miniseed3/en/latest/definition.html#reserved-extra-headers).
+///
+/// # Examples
+///
+/// ```
+/// use mseed::{EventDetection, MSExtraHeaders};
+/// use serde_json::json;
+///
+/// let mut headers = MSExtraHeaders::new();
+/// headers.set_time_quality(80).unwrap();
+/// headers
+///     .add_event_detection(&EventDetection {
+///         detection_type: Some("MURDOCK".to_string()),
+///         signal_amplitude: Some(80.0),
+///         ..Default::default()
+///     })
+///     .unwrap();
+/// headers.set("/Operator/Comment", json!("tested")).unwrap();
+///
+/// assert_eq!(headers.time_quality(), Some(80));
+/// assert_eq!(headers.get("/Operator/Comment"), Some(&json!("tested")));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSExtraHeaders(Value);
+
+impl MSExtraHeaders {
+    /// Creates new empty extra headers.
+    pub fn new() -> Self {
+        Self(Value::Object(Map::new()))
+    }
+
+    /// Parses extra headers from a JSON encoded slice of bytes.
+    ///
+    /// An empty slice results in empty extra headers.
+    pub fn from_slice(buf: &[u8]) -> MSResult<Self> {
+        if buf.is_empty() {
+            return Ok(Self::new());
+        }
+
+        let value = serde_json::from_slice(buf)
+            .map_err(|e| MSError::from_str(&format!("invalid extra headers ({})", e)))?;
+        Self::from_value(value)
+    }
+
+    /// Creates extra headers from `value`.
+    ///
+    /// Returns an error if `value` is not a JSON object.
+    pub fn from_value(value: Value) -> MSResult<Self> {
+        if !value.is_object() {
+            return Err(MSError::from_str(
+                "invalid extra headers (not a JSON object)",
+            ));
+        }
+
+        Ok(Self(value))
+    }
+
+    /// Returns a reference to the underlying JSON value.
+    pub fn as_value(&self) -> &Value {
+        &self.0
+    }
+
+    /// Consumes the extra headers, returning the underlying JSON value.
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+
+    /// Returns whether the extra headers are empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.as_object().map(Map::is_empty).unwrap_or(true)
+    }
+
+    /// Returns the compact JSON representation of the extra headers.
+    ///
+    /// The result may be passed to [`PackInfo::extra_headers`](crate::PackInfo::extra_headers).
+    pub fn to_cstring(&self) -> MSResult<CString> {
+        CString::new(self.0.to_string()).map_err(|e| MSError::from_str(&e.to_string()))
+    }
+
+    /// Returns the value at the JSON Pointer `pointer`, if available.
+    pub fn get(&self, pointer: &str) -> Option<&Value> {
+        self.0.pointer(pointer)
+    }
+
+    /// Sets the value at the JSON Pointer `pointer` to `value`.
+    ///
+    /// Missing intermediate objects are created. Returns an error if a parent of the target is
+    /// not an object or if `pointer` is invalid. An empty `pointer` refers to the extra headers
+    /// as a whole, i.e. `value` is required to be an object.
+    pub fn set(&mut self, pointer: &str, value: Value) -> MSResult<()> {
+        if pointer.is_empty() && !value.is_object() {
+            return Err(MSError::from_str("extra headers must be a JSON object"));
+        }
+
+        *pointer_entry(&mut self.0, pointer)? = value;
+        Ok(())
+    }
+
+    /// Removes the value at the JSON Pointer `pointer` and returns it, if available.
+    pub fn remove(&mut self, pointer: &str) -> Option<Value> {
+        let (parent, key) = split_pointer(pointer)?;
+        match self.0.pointer_mut(parent)? {
+            Value::Object(obj) => obj.remove(&key),
+            Value::Array(arr) => {
+                let idx = key.parse::<usize>().ok()?;
+                (idx < arr.len()).then(|| arr.remove(idx))
+            }
+            _ => None,
+        }
+    }
+
+    /// Applies the [JSON Merge Patch](https://www.rfc-editor.org/rfc/rfc7386) `patch` to the
+    /// value at the JSON Pointer `pointer`.
+    ///
+    /// An empty `pointer` refers to the extra headers as a whole, i.e. `patch` is required to be
+    /// an object.
+    pub fn merge(&mut self, pointer: &str, patch: &Value) -> MSResult<()> {
+        if pointer.is_empty() && !patch.is_object() {
+            return Err(MSError::from_str("extra headers must be a JSON object"));
+        }
+
+        merge_patch(pointer_entry(&mut self.0, pointer)?, patch);
+        Ok(())
+    }
+
+    /// Returns the timing quality (`FDSN.Time.Quality`) in percent, if available.
+    pub fn time_quality(&self) -> Option<u8> {
+        self.get(TIME_QUALITY)
+            .and_then(Value::as_u64)
+            .and_then(|v| u8::try_from(v).ok())
+    }
+
+    /// Sets the timing quality (`FDSN.Time.Quality`) in percent.
+    pub fn set_time_quality(&mut self, quality: u8) -> MSResult<()> {
+        self.set(TIME_QUALITY, Value::from(quality))
+    }
+
+    /// Returns the timing exceptions (`FDSN.Time.Exception`).
+    pub fn time_exceptions(&self) -> MSResult<Vec<TimingException>> {
+        self.typed_array(TIME_EXCEPTION, TimingException::from_value)
+    }
+
+    /// Appends a timing exception to `FDSN.Time.Exception`.
+    pub fn add_time_exception(&mut self, exception: &TimingException) -> MSResult<()> {
+        self.push(TIME_EXCEPTION, exception.to_value()?)
+    }
+
+    /// Returns the event detections (`FDSN.Event.Detection`).
+    pub fn event_detections(&self) -> MSResult<Vec<EventDetection>> {
+        self.typed_array(EVENT_DETECTION, EventDetection::from_value)
+    }
+
+    /// Appends an event detection to `FDSN.Event.Detection`.
+    pub fn add_event_detection(&mut self, detection: &EventDetection) -> MSResult<()> {
+        self.push(EVENT_DETECTION, detection.to_value()?)
+    }
+
+    /// Returns the calibration sequences (`FDSN.Calibration.Sequence`).
+    pub fn calibrations(&self) -> MSResult<Vec<Calibration>> {
+        self.typed_array(CALIBRATION_SEQUENCE, Calibration::from_value)
+    }
+
+    /// Appends a calibration to `FDSN.Calibration.Sequence`.
+    pub fn add_calibration(&mut self, calibration: &Calibration) -> MSResult<()> {
+        self.push(CALIBRATION_SEQUENCE, calibration.to_value()?)
+    }
+
+    /// Returns the clock description (`FDSN.Clock`), if available.
+    pub fn clock(&self) -> MSResult<Option<Clock>> {
+        self.get(CLOCK).map(Clock::from_value).transpose()
+    }
+
+    /// Sets the clock description (`FDSN.Clock`).
+    pub fn set_clock(&mut self, clock: &Clock) -> MSResult<()> {
+        self.set(CLOCK, clock.to_value())
+    }
+
+    /// Returns the recenterings (`FDSN.Recenter.Sequence`).
+    pub fn recenters(&self) -> MSResult<Vec<Recenter>> {
+        self.typed_array(RECENTER_SEQUENCE, Recenter::from_value)
+    }
+
+    /// Appends a recentering to `FDSN.Recenter.Sequence`.
+    pub fn add_recenter(&mut self, recenter: &Recenter) -> MSResult<()> {
+        self.push(RECENTER_SEQUENCE, recenter.to_value()?)
+    }
+
+    fn typed_array<T, F>(&self, pointer: &str, f: F) -> MSResult<Vec<T>>
+    where
+        F: Fn(&Value) -> MSResult<T>,
+    {
+        match self.get(pointer) {
+            None => Ok(vec![]),
+            Some(Value::Array(arr)) => arr.iter().map(f).collect(),
+            Some(_) => Err(MSError::from_str(&format!(
+                "invalid extra header (not an array): {}",
+                pointer
+            ))),
+        }
+    }
+
+    fn push(&mut self, pointer: &str, value: Value) -> MSResult<()> {
+        let entry = pointer_entry(&mut self.0, pointer)?;
+        if entry.is_null() {
+            *entry = Value::Array(vec![]);
+        }
+
+        match entry {
+            Value::Array(arr) => {
+                arr.push(value);
+                Ok(())
+            }
+            _ => Err(MSError::from_str(&format!(
+                "invalid extra header (not an array): {}",
+                pointer
+            ))),
+        }
+    }
+}
+
+impl Default for MSExtraHeaders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Timing exception (`FDSN.Time.Exception`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimingException {
+    /// Time of the exception.
+    pub time: Option<OffsetDateTime>,
+    /// VCO correction, from 0 to 100%.
+    pub vco_correction: Option<f64>,
+    /// Reception quality, from 0 to 100%.
+    pub reception_quality: Option<i64>,
+    /// Count of exceptions.
+    pub count: Option<i64>,
+    /// Type of exception, e.g. `MISSING` or `UNEXPECTED`.
+    pub exception_type: Option<String>,
+    /// Description of the clock status.
+    pub clock_status: Option<String>,
+}
+
+impl TimingException {
+    fn from_value(value: &Value) -> MSResult<Self> {
+        let obj = as_object(value, TIME_EXCEPTION)?;
+        Ok(Self {
+            time: field_time(obj, "Time")?,
+            vco_correction: field_f64(obj, "VCOCorrection"),
+            reception_quality: field_i64(obj, "ReceptionQuality"),
+            count: field_i64(obj, "Count"),
+            exception_type: field_string(obj, "Type"),
+            clock_status: field_string(obj, "ClockStatus"),
+        })
+    }
+
+    fn to_value(&self) -> MSResult<Value> {
+        let mut obj = Map::new();
+        insert_time(&mut obj, "Time", self.time)?;
+        insert(&mut obj, "VCOCorrection", self.vco_correction);
+        insert(&mut obj, "ReceptionQuality", self.reception_quality);
+        insert(&mut obj, "Count", self.count);
+        insert(&mut obj, "Type", self.exception_type.as_deref());
+        insert(&mut obj, "ClockStatus", self.clock_status.as_deref());
+        Ok(Value::Object(obj))
+    }
+}
+
+/// Event detection (`FDSN.Event.Detection`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventDetection {
+    /// Detector type, e.g. `GENERIC` or `MURDOCK`.
+    pub detection_type: Option<String>,
+    /// Detector name.
+    pub detector: Option<String>,
+    /// Signal amplitude.
+    pub signal_amplitude: Option<f64>,
+    /// Signal period.
+    pub signal_period: Option<f64>,
+    /// Background estimate.
+    pub background_estimate: Option<f64>,
+    /// Detection wave, e.g. `DILATATION` or `COMPRESSION`.
+    pub wave: Option<String>,
+    /// Units of amplitude and background estimate.
+    pub units: Option<String>,
+    /// Onset time of the detection.
+    pub onset_time: Option<OffsetDateTime>,
+    /// Signal to noise ratio for the Murdock event detection.
+    pub med_snr: Option<Vec<u8>>,
+    /// Murdock event detection lookback value, 0, 1 or 2.
+    pub med_lookback: Option<i64>,
+    /// Murdock event detection pick algorithm, 0 or 1.
+    pub med_pick_algorithm: Option<i64>,
+}
+
+impl EventDetection {
+    fn from_value(value: &Value) -> MSResult<Self> {
+        let obj = as_object(value, EVENT_DETECTION)?;
+        let med_snr = obj.get("MEDSNR").and_then(Value::as_array).map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_u64().and_then(|v| u8::try_from(v).ok()))
+                .collect()
+        });
+
+        Ok(Self {
+            detection_type: field_string(obj, "Type"),
+            detector: field_string(obj, "Detector"),
+            signal_amplitude: field_f64(obj, "SignalAmplitude"),
+            signal_period: field_f64(obj, "SignalPeriod"),
+            background_estimate: field_f64(obj, "BackgroundEstimate"),
+            wave: field_string(obj, "Wave"),
+            units: field_string(obj, "Units"),
+            onset_time: field_time(obj, "OnsetTime")?,
+            med_snr,
+            med_lookback: field_i64(obj, "MEDLookback"),
+            med_pick_algorithm: field_i64(obj, "MEDPickAlgorithm"),
+        })
+    }
+
+    fn to_value(&self) -> MSResult<Value> {
+        let mut obj = Map::new();
+        insert(&mut obj, "Type", self.detection_type.as_deref());
+        insert(&mut obj, "Detector", self.detector.as_deref());
+        insert(&mut obj, "SignalAmplitude", self.signal_amplitude);
+        insert(&mut obj, "SignalPeriod", self.signal_period);
+        insert(&mut obj, "BackgroundEstimate", self.background_estimate);
+        insert(&mut obj, "Wave", self.wave.as_deref());
+        insert(&mut obj, "Units", self.units.as_deref());
+        insert_time(&mut obj, "OnsetTime", self.onset_time)?;
+        insert(&mut obj, "MEDSNR", self.med_snr.clone());
+        insert(&mut obj, "MEDLookback", self.med_lookback);
+        insert(&mut obj, "MEDPickAlgorithm", self.med_pick_algorithm);
+        Ok(Value::Object(obj))
+    }
+}
+
+/// Calibration (`FDSN.Calibration.Sequence`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Calibration {
+    /// Calibration type, e.g. `STEP`, `SINE` or `PSEUDORANDOM`.
+    pub calibration_type: Option<String>,
+    /// Begin time of the calibration.
+    pub begin_time: Option<OffsetDateTime>,
+    /// End time of the calibration.
+    pub end_time: Option<OffsetDateTime>,
+    /// Number of step calibrations.
+    pub steps: Option<i64>,
+    /// Whether the first pulse of a step calibration is positive.
+    pub step_first_pulse_positive: Option<bool>,
+    /// Whether the calibration step polarity alternates.
+    pub step_alternate_sign: Option<bool>,
+    /// Calibration trigger, e.g. `AUTOMATIC` or `MANUAL`.
+    pub trigger: Option<String>,
+    /// Whether the calibration continues from a previous record.
+    pub continued: Option<bool>,
+    /// Amplitude of the calibration signal.
+    pub amplitude: Option<f64>,
+    /// Units of the calibration input, usually volts or amps.
+    pub input_units: Option<String>,
+    /// Amplitude range, e.g. `PEAKTOPTEAK`, `ZEROTOPEAK` or `RMS`.
+    pub amplitude_range: Option<String>,
+    /// Duration of the calibration in seconds.
+    pub duration: Option<f64>,
+    /// Period of the sine calibration signal in seconds.
+    pub sine_period: Option<f64>,
+    /// Interval between calibration steps in seconds.
+    pub step_between: Option<f64>,
+    /// Channel containing the calibration input.
+    pub input_channel: Option<String>,
+    /// Reference amplitude.
+    pub reference_amplitude: Option<f64>,
+    /// Coupling of the calibrator, e.g. `Resistive` or `Capacitive`.
+    pub coupling: Option<String>,
+    /// Rolloff of the calibrator filters.
+    pub rolloff: Option<String>,
+    /// Noise characteristics, e.g. `White` or `Red`.
+    pub noise: Option<String>,
+}
+
+impl Calibration {
+    fn from_value(value: &Value) -> MSResult<Self> {
+        let obj = as_object(value, CALIBRATION_SEQUENCE)?;
+        Ok(Self {
+            calibration_type: field_string(obj, "Type"),
+            begin_time: field_time(obj, "BeginTime")?,
+            end_time: field_time(obj, "EndTime")?,
+            steps: field_i64(obj, "Steps"),
+            step_first_pulse_positive: field_bool(obj, "StepFirstPulsePositive"),
+            step_alternate_sign: field_bool(obj, "StepAlternateSign"),
+            trigger: field_string(obj, "Trigger"),
+            continued: field_bool(obj, "Continued"),
+            amplitude: field_f64(obj, "Amplitude"),
+            input_units: field_string(obj, "InputUnits"),
+            amplitude_range: field_string(obj, "AmplitudeRange"),
+            duration: field_f64(obj, "Duration"),
+            sine_period: field_f64(obj, "SinePeriod"),
+            step_between: field_f64(obj, "StepBetween"),
+            input_channel: field_string(obj, "InputChannel"),
+            reference_amplitude: field_f64(obj, "ReferenceAmplitude"),
+            coupling: field_string(obj, "Coupling"),
+            rolloff: field_string(obj, "Rolloff"),
+            noise: field_string(obj, "Noise"),
+        })
+    }
+
+    fn to_value(&self) -> MSResult<Value> {
+        let mut obj = Map::new();
+        insert(&mut obj, "Type", self.calibration_type.as_deref());
+        insert_time(&mut obj, "BeginTime", self.begin_time)?;
+        insert_time(&mut obj, "EndTime", self.end_time)?;
+        insert(&mut obj, "Steps", self.steps);
+        insert(
+            &mut obj,
+            "StepFirstPulsePositive",
+            self.step_first_pulse_positive,
+        );
+        insert(&mut obj, "StepAlternateSign", self.step_alternate_sign);
+        insert(&mut obj, "Trigger", self.trigger.as_deref());
+        insert(&mut obj, "Continued", self.continued);
+        insert(&mut obj, "Amplitude", self.amplitude);
+        insert(&mut obj, "InputUnits", self.input_units.as_deref());
+        insert(&mut obj, "AmplitudeRange", self.amplitude_range.as_deref());
+        insert(&mut obj, "Duration", self.duration);
+        insert(&mut obj, "SinePeriod", self.sine_period);
+        insert(&mut obj, "StepBetween", self.step_between);
+        insert(&mut obj, "InputChannel", self.input_channel.as_deref());
+        insert(&mut obj, "ReferenceAmplitude", self.reference_amplitude);
+        insert(&mut obj, "Coupling", self.coupling.as_deref());
+        insert(&mut obj, "Rolloff", self.rolloff.as_deref());
+        insert(&mut obj, "Noise", self.noise.as_deref());
+        Ok(Value::Object(obj))
+    }
+}
+
+/// Clock description (`FDSN.Clock`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clock {
+    /// Model of the clock.
+    pub model: Option<String>,
+}
+
+impl Clock {
+    fn from_value(value: &Value) -> MSResult<Self> {
+        let obj = as_object(value, CLOCK)?;
+        Ok(Self {
+            model: field_string(obj, "Model"),
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        let mut obj = Map::new();
+        insert(&mut obj, "Model", self.model.as_deref());
+        Value::Object(obj)
+    }
+}
+
+/// Recentering of the mass positions (`FDSN.Recenter.Sequence`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recenter {
+    /// Recentering type, e.g. `MASS` or `GIMBAL`.
+    pub recenter_type: Option<String>,
+    /// Begin time of the recentering.
+    pub begin_time: Option<OffsetDateTime>,
+    /// Estimated end time of the recentering.
+    pub end_time: Option<OffsetDateTime>,
+    /// Recentering trigger, e.g. `AUTOMATIC` or `MANUAL`.
+    pub trigger: Option<String>,
+}
+
+impl Recenter {
+    fn from_value(value: &Value) -> MSResult<Self> {
+        let obj = as_object(value, RECENTER_SEQUENCE)?;
+        Ok(Self {
+            recenter_type: field_string(obj, "Type"),
+            begin_time: field_time(obj, "BeginTime")?,
+            end_time: field_time(obj, "EndTime")?,
+            trigger: field_string(obj, "Trigger"),
+        })
+    }
+
+    fn to_value(&self) -> MSResult<Value> {
+        let mut obj = Map::new();
+        insert(&mut obj, "Type", self.recenter_type.as_deref());
+        insert_time(&mut obj, "BeginTime", self.begin_time)?;
+        insert_time(&mut obj, "EndTime", self.end_time)?;
+        insert(&mut obj, "Trigger", self.trigger.as_deref());
+        Ok(Value::Object(obj))
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for f64 {}
+    impl Sealed for i64 {}
+    impl Sealed for bool {}
+    impl Sealed for String {}
+    impl Sealed for serde_json::Value {}
+}
+
+/// Types of extra header values which may be accessed by means of [`MSRecord::get_extra_header()`]
+/// and [`MSRecord::set_extra_header()`].
+///
+/// Implemented for `f64` (JSON numbers), `i64` (JSON integers), `bool`, `String` and arbitrary
+/// [`serde_json::Value`]s.
+pub trait MSExtraHeaderValue: sealed::Sealed + Sized {
+    #[doc(hidden)]
+    fn get(msr: &MSRecord, ptr: &CStr) -> MSResult<Option<Self>>;
+
+    #[doc(hidden)]
+    fn set(&self, msr: &mut MSRecord, ptr: &CStr) -> MSResult<()>;
+}
+
+macro_rules! impl_extra_header_value {
+    ($t:ty, $type_char:literal) => {
+        impl MSExtraHeaderValue for $t {
+            fn get(msr: &MSRecord, ptr: &CStr) -> MSResult<Option<Self>> {
+                let mut value = <$t>::default();
+                let rv = unsafe {
+                    mseh_get(
+                        msr,
+                        ptr,
+                        (&mut value) as *mut $t as *mut c_void,
+                        $type_char,
+                        0,
+                    )?
+                };
+                Ok(rv.then_some(value))
+            }
+
+            fn set(&self, msr: &mut MSRecord, ptr: &CStr) -> MSResult<()> {
+                let mut value = *self;
+                unsafe { mseh_set(msr, ptr, (&mut value) as *mut $t as *mut c_void, $type_char) }
+            }
+        }
+    };
+}
+
+impl_extra_header_value!(f64, b'n');
+impl_extra_header_value!(i64, b'i');
+
+impl MSExtraHeaderValue for bool {
+    fn get(msr: &MSRecord, ptr: &CStr) -> MSResult<Option<Self>> {
+        let mut value: c_int = 0;
+        let rv = unsafe { mseh_get(msr, ptr, (&mut value) as *mut c_int as *mut c_void, b'b', 0)? };
+        Ok(rv.then_some(value != 0))
+    }
+
+    fn set(&self, msr: &mut MSRecord, ptr: &CStr) -> MSResult<()> {
+        let mut value = c_int::from(*self);
+        unsafe { mseh_set(msr, ptr, (&mut value) as *mut c_int as *mut c_void, b'b') }
+    }
+}
+
+impl MSExtraHeaderValue for String {
+    fn get(msr: &MSRecord, ptr: &CStr) -> MSResult<Option<Self>> {
+        // a string value cannot be longer than the extra headers
+        let mut buf = vec![0u8; msr.extra_headers().map_or(0, |extra| extra.len()) + 1];
+        let rv = unsafe {
+            mseh_get(
+                msr,
+                ptr,
+                buf.as_mut_ptr() as *mut c_void,
+                b's',
+                buf.len() as u32,
+            )?
+        };
+        if !rv {
+            return Ok(None);
+        }
+
+        let s = CStr::from_bytes_until_nul(&buf)
+            .map_err(|e| MSError::from_str(&e.to_string()))?
+            .to_str()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        Ok(Some(s.to_string()))
+    }
+
+    fn set(&self, msr: &mut MSRecord, ptr: &CStr) -> MSResult<()> {
+        let value = CString::new(self.as_str()).map_err(|e| MSError::from_str(&e.to_string()))?;
+        unsafe { mseh_set(msr, ptr, value.as_ptr() as *mut c_void, b's') }
+    }
+}
+
+impl MSExtraHeaderValue for Value {
+    fn get(msr: &MSRecord, ptr: &CStr) -> MSResult<Option<Self>> {
+        let ptr = ptr
+            .to_str()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        let headers = msr.parse_extra_headers()?;
+        Ok(headers.get(ptr).cloned())
+    }
+
+    fn set(&self, msr: &mut MSRecord, ptr: &CStr) -> MSResult<()> {
+        let ptr = ptr
+            .to_str()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        let mut headers = msr.parse_extra_headers()?;
+        headers.set(ptr, self.clone())?;
+        msr.set_extra_headers(&headers)
+    }
+}
+
+/// Wraps `mseh_get_ptr_r()`. Returns whether the value was found.
+unsafe fn mseh_get(
+    msr: &MSRecord,
+    ptr: &CStr,
+    value: *mut c_void,
+    type_char: u8,
+    max_len: u32,
+) -> MSResult<bool> {
+    let rv = raw::mseh_get_ptr_r(
+        msr.get_raw(),
+        ptr.as_ptr(),
+        value,
+        type_char as c_char,
+        max_len,
+        ptr::null_mut(),
+    );
+
+    Ok(check(rv)? == 0)
+}
+
+/// Wraps `mseh_set_ptr_r()`.
+unsafe fn mseh_set(
+    msr: &mut MSRecord,
+    ptr: &CStr,
+    value: *mut c_void,
+    type_char: u8,
+) -> MSResult<()> {
+    check(raw::mseh_set_ptr_r(
+        msr.get_raw_mut(),
+        ptr.as_ptr(),
+        value,
+        type_char as c_char,
+        ptr::null_mut(),
+    ))?;
+
+    Ok(())
+}
+
+fn to_pointer(ptr: &str) -> MSResult<CString> {
+    CString::new(ptr).map_err(|e| MSError::from_str(&e.to_string()))
+}
+
+impl MSRecord {
+    /// Parses the records' extra headers.
+    ///
+    /// Returns empty extra headers if the record does not have any.
+    pub fn parse_extra_headers(&self) -> MSResult<MSExtraHeaders> {
+        MSExtraHeaders::from_slice(self.extra_headers().unwrap_or_default())
+    }
+
+    /// Replaces the records' extra headers with `headers`.
+    ///
+    /// Note that the raw record (see [`MSRecord::raw()`]) is not updated. Use e.g.
+    /// [`repack_mseed3()`](crate::repack_mseed3) in order to create a record including the
+    /// modified extra headers.
+    pub fn set_extra_headers(&mut self, headers: &MSExtraHeaders) -> MSResult<()> {
+        let json = if headers.is_empty() {
+            None
+        } else {
+            Some(headers.to_cstring()?)
+        };
+        let json_ptr = json.map_or(ptr::null_mut(), CString::into_raw);
+
+        let rv = unsafe { raw::mseh_replace(self.get_raw_mut(), json_ptr) };
+        if !json_ptr.is_null() {
+            unsafe {
+                let _ = CString::from_raw(json_ptr);
+            }
+        }
+        check(rv)?;
+
+        Ok(())
+    }
+
+    /// Returns whether an extra header value exists at the JSON Pointer `ptr`.
+    pub fn extra_header_exists(&self, ptr: &str) -> bool {
+        to_pointer(ptr)
+            .and_then(|ptr| unsafe { mseh_get(self, &ptr, ptr::null_mut(), 0, 0) })
+            .unwrap_or(false)
+    }
+
+    /// Returns the extra header value at the JSON Pointer `ptr`, if available.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mseed::{MSControlFlags, MSReader};
+    ///
+    /// let mut reader = MSReader::new("path/to/data.mseed3").unwrap();
+    /// let msr = reader.next().unwrap().unwrap();
+    ///
+    /// let quality: Option<i64> = msr.get_extra_header("/FDSN/Time/Quality").unwrap();
+    /// ```
+    pub fn get_extra_header<T: MSExtraHeaderValue>(&self, ptr: &str) -> MSResult<Option<T>> {
+        T::get(self, &to_pointer(ptr)?)
+    }
+
+    /// Sets the extra header value at the JSON Pointer `ptr` to `value`.
+    ///
+    /// Missing intermediate objects are created. Note that the raw record (see
+    /// [`MSRecord::raw()`]) is not updated.
+    pub fn set_extra_header<T: MSExtraHeaderValue>(&mut self, ptr: &str, value: T) -> MSResult<()> {
+        value.set(self, &to_pointer(ptr)?)
+    }
+
+    /// Applies the [JSON Merge Patch](https://www.rfc-editor.org/rfc/rfc7386) `patch` to the
+    /// extra header value at the JSON Pointer `ptr`.
+    ///
+    /// An empty `ptr` refers to the extra headers as a whole. Note that the raw record (see
+    /// [`MSRecord::raw()`]) is not updated.
+    pub fn merge_extra_headers(&mut self, ptr: &str, patch: &Value) -> MSResult<()> {
+        let patch =
+            CString::new(patch.to_string()).map_err(|e| MSError::from_str(&e.to_string()))?;
+        unsafe { mseh_set(self, &to_pointer(ptr)?, patch.as_ptr() as *mut c_void, b'M') }
+    }
+}
+
+/// Splits a JSON Pointer into the pointer of its parent and the unescaped last reference token.
+fn split_pointer(pointer: &str) -> Option<(&str, String)> {
+    let idx = pointer.rfind('/')?;
+    let key = pointer[idx + 1..].replace("~1", "/").replace("~0", "~");
+    Some((&pointer[..idx], key))
+}
+
+/// Returns the value at `pointer`, creating missing objects on the way.
+fn pointer_entry<'a>(mut value: &'a mut Value, pointer: &str) -> MSResult<&'a mut Value> {
+    if pointer.is_empty() {
+        return Ok(value);
+    }
+    if !pointer.starts_with('/') {
+        return Err(MSError::from_str(&format!(
+            "invalid JSON Pointer: {}",
+            pointer
+        )));
+    }
+
+    for token in pointer[1..].split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        if value.is_null() {
+            *value = Value::Object(Map::new());
+        }
+
+        value = match value {
+            Value::Object(obj) => obj.entry(token).or_insert(Value::Null),
+            Value::Array(arr) => token
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| arr.get_mut(idx))
+                .ok_or_else(|| {
+                    MSError::from_str(&format!("invalid array index in JSON Pointer: {}", pointer))
+                })?,
+            _ => {
+                return Err(MSError::from_str(&format!(
+                    "invalid JSON Pointer (not an object): {}",
+                    pointer
+                )))
+            }
+        };
+    }
+
+    Ok(value)
+}
+
+/// Applies a JSON Merge Patch according to RFC 7386.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    if let Value::Object(obj) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                obj.remove(key);
+            } else {
+                merge_patch(obj.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn as_object<'a>(value: &'a Value, pointer: &str) -> MSResult<&'a Map<String, Value>> {
+    value.as_object().ok_or_else(|| {
+        MSError::from_str(&format!(
+            "invalid extra header (not an object): {}",
+            pointer
+        ))
+    })
+}
+
+fn field_string(obj: &Map<String, Value>, key: &str) -> Option<String> {
+    obj.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn field_f64(obj: &Map<String, Value>, key: &str) -> Option<f64> {
+    obj.get(key).and_then(Value::as_f64)
+}
+
+fn field_i64(obj: &Map<String, Value>, key: &str) -> Option<i64> {
+    obj.get(key).and_then(Value::as_i64)
+}
+
+fn field_bool(obj: &Map<String, Value>, key: &str) -> Option<bool> {
+    obj.get(key).and_then(Value::as_bool)
+}
+
+fn field_time(obj: &Map<String, Value>, key: &str) -> MSResult<Option<OffsetDateTime>> {
+    obj.get(key)
+        .and_then(Value::as_str)
+        .map(|s| util::timestr_to_nstime(s).and_then(util::nstime_to_time))
+        .transpose()
+}
+
+fn insert<T: Into<Value>>(obj: &mut Map<String, Value>, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        obj.insert(key.to_string(), value.into());
+    }
+}
+
+fn insert_time(
+    obj: &mut Map<String, Value>,
+    key: &str,
+    value: Option<OffsetDateTime>,
+) -> MSResult<()> {
+    if let Some(t) = value {
+        let s = util::nstime_to_string(
+            util::time_to_nstime(&t),
+            MSTimeFormat::IsoMonthDayZ,
+            MSSubSeconds::NanoMicroNone,
+        )?;
+        obj.insert(key.to_string(), Value::String(s));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::{test, MSControlFlags};
+
+    #[test]
+    fn test_extra_headers_pointer() {
+        let mut headers =
+            MSExtraHeaders::from_slice(b"{\"FDSN\":{\"Time\":{\"Quality\":100}}}").unwrap();
+        assert_eq!(headers.time_quality(), Some(100));
+        assert_eq!(headers.get("/FDSN/Time/Quality"), Some(&json!(100)));
+        assert_eq!(headers.get("/FDSN/Clock"), None);
+
+        headers.set("/FDSN/Event/Begin", json!(true)).unwrap();
+        headers.set("/Op~1Comment", json!("a/b")).unwrap();
+        assert_eq!(
+            headers.as_value(),
+            &json!({
+                "FDSN": {"Time": {"Quality": 100}, "Event": {"Begin": true}},
+                "Op/Comment": "a/b"
+            })
+        );
+        assert!(headers.set("/FDSN/Time/Quality/Value", json!(1)).is_err());
+        assert!(headers.set("FDSN", json!(1)).is_err());
+        assert!(headers.set("", json!(1)).is_err());
+        assert!(headers.set("", json!([])).is_err());
+        assert!(headers.as_value().is_object());
+
+        assert_eq!(headers.remove("/Op~1Comment"), Some(json!("a/b")));
+        assert_eq!(headers.remove("/Op~1Comment"), None);
+
+        headers
+            .merge(
+                "/FDSN",
+                &json!({"Time": {"Quality": null, "Correction": 1.5}, "Event": null}),
+            )
+            .unwrap();
+        assert_eq!(
+            headers.as_value(),
+            &json!({"FDSN": {"Time": {"Correction": 1.5}}})
+        );
+        assert_eq!(headers.time_quality(), None);
+
+        assert!(headers.merge("", &json!(1)).is_err());
+        assert!(headers.merge("", &json!(null)).is_err());
+        assert_eq!(
+            headers.as_value(),
+            &json!({"FDSN": {"Time": {"Correction": 1.5}}})
+        );
+
+        headers.merge("", &json!({"FDSN": null})).unwrap();
+        assert!(headers.is_empty());
+        assert_eq!(headers.to_cstring().unwrap().to_str().unwrap(), "{}");
+
+        assert!(MSExtraHeaders::from_slice(b"[]").is_err());
+        assert!(MSExtraHeaders::from_slice(b"{").is_err());
+        assert!(MSExtraHeaders::from_slice(b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_extra_headers_typed() {
+        let onset_time =
+            OffsetDateTime::parse("2004-07-28T20:28:06.185Z", &Iso8601::DEFAULT).unwrap();
+        let detection = EventDetection {
+            detection_type: Some("MURDOCK".to_string()),
+            detector: Some("Z_SPWWSS".to_string()),
+            signal_amplitude: Some(80.0),
+            onset_time: Some(onset_time),
+            med_snr: Some(vec![1, 3, 2, 1, 4, 0]),
+            ..Default::default()
+        };
+        let clock = Clock {
+            model: Some("Acme GPS".to_string()),
+        };
+
+        let mut headers = MSExtraHeaders::new();
+        headers.add_event_detection(&detection).unwrap();
+        headers.add_event_detection(&detection).unwrap();
+        headers.set_clock(&clock).unwrap();
+        assert_eq!(
+            headers.get("/FDSN/Event/Detection/0/OnsetTime"),
+            Some(&json!("2004-07-28T20:28:06.185000Z"))
+        );
+
+        assert_eq!(
+            headers.event_detections().unwrap(),
+            vec![detection.clone(); 2]
+        );
+        assert_eq!(headers.clock().unwrap(), Some(clock));
+        assert!(headers.calibrations().unwrap().is_empty());
+        assert!(headers.recenters().unwrap().is_empty());
+
+        headers.set(RECENTER_SEQUENCE, json!({})).unwrap();
+        assert!(headers.recenters().is_err());
+        assert!(headers.add_recenter(&Recenter::default()).is_err());
+    }
+
+    #[test]
+    fn test_record_extra_headers() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let mut msr = MSRecord::parse(&buf, MSControlFlags::empty()).unwrap();
+
+        assert!(msr.extra_header_exists("/FDSN/Time/Quality"));
+        assert!(!msr.extra_header_exists("/FDSN/Clock/Model"));
+        assert_eq!(
+            msr.get_extra_header::<i64>("/FDSN/Time/Quality").unwrap(),
+            Some(100)
+        );
+        assert_eq!(
+            msr.get_extra_header::<f64>("/FDSN/Time/Quality").unwrap(),
+            Some(100.0)
+        );
+        assert_eq!(
+            msr.get_extra_header::<String>("/FDSN/Clock/Model").unwrap(),
+            None
+        );
+        assert_eq!(
+            msr.get_extra_header::<Value>("/FDSN/Time").unwrap(),
+            Some(json!({"Quality": 100}))
+        );
+
+        msr.set_extra_header("/FDSN/Time/Quality", 80i64).unwrap();
+        msr.set_extra_header("/FDSN/Clock/Model", "Acme GPS".to_string())
+            .unwrap();
+        msr.set_extra_header("/FDSN/Event/Begin", true).unwrap();
+        msr.set_extra_header("/Operator", json!({"Comments": ["first"]}))
+            .unwrap();
+        assert_eq!(
+            msr.get_extra_header::<String>("/FDSN/Clock/Model").unwrap(),
+            Some("Acme GPS".to_string())
+        );
+        assert_eq!(
+            msr.get_extra_header::<bool>("/FDSN/Event/Begin").unwrap(),
+            Some(true)
+        );
+
+        msr.merge_extra_headers(
+            "/FDSN",
+            &json!({"Event": null, "Time": {"Correction": 1.5}}),
+        )
+        .unwrap();
+        let headers = msr.parse_extra_headers().unwrap();
+        assert_eq!(
+            headers.as_value(),
+            &json!({
+                "FDSN": {"Time": {"Quality": 80, "Correction": 1.5}, "Clock": {"Model": "Acme GPS"}},
+                "Operator": {"Comments": ["first"]}
+            })
+        );
+
+        msr.set_extra_headers(&MSExtraHeaders::new()).unwrap();
+        assert!(msr.extra_headers().is_none());
+        assert!(msr.parse_extra_headers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_record_event_detection() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-detection.record.mseed2");
+
+        let buf = fs::read(p).unwrap();
+        let msr = MSRecord::parse(&buf, MSControlFlags::empty()).unwrap();
+
+        let detections = msr
+            .parse_extra_headers()
+            .unwrap()
+            .event_detections()
+            .unwrap();
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].detection_type.as_deref(), Some("MURDOCK"));
+        assert_eq!(detections[0].detector.as_deref(), Some("Z_SPWWSS"));
+        assert_eq!(detections[0].signal_amplitude, Some(80.0));
+        assert_eq!(detections[0].med_snr, Some(vec![1, 3, 2, 1, 4, 0]));
+    }
+}
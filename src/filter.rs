@@ -0,0 +1,662 @@
+use std::fmt;
+use std::io::{BufRead, Read};
+
+use time::OffsetDateTime;
+
+use crate::{
+    MSBufStreamReader, MSDataEncoding, MSFileParam, MSRecord, MSResult, MSSelections,
+    MSStreamReader,
+};
+
+/// A predicate over the header fields of miniSEED records.
+///
+/// Filters are composed by means of [`and()`](MSRecordFilter::and),
+/// [`or()`](MSRecordFilter::or) and [`not()`](MSRecordFilter::not). Apart from the filters
+/// provided by this crate, closures of the form `Fn(&MSRecord) -> bool` and [`MSSelections`] may
+/// be used as filters, as well.
+///
+/// # Examples
+///
+/// ```
+/// use mseed::{EncodingFilter, MSDataEncoding, MSRecordFilter, PubVersionFilter, SidFilter};
+///
+/// let filter = SidFilter::new("FDSN:IU_*_*_B_H_?")
+///     .and(PubVersionFilter::new(2))
+///     .and(EncodingFilter::new(MSDataEncoding::Steim1).not());
+/// ```
+pub trait MSRecordFilter {
+    /// Returns whether `msr` matches the filter.
+    fn matches(&self, msr: &MSRecord) -> bool;
+
+    /// Returns a filter matching records which match both `self` and `other`.
+    ///
+    /// `other` is evaluated only if `self` matches.
+    fn and<F: MSRecordFilter>(self, other: F) -> AndFilter<Self, F>
+    where
+        Self: Sized,
+    {
+        AndFilter(self, other)
+    }
+
+    /// Returns a filter matching records which match either `self` or `other`.
+    ///
+    /// `other` is evaluated only if `self` does not match.
+    fn or<F: MSRecordFilter>(self, other: F) -> OrFilter<Self, F>
+    where
+        Self: Sized,
+    {
+        OrFilter(self, other)
+    }
+
+    /// Returns a filter matching records which do not match `self`.
+    fn not(self) -> NotFilter<Self>
+    where
+        Self: Sized,
+    {
+        NotFilter(self)
+    }
+}
+
+impl<F: Fn(&MSRecord) -> bool> MSRecordFilter for F {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        self(msr)
+    }
+}
+
+impl MSRecordFilter for MSSelections {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        MSSelections::matches(self, msr)
+    }
+}
+
+/// Filter matching records which match both of the inner filters.
+#[derive(Debug, Clone)]
+pub struct AndFilter<A, B>(A, B);
+
+impl<A: MSRecordFilter, B: MSRecordFilter> MSRecordFilter for AndFilter<A, B> {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        self.0.matches(msr) && self.1.matches(msr)
+    }
+}
+
+/// Filter matching records which match any of the inner filters.
+#[derive(Debug, Clone)]
+pub struct OrFilter<A, B>(A, B);
+
+impl<A: MSRecordFilter, B: MSRecordFilter> MSRecordFilter for OrFilter<A, B> {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        self.0.matches(msr) || self.1.matches(msr)
+    }
+}
+
+/// Filter matching records which do not match the inner filter.
+#[derive(Debug, Clone)]
+pub struct NotFilter<A>(A);
+
+impl<A: MSRecordFilter> MSRecordFilter for NotFilter<A> {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        !self.0.matches(msr)
+    }
+}
+
+/// Filter matching records by their [FDSN source
+/// identifier](https://docs.fdsn.org/projects/source-identifiers/).
+///
+/// The pattern is matched against the entire source identifier and may contain the following
+/// wildcards:
+/// - `*` matches zero or more characters
+/// - `?` matches any single character
+/// - `[set]` matches any single character in `set`, where `set` may contain ranges (e.g. `[a-z]`)
+///   and is negated if it starts with `!` or `^`
+/// - `\` escapes the following character
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SidFilter {
+    pattern: String,
+}
+
+impl SidFilter {
+    /// Creates a new `SidFilter` from the glob pattern `pattern`.
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+        }
+    }
+
+    /// Returns the glob pattern.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns whether `sid` matches the glob pattern.
+    pub fn matches_sid(&self, sid: &str) -> bool {
+        glob_match(self.pattern.as_bytes(), sid.as_bytes())
+    }
+}
+
+impl MSRecordFilter for SidFilter {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        self.matches_sid(&msr.sid_lossy())
+    }
+}
+
+/// Filter matching records overlapping a time window.
+///
+/// Both the start and the end of the time window are inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeWindowFilter {
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+}
+
+impl TimeWindowFilter {
+    /// Creates a new `TimeWindowFilter` from `start` and `end`.
+    pub fn new(start: OffsetDateTime, end: OffsetDateTime) -> Self {
+        Self { start, end }
+    }
+}
+
+impl MSRecordFilter for TimeWindowFilter {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        match msr.start_time() {
+            Ok(start) if start <= self.end => {}
+            _ => return false,
+        }
+
+        matches!(msr.end_time(), Ok(end) if end >= self.start)
+    }
+}
+
+/// Filter matching records with a minimum publication version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PubVersionFilter {
+    min: u8,
+}
+
+impl PubVersionFilter {
+    /// Creates a new `PubVersionFilter` matching records with a publication version of at least
+    /// `min`.
+    pub fn new(min: u8) -> Self {
+        Self { min }
+    }
+}
+
+impl MSRecordFilter for PubVersionFilter {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        msr.pub_version() >= self.min
+    }
+}
+
+/// Filter matching records by their data encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingFilter {
+    encoding: MSDataEncoding,
+}
+
+impl EncodingFilter {
+    /// Creates a new `EncodingFilter` matching records encoded with `encoding`.
+    pub fn new(encoding: MSDataEncoding) -> Self {
+        Self { encoding }
+    }
+}
+
+impl MSRecordFilter for EncodingFilter {
+    fn matches(&self, msr: &MSRecord) -> bool {
+        msr.encoding()
+            .is_ok_and(|encoding| encoding == self.encoding)
+    }
+}
+
+mod sealed {
+    /// Readers which may defer unpacking data samples.
+    pub trait Sealed {
+        /// Disables unpacking data samples while reading and returns whether unpacking was
+        /// enabled.
+        fn defer_unpacking(&mut self) -> bool;
+    }
+}
+
+impl sealed::Sealed for MSFileParam {
+    fn defer_unpacking(&mut self) -> bool {
+        self.disable_unpacking()
+    }
+}
+
+impl<R: Read> sealed::Sealed for MSStreamReader<R> {
+    fn defer_unpacking(&mut self) -> bool {
+        self.disable_unpacking()
+    }
+}
+
+impl<R: BufRead> sealed::Sealed for MSBufStreamReader<R> {
+    fn defer_unpacking(&mut self) -> bool {
+        self.disable_unpacking()
+    }
+}
+
+impl<R> sealed::Sealed for MSFilteredReader<R> {
+    fn defer_unpacking(&mut self) -> bool {
+        self.unpack
+    }
+}
+
+/// Extension trait for filtering the records read by a reader.
+///
+/// Filters are applied to the record header fields, only. If the reader was created with
+/// [`MSControlFlags::MSF_UNPACKDATA`] set, unpacking data samples is deferred until a record
+/// matches, i.e. data samples are never decoded for records which are filtered out. Filtering an
+/// already filtered reader requires records to match both filters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSDataEncoding, MSReader, MSReaderFilterExt};
+/// use time::{Duration, OffsetDateTime};
+///
+/// let end = OffsetDateTime::now_utc();
+/// let start = end - Duration::hours(1);
+///
+/// let reader = MSReader::new_with_flags("path/to/data.mseed", MSControlFlags::MSF_UNPACKDATA)
+///     .unwrap()
+///     .filter_sid("FDSN:IU_*_*_B_H_?")
+///     .time_window(start, end)
+///     .min_pub_version(2)
+///     .encoding(MSDataEncoding::Steim2);
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+pub trait MSReaderFilterExt: sealed::Sealed + Sized {
+    /// The reader wrapped by the filtered reader.
+    type Reader;
+
+    /// Returns a reader yielding only records matching `filter`.
+    fn filter_records<F: MSRecordFilter + 'static>(
+        self,
+        filter: F,
+    ) -> MSFilteredReader<Self::Reader>;
+
+    /// Returns a reader yielding only records whose source identifier matches the glob pattern
+    /// `pattern`.
+    ///
+    /// See also [`SidFilter`].
+    fn filter_sid(self, pattern: &str) -> MSFilteredReader<Self::Reader> {
+        self.filter_records(SidFilter::new(pattern))
+    }
+
+    /// Returns a reader yielding only records overlapping the time window from `start` to `end`.
+    ///
+    /// See also [`TimeWindowFilter`].
+    fn time_window(
+        self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> MSFilteredReader<Self::Reader> {
+        self.filter_records(TimeWindowFilter::new(start, end))
+    }
+
+    /// Returns a reader yielding only records with a publication version of at least `min`.
+    ///
+    /// See also [`PubVersionFilter`].
+    fn min_pub_version(self, min: u8) -> MSFilteredReader<Self::Reader> {
+        self.filter_records(PubVersionFilter::new(min))
+    }
+
+    /// Returns a reader yielding only records encoded with `encoding`.
+    ///
+    /// See also [`EncodingFilter`].
+    fn encoding(self, encoding: MSDataEncoding) -> MSFilteredReader<Self::Reader> {
+        self.filter_records(EncodingFilter::new(encoding))
+    }
+}
+
+impl MSReaderFilterExt for MSFileParam {
+    type Reader = Self;
+
+    fn filter_records<F: MSRecordFilter + 'static>(
+        self,
+        filter: F,
+    ) -> MSFilteredReader<Self::Reader> {
+        MSFilteredReader::new(self, Box::new(filter))
+    }
+}
+
+impl<R: Read> MSReaderFilterExt for MSStreamReader<R> {
+    type Reader = Self;
+
+    fn filter_records<F: MSRecordFilter + 'static>(
+        self,
+        filter: F,
+    ) -> MSFilteredReader<Self::Reader> {
+        MSFilteredReader::new(self, Box::new(filter))
+    }
+}
+
+impl<R: BufRead> MSReaderFilterExt for MSBufStreamReader<R> {
+    type Reader = Self;
+
+    fn filter_records<F: MSRecordFilter + 'static>(
+        self,
+        filter: F,
+    ) -> MSFilteredReader<Self::Reader> {
+        MSFilteredReader::new(self, Box::new(filter))
+    }
+}
+
+impl<R> MSReaderFilterExt for MSFilteredReader<R> {
+    type Reader = R;
+
+    fn filter_records<F: MSRecordFilter + 'static>(
+        self,
+        filter: F,
+    ) -> MSFilteredReader<Self::Reader> {
+        let prev = self.filter;
+        MSFilteredReader {
+            reader: self.reader,
+            filter: Box::new(move |msr: &MSRecord| prev.matches(msr) && filter.matches(msr)),
+            unpack: self.unpack,
+        }
+    }
+}
+
+/// A reader yielding only records matching a filter.
+///
+/// Created by the methods of [`MSReaderFilterExt`].
+pub struct MSFilteredReader<R> {
+    reader: R,
+    filter: Box<dyn MSRecordFilter>,
+    unpack: bool,
+}
+
+impl<R: sealed::Sealed> MSFilteredReader<R> {
+    fn new(mut reader: R, filter: Box<dyn MSRecordFilter>) -> Self {
+        let unpack = reader.defer_unpacking();
+        Self {
+            reader,
+            filter,
+            unpack,
+        }
+    }
+}
+
+impl<R> MSFilteredReader<R> {
+    /// Consumes this `MSFilteredReader`, returning the underlying reader.
+    ///
+    /// Note that unpacking data samples remains disabled for the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+}
+
+impl<R> fmt::Debug for MSFilteredReader<R>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MSFilteredReader")
+            .field("reader", &self.reader)
+            .field("unpack", &self.unpack)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> Iterator for MSFilteredReader<R>
+where
+    R: Iterator<Item = MSResult<MSRecord>>,
+{
+    type Item = MSResult<MSRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for rv in self.reader.by_ref() {
+            let mut msr = match rv {
+                Ok(msr) => msr,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if !self.filter.matches(&msr) {
+                continue;
+            }
+
+            if self.unpack {
+                if let Err(e) = msr.unpack_data() {
+                    return Some(Err(e));
+                }
+            }
+
+            return Some(Ok(msr));
+        }
+
+        None
+    }
+}
+
+/// Matches `s` against the glob pattern `pattern`.
+///
+/// Refer to [`SidFilter`] for the supported wildcards.
+pub(crate) fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // position to resume from after the most recent `*`
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        let step = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, i));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_set(pattern, p, s[i]),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == s[i]).then_some(p + 2),
+            Some(&c) => (c == s[i]).then_some(p + 1),
+            None => None,
+        };
+
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                i += 1;
+            }
+            (None, Some((bp, bi))) => {
+                backtrack = Some((bp, bi + 1));
+                p = bp;
+                i = bi + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Matches `c` against the set starting at `pattern[start]` (i.e. at `[`).
+///
+/// Returns the position following the set if `c` matches. An unterminated set matches `[`
+/// literally.
+fn match_set(pattern: &[u8], start: usize, c: u8) -> Option<usize> {
+    let mut p = start + 1;
+    let negate = matches!(pattern.get(p), Some(b'!' | b'^'));
+    if negate {
+        p += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match pattern.get(p) {
+            None => return (c == b'[').then_some(start + 1),
+            Some(b']') if !first => break,
+            Some(&lo) => {
+                if pattern.get(p + 1) == Some(&b'-')
+                    && !matches!(pattern.get(p + 2), None | Some(b']'))
+                {
+                    let hi = pattern[p + 2];
+                    matched |= lo <= c && c <= hi;
+                    p += 3;
+                } else {
+                    matched |= lo == c;
+                    p += 1;
+                }
+            }
+        }
+        first = false;
+    }
+
+    (matched != negate).then_some(p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::{test, MSControlFlags, MSReader};
+
+    #[test]
+    fn test_glob_match() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("FDSN:IU_COLA_00_L_H_Z", "FDSN:IU_COLA_00_L_H_Z", true),
+            ("FDSN:IU_COLA_00_L_H_Z", "FDSN:IU_COLA_00_L_H_1", false),
+            ("FDSN:IU_*_*_B_H_?", "FDSN:IU_ANMO_00_B_H_Z", true),
+            ("FDSN:IU_*_*_B_H_?", "FDSN:IU_ANMO__B_H_Z", true),
+            ("FDSN:IU_*_*_B_H_?", "FDSN:IU_ANMO_00_L_H_Z", false),
+            ("FDSN:IU_*_*_B_H_?", "FDSN:IU_ANMO_00_B_H_ZZ", false),
+            ("*", "", true),
+            ("*", "FDSN:XX_TEST__X_Y_Z", true),
+            ("", "FDSN:XX_TEST__X_Y_Z", false),
+            ("*_[1Z]", "FDSN:IU_COLA_00_L_H_1", true),
+            ("*_[1Z]", "FDSN:IU_COLA_00_L_H_2", false),
+            ("*_[!1Z]", "FDSN:IU_COLA_00_L_H_2", true),
+            ("*_[^1Z]", "FDSN:IU_COLA_00_L_H_Z", false),
+            ("*_[0-9]", "FDSN:IU_COLA_00_L_H_2", true),
+            ("*_[0-9]", "FDSN:IU_COLA_00_L_H_Z", false),
+            ("*_[]]", "A_]", true),
+            ("*_[a-]", "A_-", true),
+            ("A_[", "A_[", true),
+            ("A\\*", "A*", true),
+            ("A\\*", "AB", false),
+            ("*A*B*", "xxAyyBzz", true),
+            ("*A*B", "xxAyyBzz", false),
+            ("FDSN:IU_COLA_00_L_H_?*", "FDSN:IU_COLA_00_L_H_", false),
+        ];
+
+        for (pattern, s, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), s.as_bytes()),
+                *expected,
+                "pattern: {}, s: {}",
+                pattern,
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_filter_sid() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let reader = MSReader::new_with_flags(p, MSControlFlags::MSF_UNPACKDATA)
+            .unwrap()
+            .filter_sid("FDSN:IU_COLA_00_L_H_[1Z]");
+
+        let mut cnt = 0;
+        for msr in reader {
+            let msr = msr.unwrap();
+            assert!(!msr.sid().unwrap().ends_with('2'));
+            assert_eq!(msr.num_samples(), msr.sample_cnt());
+            cnt += 1;
+        }
+        assert!(cnt > 0);
+    }
+
+    #[test]
+    fn test_filter_deferred_unpacking() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let not_unpacked = |msr: &MSRecord| {
+            assert_eq!(msr.num_samples(), 0);
+            true
+        };
+        let reader = MSReader::new_with_flags(p.clone(), MSControlFlags::MSF_UNPACKDATA)
+            .unwrap()
+            .filter_records(not_unpacked);
+        for msr in reader {
+            let msr = msr.unwrap();
+            assert_eq!(msr.num_samples(), msr.sample_cnt());
+        }
+
+        let reader = MSReader::new(p)
+            .unwrap()
+            .filter_records(SidFilter::new("*_Z"));
+        for msr in reader {
+            assert_eq!(msr.unwrap().num_samples(), 0);
+        }
+    }
+
+    #[test]
+    fn test_filter_combinators() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let start = OffsetDateTime::parse("2010-02-27T07:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let end = OffsetDateTime::parse("2010-02-27T08:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let filter = SidFilter::new("*_1")
+            .or(SidFilter::new("*_2"))
+            .and(TimeWindowFilter::new(start, end))
+            .and(EncodingFilter::new(MSDataEncoding::Steim1).not());
+
+        let all: Vec<MSRecord> = MSReader::new(p.clone())
+            .unwrap()
+            .map(|msr| msr.unwrap())
+            .collect();
+        let expected: Vec<(String, OffsetDateTime)> = all
+            .iter()
+            .filter(|msr| {
+                let sid = msr.sid().unwrap();
+                (sid.ends_with('1') || sid.ends_with('2'))
+                    && msr.start_time().unwrap() <= end
+                    && msr.end_time().unwrap() >= start
+                    && msr.encoding().unwrap() != MSDataEncoding::Steim1
+            })
+            .map(|msr| (msr.sid().unwrap(), msr.start_time().unwrap()))
+            .collect();
+        assert!(!expected.is_empty());
+
+        let filtered: Vec<(String, OffsetDateTime)> = MSReader::new(p.clone())
+            .unwrap()
+            .filter_records(filter)
+            .map(|msr| {
+                let msr = msr.unwrap();
+                (msr.sid().unwrap(), msr.start_time().unwrap())
+            })
+            .collect();
+        assert_eq!(filtered, expected);
+
+        let chained: Vec<(String, OffsetDateTime)> = MSReader::new(p)
+            .unwrap()
+            .filter_sid("*_[12]")
+            .time_window(start, end)
+            .min_pub_version(1)
+            .filter_records(|msr: &MSRecord| msr.encoding().unwrap() != MSDataEncoding::Steim1)
+            .map(|msr| {
+                let msr = msr.unwrap();
+                (msr.sid().unwrap(), msr.start_time().unwrap())
+            })
+            .collect();
+        assert_eq!(chained, expected);
+    }
+}
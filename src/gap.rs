@@ -0,0 +1,438 @@
+use time::{Duration, OffsetDateTime};
+
+use crate::{raw, util, MSError, MSRecordBuilder, MSResult, MSTolerance, MSTraceId, MSTraceList};
+
+/// Describes the discontinuity between two consecutive trace segments of a trace identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GapInfo {
+    /// The [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    pub sid: String,
+    /// The publication version.
+    pub pub_version: u8,
+    /// The time of the last sample of the previous trace segment.
+    pub prev_end: OffsetDateTime,
+    /// The time of the first sample of the next trace segment.
+    pub next_start: OffsetDateTime,
+    /// The absolute time difference between `prev_end` and `next_start`.
+    ///
+    /// For overlaps, the duration is limited to the coverage of the next trace segment.
+    pub duration: Duration,
+    /// The number of missing samples (gaps) or overlapping samples (overlaps), respectively.
+    pub sample_cnt: i64,
+    /// Whether the next trace segment starts within the time tolerance of the time expected.
+    pub within_tolerance: bool,
+}
+
+/// A gap or an overlap between two consecutive trace segments.
+///
+/// Corresponds to the entries printed by `mstl3_printgaplist()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MSGap {
+    /// The next trace segment starts after the time expected, i.e. samples are missing.
+    Gap(GapInfo),
+    /// The next trace segment starts before the time expected, i.e. samples overlap.
+    Overlap(GapInfo),
+}
+
+impl MSGap {
+    /// Returns the description of the gap or overlap.
+    pub fn info(&self) -> &GapInfo {
+        match self {
+            Self::Gap(info) | Self::Overlap(info) => info,
+        }
+    }
+
+    /// Returns whether the entry is an overlap.
+    pub fn is_overlap(&self) -> bool {
+        matches!(self, Self::Overlap(_))
+    }
+}
+
+/// Data availability of a trace identifier within a time window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Availability {
+    /// The [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    pub sid: String,
+    /// The publication version.
+    pub pub_version: u8,
+    /// The start of the time window.
+    pub start: OffsetDateTime,
+    /// The end of the time window.
+    pub end: OffsetDateTime,
+    /// The percentage of the time window covered by data samples.
+    pub coverage: f64,
+    /// The longest period within the time window not covered by data samples.
+    pub longest_gap: Duration,
+    /// The number of trace segments intersecting the time window.
+    pub num_segments: usize,
+}
+
+impl MSTraceId {
+    /// Returns the gaps and overlaps between the trace segments of the trace identifier.
+    ///
+    /// Whether an entry is within tolerance is determined by means of the libmseed default time
+    /// tolerance, i.e. half a sample period.
+    pub fn gaps(&self) -> MSResult<Vec<MSGap>> {
+        self.gaps_impl(None)
+    }
+
+    /// Returns the gaps and overlaps between the trace segments of the trace identifier,
+    /// determining whether an entry is within tolerance by means of `tolerance`.
+    ///
+    /// The tolerance closures are called with a header-only record describing the next trace
+    /// segment.
+    pub fn gaps_with_tolerance(&self, tolerance: &MSTolerance) -> MSResult<Vec<MSGap>> {
+        self.gaps_impl(Some(tolerance))
+    }
+
+    fn gaps_impl(&self, tolerance: Option<&MSTolerance>) -> MSResult<Vec<MSGap>> {
+        let sid = self.sid()?;
+        let pub_version = self.pub_version();
+        let segments = self.segments()?;
+
+        let mut rv = vec![];
+        for pair in segments.windows(2) {
+            let (prev, next) = (&pair[0], &pair[1]);
+            // skip segments without sample rate, usually from SOH records
+            if prev.sample_rate == 0.0 {
+                continue;
+            }
+
+            let (time_tolerance, sample_rate_tolerance) = match tolerance {
+                Some(tolerance) => {
+                    let msr = MSRecordBuilder::new()
+                        .sid(&sid)
+                        .pub_version(pub_version)
+                        .start_time(util::nstime_to_time(next.start)?)
+                        .sample_rate(next.sample_rate)
+                        .build()?;
+                    (
+                        tolerance.eval_time(&msr).unwrap_or(-1.0),
+                        tolerance.eval_sample_rate(&msr).unwrap_or(-1.0),
+                    )
+                }
+                None => (-1.0, -1.0),
+            };
+
+            if !is_rate_tolerable(prev.sample_rate, next.sample_rate, sample_rate_tolerance) {
+                continue;
+            }
+
+            let mut gap = next.start - prev.end;
+            // check that any overlap is not larger than the trace coverage
+            if gap < 0 {
+                let coverage = next.end - next.start + sample_period(next.sample_rate);
+                if -gap > coverage {
+                    gap = -coverage;
+                }
+            }
+            let duration = Duration::nanoseconds(gap.abs());
+            let gap = gap as f64 / raw::NSTMODULUS as f64;
+
+            let sample_cnt = if gap > 0.0 {
+                (gap * prev.sample_rate).round() as i64 - 1
+            } else {
+                (-gap * prev.sample_rate).round() as i64 + 1
+            };
+
+            let period = 1.0 / prev.sample_rate;
+            let time_tolerance = if time_tolerance < 0.0 {
+                0.5 * period
+            } else {
+                time_tolerance
+            };
+
+            let info = GapInfo {
+                sid: sid.clone(),
+                pub_version,
+                prev_end: util::nstime_to_time(prev.end)?,
+                next_start: util::nstime_to_time(next.start)?,
+                duration,
+                sample_cnt: sample_cnt.max(0),
+                within_tolerance: (gap - period).abs() <= time_tolerance,
+            };
+            rv.push(if gap < 0.0 {
+                MSGap::Overlap(info)
+            } else {
+                MSGap::Gap(info)
+            });
+        }
+
+        Ok(rv)
+    }
+
+    /// Returns the data availability of the trace identifier within the time window from
+    /// `start` to `end`.
+    ///
+    /// A data sample is considered to cover a sample period.
+    pub fn availability(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> MSResult<Availability> {
+        let (window_start, window_end) = (util::time_to_nstime(&start), util::time_to_nstime(&end));
+        if window_start >= window_end {
+            return Err(MSError::from_str("empty time window"));
+        }
+
+        let mut num_segments = 0;
+        let mut covered = 0;
+        let mut longest_gap = 0;
+        let mut cursor = window_start;
+        for seg in self.segments()? {
+            let seg_end = seg.end + sample_period(seg.sample_rate);
+            if seg_end <= window_start || seg.start >= window_end {
+                continue;
+            }
+            num_segments += 1;
+
+            let seg_start = seg.start.max(window_start);
+            let seg_end = seg_end.min(window_end);
+            if seg_start > cursor {
+                longest_gap = longest_gap.max(seg_start - cursor);
+            }
+            if seg_end > cursor {
+                covered += seg_end - seg_start.max(cursor);
+                cursor = seg_end;
+            }
+        }
+        longest_gap = longest_gap.max(window_end - cursor);
+
+        Ok(Availability {
+            sid: self.sid()?,
+            pub_version: self.pub_version(),
+            start,
+            end,
+            coverage: 100.0 * covered as f64 / (window_end - window_start) as f64,
+            longest_gap: Duration::nanoseconds(longest_gap),
+            num_segments,
+        })
+    }
+
+    /// Returns the time ranges of the trace segments ordered by start time.
+    fn segments(&self) -> MSResult<Vec<SegmentRange>> {
+        let mut rv = self
+            .iter()
+            .map(|seg| {
+                Ok(SegmentRange {
+                    start: util::time_to_nstime(&seg.start_time()?),
+                    end: util::time_to_nstime(&seg.end_time()?),
+                    sample_rate: seg.sample_rate_hz(),
+                })
+            })
+            .collect::<MSResult<Vec<_>>>()?;
+        rv.sort_by_key(|seg| (seg.start, seg.end));
+
+        Ok(rv)
+    }
+}
+
+impl MSTraceList {
+    /// Returns the gaps and overlaps of all trace identifiers of the trace list.
+    ///
+    /// See also [`MSTraceId::gaps()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mseed::{MSControlFlags, MSGap, MSTraceList};
+    ///
+    /// let mstl = MSTraceList::read("path/to/data.mseed", MSControlFlags::empty()).unwrap();
+    ///
+    /// for gap in mstl.gaps().unwrap() {
+    ///     if let MSGap::Gap(info) = gap {
+    ///         println!("{}: {} samples missing", info.sid, info.sample_cnt);
+    ///     }
+    /// }
+    /// ```
+    pub fn gaps(&self) -> MSResult<Vec<MSGap>> {
+        let mut rv = vec![];
+        for tid in self.iter() {
+            rv.extend(tid.gaps()?);
+        }
+
+        Ok(rv)
+    }
+
+    /// Returns the gaps and overlaps of all trace identifiers of the trace list, determining
+    /// whether an entry is within tolerance by means of `tolerance`.
+    ///
+    /// See also [`MSTraceId::gaps_with_tolerance()`].
+    pub fn gaps_with_tolerance(&self, tolerance: &MSTolerance) -> MSResult<Vec<MSGap>> {
+        let mut rv = vec![];
+        for tid in self.iter() {
+            rv.extend(tid.gaps_with_tolerance(tolerance)?);
+        }
+
+        Ok(rv)
+    }
+
+    /// Returns the data availability of each trace identifier of the trace list within the time
+    /// window from `start` to `end`.
+    ///
+    /// See also [`MSTraceId::availability()`].
+    pub fn availability(
+        &self,
+        start: OffsetDateTime,
+        end: OffsetDateTime,
+    ) -> MSResult<Vec<Availability>> {
+        self.iter()
+            .map(|tid| tid.availability(start, end))
+            .collect()
+    }
+}
+
+/// The time range of a trace segment in nanosecond times.
+struct SegmentRange {
+    start: i64,
+    end: i64,
+    sample_rate: f64,
+}
+
+/// Returns the sample period in nanoseconds, or zero if the sample rate is not positive.
+fn sample_period(sample_rate: f64) -> i64 {
+    if sample_rate > 0.0 {
+        (raw::NSTMODULUS as f64 / sample_rate).round() as i64
+    } else {
+        0
+    }
+}
+
+/// Checks whether the sample rates `a` and `b` are tolerable.
+///
+/// If `tolerance` is negative, corresponds to `MS_ISRATETOLERABLE()`.
+fn is_rate_tolerable(a: f64, b: f64, tolerance: f64) -> bool {
+    if tolerance < 0.0 {
+        (1.0 - a / b).abs() < 0.0001
+    } else {
+        (a - b).abs() <= tolerance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{rewrite_header, test, MSControlFlags, MSRecord};
+
+    /// Returns the records of `f` ordered by start time.
+    fn sorted_records(f: &str) -> Vec<(OffsetDateTime, i64, Vec<u8>)> {
+        let mut rv: Vec<_> = test::records(f)
+            .into_iter()
+            .map(|rec| {
+                let msr = MSRecord::parse(&rec, MSControlFlags::empty()).unwrap();
+                (msr.start_time().unwrap(), msr.sample_cnt(), rec)
+            })
+            .collect();
+        rv.sort_by_key(|(start_time, _, _)| *start_time);
+
+        rv
+    }
+
+    #[test]
+    fn test_gaps_none() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mstl = MSTraceList::read(p, MSControlFlags::empty()).unwrap();
+        assert_eq!(mstl.gaps().unwrap(), vec![]);
+
+        let tid = mstl.iter().next().unwrap();
+        let (start, end) = (tid.start_time().unwrap(), tid.end_time().unwrap());
+        let availability = mstl
+            .availability(start, end + Duration::seconds(1))
+            .unwrap();
+        assert_eq!(availability.len(), 3);
+        for (a, sid) in availability.iter().zip([
+            "FDSN:IU_COLA_00_L_H_1",
+            "FDSN:IU_COLA_00_L_H_2",
+            "FDSN:IU_COLA_00_L_H_Z",
+        ]) {
+            assert_eq!(a.sid, sid);
+            assert_eq!(a.coverage, 100.0);
+            assert_eq!(a.longest_gap, Duration::ZERO);
+            assert_eq!(a.num_segments, 1);
+        }
+
+        // the window exceeds the data
+        let availability = tid
+            .availability(start - Duration::seconds(4200), end + Duration::seconds(1))
+            .unwrap();
+        assert_eq!(availability.coverage, 50.0);
+        assert_eq!(availability.longest_gap, Duration::seconds(4200));
+        assert_eq!(availability.num_segments, 1);
+
+        assert!(tid.availability(end, start).is_err());
+    }
+
+    #[test]
+    fn test_gaps_missing_record() {
+        for f in [
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+        ] {
+            let mut records = sorted_records(f);
+            let (first_start, _, _) = records[0];
+            let (missing_start, missing_cnt, _) = records.remove(records.len() / 2);
+            let buf: Vec<u8> = records.into_iter().flat_map(|(_, _, rec)| rec).collect();
+
+            let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::empty()).unwrap();
+            let gaps = mstl.gaps().unwrap();
+            assert_eq!(gaps.len(), 1);
+            assert!(!gaps[0].is_overlap());
+
+            let info = gaps[0].info();
+            assert_eq!(info.sid, "FDSN:XX_TEST_00_L_H_Z");
+            assert_eq!(info.prev_end, missing_start - Duration::seconds(1));
+            assert_eq!(
+                info.next_start,
+                missing_start + Duration::seconds(missing_cnt)
+            );
+            assert_eq!(info.duration, Duration::seconds(missing_cnt + 1));
+            assert_eq!(info.sample_cnt, missing_cnt);
+            assert!(!info.within_tolerance);
+
+            let availability = mstl
+                .availability(first_start, first_start + Duration::seconds(3952))
+                .unwrap();
+            assert_eq!(availability.len(), 1);
+            let availability = &availability[0];
+            assert_eq!(
+                availability.coverage,
+                100.0 * (3952 - missing_cnt) as f64 / 3952.0
+            );
+            assert_eq!(availability.longest_gap, Duration::seconds(missing_cnt));
+            assert_eq!(availability.num_segments, 2);
+        }
+    }
+
+    #[test]
+    fn test_gaps_with_tolerance() {
+        let mut records = sorted_records("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+        // shift the start time of a record by 700 ms
+        let idx = records.len() / 2;
+        records[idx].2 = rewrite_header(&records[idx].2, |msr| {
+            msr.apply_time_correction(Duration::milliseconds(700))
+        })
+        .unwrap();
+        let buf: Vec<u8> = records.into_iter().flat_map(|(_, _, rec)| rec).collect();
+
+        let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::empty()).unwrap();
+        let gaps = mstl.gaps().unwrap();
+        assert_eq!(gaps.len(), 2);
+        assert!(gaps.iter().all(|gap| !gap.info().within_tolerance));
+        assert_eq!(gaps[0].info().duration, Duration::milliseconds(1700));
+        assert_eq!(gaps[0].info().sample_cnt, 1);
+        assert_eq!(gaps[1].info().duration, Duration::milliseconds(300));
+        assert_eq!(gaps[1].info().sample_cnt, 0);
+
+        let tolerance = MSTolerance::new().time(1.0);
+        let gaps = mstl.gaps_with_tolerance(&tolerance).unwrap();
+        assert_eq!(gaps.len(), 2);
+        assert!(gaps.iter().all(|gap| gap.info().within_tolerance));
+    }
+}
@@ -0,0 +1,567 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::iter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+
+use crate::mmap::build_index;
+use crate::{MSControlFlags, MSError, MSRecord, MSRecordIndexEntry, MSResult};
+
+/// Magic bytes identifying a persisted archive index.
+const MAGIC: &[u8; 4] = b"MSIX";
+/// Version of the on-disk format of archive indexes.
+const FORMAT_VERSION: u32 = 1;
+
+/// Indexed state of a single file of an archive.
+#[derive(Debug, Clone, PartialEq)]
+struct FileEntry {
+    /// Modification time of the file as nanoseconds since the Unix epoch.
+    mtime: i128,
+    /// Size of the file in bytes.
+    size: u64,
+    /// Index entries of the records of the file.
+    records: Vec<MSRecordIndexEntry>,
+}
+
+/// A byte range of an archive file containing records matching a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MSIndexRange {
+    /// Path to the file.
+    pub path: PathBuf,
+    /// Byte range within the file.
+    pub range: Range<u64>,
+}
+
+/// A persistent index of the miniSEED records of an archive directory tree.
+///
+/// The index records per file the [`MSRecordIndexEntry`] of each record, i.e. the source
+/// identifier, publication version, time span, sample rate, byte offset, record length and CRC.
+/// It is updated incrementally, i.e. only files which were added or whose modification time or
+/// size changed are re-scanned (see [`MSArchiveIndex::update()`]). Files which cannot be
+/// identified as miniSEED are indexed without records.
+///
+/// Indexes are persisted by means of a compact binary format (see [`MSArchiveIndex::save()`]
+/// and [`MSArchiveIndex::load()`]).
+///
+/// # Examples
+///
+/// ```no_run
+/// use time::{Duration, OffsetDateTime};
+///
+/// use mseed::{MSArchiveIndex, MSControlFlags};
+///
+/// let index_path = "path/to/archive.idx";
+/// let mut index =
+///     MSArchiveIndex::load(index_path).unwrap_or_else(|_| MSArchiveIndex::new("path/to/archive"));
+/// index.update().unwrap();
+/// index.save(index_path).unwrap();
+///
+/// let end = OffsetDateTime::now_utc();
+/// let start = end - Duration::hours(1);
+/// for r in index.byte_ranges("FDSN:XX_STA__B_H_Z", &start, &end) {
+///     println!("{}: {:?}", r.path.display(), r.range);
+/// }
+///
+/// for msr in index.read_records("FDSN:XX_STA__B_H_Z", &start, &end, MSControlFlags::MSF_UNPACKDATA) {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSArchiveIndex {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, FileEntry>,
+}
+
+impl MSArchiveIndex {
+    /// Creates a new empty index for the archive directory tree at `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the root directory of the archive.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns an iterator over the paths of the files indexed, relative to the root directory.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(|p| p.as_path())
+    }
+
+    /// Returns the index entries of the records of the file at `path`, relative to the root
+    /// directory.
+    pub fn records<P: AsRef<Path>>(&self, path: P) -> Option<&[MSRecordIndexEntry]> {
+        self.files.get(path.as_ref()).map(|f| f.records.as_slice())
+    }
+
+    /// Walks the archive directory tree and updates the index.
+    ///
+    /// Files which were added or whose modification time or size changed are re-scanned, files
+    /// which were removed are dropped from the index. Returns the number of files re-scanned.
+    ///
+    /// If updating fails, the index is left unchanged.
+    pub fn update(&mut self) -> MSResult<usize> {
+        let mut paths = Vec::new();
+        walk(&self.root, &mut paths).map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        let mut present = BTreeSet::new();
+        let mut rescanned = BTreeMap::new();
+        for path in paths {
+            let meta = fs::metadata(&path).map_err(|e| MSError::from_str(&e.to_string()))?;
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos() as i128);
+            let size = meta.len();
+
+            let rel = path.strip_prefix(&self.root).unwrap().to_path_buf();
+            match self.files.get(&rel) {
+                Some(entry) if entry.mtime == mtime && entry.size == size => {}
+                _ => {
+                    let entry = FileEntry {
+                        mtime,
+                        size,
+                        records: index_file(&path)?,
+                    };
+                    rescanned.insert(rel.clone(), entry);
+                }
+            }
+
+            present.insert(rel);
+        }
+
+        let cnt = rescanned.len();
+        self.files.retain(|path, _| present.contains(path));
+        self.files.extend(rescanned);
+
+        Ok(cnt)
+    }
+
+    /// Returns an iterator over the files and index entries of the records of `sid` overlapping
+    /// with the time window from `start` to `end` (inclusive).
+    pub fn query<'a>(
+        &'a self,
+        sid: &'a str,
+        start: &'a time::OffsetDateTime,
+        end: &'a time::OffsetDateTime,
+    ) -> impl Iterator<Item = (PathBuf, &'a MSRecordIndexEntry)> + 'a {
+        self.files.iter().flat_map(move |(path, f)| {
+            f.records
+                .iter()
+                .filter(move |entry| entry.sid == sid && entry.overlaps(start, end))
+                .map(move |entry| (self.root.join(path), entry))
+        })
+    }
+
+    /// Returns the byte ranges containing records of `sid` overlapping with the time window from
+    /// `start` to `end` (inclusive).
+    ///
+    /// Byte ranges of records which are adjacent within a file are merged.
+    pub fn byte_ranges(
+        &self,
+        sid: &str,
+        start: &time::OffsetDateTime,
+        end: &time::OffsetDateTime,
+    ) -> Vec<MSIndexRange> {
+        let mut rv: Vec<MSIndexRange> = Vec::new();
+        for (path, entry) in self.query(sid, start, end) {
+            let range = entry.byte_range();
+            match rv.last_mut() {
+                Some(last) if last.path == path && last.range.end == range.start => {
+                    last.range.end = range.end;
+                }
+                _ => rv.push(MSIndexRange { path, range }),
+            }
+        }
+
+        rv
+    }
+
+    /// Returns an iterator reading and parsing the records of `sid` overlapping with the time
+    /// window from `start` to `end` (inclusive) with control flags `flags`.
+    ///
+    /// Each record owns a copy of its raw record (see [`MSRecord::raw()`]).
+    pub fn read_records<'a>(
+        &'a self,
+        sid: &'a str,
+        start: &'a time::OffsetDateTime,
+        end: &'a time::OffsetDateTime,
+        flags: MSControlFlags,
+    ) -> impl Iterator<Item = MSResult<MSRecord>> + 'a {
+        let mut entries = self.query(sid, start, end);
+        let mut file: Option<(PathBuf, File)> = None;
+        let mut buf = Vec::new();
+
+        iter::from_fn(move || {
+            let (path, entry) = entries.next()?;
+
+            let rv = (|| -> io::Result<()> {
+                let f = match file {
+                    Some((ref p, ref mut f)) if *p == path => f,
+                    _ => {
+                        let f = File::open(&path)?;
+                        &mut file.insert((path, f)).1
+                    }
+                };
+
+                buf.resize(entry.rec_len as usize, 0);
+                f.seek(SeekFrom::Start(entry.offset))?;
+                f.read_exact(&mut buf)
+            })();
+
+            Some(
+                rv.map_err(|e| MSError::from_str(&e.to_string()))
+                    .and_then(|_| MSRecord::parse_owned(&buf, flags)),
+            )
+        })
+    }
+
+    /// Loads a persisted index from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> MSResult<Self> {
+        let file = File::open(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+        read_index(&mut BufReader::new(file)).map_err(|e| MSError::from_str(&e.to_string()))
+    }
+
+    /// Persists the index to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> MSResult<()> {
+        let file = File::create(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+        let mut writer = BufWriter::new(file);
+        write_index(self, &mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+}
+
+/// Recursively collects the paths of the regular files below `dir`, sorted by name.
+fn walk(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, paths)?;
+        } else if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates the index entries of the records of the file at `path`.
+///
+/// Data which cannot be identified as miniSEED and corrupt records are skipped, i.e. files
+/// which cannot be identified as miniSEED are indexed without records.
+fn index_file(path: &Path) -> MSResult<Vec<MSRecordIndexEntry>> {
+    let file = File::open(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+    // SAFETY: the file must not be modified while mapped
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    build_index(&mmap, MSControlFlags::MSF_SKIPNOTDATA)
+}
+
+fn write_index<W: Write>(index: &MSArchiveIndex, w: &mut W) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    write_str(w, path_to_str(&index.root)?)?;
+
+    w.write_all(&(index.files.len() as u64).to_le_bytes())?;
+    for (path, f) in &index.files {
+        write_str(w, path_to_str(path)?)?;
+        w.write_all(&f.mtime.to_le_bytes())?;
+        w.write_all(&f.size.to_le_bytes())?;
+
+        w.write_all(&(f.records.len() as u64).to_le_bytes())?;
+        for entry in &f.records {
+            write_str(w, &entry.sid)?;
+            w.write_all(&[entry.pub_version])?;
+            w.write_all(&entry.start_time.unix_timestamp_nanos().to_le_bytes())?;
+            w.write_all(&entry.end_time.unix_timestamp_nanos().to_le_bytes())?;
+            w.write_all(&entry.sample_rate.to_le_bytes())?;
+            w.write_all(&entry.offset.to_le_bytes())?;
+            w.write_all(&entry.rec_len.to_le_bytes())?;
+            w.write_all(&entry.crc.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_index<R: Read>(r: &mut R) -> io::Result<MSArchiveIndex> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not an archive index"));
+    }
+    let version = u32::from_le_bytes(read_array(r)?);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(&format!(
+            "unsupported archive index version: {}",
+            version
+        )));
+    }
+
+    let root = PathBuf::from(read_str(r)?);
+
+    let num_files = u64::from_le_bytes(read_array(r)?);
+    let mut files = BTreeMap::new();
+    for _ in 0..num_files {
+        let path = PathBuf::from(read_str(r)?);
+        let mtime = i128::from_le_bytes(read_array(r)?);
+        let size = u64::from_le_bytes(read_array(r)?);
+
+        let num_records = u64::from_le_bytes(read_array(r)?);
+        let mut records = Vec::new();
+        for _ in 0..num_records {
+            let sid = read_str(r)?;
+            let [pub_version] = read_array(r)?;
+            let start_time = read_time(r)?;
+            let end_time = read_time(r)?;
+            let sample_rate = f64::from_le_bytes(read_array(r)?);
+            let offset = u64::from_le_bytes(read_array(r)?);
+            let rec_len = u64::from_le_bytes(read_array(r)?);
+            let crc = u32::from_le_bytes(read_array(r)?);
+
+            records.push(MSRecordIndexEntry {
+                offset,
+                rec_len,
+                sid,
+                start_time,
+                end_time,
+                pub_version,
+                sample_rate,
+                crc,
+            });
+        }
+
+        files.insert(
+            path,
+            FileEntry {
+                mtime,
+                size,
+                records,
+            },
+        );
+    }
+
+    Ok(MSArchiveIndex { root, files })
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn path_to_str(path: &Path) -> io::Result<&str> {
+    path.to_str()
+        .ok_or_else(|| invalid_data(&format!("invalid path: {}", path.display())))
+}
+
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_str<R: Read>(r: &mut R) -> io::Result<String> {
+    let len = u32::from_le_bytes(read_array(r)?) as usize;
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|e| invalid_data(&e.to_string()))
+}
+
+fn read_time<R: Read>(r: &mut R) -> io::Result<time::OffsetDateTime> {
+    let nst = i128::from_le_bytes(read_array(r)?);
+    time::OffsetDateTime::from_unix_timestamp_nanos(nst).map_err(|e| invalid_data(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::test::{self, ScratchDir};
+
+    /// Creates a scratch archive directory containing a copy of `files`.
+    fn create_archive(name: &str, files: &[(&str, &str)]) -> ScratchDir {
+        let root = test::scratch_dir(name);
+
+        for (src, dst) in files {
+            let mut p = test::test_data_base_dir();
+            p.push(src);
+
+            let dst = root.join(dst);
+            fs::create_dir_all(dst.parent().unwrap()).unwrap();
+            fs::copy(p, dst).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn test_archive_index_update_save_load() {
+        let root = create_archive(
+            "index-update",
+            &[
+                ("reference-testdata-steim2.mseed3", "a/steim2.mseed"),
+                ("testdata-3channel-signal.mseed2", "b/c/signal.mseed"),
+                ("testdata-3channel-signal.mseed3", "b/signal.mseed"),
+            ],
+        );
+        fs::write(root.join("README"), b"not miniSEED").unwrap();
+
+        let mut index = MSArchiveIndex::new(&root);
+        assert_eq!(index.update().unwrap(), 4);
+        assert_eq!(index.update().unwrap(), 0);
+
+        let files: Vec<&Path> = index.files().collect();
+        assert_eq!(
+            files,
+            vec![
+                Path::new("README"),
+                Path::new("a/steim2.mseed"),
+                Path::new("b/c/signal.mseed"),
+                Path::new("b/signal.mseed"),
+            ]
+        );
+        assert!(index.records("README").unwrap().is_empty());
+
+        let records = index.records("b/signal.mseed").unwrap();
+        assert_eq!(records.len(), 107);
+        assert_eq!(records[0].sid, "FDSN:IU_COLA_00_L_H_1");
+        assert_eq!(records[106].sid, "FDSN:IU_COLA_00_L_H_Z");
+        assert_eq!(records[0].pub_version, 4);
+        assert_eq!(records[0].crc, 0x4F3EAB65);
+
+        let index_dir = test::scratch_dir("index-update-idx");
+        let index_path = index_dir.join("archive.idx");
+        index.save(&index_path).unwrap();
+        let loaded = MSArchiveIndex::load(&index_path).unwrap();
+        assert_eq!(loaded, index);
+
+        // incremental update
+        fs::remove_file(root.join("b/c/signal.mseed")).unwrap();
+        fs::write(root.join("README"), b"still not miniSEED").unwrap();
+        let mut index = loaded;
+        assert_eq!(index.update().unwrap(), 1);
+        assert_eq!(index.files().count(), 3);
+    }
+
+    #[test]
+    fn test_archive_index_corrupt_record() {
+        let root = create_archive(
+            "index-corrupt",
+            &[("testdata-3channel-signal.mseed3", "signal.mseed")],
+        );
+
+        let mut index = MSArchiveIndex::new(&root);
+        index.update().unwrap();
+        let records = index.records("signal.mseed").unwrap().to_vec();
+        assert_eq!(records.len(), 107);
+
+        // corrupt the fixed header of a record midway
+        let corrupt = &records[50];
+        let path = root.join("signal.mseed");
+        let mut buf = fs::read(&path).unwrap();
+        let offset = corrupt.offset as usize;
+        buf[offset..offset + 40].fill(0);
+        fs::write(&path, buf).unwrap();
+
+        let mut index = MSArchiveIndex::new(&root);
+        assert_eq!(index.update().unwrap(), 1);
+        let indexed = index.records("signal.mseed").unwrap();
+        assert_eq!(indexed.len(), 106);
+        assert!(indexed.iter().all(|e| e.offset != corrupt.offset));
+        assert_eq!(indexed[..50], records[..50]);
+        assert_eq!(indexed[50..], records[51..]);
+    }
+
+    #[test]
+    fn test_archive_index_query() {
+        let root = create_archive(
+            "index-query",
+            &[
+                (
+                    "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+                    "mixed.mseed",
+                ),
+                ("testdata-3channel-signal.mseed3", "signal.mseed"),
+            ],
+        );
+
+        let mut index = MSArchiveIndex::new(&root);
+        index.update().unwrap();
+
+        let records = index.records("mixed.mseed").unwrap();
+        let sid = records[0].sid.clone();
+        let start = records.iter().map(|e| e.start_time).min().unwrap();
+        let end = records.iter().map(|e| e.end_time).max().unwrap();
+
+        // all records of the file are adjacent
+        let ranges = index.byte_ranges(&sid, &start, &end);
+        assert_eq!(
+            ranges,
+            vec![MSIndexRange {
+                path: root.join("mixed.mseed"),
+                range: 0..fs::metadata(root.join("mixed.mseed")).unwrap().len(),
+            }]
+        );
+
+        // select a single record
+        let entry = records[2].clone();
+        let ranges = index.byte_ranges(&sid, &entry.start_time, &entry.start_time);
+        assert_eq!(
+            ranges,
+            vec![MSIndexRange {
+                path: root.join("mixed.mseed"),
+                range: entry.byte_range(),
+            }]
+        );
+
+        let msrs: Vec<MSRecord> = index
+            .read_records(
+                &sid,
+                &entry.start_time,
+                &entry.start_time,
+                MSControlFlags::MSF_UNPACKDATA,
+            )
+            .map(|msr| msr.unwrap())
+            .collect();
+        assert_eq!(msrs.len(), 1);
+        assert_eq!(msrs[0].start_time().unwrap(), entry.start_time);
+        assert_eq!(msrs[0].num_samples(), msrs[0].sample_cnt());
+
+        // the raw records remain valid while subsequent records are read
+        let buf = fs::read(root.join("mixed.mseed")).unwrap();
+        let msrs: Vec<MSRecord> = index
+            .read_records(&sid, &start, &end, MSControlFlags::empty())
+            .map(|msr| msr.unwrap())
+            .collect();
+        assert_eq!(msrs.len(), records.len());
+        for (msr, entry) in msrs.iter().zip(records) {
+            let range = entry.offset as usize..(entry.offset + entry.rec_len) as usize;
+            assert_eq!(msr.raw().unwrap(), &buf[range]);
+        }
+
+        assert!(index
+            .byte_ranges("FDSN:XX_NONE__B_H_Z", &start, &end)
+            .is_empty());
+    }
+}
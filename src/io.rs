@@ -1,19 +1,23 @@
+use std::cmp;
 use std::ffi::{c_char, c_int, c_void, CString};
-use std::io::Write;
+use std::io::{self, BufRead, Read, Write};
 use std::path::{Path, PathBuf};
 use std::ptr;
 use std::slice::from_raw_parts;
 use std::str::FromStr;
 
+use crate::opaque::opaque_record;
 use crate::{
-    error::{check, check_eof},
-    raw, MSControlFlags, MSDataEncoding, MSError, MSRecord, MSResult, MSTraceList,
+    detect,
+    error::{check, check_eof, MS_NOTSEED, MS_WRONGLENGTH},
+    raw, MSControlFlags, MSDataEncoding, MSError, MSRecord, MSResult, MSSelections, MSTraceList,
 };
 use raw::{MS3FileParam, MS3Record};
 
 /// Counterpart of [`MSWriter`].
 ///
-/// Note that currently only reading miniSEED records from files is implemented.
+/// Note that currently only reading miniSEED records from files is implemented. For reading
+/// records from arbitrary sources implementing [`Read`] please refer to [`MSStreamReader`].
 pub type MSReader = MSFileParam;
 
 /// Holds the connection information that [`MSFileParam`] should use for reading.
@@ -25,6 +29,7 @@ pub struct ConnectionInfo(CString);
 pub struct MSFileParam {
     connection_info: ConnectionInfo,
     flags: MSControlFlags,
+    selections: Option<MSSelections>,
     inner: *mut MS3FileParam,
 }
 
@@ -36,6 +41,7 @@ impl MSFileParam {
         Ok(Self {
             connection_info,
             flags: MSControlFlags::empty(),
+            selections: None,
             inner: ptr::null_mut(),
         })
     }
@@ -50,9 +56,33 @@ impl MSFileParam {
         Ok(Self {
             connection_info,
             flags,
+            selections: None,
             inner: ptr::null_mut(),
         })
     }
+
+    /// Creates a new `MSFileParam` state container from `path_or_url`, control flags `flags` and
+    /// data selections `selections`.
+    ///
+    /// Records not matching any of the `selections` are skipped before their data samples are
+    /// unpacked. If `selections` is empty, all records are read.
+    pub fn new_with_selections<T: IntoConnectionInfo>(
+        path_or_url: T,
+        flags: MSControlFlags,
+        selections: MSSelections,
+    ) -> MSResult<Self> {
+        let mut rv = Self::new_with_flags(path_or_url, flags)?;
+        rv.selections = Some(selections);
+
+        Ok(rv)
+    }
+
+    /// Disables unpacking data samples and returns whether unpacking was enabled.
+    pub(crate) fn disable_unpacking(&mut self) -> bool {
+        let rv = self.flags.contains(MSControlFlags::MSF_UNPACKDATA);
+        self.flags.remove(MSControlFlags::MSF_UNPACKDATA);
+        rv
+    }
 }
 
 impl Iterator for MSFileParam {
@@ -60,12 +90,17 @@ impl Iterator for MSFileParam {
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut msr: *mut MS3Record = ptr::null_mut();
+        let selections = self
+            .selections
+            .as_ref()
+            .map_or(ptr::null(), |selections| selections.get_raw());
         let rv = unsafe {
-            raw::ms3_readmsr_r(
+            raw::ms3_readmsr_selection(
                 (&mut self.inner) as *mut *mut MS3FileParam,
                 (&mut msr) as *mut *mut MS3Record,
                 self.connection_info.0.as_ptr(),
                 self.flags.bits(),
+                selections,
                 0,
             )
         };
@@ -99,6 +134,12 @@ impl Drop for MSFileParam {
     }
 }
 
+impl ConnectionInfo {
+    pub(crate) fn as_ptr(&self) -> *const c_char {
+        self.0.as_ptr()
+    }
+}
+
 impl FromStr for ConnectionInfo {
     type Err = MSError;
 
@@ -199,6 +240,377 @@ fn url_to_connection_info(url: url::Url) -> MSResult<ConnectionInfo> {
     Ok(ConnectionInfo(url))
 }
 
+/// Default capacity of the internal buffer of [`RecordFramer`].
+const DEFAULT_BUF_SIZE: usize = 8192;
+/// Minimum number of bytes required for detecting a record, i.e. the length of a miniSEED v2
+/// fixed section of data header.
+const MIN_DETECT_LEN: usize = 48;
+/// Maximum record length supported by libmseed.
+const MAX_REC_LEN: usize = 10_485_760;
+
+/// Result of a single [`RecordFramer::step()`].
+#[derive(Debug)]
+pub(crate) enum FramerStep {
+    /// At least the given number of bytes must be buffered in order to continue framing.
+    Read(usize),
+    /// A parsed record or an error.
+    Record(MSResult<MSRecord>),
+    /// No more records are available.
+    Done,
+}
+
+/// A buffer framing miniSEED records by means of [`detect()`].
+///
+/// The framer is agnostic of the underlying I/O. Data is fed into the framer by means of
+/// [`RecordFramer::spare()`] and [`RecordFramer::commit()`].
+#[derive(Debug)]
+pub(crate) struct RecordFramer {
+    flags: MSControlFlags,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl RecordFramer {
+    /// Creates a new `RecordFramer` parsing records with control flags `flags`.
+    pub fn new(flags: MSControlFlags) -> Self {
+        Self {
+            flags,
+            buf: Vec::with_capacity(DEFAULT_BUF_SIZE),
+            pos: 0,
+            filled: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Disables unpacking data samples and returns whether unpacking was enabled.
+    pub fn disable_unpacking(&mut self) -> bool {
+        let rv = self.flags.contains(MSControlFlags::MSF_UNPACKDATA);
+        self.flags.remove(MSControlFlags::MSF_UNPACKDATA);
+        rv
+    }
+
+    /// Returns the number of buffered bytes not consumed, yet.
+    fn available(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Prepares the buffer for reading such that at least `len` bytes can be buffered and returns
+    /// the spare part of the buffer to be read into.
+    pub fn spare(&mut self, len: usize) -> &mut [u8] {
+        if self.pos > 0 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+
+        self.filled = self.buf.len();
+        let additional = cmp::max(len.saturating_sub(self.filled), DEFAULT_BUF_SIZE);
+        self.buf.resize(self.filled + additional, 0);
+
+        &mut self.buf[self.filled..]
+    }
+
+    /// Commits `n` bytes read into the spare part of the buffer.
+    ///
+    /// A value of `0` indicates the end of the input.
+    pub fn commit(&mut self, n: usize) {
+        self.buf.truncate(self.filled + n);
+        if n == 0 {
+            self.eof = true;
+        }
+    }
+
+    /// Discards the spare part of the buffer, e.g. if reading failed.
+    pub fn discard(&mut self) {
+        self.buf.truncate(self.filled);
+    }
+
+    /// Stops framing, i.e. all subsequent steps return [`FramerStep::Done`].
+    pub fn finish(&mut self) {
+        self.done = true;
+    }
+
+    /// Determines the length of the record at the current position.
+    fn frame(&self) -> MSResult<Frame> {
+        let avail = self.available();
+        if avail == 0 && self.eof {
+            return Ok(Frame::End);
+        }
+
+        if avail < MIN_DETECT_LEN {
+            if self.eof {
+                return Ok(Frame::NotData);
+            }
+            return Ok(Frame::Incomplete(MIN_DETECT_LEN));
+        }
+
+        match detect(&self.buf[self.pos..]) {
+            Ok(det) if det.format_version == 0 => Ok(Frame::NotData),
+            Ok(det) => match det.rec_len {
+                Some(rec_len) => {
+                    let rec_len = rec_len as usize;
+                    if avail >= rec_len {
+                        Ok(Frame::Record(rec_len))
+                    } else if self.eof {
+                        Err(MSError::from_raw(MS_WRONGLENGTH))
+                    } else {
+                        Ok(Frame::Incomplete(rec_len))
+                    }
+                }
+                // the record length of miniSEED v2 records without blockette 1000 is determined by
+                // searching the next record header
+                None if self.eof => Ok(Frame::Record(avail)),
+                None if avail >= MAX_REC_LEN => {
+                    Err(MSError::from_str("cannot determine record length"))
+                }
+                None => Ok(Frame::Incomplete(cmp::min(avail * 2, MAX_REC_LEN))),
+            },
+            Err(_) => Ok(Frame::NotData),
+        }
+    }
+
+    /// Frames and parses the next record from the buffered data.
+    pub fn step(&mut self) -> FramerStep {
+        while !self.done {
+            match self.frame() {
+                Ok(Frame::Incomplete(len)) => return FramerStep::Read(len),
+                Ok(Frame::Record(rec_len)) => {
+                    let rec = &self.buf[self.pos..self.pos + rec_len];
+                    self.pos += rec_len;
+
+                    return FramerStep::Record(MSRecord::parse_owned(rec, self.flags));
+                }
+                Ok(Frame::NotData) => {
+                    if self.flags.contains(MSControlFlags::MSF_SKIPNOTDATA) {
+                        self.pos += 1;
+                        continue;
+                    }
+
+                    self.done = true;
+                    return FramerStep::Record(Err(MSError::from_raw(MS_NOTSEED)));
+                }
+                Ok(Frame::End) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return FramerStep::Record(Err(e));
+                }
+            }
+        }
+
+        FramerStep::Done
+    }
+}
+
+/// Outcome of framing the data at the current position of a [`RecordFramer`].
+#[derive(Debug)]
+enum Frame {
+    /// At least the given number of bytes must be buffered.
+    Incomplete(usize),
+    /// A record of the given length.
+    Record(usize),
+    /// Data which cannot be identified as miniSEED.
+    NotData,
+    /// The end of the input.
+    End,
+}
+
+/// A reader for miniSEED records from an arbitrary source implementing [`Read`].
+///
+/// Records are framed by means of [`detect()`] and parsed with [`MSRecord::parse()`]. In contrast
+/// to [`MSReader`], which is restricted to files, `MSStreamReader` may be used to read records from
+/// sockets, decompression streams, standard input, etc. Since records are buffered internally,
+/// wrapping the source into a [`BufReader`](std::io::BufReader) is not required.
+///
+/// Reading is controlled by the following flags:
+/// - If `flags` has [`MSControlFlags::MSF_SKIPNOTDATA`] set, input that cannot be identified as
+///   miniSEED is skipped. Otherwise, an error is returned and iteration stops.
+/// - If `flags` has [`MSControlFlags::MSF_VALIDATECRC`] set, the CRC of miniSEED v3 records is
+///   validated.
+/// - If `flags` has [`MSControlFlags::MSF_UNPACKDATA`] set, data samples are unpacked.
+///
+/// Each record owns a copy of its raw record (see [`MSRecord::raw()`]), i.e. records remain valid
+/// while subsequent records are read. For sources implementing [`BufRead`] please refer to
+/// [`MSBufStreamReader`].
+///
+/// # Examples
+///
+/// Reading records from standard input:
+///
+/// ```no_run
+/// use std::io;
+///
+/// use mseed::{MSControlFlags, MSStreamReader};
+///
+/// let reader = MSStreamReader::new_with_flags(io::stdin(), MSControlFlags::MSF_SKIPNOTDATA);
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MSStreamReader<R> {
+    reader: R,
+    framer: RecordFramer,
+}
+
+impl<R: Read> MSStreamReader<R> {
+    /// Creates a new `MSStreamReader` reading records from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::new_with_flags(inner, MSControlFlags::empty())
+    }
+
+    /// Creates a new `MSStreamReader` reading records from `inner` with control flags `flags`.
+    pub fn new_with_flags(inner: R, flags: MSControlFlags) -> Self {
+        Self {
+            reader: inner,
+            framer: RecordFramer::new(flags),
+        }
+    }
+
+    /// Consumes this `MSStreamReader`, returning the underlying reader.
+    ///
+    /// Note that any leftover data in the internal buffer is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Disables unpacking data samples and returns whether unpacking was enabled.
+    pub(crate) fn disable_unpacking(&mut self) -> bool {
+        self.framer.disable_unpacking()
+    }
+}
+
+impl<R: Read> Iterator for MSStreamReader<R> {
+    type Item = MSResult<MSRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.framer.step() {
+                FramerStep::Read(len) => {
+                    let spare = self.framer.spare(len);
+                    match self.reader.read(spare) {
+                        Ok(n) => self.framer.commit(n),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => self.framer.discard(),
+                        Err(e) => {
+                            self.framer.discard();
+                            self.framer.finish();
+                            return Some(Err(MSError::from_str(&e.to_string())));
+                        }
+                    }
+                }
+                FramerStep::Record(rv) => return Some(rv),
+                FramerStep::Done => return None,
+            }
+        }
+    }
+}
+
+/// A reader for miniSEED records from an arbitrary source implementing [`BufRead`].
+///
+/// In contrast to [`MSStreamReader`], data is taken from the buffer of the source by means of
+/// [`BufRead::fill_buf()`] and [`BufRead::consume()`], i.e. no more data is consumed from the
+/// source than read into the reader's buffer. See [`MSStreamReader`] for the control flags
+/// honored.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io;
+///
+/// use mseed::{MSBufStreamReader, MSControlFlags};
+///
+/// let reader = MSBufStreamReader::new_with_flags(io::stdin().lock(), MSControlFlags::MSF_UNPACKDATA);
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MSBufStreamReader<R> {
+    reader: R,
+    framer: RecordFramer,
+}
+
+impl<R: BufRead> MSBufStreamReader<R> {
+    /// Creates a new `MSBufStreamReader` reading records from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self::new_with_flags(inner, MSControlFlags::empty())
+    }
+
+    /// Creates a new `MSBufStreamReader` reading records from `inner` with control flags
+    /// `flags`.
+    pub fn new_with_flags(inner: R, flags: MSControlFlags) -> Self {
+        Self {
+            reader: inner,
+            framer: RecordFramer::new(flags),
+        }
+    }
+
+    /// Consumes this `MSBufStreamReader`, returning the underlying reader.
+    ///
+    /// Note that any leftover data in the internal buffer is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Disables unpacking data samples and returns whether unpacking was enabled.
+    pub(crate) fn disable_unpacking(&mut self) -> bool {
+        self.framer.disable_unpacking()
+    }
+}
+
+impl<R: BufRead> Iterator for MSBufStreamReader<R> {
+    type Item = MSResult<MSRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.framer.step() {
+                FramerStep::Read(len) => match self.reader.fill_buf() {
+                    Ok(data) => {
+                        let spare = self.framer.spare(len);
+                        let n = cmp::min(data.len(), spare.len());
+                        spare[..n].copy_from_slice(&data[..n]);
+                        self.framer.commit(n);
+                        self.reader.consume(n);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.framer.finish();
+                        return Some(Err(MSError::from_str(&e.to_string())));
+                    }
+                },
+                FramerStep::Record(rv) => return Some(rv),
+                FramerStep::Done => return None,
+            }
+        }
+    }
+}
+
 /// Generic miniSEED record writer.
 #[derive(Debug)]
 pub struct MSWriter<W> {
@@ -233,7 +645,15 @@ impl<W: Write> MSWriter<W> {
     ///  case of miniSEED 2, unfilled.
     ///  If `flags` has [`MSControlFlags::MSF_PACKVER2`] set `msr` is packed as miniSEED v2
     ///  regardless of msr's [`MSRecord::format_version`].
+    ///  Opaque records (see [`MSRecord::is_opaque()`]) are written verbatim.
     pub fn write_record(&mut self, msr: &MSRecord, flags: MSControlFlags) -> MSResult<c_int> {
+        if let Some(rec) = opaque_record(msr, flags)? {
+            self.writer
+                .write_all(rec)
+                .map_err(|e| MSError::from_str(&e.to_string()))?;
+            return Ok(1);
+        }
+
         // XXX(damb): reimplementation of [`raw::msr3_writemseed`]
         unsafe {
             check(raw::msr3_pack(
@@ -295,7 +715,20 @@ mod tests {
 
     use pretty_assertions::assert_eq;
 
-    use crate::{test, MSControlFlags};
+    use crate::{test, MSControlFlags, MSErrorCode};
+
+    /// Reader returning at most `chunk_size` bytes per read.
+    struct ChunkedReader<R> {
+        inner: R,
+        chunk_size: usize,
+    }
+
+    impl<R: Read> Read for ChunkedReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = cmp::min(buf.len(), self.chunk_size);
+            self.inner.read(&mut buf[..len])
+        }
+    }
 
     #[test]
     fn test_read_write_msr() {
@@ -334,4 +767,133 @@ mod tests {
             assert_eq!(written, expected);
         }
     }
+
+    #[test]
+    fn test_stream_reader() {
+        let test_data = vec![
+            "reference-testdata-text.mseed2",
+            "reference-testdata-text.mseed3",
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-steim2.mseed3",
+            "testdata-3channel-signal.mseed2",
+            "testdata-3channel-signal.mseed3",
+            "testdata-no-blockette1000-steim1.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+        ];
+
+        for f in &test_data {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let flags = MSControlFlags::MSF_UNPACKDATA | MSControlFlags::MSF_VALIDATECRC;
+            let expected: Vec<(String, i64, i64)> = MSReader::new_with_flags(p.clone(), flags)
+                .unwrap()
+                .map(|msr| {
+                    let msr = msr.unwrap();
+                    (msr.sid_lossy(), msr.sample_cnt(), msr.num_samples())
+                })
+                .collect();
+            assert!(!expected.is_empty());
+
+            for chunk_size in [1, 7, 512, 8192] {
+                let file = File::open(&p).unwrap();
+                let reader = ChunkedReader {
+                    inner: file,
+                    chunk_size,
+                };
+
+                let read: Vec<(String, i64, i64)> = MSStreamReader::new_with_flags(reader, flags)
+                    .map(|msr| {
+                        let msr = msr.unwrap();
+                        (msr.sid_lossy(), msr.sample_cnt(), msr.num_samples())
+                    })
+                    .collect();
+
+                assert_eq!(read, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_buf_stream_reader() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let flags = MSControlFlags::MSF_UNPACKDATA;
+        let expected: Vec<(String, i64, i64)> = MSReader::new_with_flags(p.clone(), flags)
+            .unwrap()
+            .map(|msr| {
+                let msr = msr.unwrap();
+                (msr.sid_lossy(), msr.sample_cnt(), msr.num_samples())
+            })
+            .collect();
+        assert!(!expected.is_empty());
+
+        for capacity in [1, 7, 512, 8192] {
+            let reader = BufReader::with_capacity(capacity, File::open(&p).unwrap());
+
+            let read: Vec<(String, i64, i64)> = MSBufStreamReader::new_with_flags(reader, flags)
+                .map(|msr| {
+                    let msr = msr.unwrap();
+                    (msr.sid_lossy(), msr.sample_cnt(), msr.num_samples())
+                })
+                .collect();
+
+            assert_eq!(read, expected);
+        }
+    }
+
+    #[test]
+    fn test_stream_reader_owned_records() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let buf = std::fs::read(&p).unwrap();
+        let records: Vec<MSRecord> = MSStreamReader::new(ChunkedReader {
+            inner: buf.as_slice(),
+            chunk_size: 7,
+        })
+        .collect::<MSResult<_>>()
+        .unwrap();
+
+        // the raw records remain valid while subsequent records are read
+        let mut pos = 0;
+        for msr in &records {
+            let raw = msr.raw().unwrap();
+            assert_eq!(raw, &buf[pos..pos + raw.len()]);
+            pos += raw.len();
+        }
+        assert_eq!(pos, buf.len());
+    }
+
+    #[test]
+    fn test_stream_reader_skip_not_data() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut buf = vec![0u8; 100];
+        File::open(&p).unwrap().read_to_end(&mut buf).unwrap();
+
+        let mut reader = MSStreamReader::new(buf.as_slice());
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.code(), MSErrorCode::NotSEED);
+        assert!(reader.next().is_none());
+
+        let reader = MSStreamReader::new_with_flags(
+            buf.as_slice(),
+            MSControlFlags::MSF_SKIPNOTDATA | MSControlFlags::MSF_UNPACKDATA,
+        );
+        let mut cnt = 0;
+        for msr in reader {
+            let msr = msr.unwrap();
+            assert_eq!(msr.num_samples(), msr.sample_cnt());
+            cnt += 1;
+        }
+        assert_eq!(cnt, MSReader::new(p).unwrap().count());
+    }
 }
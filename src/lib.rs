@@ -41,6 +41,35 @@
 //! ```
 //!
 //!
+//! Reading miniSEED records from arbitrary sources implementing [`std::io::Read`] or
+//! [`std::io::BufRead`] is supported by [`MSStreamReader`] and [`MSBufStreamReader`],
+//! respectively. If the `tokio` feature is enabled, [`MSAsyncReader`] and [`MSAsyncWriter`]
+//! provide asynchronous record I/O based on [tokio](https://tokio.rs). For random access to the
+//! records of large files [`MSMmapReader`] maps a file into memory and indexes its records.
+//! Entire archive directory trees are indexed persistently by [`MSArchiveIndex`], while
+//! [`SdsArchive`] reads and writes archives organized according to the SeisComP Data Structure.
+//! Arbitrary archive layouts are written by [`MSArchiveWriter`] configured by a path template.
+//! Records are filtered either by libmseed data selections (see [`MSSelections`]) or by composable
+//! header predicates (see [`MSReaderFilterExt`]).
+//!
+//! Steim-1 and Steim-2 compressed data may be decoded and encoded without libmseed by means of
+//! [`decode_steim1()`], [`decode_steim2()`], [`encode_steim1()`] and [`encode_steim2()`].
+//! The integrity of records received e.g. from field stations is checked by
+//! [`MSRecord::validate()`] and [`validate_file()`], reporting typed findings. SEED 2.x blockettes
+//! of miniSEED v2 records are accessed by means of [`MSRecord::blockettes()`]. Full and dataless
+//! SEED volumes, including their control headers, are read by [`SeedVolume`]. Records carrying
+//! opaque payloads, e.g. state-of-health data, are read by means of
+//! [`MSRecord::opaque_payloads()`] and written by [`pack_opaque()`]. Record headers of
+//! existing files are rewritten without re-encoding the data by [`rewrite_headers()`] and
+//! [`rewrite_headers_in_place()`].
+//!
+//! If the `ndarray` feature is enabled, the data samples of trace segments are accessible as
+//! [ndarray](https://docs.rs/ndarray) arrays, e.g. by means of `MSTraceSegment::as_array()` or
+//! `MSTraceList::to_array2()`, while `pack_array()` packs arrays into records. Likewise, the
+//! `arrow` feature converts trace lists from and into [Apache Arrow](https://arrow.apache.org)
+//! record batches and Parquet files, e.g. by means of `MSTraceList::to_record_batches()` and
+//! `MSTraceList::write_parquet()`.
+//!
 //! ## Low-level miniSEED record I/O
 //!
 //! Creating miniSEED records from raw data samples is possible using the low-level [`pack_raw()`]
@@ -84,29 +113,96 @@ use bitflags::bitflags;
 
 use libmseed_sys as raw;
 
+pub use crate::archive::MSArchiveWriter;
+#[cfg(feature = "ndarray")]
+pub use crate::array::pack_array;
+#[cfg(feature = "tokio")]
+pub use crate::async_io::{MSAsyncReader, MSAsyncWriter};
+pub use crate::blockette::{
+    Blockette, Blockette100, Blockette1000, Blockette1001, Blockette200, Blockette2000,
+    Blockette201, Blockette300, Blockette310, Blockette320, Blockette390, Blockette395,
+    Blockette500, RawBlockette,
+};
 pub use crate::error::MSError;
-pub use crate::io::{ConnectionInfo, IntoConnectionInfo, MSFileParam, MSReader, MSWriter};
+pub use crate::extra_headers::{
+    Calibration, Clock, EventDetection, MSExtraHeaderValue, MSExtraHeaders, Recenter,
+    TimingException,
+};
+pub use crate::filter::{
+    AndFilter, EncodingFilter, MSFilteredReader, MSReaderFilterExt, MSRecordFilter, NotFilter,
+    OrFilter, PubVersionFilter, SidFilter, TimeWindowFilter,
+};
+pub use crate::gap::{Availability, GapInfo, MSGap};
+pub use crate::index::{MSArchiveIndex, MSIndexRange};
+pub use crate::io::{
+    ConnectionInfo, IntoConnectionInfo, MSBufStreamReader, MSFileParam, MSReader, MSStreamReader,
+    MSWriter,
+};
+pub use crate::mmap::{MSMmapReader, MSRecordIndexEntry};
+pub use crate::opaque::{pack_opaque, OpaquePayload, OpaqueTags};
 pub use crate::pack::{
     pack_header2, pack_header3, pack_raw, pack_record, pack_trace_list, repack_mseed3, PackInfo,
     TlPackInfo,
 };
 pub use crate::record::{
-    detect, MSBitFieldFlags, MSDataEncoding, MSRecord, MSSampleType, RecordDetection, RecordDisplay,
+    detect, MSBitFieldFlags, MSByteOrder, MSDataEncoding, MSRecord, MSSampleType, RecordByteOrder,
+    RecordDetection, RecordDisplay,
+};
+pub use crate::record_builder::MSRecordBuilder;
+pub use crate::record_ref::MSRecordRef;
+pub use crate::rewrite::{rewrite_header, rewrite_headers, rewrite_headers_in_place};
+pub use crate::samples::Samples;
+pub use crate::sds::{SdsArchive, SdsReader};
+pub use crate::seed_volume::{
+    Blockette10, Blockette11, Blockette30, Blockette33, Blockette34, Blockette50, Blockette52,
+    Blockette53, Blockette54, Blockette57, Blockette58, Blockette61, CalibrationHistory,
+    ChannelResponse, Coefficient, ComplexValue, SeedChannel, SeedStation, SeedVolume,
+};
+pub use crate::selection::MSSelections;
+pub use crate::steim::{
+    decode_steim1, decode_steim2, encode_steim1, encode_steim2, SteimData, STEIM_FRAME_LEN,
 };
+pub use crate::tolerance::MSTolerance;
 pub use crate::trace::{
-    DataSampleType, MSTraceId, MSTraceIdIter, MSTraceList, MSTraceSegment, MSTraceSegmentIter,
-    TraceListDisplay,
+    DataSampleType, MSTraceId, MSTraceIdIter, MSTraceList, MSTraceListOptions, MSTraceSegment,
+    MSTraceSegmentIter, TraceListDisplay,
 };
 pub use crate::util::{
     factor_multiplier_to_sample_rate, seedchan2xchan, xchan2seedchan, MSSubSeconds, MSTimeFormat,
 };
+pub use crate::validate::{validate_buffer, validate_file, ValidationFinding, ValidationIssue};
 
+mod archive;
+#[cfg(feature = "ndarray")]
+mod array;
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod blockette;
 mod error;
+mod extra_headers;
+mod filter;
+mod gap;
+mod index;
 mod io;
+mod mmap;
+mod opaque;
 mod pack;
 mod record;
+mod record_builder;
+mod record_ref;
+mod rewrite;
+mod samples;
+mod sds;
+mod seed_volume;
+mod selection;
+mod steim;
+mod swap;
+mod tolerance;
 mod trace;
 mod util;
+mod validate;
 
 #[cfg(test)]
 mod test;
@@ -0,0 +1,306 @@
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::error::{MS_NOTSEED, MS_WRONGLENGTH};
+use crate::{detect, MSControlFlags, MSError, MSRecord, MSResult};
+
+/// An entry of the record index of a [`MSMmapReader`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSRecordIndexEntry {
+    /// Byte offset of the record within the file.
+    pub offset: u64,
+    /// Length of the record in bytes.
+    pub rec_len: u64,
+    /// [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/) of the
+    /// record.
+    pub sid: String,
+    /// Time of the first sample of the record.
+    pub start_time: time::OffsetDateTime,
+    /// Time of the last sample of the record.
+    pub end_time: time::OffsetDateTime,
+    /// Publication version of the record.
+    pub pub_version: u8,
+    /// Nominal sample rate as samples per second (`Hz`).
+    pub sample_rate: f64,
+    /// CRC of the record. `0` for miniSEED v2 records.
+    pub crc: u32,
+}
+
+impl MSRecordIndexEntry {
+    /// Returns the byte range of the record within the file.
+    pub fn byte_range(&self) -> Range<u64> {
+        self.offset..self.offset + self.rec_len
+    }
+
+    /// Returns whether the record overlaps with the time window from `start` to `end`
+    /// (inclusive).
+    pub fn overlaps(&self, start: &time::OffsetDateTime, end: &time::OffsetDateTime) -> bool {
+        self.start_time <= *end && self.end_time >= *start
+    }
+}
+
+/// A reader providing random access to the miniSEED records of a memory-mapped file.
+///
+/// When opened, the file is walked by means of [`detect()`] and an in-memory index of all
+/// records is built (see [`MSRecordIndexEntry`]). Afterwards, records are parsed directly from
+/// the mapped memory without re-reading the file.
+///
+/// Records returned own a copy of their raw record (see [`MSRecord::raw()`]), i.e. they may
+/// outlive the reader. For zero-copy access to the mapped memory please refer to
+/// [`MSMmapReader::get_raw()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use time::{Duration, OffsetDateTime};
+///
+/// use mseed::{MSControlFlags, MSMmapReader};
+///
+/// let reader = MSMmapReader::open("path/to/data.mseed").unwrap();
+///
+/// // fetch a single record
+/// let msr = reader.get(42, MSControlFlags::MSF_UNPACKDATA).unwrap().unwrap();
+/// println!("{}", msr);
+///
+/// // fetch all records overlapping a time window
+/// let end = OffsetDateTime::now_utc();
+/// let start = end - Duration::hours(1);
+/// for msr in reader.read_window(&start, &end, MSControlFlags::MSF_UNPACKDATA) {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MSMmapReader {
+    mmap: Mmap,
+    index: Vec<MSRecordIndexEntry>,
+}
+
+impl MSMmapReader {
+    /// Maps the file at `path` into memory and indexes its records.
+    pub fn open<P: AsRef<Path>>(path: P) -> MSResult<Self> {
+        Self::open_with_flags(path, MSControlFlags::empty())
+    }
+
+    /// Maps the file at `path` into memory and indexes its records with control flags `flags`.
+    ///
+    /// If `flags` has [`MSControlFlags::MSF_SKIPNOTDATA`] set, data that cannot be identified as
+    /// miniSEED as well as corrupt records are skipped while indexing. Otherwise, an error is
+    /// returned.
+    pub fn open_with_flags<P: AsRef<Path>>(path: P, flags: MSControlFlags) -> MSResult<Self> {
+        let file = File::open(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+        // SAFETY: the file must not be modified while mapped
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        let index = build_index(&mmap, flags)?;
+
+        Ok(Self { mmap, index })
+    }
+
+    /// Returns the record index.
+    pub fn index(&self) -> &[MSRecordIndexEntry] {
+        &self.index
+    }
+
+    /// Returns the number of records indexed.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Returns whether no records were indexed.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns the raw bytes of the record at position `n`, if available.
+    pub fn get_raw(&self, n: usize) -> Option<&[u8]> {
+        let entry = self.index.get(n)?;
+        Some(&self.mmap[entry.offset as usize..(entry.offset + entry.rec_len) as usize])
+    }
+
+    /// Parses the record at position `n` with control flags `flags`, if available.
+    pub fn get(&self, n: usize, flags: MSControlFlags) -> Option<MSResult<MSRecord>> {
+        self.get_raw(n).map(|rec| MSRecord::parse_owned(rec, flags))
+    }
+
+    /// Returns an iterator over the positions of the records overlapping with the time window
+    /// from `start` to `end` (inclusive).
+    pub fn window_indices<'a>(
+        &'a self,
+        start: &'a time::OffsetDateTime,
+        end: &'a time::OffsetDateTime,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.index
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| entry.overlaps(start, end))
+            .map(|(i, _)| i)
+    }
+
+    /// Returns an iterator parsing the records overlapping with the time window from `start` to
+    /// `end` (inclusive) with control flags `flags`.
+    pub fn read_window<'a>(
+        &'a self,
+        start: &'a time::OffsetDateTime,
+        end: &'a time::OffsetDateTime,
+        flags: MSControlFlags,
+    ) -> impl Iterator<Item = MSResult<MSRecord>> + 'a {
+        self.window_indices(start, end)
+            .filter_map(move |i| self.get(i, flags))
+    }
+}
+
+/// Walks `buf` and creates an index entry for each record.
+pub(crate) fn build_index(buf: &[u8], flags: MSControlFlags) -> MSResult<Vec<MSRecordIndexEntry>> {
+    let mut rv = Vec::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let avail = buf.len() - pos;
+        let rec_len = match detect(&buf[pos..]) {
+            Ok(det) if det.format_version != 0 => match det.rec_len {
+                Some(rec_len) if rec_len as usize > avail => Err(MSError::from_raw(MS_WRONGLENGTH)),
+                Some(rec_len) => Ok(rec_len as usize),
+                // the next record header was not found, i.e. the record spans until the end of
+                // the file
+                None => Ok(avail),
+            },
+            _ => Err(MSError::from_raw(MS_NOTSEED)),
+        };
+
+        match rec_len.and_then(|rec_len| index_entry(&buf[pos..pos + rec_len], pos)) {
+            Ok(entry) => {
+                pos += entry.rec_len as usize;
+                rv.push(entry);
+            }
+            // skip both, data not identified as miniSEED and corrupt records
+            Err(_) if flags.contains(MSControlFlags::MSF_SKIPNOTDATA) => pos += 1,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(rv)
+}
+
+/// Creates the index entry of the record `rec` located at byte offset `pos`.
+fn index_entry(rec: &[u8], pos: usize) -> MSResult<MSRecordIndexEntry> {
+    let msr = MSRecord::parse(rec, MSControlFlags::empty())?;
+
+    Ok(MSRecordIndexEntry {
+        offset: pos as u64,
+        rec_len: rec.len() as u64,
+        sid: msr.sid()?,
+        start_time: msr.start_time()?,
+        end_time: msr.end_time()?,
+        pub_version: msr.pub_version(),
+        sample_rate: msr.sample_rate_hz(),
+        crc: msr.crc(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{test, MSReader};
+
+    #[test]
+    fn test_mmap_reader_index() {
+        let test_data = vec![
+            "reference-testdata-text.mseed2",
+            "reference-testdata-steim2.mseed3",
+            "testdata-no-blockette1000-steim1.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+        ];
+
+        for f in &test_data {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let expected: Vec<(String, time::OffsetDateTime, usize)> = MSReader::new(p.clone())
+                .unwrap()
+                .map(|msr| {
+                    let msr = msr.unwrap();
+                    (
+                        msr.sid().unwrap(),
+                        msr.start_time().unwrap(),
+                        msr.raw().unwrap().len(),
+                    )
+                })
+                .collect();
+
+            let reader = MSMmapReader::open(&p).unwrap();
+            let indexed: Vec<(String, time::OffsetDateTime, usize)> = reader
+                .index()
+                .iter()
+                .map(|entry| (entry.sid.clone(), entry.start_time, entry.rec_len as usize))
+                .collect();
+
+            assert_eq!(indexed, expected);
+
+            let buf = fs::read(&p).unwrap();
+            let total: u64 = reader.index().iter().map(|entry| entry.rec_len).sum();
+            assert_eq!(total as usize, buf.len());
+
+            for (i, entry) in reader.index().iter().enumerate() {
+                assert_eq!(
+                    reader.get_raw(i).unwrap(),
+                    &buf[entry.offset as usize..(entry.offset + entry.rec_len) as usize]
+                );
+            }
+            assert!(reader.get(reader.len(), MSControlFlags::empty()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_mmap_reader_read_window() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let reader = MSMmapReader::open(&p).unwrap();
+        assert!(!reader.is_empty());
+
+        // select the time span of a single record
+        let entry = reader.index()[3].clone();
+        let selected: Vec<usize> = reader
+            .window_indices(&entry.start_time, &entry.start_time)
+            .collect();
+        assert_eq!(selected, vec![3]);
+
+        let records: Vec<MSRecord> = reader
+            .read_window(
+                &entry.start_time,
+                &entry.end_time,
+                MSControlFlags::MSF_UNPACKDATA,
+            )
+            .map(|msr| msr.unwrap())
+            .collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].start_time().unwrap(), entry.start_time);
+        assert_eq!(records[0].num_samples(), records[0].sample_cnt());
+
+        // select everything
+        let all = reader
+            .window_indices(
+                &time::OffsetDateTime::UNIX_EPOCH,
+                &(time::OffsetDateTime::UNIX_EPOCH + time::Duration::days(365 * 100)),
+            )
+            .count();
+        assert_eq!(all, reader.len());
+
+        // the records outlive the reader
+        let expected = reader.get_raw(3).unwrap().to_vec();
+        drop(reader);
+        assert_eq!(records[0].raw().unwrap(), &expected[..]);
+    }
+}
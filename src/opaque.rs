@@ -0,0 +1,645 @@
+use crate::blockette::{append_blockettes, mseed2_rec_len};
+use crate::pack::byte_order_conversion;
+use crate::record_ref::{MS2_FSDH_LEN, MS3_FSDH_LEN};
+use crate::swap::set_mseed2_byte_order;
+use crate::validate::crc;
+use crate::{
+    pack_header2, pack_header3, raw, Blockette, Blockette2000, MSByteOrder, MSControlFlags,
+    MSDataEncoding, MSError, MSExtraHeaders, MSRecord, MSRecordBuilder, MSRecordRef, MSResult,
+    PackInfo,
+};
+
+/// Length of the fixed part of blockette 2000.
+const B2000_LEN: usize = 15;
+
+/// Tags describing an opaque payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpaqueTags {
+    /// Opaque data header fields of blockette 2000 (miniSEED v2).
+    HeaderFields(Vec<String>),
+    /// Extra headers of the record (miniSEED v3).
+    ExtraHeaders(MSExtraHeaders),
+}
+
+/// An opaque, i.e. non-waveform, payload of a record.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSReader, OpaqueTags};
+///
+/// let reader = MSReader::new_with_flags("path/to/soh.mseed", MSControlFlags::empty()).unwrap();
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     for payload in msr.opaque_payloads().unwrap() {
+///         if let OpaqueTags::HeaderFields(fields) = &payload.tags {
+///             println!("{:?}: {} bytes", fields, payload.data.len());
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpaquePayload<'a> {
+    /// Opaque data.
+    pub data: &'a [u8],
+    /// Tags describing the opaque data.
+    pub tags: OpaqueTags,
+}
+
+impl<'a> MSRecordRef<'a> {
+    /// Returns whether the record carries opaque payloads rather than data samples.
+    ///
+    /// miniSEED v2 records are opaque if they carry blockette 2000 and do not contain any data
+    /// samples, while miniSEED v3 records are opaque if encoded as [`MSDataEncoding::Opaque`].
+    pub fn is_opaque(&self) -> bool {
+        match self.format_version() {
+            3 => self.encoding().is_ok_and(|e| e == MSDataEncoding::Opaque),
+            _ => self.sample_cnt() == 0 && !self.b2000_offsets().is_empty(),
+        }
+    }
+
+    /// Returns the opaque payloads of the record.
+    ///
+    /// For miniSEED v2 records a payload is returned for each blockette 2000 tagged by the
+    /// blockette's opaque data header fields. miniSEED v3 records carry a single payload tagged by
+    /// the record's extra headers. Returns an empty vector if the record is not opaque (see
+    /// [`MSRecordRef::is_opaque()`]).
+    pub fn opaque_payloads(&self) -> MSResult<Vec<OpaquePayload<'a>>> {
+        if !self.is_opaque() {
+            return Ok(vec![]);
+        }
+
+        match self.format_version() {
+            3 => Ok(vec![OpaquePayload {
+                data: self.data_payload(),
+                tags: OpaqueTags::ExtraHeaders(MSExtraHeaders::from_slice(
+                    self.extra_headers().unwrap_or_default(),
+                )?),
+            }]),
+            _ => self
+                .b2000_offsets()
+                .into_iter()
+                .map(|offset| self.parse_b2000(offset))
+                .collect(),
+        }
+    }
+
+    /// Returns the offsets of blockette 2000 within the blockette chain.
+    fn b2000_offsets(&self) -> Vec<usize> {
+        let buf = self.raw();
+        let mut rv = vec![];
+
+        let mut offset = self.u16_at(46) as usize;
+        let mut cnt = 0;
+        while offset >= MS2_FSDH_LEN && offset + 4 <= buf.len() && cnt < buf[39] {
+            if self.u16_at(offset) == 2000 {
+                rv.push(offset);
+            }
+
+            let next = self.u16_at(offset + 2) as usize;
+            if next <= offset {
+                break;
+            }
+            offset = next;
+            cnt += 1;
+        }
+
+        rv
+    }
+
+    fn parse_b2000(&self, offset: usize) -> MSResult<OpaquePayload<'a>> {
+        let buf = self.raw();
+        let truncated =
+            || MSError::from_str(&format!("truncated blockette 2000 at offset {}", offset));
+        if offset + B2000_LEN > buf.len() {
+            return Err(truncated());
+        }
+
+        let total_len = self.u16_at(offset + 4) as usize;
+        let data_offset = self.u16_at(offset + 6) as usize;
+        if data_offset < B2000_LEN || data_offset > total_len || offset + total_len > buf.len() {
+            return Err(truncated());
+        }
+
+        let header_fields = buf[offset + B2000_LEN..offset + data_offset]
+            .split(|b| *b == b'~')
+            .take(buf[offset + 14] as usize)
+            .map(|field| String::from_utf8_lossy(field).into_owned())
+            .collect();
+
+        Ok(OpaquePayload {
+            data: &buf[offset + data_offset..offset + total_len],
+            tags: OpaqueTags::HeaderFields(header_fields),
+        })
+    }
+}
+
+impl MSRecord {
+    /// Returns whether the record carries opaque payloads rather than data samples.
+    ///
+    /// See [`MSRecordRef::is_opaque()`].
+    pub fn is_opaque(&self) -> bool {
+        match self.raw() {
+            Some(rec) => MSRecordRef::parse(rec).is_ok_and(|rec| rec.is_opaque()),
+            None => self.encoding().is_ok_and(|e| e == MSDataEncoding::Opaque),
+        }
+    }
+
+    /// Returns the opaque payloads of the record.
+    ///
+    /// See [`MSRecordRef::opaque_payloads()`]. Returns an error if the record is opaque but the
+    /// raw record is not available.
+    pub fn opaque_payloads(&self) -> MSResult<Vec<OpaquePayload<'_>>> {
+        match self.raw() {
+            Some(rec) => MSRecordRef::parse(rec)?.opaque_payloads(),
+            None if self.is_opaque() => Err(MSError::from_str("raw record not available")),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Returns the raw record of `msr` if `msr` is opaque.
+///
+/// Opaque records are not repacked but copied verbatim, since libmseed neither retains
+/// blockette 2000 nor encodes opaque data.
+pub(crate) fn opaque_record(msr: &MSRecord, flags: MSControlFlags) -> MSResult<Option<&[u8]>> {
+    if !msr.is_opaque() {
+        return Ok(None);
+    }
+
+    let rec = msr
+        .raw()
+        .ok_or_else(|| MSError::from_str("raw record not available"))?;
+    if flags.contains(MSControlFlags::MSF_PACKVER2) && msr.format_version() != 2 {
+        return Err(MSError::from_str(
+            "cannot convert opaque record into miniSEED v2",
+        ));
+    }
+
+    Ok(Some(rec))
+}
+
+/// Returns a copy of `buf` without opaque records, or `None` if `buf` does not contain any.
+pub(crate) fn strip_opaque(buf: &[u8]) -> Option<Vec<u8>> {
+    let mut rv: Option<Vec<u8>> = None;
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < buf.len() {
+        match MSRecordRef::parse(&buf[pos..]) {
+            Ok(rec) if rec.is_opaque() => {
+                rv.get_or_insert_with(Vec::new)
+                    .extend_from_slice(&buf[start..pos]);
+                pos += rec.rec_len();
+                start = pos;
+            }
+            Ok(rec) => pos += rec.rec_len(),
+            Err(_) => pos += 1,
+        }
+    }
+
+    if let Some(rv) = rv.as_mut() {
+        rv.extend_from_slice(&buf[start..]);
+    }
+
+    rv
+}
+
+/// Packs the opaque payload `data` into miniSEED records.
+///
+/// `start_time` is the time the opaque data refers to. Buffers containing the packed miniSEED
+/// records are passed to the `record_handler` closure. Returns on success the number of packed
+/// records.
+///
+/// miniSEED v2 records (i.e. if [`PackInfo::format_version`] is `2` or `flags` has
+/// [`MSControlFlags::MSF_PACKVER2`] set) require `tags` to be
+/// [`OpaqueTags::HeaderFields`]. The payload is split into blockettes 2000 of as many records of
+/// [`PackInfo::rec_len`] bytes as required, where the data flags of the blockettes indicate
+/// whether the payload is split. [`PackInfo::blockettes`] and [`PackInfo::byte_order`] are
+/// honored.
+///
+/// miniSEED v3 records require `tags` to be [`OpaqueTags::ExtraHeaders`], replacing
+/// [`PackInfo::extra_headers`]. The payload is packed into a single record. An error is returned if
+/// the record exceeds [`PackInfo::rec_len`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use time::OffsetDateTime;
+///
+/// use mseed::{MSControlFlags, OpaqueTags, PackInfo};
+///
+/// let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+/// info.format_version = 2;
+/// info.rec_len = 512;
+///
+/// let soh = std::fs::read("path/to/soh.bin").unwrap();
+/// let tags = OpaqueTags::HeaderFields(vec!["SOH".to_string(), "v1".to_string()]);
+///
+/// let mut records = vec![];
+/// mseed::pack_opaque(
+///     &soh,
+///     &tags,
+///     &OffsetDateTime::now_utc(),
+///     |rec: &[u8]| records.extend_from_slice(rec),
+///     &info,
+///     MSControlFlags::empty(),
+/// )
+/// .unwrap();
+/// ```
+pub fn pack_opaque<F>(
+    data: &[u8],
+    tags: &OpaqueTags,
+    start_time: &time::OffsetDateTime,
+    mut record_handler: F,
+    info: &PackInfo,
+    flags: MSControlFlags,
+) -> MSResult<usize>
+where
+    F: FnMut(&[u8]),
+{
+    let pack_ver2 = info.format_version == 2 || flags.contains(MSControlFlags::MSF_PACKVER2);
+    let byte_order = byte_order_conversion(info.byte_order, pack_ver2)?;
+    let sid = info
+        .sid()
+        .to_str()
+        .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    let builder = MSRecordBuilder::new()
+        .sid(sid)
+        .start_time(*start_time)
+        .sample_rate(0.0)
+        .pub_version(info.pub_version)
+        .flags(info.flags)
+        .encoding(MSDataEncoding::Text)
+        .samples_text(Vec::new());
+
+    match (pack_ver2, tags) {
+        (true, OpaqueTags::HeaderFields(header_fields)) => {
+            let rec_len = mseed2_rec_len(info.rec_len)?;
+            let mut builder = builder.format_version(2).rec_len(rec_len as i32);
+            if let Some(extra_headers) = &info.extra_headers {
+                builder =
+                    builder.extra_headers(MSExtraHeaders::from_slice(extra_headers.as_bytes())?);
+            }
+
+            pack_opaque2(
+                &builder.build()?,
+                data,
+                header_fields,
+                &info.blockettes,
+                rec_len,
+                byte_order,
+                &mut record_handler,
+            )
+        }
+        (false, OpaqueTags::ExtraHeaders(extra_headers)) => {
+            if !info.blockettes.is_empty() {
+                return Err(MSError::from_str(
+                    "blockettes require records to be packed as miniSEED v2",
+                ));
+            }
+
+            let msr = builder
+                .format_version(3)
+                .extra_headers(extra_headers.clone())
+                .build()?;
+            let rec = pack_opaque3(&msr, data)?;
+            if info.rec_len > 0 && rec.len() > info.rec_len as usize {
+                return Err(MSError::from_str(&format!(
+                    "opaque data exceeds record length: {} > {}",
+                    rec.len(),
+                    info.rec_len
+                )));
+            }
+
+            record_handler(&rec);
+            Ok(1)
+        }
+        (true, _) => Err(MSError::from_str(
+            "opaque miniSEED v2 records require blockette 2000 header fields",
+        )),
+        (false, _) => Err(MSError::from_str(
+            "opaque miniSEED v3 records require extra headers",
+        )),
+    }
+}
+
+fn pack_opaque2<F>(
+    msr: &MSRecord,
+    data: &[u8],
+    header_fields: &[String],
+    blockettes: &[Blockette],
+    rec_len: usize,
+    byte_order: Option<MSByteOrder>,
+    record_handler: &mut F,
+) -> MSResult<usize>
+where
+    F: FnMut(&[u8]),
+{
+    if header_fields.iter().any(|field| field.contains('~')) {
+        return Err(MSError::from_str(
+            "blockette 2000 header fields must not contain '~'",
+        ));
+    }
+
+    let encoded = blockettes
+        .iter()
+        .map(Blockette::to_bytes)
+        .collect::<MSResult<Vec<_>>>()?;
+
+    let mut header = vec![0u8; rec_len];
+    let header_len = pack_header2(msr, &mut header)?;
+    let header_len = append_blockettes(&mut header, header_len, &encoded)?;
+
+    let header_fields_len: usize = header_fields.iter().map(|field| field.len() + 1).sum();
+    let room = rec_len
+        .checked_sub(header_len + B2000_LEN + header_fields_len)
+        .filter(|room| *room > 0)
+        .ok_or_else(|| MSError::from_str("blockette 2000 exceeds record length"))?;
+
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(room).collect()
+    };
+
+    let cnt = chunks.len();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        // opaque record identifier, i.e. whether the payload is split
+        let split = match i {
+            _ if cnt == 1 => 0b00,
+            0 => 0b01,
+            i if i + 1 == cnt => 0b10,
+            _ => 0b11,
+        };
+        let b2000 = Blockette::Opaque(Blockette2000 {
+            record_number: 0,
+            word_order: 1,
+            flags: split << 2,
+            header_fields: header_fields.to_vec(),
+            data: chunk.to_vec(),
+        })
+        .to_bytes()?;
+
+        let mut rec = header.clone();
+        append_blockettes(&mut rec, header_len, &[b2000])?;
+        if let Some(byte_order) = byte_order {
+            set_mseed2_byte_order(&mut rec, byte_order.into())?;
+        }
+
+        record_handler(&rec);
+    }
+
+    Ok(cnt)
+}
+
+fn pack_opaque3(msr: &MSRecord, data: &[u8]) -> MSResult<Vec<u8>> {
+    let extra_len = msr.extra_headers().map_or(0, |extra| extra.len());
+    let mut rec = vec![0u8; MS3_FSDH_LEN + raw::LM_SIDLEN as usize + extra_len];
+    let header_len = pack_header3(msr, &mut rec)?;
+    rec.truncate(header_len);
+
+    let data_len = u32::try_from(data.len()).map_err(|e| MSError::from_str(&e.to_string()))?;
+    rec[15] = MSDataEncoding::Opaque as u8;
+    rec[24..28].copy_from_slice(&0u32.to_le_bytes());
+    rec[36..40].copy_from_slice(&data_len.to_le_bytes());
+    rec.extend_from_slice(data);
+
+    let crc = crc(&rec);
+    rec[28..32].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(rec)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use crate::{test, MSReader, MSTraceList, MSWriter};
+
+    const START_TIME: &str = "2012-01-01T00:00:00Z";
+
+    #[test]
+    fn test_pack_opaque_mseed2() {
+        let data: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        let header_fields = vec!["SOH".to_string(), "v1".to_string()];
+
+        for byte_order in [MSByteOrder::BigEndian, MSByteOrder::LittleEndian] {
+            let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+            info.format_version = 2;
+            info.rec_len = 512;
+            info.byte_order = Some(byte_order);
+
+            let mut records = vec![];
+            let cnt = pack_opaque(
+                &data,
+                &OpaqueTags::HeaderFields(header_fields.clone()),
+                &test::parse_time(START_TIME),
+                |rec: &[u8]| records.push(rec.to_vec()),
+                &info,
+                MSControlFlags::empty(),
+            )
+            .unwrap();
+            assert_eq!(cnt, records.len());
+            assert!(cnt > 1);
+
+            let mut unpacked = vec![];
+            for (i, rec) in records.iter().enumerate() {
+                assert_eq!(rec.len(), 512);
+
+                let rec_ref = MSRecordRef::parse(rec).unwrap();
+                assert!(rec_ref.is_opaque());
+                assert_eq!(rec_ref.byte_order().header, byte_order);
+
+                let payloads = rec_ref.opaque_payloads().unwrap();
+                assert_eq!(payloads.len(), 1);
+                assert_eq!(
+                    payloads[0].tags,
+                    OpaqueTags::HeaderFields(header_fields.clone())
+                );
+                unpacked.extend_from_slice(payloads[0].data);
+
+                let blockettes = rec_ref.blockettes().unwrap();
+                let b2000 = blockettes
+                    .iter()
+                    .find_map(|b| match b {
+                        Blockette::Opaque(b2000) => Some(b2000),
+                        _ => None,
+                    })
+                    .unwrap();
+                let split = match i {
+                    0 => 0b01,
+                    i if i + 1 == cnt => 0b10,
+                    _ => 0b11,
+                };
+                assert_eq!(b2000.flags >> 2, split);
+
+                let msr = MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+                assert!(msr.is_opaque());
+                assert_eq!(msr.sid().unwrap(), "FDSN:XX_TEST__S_O_H");
+                assert_eq!(msr.start_time().unwrap(), test::parse_time(START_TIME));
+                assert_eq!(msr.sample_cnt(), 0);
+            }
+            assert_eq!(unpacked, data);
+        }
+
+        // tags must match the format version
+        let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+        info.format_version = 2;
+        assert!(pack_opaque(
+            &data,
+            &OpaqueTags::ExtraHeaders(MSExtraHeaders::new()),
+            &test::parse_time(START_TIME),
+            |_: &[u8]| {},
+            &info,
+            MSControlFlags::empty(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_pack_opaque_mseed3() {
+        let data = b"\x00\x01state-of-health\xff".to_vec();
+        let mut extra_headers = MSExtraHeaders::new();
+        extra_headers
+            .set("/Station/SOH", json!({"Format": "binary", "Version": 1}))
+            .unwrap();
+
+        let info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+        let mut records = vec![];
+        let cnt = pack_opaque(
+            &data,
+            &OpaqueTags::ExtraHeaders(extra_headers.clone()),
+            &test::parse_time(START_TIME),
+            |rec: &[u8]| records.push(rec.to_vec()),
+            &info,
+            MSControlFlags::empty(),
+        )
+        .unwrap();
+        assert_eq!(cnt, 1);
+
+        let rec_ref = MSRecordRef::parse(&records[0]).unwrap();
+        assert!(rec_ref.is_opaque());
+        assert_eq!(rec_ref.encoding().unwrap(), MSDataEncoding::Opaque);
+        assert_eq!(
+            rec_ref.opaque_payloads().unwrap(),
+            vec![OpaquePayload {
+                data: &data,
+                tags: OpaqueTags::ExtraHeaders(extra_headers.clone()),
+            }]
+        );
+
+        let msr = MSRecord::parse(
+            &records[0],
+            MSControlFlags::MSF_UNPACKDATA | MSControlFlags::MSF_VALIDATECRC,
+        )
+        .unwrap();
+        assert!(msr.is_opaque());
+        assert_eq!(msr.opaque_payloads().unwrap().len(), 1);
+        assert_eq!(msr.opaque_payloads().unwrap()[0].data, &data[..]);
+
+        // the record length is exceeded
+        let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+        info.rec_len = 128;
+        assert!(pack_opaque(
+            &[0u8; 256],
+            &OpaqueTags::ExtraHeaders(extra_headers),
+            &test::parse_time(START_TIME),
+            |_: &[u8]| {},
+            &info,
+            MSControlFlags::empty(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_opaque_round_trip() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut buf = fs::read(&p).unwrap();
+        let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+        info.rec_len = -1;
+        pack_opaque(
+            b"opaque",
+            &OpaqueTags::ExtraHeaders(MSExtraHeaders::new()),
+            &test::parse_time(START_TIME),
+            |rec: &[u8]| buf.extend_from_slice(rec),
+            &info,
+            MSControlFlags::empty(),
+        )
+        .unwrap();
+        info.format_version = 2;
+        info.rec_len = 256;
+        pack_opaque(
+            b"opaque",
+            &OpaqueTags::HeaderFields(vec!["SOH".to_string()]),
+            &test::parse_time(START_TIME),
+            |rec: &[u8]| buf.extend_from_slice(rec),
+            &info,
+            MSControlFlags::empty(),
+        )
+        .unwrap();
+
+        // opaque records are left out of trace lists
+        let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        let expected =
+            MSTraceList::from_buffer(&fs::read(&p).unwrap(), MSControlFlags::MSF_UNPACKDATA)
+                .unwrap();
+        assert_eq!(mstl.len(), expected.len());
+        assert!(mstl
+            .iter()
+            .all(|tid| tid.sid().unwrap() != "FDSN:XX_TEST__S_O_H"));
+
+        let dir = test::scratch_dir("opaque");
+        let path = dir.join("opaque.mseed");
+        fs::write(&path, &buf).unwrap();
+
+        // also if read from a file, including record lists
+        for flags in [
+            MSControlFlags::MSF_UNPACKDATA,
+            MSControlFlags::MSF_RECORDLIST,
+        ] {
+            let mstl = MSTraceList::read(path.clone(), flags).unwrap();
+            assert_eq!(mstl.len(), expected.len());
+            assert!(mstl
+                .iter()
+                .all(|tid| tid.sid().unwrap() != "FDSN:XX_TEST__S_O_H"));
+        }
+        let mstl = MSTraceList::read(path.clone(), MSControlFlags::MSF_RECORDLIST).unwrap();
+        for (tid, expected) in mstl.iter().zip(expected.iter()) {
+            for (mut seg, mut expected) in tid.iter().zip(expected.iter()) {
+                assert_eq!(
+                    seg.data_samples::<i32>().unwrap(),
+                    expected.data_samples::<i32>().unwrap()
+                );
+            }
+        }
+
+        let mut writer = MSWriter::new(Vec::new());
+        let mut cnt_opaque = 0;
+        for msr in MSReader::new_with_flags(path.clone(), MSControlFlags::MSF_UNPACKDATA).unwrap() {
+            let msr = msr.unwrap();
+            if msr.is_opaque() {
+                cnt_opaque += 1;
+                assert_eq!(msr.opaque_payloads().unwrap()[0].data, b"opaque");
+            }
+            writer
+                .write_record(&msr, MSControlFlags::MSF_FLUSHDATA)
+                .unwrap();
+        }
+        assert_eq!(cnt_opaque, 2);
+
+        // opaque records are written verbatim
+        let orig_len = fs::metadata(&p).unwrap().len() as usize;
+        assert!(writer.into_inner().ends_with(&buf[orig_len..]));
+    }
+}
@@ -3,9 +3,12 @@ use std::mem;
 use std::ptr;
 use std::slice;
 
+use crate::blockette::pack_mseed2;
+use crate::opaque::opaque_record;
+use crate::swap::set_mseed2_byte_order;
 use crate::{
-    error::check, raw, util, MSBitFieldFlags, MSControlFlags, MSDataEncoding, MSError, MSRecord,
-    MSResult, MSSampleType, MSTraceList,
+    error::check, raw, util, Blockette, MSBitFieldFlags, MSByteOrder, MSControlFlags,
+    MSDataEncoding, MSError, MSRecord, MSResult, MSSampleType, MSTraceList,
 };
 use raw::MS3Record;
 
@@ -23,8 +26,15 @@ pub struct TlPackInfo {
     /// Extra headers.
     ///
     /// If not `None` it is expected to contain extra headers, i.e. a string containing (compact)
-    /// JSON, that will be added to each output record.
+    /// JSON, that will be added to each output record. See also
+    /// [`MSExtraHeaders::to_cstring()`](crate::MSExtraHeaders::to_cstring).
     pub extra_headers: Option<CString>,
+    /// Byte order of miniSEED v2 output records.
+    ///
+    /// If `None`, miniSEED v2 records are packed in big-endian byte order. Note that miniSEED v3
+    /// records are always little-endian, i.e. forcing big-endian when packing miniSEED v3 is an
+    /// error.
+    pub byte_order: Option<MSByteOrder>,
 }
 
 impl Default for TlPackInfo {
@@ -33,6 +43,7 @@ impl Default for TlPackInfo {
             encoding: MSDataEncoding::Steim2,
             rec_len: 4096,
             extra_headers: None,
+            byte_order: None,
         }
     }
 }
@@ -59,6 +70,31 @@ pub fn pack_trace_list<F>(
     info: &TlPackInfo,
     flags: MSControlFlags,
 ) -> MSResult<(usize, usize)>
+where
+    F: FnMut(&[u8]),
+{
+    let pack_ver2 = flags.contains(MSControlFlags::MSF_PACKVER2);
+    match byte_order_conversion(info.byte_order, pack_ver2)? {
+        Some(byte_order) => {
+            let mut err = None;
+            let rv = pack_trace_list_impl(
+                mstl,
+                byte_order_handler(&mut record_handler, byte_order, &mut err),
+                info,
+                flags,
+            );
+            err.map_or(rv, Err)
+        }
+        None => pack_trace_list_impl(mstl, record_handler, info, flags),
+    }
+}
+
+fn pack_trace_list_impl<F>(
+    mstl: &mut MSTraceList,
+    mut record_handler: F,
+    info: &TlPackInfo,
+    flags: MSControlFlags,
+) -> MSResult<(usize, usize)>
 where
     F: FnMut(&[u8]),
 {
@@ -113,8 +149,19 @@ pub struct PackInfo {
     /// Extra headers.
     ///
     /// If not `None` it is expected to contain extra headers, i.e. a string containing (compact)
-    /// JSON, that will be added to each output record.
+    /// JSON, that will be added to each output record. See also
+    /// [`MSExtraHeaders::to_cstring()`](crate::MSExtraHeaders::to_cstring).
     pub extra_headers: Option<CString>,
+    /// Additional SEED 2.x blockettes.
+    ///
+    /// If not empty, the blockettes are added to each output record. Only blockettes 100, 500
+    /// and 2000 are supported. Blockettes require records to be packed as miniSEED v2, i.e.
+    /// either [`PackInfo::format_version`] is `2` or [`MSControlFlags::MSF_PACKVER2`] is set.
+    pub blockettes: Vec<Blockette>,
+    /// Byte order of miniSEED v2 output records.
+    ///
+    /// See [`TlPackInfo::byte_order`].
+    pub byte_order: Option<MSByteOrder>,
 }
 
 impl PackInfo {
@@ -132,6 +179,8 @@ impl PackInfo {
             encoding: MSDataEncoding::Steim2,
             rec_len: 4096,
             extra_headers: None,
+            blockettes: vec![],
+            byte_order: None,
         })
     }
 
@@ -172,7 +221,7 @@ impl PackInfo {
     }
 }
 
-fn sid_as_cstring<T>(sid: T) -> MSResult<CString>
+pub(crate) fn sid_as_cstring<T>(sid: T) -> MSResult<CString>
 where
     T: Into<Vec<u8>>,
 {
@@ -197,6 +246,10 @@ where
 /// If `flags` has [`MSControlFlags::MSF_PACKVER2`] set records are packed as miniSEED v2
 /// regardless of [`PackInfo::format_version`].
 ///
+/// If [`PackInfo::blockettes`] is not empty, the blockettes are appended to the blockette chain
+/// of each miniSEED v2 record. miniSEED v2 records are converted into [`PackInfo::byte_order`],
+/// if set.
+///
 /// See also [`raw::msr3_pack`].
 ///
 /// # Examples
@@ -287,6 +340,33 @@ pub fn pack_raw<T, F>(
     info: &PackInfo,
     flags: MSControlFlags,
 ) -> MSResult<(usize, usize)>
+where
+    F: FnMut(&[u8]),
+{
+    let pack_ver2 = info.format_version == 2 || flags.contains(MSControlFlags::MSF_PACKVER2);
+    match byte_order_conversion(info.byte_order, pack_ver2)? {
+        Some(byte_order) => {
+            let mut err = None;
+            let rv = pack_raw_impl(
+                data_samples,
+                start_time,
+                byte_order_handler(&mut record_handler, byte_order, &mut err),
+                info,
+                flags,
+            );
+            err.map_or(rv, Err)
+        }
+        None => pack_raw_impl(data_samples, start_time, record_handler, info, flags),
+    }
+}
+
+fn pack_raw_impl<T, F>(
+    data_samples: &mut [T],
+    start_time: &time::OffsetDateTime,
+    mut record_handler: F,
+    info: &PackInfo,
+    flags: MSControlFlags,
+) -> MSResult<(usize, usize)>
 where
     F: FnMut(&[u8]),
 {
@@ -334,18 +414,27 @@ where
         }
     }
 
-    let mut cnt_samples: i64 = 0;
-    let cnt_samples_ptr: *mut i64 = &mut cnt_samples;
+    let rv = if info.blockettes.is_empty() {
+        let mut cnt_samples: i64 = 0;
+        let cnt_samples_ptr: *mut i64 = &mut cnt_samples;
 
-    let cnt_records = unsafe {
-        check(raw::msr3_pack(
-            msr,
-            Some(rh_wrapper::<F>),
-            (&mut record_handler) as *mut _ as *mut c_void,
-            cnt_samples_ptr,
-            flags.bits(),
-            0,
-        ))?
+        unsafe {
+            check(raw::msr3_pack(
+                msr,
+                Some(rh_wrapper::<F>),
+                (&mut record_handler) as *mut _ as *mut c_void,
+                cnt_samples_ptr,
+                flags.bits(),
+                0,
+            ))
+        }
+        .map(|cnt_records| (cnt_records as usize, cnt_samples as usize))
+    } else if info.format_version == 2 || flags.contains(MSControlFlags::MSF_PACKVER2) {
+        unsafe { pack_mseed2(msr, &info.blockettes, &mut record_handler, flags) }
+    } else {
+        Err(MSError::from_str(
+            "blockettes require packing as miniSEED v2",
+        ))
     };
 
     unsafe {
@@ -361,7 +450,49 @@ where
         raw::msr3_free((&mut msr) as *mut *mut _);
     }
 
-    Ok((cnt_records as usize, cnt_samples as usize))
+    rv
+}
+
+/// Returns the byte order packed records must be converted into, if any.
+///
+/// libmseed packs miniSEED v2 records in big-endian byte order, while miniSEED v3 records are
+/// always little-endian.
+pub(crate) fn byte_order_conversion(
+    byte_order: Option<MSByteOrder>,
+    pack_ver2: bool,
+) -> MSResult<Option<MSByteOrder>> {
+    match (byte_order, pack_ver2) {
+        (Some(MSByteOrder::LittleEndian), true) => Ok(Some(MSByteOrder::LittleEndian)),
+        (Some(MSByteOrder::BigEndian), false) => Err(MSError::from_str(
+            "miniSEED v3 records are always little-endian",
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Wraps `record_handler` such that records are converted into `byte_order` before being passed
+/// on. If a conversion fails, the error is stored in `err` and subsequent records are dropped.
+fn byte_order_handler<'a, F>(
+    record_handler: &'a mut F,
+    byte_order: MSByteOrder,
+    err: &'a mut Option<MSError>,
+) -> impl FnMut(&[u8]) + 'a
+where
+    F: FnMut(&[u8]),
+{
+    let mut buf = Vec::new();
+    move |rec: &[u8]| {
+        if err.is_some() {
+            return;
+        }
+
+        buf.clear();
+        buf.extend_from_slice(rec);
+        match set_mseed2_byte_order(&mut buf, byte_order.into()) {
+            Ok(()) => record_handler(&buf),
+            Err(e) => *err = Some(e),
+        }
+    }
 }
 
 extern "C" fn rh_wrapper<F>(rec: *mut c_char, rec_len: i32, out: *mut c_void)
@@ -384,6 +515,8 @@ where
 /// into miniSEED records even though the last one will probably be smaller than requested or, in
 /// the case of miniSEED v2, unfilled.
 /// If `flags` has [`MSControlFlags::MSF_PACKVER2`] set records are packed as miniSEED v2.
+///
+/// Opaque records (see [`MSRecord::is_opaque()`]) are passed on verbatim.
 #[allow(dead_code)]
 pub fn pack_record<F>(
     msr: &MSRecord,
@@ -393,6 +526,11 @@ pub fn pack_record<F>(
 where
     F: FnMut(&[u8]),
 {
+    if let Some(rec) = opaque_record(msr, flags)? {
+        record_handler(rec);
+        return Ok((1, 0));
+    }
+
     let mut cnt_samples: i64 = 0;
     let cnt_samples_ptr: *mut i64 = &mut cnt_samples;
 
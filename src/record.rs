@@ -10,7 +10,7 @@ use serde_json::Value;
 use raw::MS3Record;
 
 use crate::error::{check, check_nst};
-use crate::{raw, util, MSControlFlags, MSError, MSResult, MSSubSeconds, MSTimeFormat};
+use crate::{raw, util, MSControlFlags, MSError, MSResult, MSSubSeconds, MSTimeFormat, Samples};
 
 /// Structure returned by [`detect()`].
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -50,6 +50,52 @@ pub fn detect<T: AsRef<[u8]>>(buf: T) -> MSResult<RecordDetection> {
     })
 }
 
+/// An enumeration of byte orders.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum MSByteOrder {
+    /// Big-endian byte order.
+    BigEndian,
+    /// Little-endian byte order.
+    LittleEndian,
+}
+
+impl MSByteOrder {
+    /// Returns the byte order of the host.
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            Self::BigEndian
+        } else {
+            Self::LittleEndian
+        }
+    }
+
+    /// Returns the opposite byte order.
+    pub fn swapped(self) -> Self {
+        match self {
+            Self::BigEndian => Self::LittleEndian,
+            Self::LittleEndian => Self::BigEndian,
+        }
+    }
+}
+
+/// Structure returned by [`MSRecord::byte_order()`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RecordByteOrder {
+    /// Byte order of the record header, including blockettes.
+    pub header: MSByteOrder,
+    /// Byte order of the data payload.
+    pub data: MSByteOrder,
+}
+
+impl From<MSByteOrder> for RecordByteOrder {
+    fn from(byte_order: MSByteOrder) -> Self {
+        Self {
+            header: byte_order,
+            data: byte_order,
+        }
+    }
+}
+
 /// An enumeration of possible sample types.
 #[repr(u8)]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -79,6 +125,9 @@ impl MSSampleType {
     }
 }
 
+/// Data encoding of opaque payloads, not defined by libmseed.
+const DE_OPAQUE: u32 = 100;
+
 /// An enumeration of possible data encodings.
 #[repr(i16)]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -109,6 +158,8 @@ pub enum MSDataEncoding {
     SRO = raw::DE_SRO as i16,
     /// **Legacy**: DWWSSN 16-bit gain ranged
     DWWSSN = raw::DE_DWWSSN as i16,
+    /// Opaque data (miniSEED v3), see also [`MSRecord::opaque_payloads()`]
+    Opaque = DE_OPAQUE as i16,
 }
 
 impl MSDataEncoding {
@@ -128,6 +179,7 @@ impl MSDataEncoding {
             raw::DE_CDSN => Ok(Self::CDSN),
             raw::DE_SRO => Ok(Self::SRO),
             raw::DE_DWWSSN => Ok(Self::DWWSSN),
+            DE_OPAQUE => Ok(Self::Opaque),
             other => Err(MSError::from_str(&format!(
                 "invalid data encoding type: {}",
                 other
@@ -138,6 +190,10 @@ impl MSDataEncoding {
 
 impl fmt::Display for MSDataEncoding {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Self::Opaque {
+            return write!(f, "Opaque data");
+        }
+
         unsafe {
             let encoding = CStr::from_ptr(raw::ms_encodingstr(
                 (*self as u8).try_into().map_err(|_| fmt::Error).unwrap(),
@@ -167,8 +223,16 @@ bitflags! {
 }
 
 /// miniSEED record structure.
-#[derive(Debug)]
-pub struct MSRecord(*mut MS3Record);
+///
+/// Records parsed by the crate's readers optionally own a copy of the raw record, such that
+/// [`MSRecord::raw()`] remains valid independently of the reader's buffers.
+pub struct MSRecord(*mut MS3Record, Option<Box<[u8]>>);
+
+impl fmt::Debug for MSRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MSRecord").field(&self.0).finish()
+    }
+}
 
 impl MSRecord {
     fn ptr(&self) -> MS3Record {
@@ -203,7 +267,19 @@ impl MSRecord {
             ))?
         };
 
-        Ok(Self(msr))
+        Ok(Self(msr, None))
+    }
+
+    /// Parses a `MSRecord` from a copy of `buf`.
+    ///
+    /// In contrast to [`MSRecord::parse()`], the raw record refers to the copy owned by the
+    /// record rather than to `buf`.
+    pub(crate) fn parse_owned(buf: &[u8], flags: MSControlFlags) -> MSResult<Self> {
+        let buf: Box<[u8]> = buf.into();
+        let mut rv = Self::parse(&buf, flags)?;
+        rv.1 = Some(buf);
+
+        Ok(rv)
     }
 
     /// Creates a `MSRecord` from a raw pointer. Takes ownership.
@@ -212,11 +288,17 @@ impl MSRecord {
     ///
     /// Takes ownership of a raw `MS3Record` pointer that was allocated by foreign code.
     pub unsafe fn from_raw(ptr: *mut MS3Record) -> Self {
-        Self(ptr)
+        Self(ptr, None)
     }
 
     /// Consumes the MSRecord and transfers ownership of the record to a C caller.
+    ///
+    /// If the record owns its raw record, the raw record is not transferred.
     pub fn into_raw(mut self) -> *mut MS3Record {
+        if self.1.is_some() && !self.0.is_null() {
+            unsafe { (*self.0).record = ptr::null() };
+        }
+
         let rv = self.0;
         self.0 = ptr::null_mut();
         rv
@@ -317,6 +399,40 @@ impl MSRecord {
         self.ptr().pubversion
     }
 
+    /// Returns the byte order of the record header and the data payload.
+    ///
+    /// miniSEED v3 headers are always little-endian, while Steim encoded data is always
+    /// big-endian. For miniSEED v2 records the byte order is reported as detected by libmseed
+    /// when parsing. Records not parsed from a buffer are reported in the byte order of the
+    /// host.
+    pub fn byte_order(&self) -> RecordByteOrder {
+        let msr = self.ptr();
+        if msr.formatversion == 3 {
+            let data = match MSDataEncoding::from_char(msr.encoding as _) {
+                Ok(MSDataEncoding::Steim1 | MSDataEncoding::Steim2) => MSByteOrder::BigEndian,
+                _ => MSByteOrder::LittleEndian,
+            };
+
+            return RecordByteOrder {
+                header: MSByteOrder::LittleEndian,
+                data,
+            };
+        }
+
+        let byte_order = |swapped: bool| {
+            if swapped {
+                MSByteOrder::native().swapped()
+            } else {
+                MSByteOrder::native()
+            }
+        };
+
+        RecordByteOrder {
+            header: byte_order(msr.swapflag & raw::MSSWAP_HEADER as u8 != 0),
+            data: byte_order(msr.swapflag & raw::MSSWAP_PAYLOAD as u8 != 0),
+        }
+    }
+
     /// Returns the number of data samples as indicated by the raw record.
     pub fn sample_cnt(&self) -> i64 {
         self.ptr().samplecnt as _
@@ -348,6 +464,10 @@ impl MSRecord {
     }
 
     /// Returns the (unpacked) data samples of the record if available.
+    ///
+    /// Note that the data samples are reinterpreted as `T` regardless of the record's
+    /// [`sample_type()`](Self::sample_type). Prefer [`samples()`](Self::samples) for type-safe
+    /// access.
     pub fn data_samples<T>(&self) -> Option<&[T]> {
         if self.ptr().datasamples.is_null() {
             return None;
@@ -361,6 +481,21 @@ impl MSRecord {
         })
     }
 
+    /// Returns the (unpacked) data samples of the record typed according to the record's
+    /// [`sample_type()`](Self::sample_type).
+    ///
+    /// Returns an error if the data samples are not unpacked, the sample type is unknown or text
+    /// data samples are not valid UTF-8.
+    pub fn samples(&self) -> MSResult<Samples<'_>> {
+        unsafe {
+            Samples::from_raw_parts(
+                self.ptr().datasamples,
+                self.ptr().numsamples as usize,
+                self.sample_type(),
+            )
+        }
+    }
+
     /// Returns the size of the buffer for (unpacked) data samples in bytes.
     pub fn data_size(&self) -> u64 {
         self.ptr().datasize
@@ -384,7 +519,13 @@ impl MSRecord {
             return Err(MSError::from_str("failed to duplicate"));
         }
 
-        Ok(Self(rv))
+        // the duplicate refers to its own copy of an owned raw record
+        let buf = self.1.clone();
+        if let Some(buf) = &buf {
+            unsafe { (*rv).record = buf.as_ptr() as *const c_char };
+        }
+
+        Ok(Self(rv, buf))
     }
 
     /// Returns an object that implements [`Display`] for printing a record with level `detail`.
@@ -722,6 +863,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_samples() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = std::fs::read(p).unwrap();
+
+        let msr = MSRecord::parse(&buf, MSControlFlags::empty()).unwrap();
+        assert!(msr.samples().is_err());
+
+        let msr = MSRecord::parse(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        let samples = msr.samples().unwrap();
+        assert_eq!(samples.sample_type(), MSSampleType::Integer32);
+        assert_eq!(samples.len(), 135);
+        assert!(samples.as_f32().is_none());
+        let data = samples.as_i32().unwrap();
+        assert_eq!(&data[..4], &[-502676, -504105, -507491, -506991]);
+        let data = samples.to_f64_vec().unwrap();
+        assert_eq!(data[134], -496168.0);
+    }
+
     #[test]
     fn test_parse_signal_mseed2() {
         let mut p = test::test_data_base_dir();
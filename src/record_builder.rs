@@ -0,0 +1,411 @@
+use std::ffi::{c_char, c_double};
+use std::mem;
+use std::ptr;
+
+use time::OffsetDateTime;
+
+use crate::pack::sid_as_cstring;
+use crate::{
+    raw, util, MSBitFieldFlags, MSDataEncoding, MSError, MSExtraHeaders, MSRecord, MSResult,
+    MSSampleType,
+};
+
+/// Owned data samples of a [`MSRecordBuilder`].
+#[derive(Debug, Clone)]
+enum Samples {
+    Integer32(Vec<i32>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+    Text(Vec<u8>),
+}
+
+impl Samples {
+    fn sample_type(&self) -> MSSampleType {
+        match self {
+            Self::Integer32(_) => MSSampleType::Integer32,
+            Self::Float32(_) => MSSampleType::Float32,
+            Self::Float64(_) => MSSampleType::Float64,
+            Self::Text(_) => MSSampleType::Text,
+        }
+    }
+
+    /// Returns the default encoding for the sample type.
+    fn default_encoding(&self) -> MSDataEncoding {
+        match self {
+            Self::Integer32(_) => MSDataEncoding::Steim2,
+            Self::Float32(_) => MSDataEncoding::Float32,
+            Self::Float64(_) => MSDataEncoding::Float64,
+            Self::Text(_) => MSDataEncoding::Text,
+        }
+    }
+
+    fn is_valid_encoding(&self, encoding: MSDataEncoding) -> bool {
+        use MSDataEncoding::*;
+
+        matches!(
+            (self, encoding),
+            (Self::Integer32(_), Integer16 | Integer32 | Steim1 | Steim2)
+                | (Self::Float32(_), Float32)
+                | (Self::Float64(_), Float64)
+                | (Self::Text(_), Text)
+        )
+    }
+
+    /// Returns a pointer to the samples, the number of samples and the size in bytes.
+    fn as_raw_parts(&self) -> (*const u8, usize, usize) {
+        match self {
+            Self::Integer32(v) => (v.as_ptr() as *const u8, v.len(), mem::size_of_val(&v[..])),
+            Self::Float32(v) => (v.as_ptr() as *const u8, v.len(), mem::size_of_val(&v[..])),
+            Self::Float64(v) => (v.as_ptr() as *const u8, v.len(), mem::size_of_val(&v[..])),
+            Self::Text(v) => (v.as_ptr(), v.len(), v.len()),
+        }
+    }
+}
+
+/// A builder for constructing [`MSRecord`]s from scratch.
+///
+/// The [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/) is
+/// mandatory. If not configured otherwise, records are built with a sample rate of `1.0` Hz,
+/// publication version `1`, format version `3` and a record length of `4096` bytes. The data
+/// encoding defaults to [`MSDataEncoding::Steim2`] for integer samples and to the natural
+/// encoding of floating point and text samples, respectively.
+///
+/// The record built owns its data samples and may be passed e.g. to
+/// [`MSWriter::write_record()`](crate::MSWriter::write_record),
+/// [`pack_record()`](crate::pack_record) or [`MSTraceList::insert()`](crate::MSTraceList::insert).
+///
+/// # Examples
+///
+/// ```no_run
+/// use time::OffsetDateTime;
+///
+/// use mseed::{MSDataEncoding, MSRecordBuilder};
+///
+/// let msr = MSRecordBuilder::new()
+///     .sid("FDSN:XX_TEST__X_Y_Z")
+///     .start_time(OffsetDateTime::now_utc())
+///     .sample_rate(100.0)
+///     .encoding(MSDataEncoding::Steim1)
+///     .samples_i32((0..1000).collect())
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(msr.num_samples(), 1000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MSRecordBuilder {
+    sid: Option<String>,
+    start_time: OffsetDateTime,
+    sample_rate: c_double,
+    pub_version: u8,
+    format_version: u8,
+    flags: MSBitFieldFlags,
+    encoding: Option<MSDataEncoding>,
+    rec_len: i32,
+    extra_headers: Option<MSExtraHeaders>,
+    samples: Option<Samples>,
+}
+
+impl MSRecordBuilder {
+    /// Creates a new `MSRecordBuilder`.
+    pub fn new() -> Self {
+        Self {
+            sid: None,
+            start_time: OffsetDateTime::UNIX_EPOCH,
+            sample_rate: 1.0,
+            pub_version: 1,
+            format_version: 3,
+            flags: MSBitFieldFlags::empty(),
+            encoding: None,
+            rec_len: 4096,
+            extra_headers: None,
+            samples: None,
+        }
+    }
+
+    /// Sets the [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    pub fn sid(mut self, sid: &str) -> Self {
+        self.sid = Some(sid.to_string());
+        self
+    }
+
+    /// Sets the time of the first data sample.
+    pub fn start_time(mut self, start_time: OffsetDateTime) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Sets the sample rate.
+    ///
+    /// Note that by [libmseed](https://github.com/EarthScope/libmseed) convention a negative
+    /// `sample_rate` value is considered as a *sample rate period* (i.e. in units of seconds
+    /// (`s`)), while a positive sample rate value is considered as a *sample rate frequency*
+    /// (i.e. in units of Hertz (`Hz`)).
+    pub fn sample_rate(mut self, sample_rate: c_double) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Sets the publication version.
+    pub fn pub_version(mut self, pub_version: u8) -> Self {
+        self.pub_version = pub_version;
+        self
+    }
+
+    /// Sets the miniSEED format version used when packing the record.
+    pub fn format_version(mut self, format_version: u8) -> Self {
+        self.format_version = format_version;
+        self
+    }
+
+    /// Sets the record level bit field flags.
+    pub fn flags(mut self, flags: MSBitFieldFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the data encoding used when packing the record.
+    pub fn encoding(mut self, encoding: MSDataEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Sets the maximum record length used when packing the record.
+    pub fn rec_len(mut self, rec_len: i32) -> Self {
+        self.rec_len = rec_len;
+        self
+    }
+
+    /// Sets the extra headers.
+    pub fn extra_headers(mut self, extra_headers: MSExtraHeaders) -> Self {
+        self.extra_headers = Some(extra_headers);
+        self
+    }
+
+    /// Sets 32-bit integer data samples.
+    pub fn samples_i32(mut self, samples: Vec<i32>) -> Self {
+        self.samples = Some(Samples::Integer32(samples));
+        self
+    }
+
+    /// Sets 32-bit floating point data samples.
+    pub fn samples_f32(mut self, samples: Vec<f32>) -> Self {
+        self.samples = Some(Samples::Float32(samples));
+        self
+    }
+
+    /// Sets 64-bit floating point data samples.
+    pub fn samples_f64(mut self, samples: Vec<f64>) -> Self {
+        self.samples = Some(Samples::Float64(samples));
+        self
+    }
+
+    /// Sets text data samples.
+    pub fn samples_text<T: Into<Vec<u8>>>(mut self, text: T) -> Self {
+        self.samples = Some(Samples::Text(text.into()));
+        self
+    }
+
+    /// Builds the record.
+    ///
+    /// Returns an error if the source identifier is missing or invalid, or if the data encoding
+    /// does not match the type of the data samples.
+    pub fn build(self) -> MSResult<MSRecord> {
+        let sid = self
+            .sid
+            .ok_or_else(|| MSError::from_str("missing source identifier"))?;
+        let sid = sid_as_cstring(sid)?;
+
+        let samples = self.samples.unwrap_or(Samples::Integer32(vec![]));
+        let encoding = self.encoding.unwrap_or_else(|| samples.default_encoding());
+        if !samples.is_valid_encoding(encoding) {
+            return Err(MSError::from_str(&format!(
+                "invalid data encoding for {:?} data samples: {}",
+                samples.sample_type(),
+                encoding
+            )));
+        }
+        let (data_ptr, num_samples, data_size) = samples.as_raw_parts();
+        let num_samples =
+            i64::try_from(num_samples).map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        let msr = unsafe { raw::msr3_init(ptr::null_mut()) };
+        if msr.is_null() {
+            return Err(MSError::from_str("failed to initialize record"));
+        }
+        // takes ownership, i.e. the record is freed on error
+        let mut rv = unsafe { MSRecord::from_raw(msr) };
+
+        unsafe {
+            let sid_len = sid.as_bytes_with_nul().len();
+            ptr::copy_nonoverlapping(sid.as_ptr(), (*msr).sid.as_mut_ptr(), sid_len);
+            (*msr).encoding = encoding as _;
+            (*msr).sampletype = samples.sample_type() as u8 as c_char;
+            (*msr).reclen = self.rec_len;
+            (*msr).starttime = util::time_to_nstime(&self.start_time);
+            (*msr).samprate = self.sample_rate;
+            (*msr).pubversion = self.pub_version;
+            (*msr).formatversion = self.format_version;
+            (*msr).flags = self.flags.bits();
+
+            if data_size > 0 {
+                // the buffer is freed by libmseed
+                let malloc = raw::libmseed_memory
+                    .malloc
+                    .ok_or_else(|| MSError::from_str("missing memory allocator"))?;
+                let buf = malloc(data_size);
+                if buf.is_null() {
+                    return Err(MSError::from_str("failed to allocate data sample buffer"));
+                }
+                ptr::copy_nonoverlapping(data_ptr, buf as *mut u8, data_size);
+
+                (*msr).datasamples = buf;
+                (*msr).datasize = data_size as u64;
+                (*msr).numsamples = num_samples;
+                (*msr).samplecnt = num_samples;
+            }
+        }
+
+        if let Some(extra_headers) = self.extra_headers {
+            rv.set_extra_headers(&extra_headers)?;
+        }
+
+        Ok(rv)
+    }
+}
+
+impl Default for MSRecordBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::{pack_record, MSControlFlags, MSTraceList, MSWriter};
+
+    #[test]
+    fn test_build_invalid() {
+        assert!(MSRecordBuilder::new().build().is_err());
+        assert!(MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .samples_f32(vec![1.0, 2.0])
+            .encoding(MSDataEncoding::Steim2)
+            .build()
+            .is_err());
+        assert!(MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .samples_text("Hello")
+            .encoding(MSDataEncoding::Integer32)
+            .build()
+            .is_err());
+        assert!(MSRecordBuilder::new()
+            .sid(&"X".repeat(raw::LM_SIDLEN as usize))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_record() {
+        let start_time = OffsetDateTime::parse("2012-01-01T00:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let samples: Vec<i32> = (0..1000).map(|i| (i % 50) * 3 - 70).collect();
+        let mut extra_headers = MSExtraHeaders::new();
+        extra_headers.set_time_quality(90).unwrap();
+
+        let msr = MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .start_time(start_time)
+            .sample_rate(20.0)
+            .pub_version(2)
+            .flags(MSBitFieldFlags::CLOCK_LOCKED)
+            .encoding(MSDataEncoding::Steim1)
+            .rec_len(512)
+            .extra_headers(extra_headers)
+            .samples_i32(samples.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(msr.sid().unwrap(), "FDSN:XX_TEST__X_Y_Z");
+        assert_eq!(msr.start_time().unwrap(), start_time);
+        assert_eq!(msr.sample_rate_hz(), 20.0);
+        assert_eq!(msr.pub_version(), 2);
+        assert_eq!(msr.flags(), MSBitFieldFlags::CLOCK_LOCKED);
+        assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Steim1);
+        assert_eq!(msr.sample_type(), MSSampleType::Integer32);
+        assert_eq!(msr.num_samples(), 1000);
+        assert_eq!(msr.sample_cnt(), 1000);
+        assert_eq!(msr.data_samples::<i32>().unwrap(), &samples[..]);
+        assert_eq!(msr.parse_extra_headers().unwrap().time_quality(), Some(90));
+        assert!(msr.raw().is_none());
+
+        let mut unpacked: Vec<i32> = vec![];
+        let (cnt_records, cnt_samples) = pack_record(
+            &msr,
+            |rec: &[u8]| {
+                assert!(rec.len() <= 512);
+                let msr = MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+                assert_eq!(msr.sid().unwrap(), "FDSN:XX_TEST__X_Y_Z");
+                assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Steim1);
+                assert_eq!(msr.pub_version(), 2);
+                assert_eq!(msr.parse_extra_headers().unwrap().time_quality(), Some(90));
+                unpacked.extend_from_slice(msr.data_samples::<i32>().unwrap());
+            },
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .unwrap();
+        assert!(cnt_records > 1);
+        assert_eq!(cnt_samples, 1000);
+        assert_eq!(unpacked, samples);
+
+        let mut writer = MSWriter::new(Vec::new());
+        writer
+            .write_record(&msr, MSControlFlags::MSF_FLUSHDATA)
+            .unwrap();
+        assert!(!writer.into_inner().is_empty());
+
+        let mut mstl = MSTraceList::new().unwrap();
+        mstl.insert(msr, true).unwrap();
+        assert_eq!(mstl.len(), 1);
+    }
+
+    #[test]
+    fn test_build_record_sample_types() {
+        let msr = MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .samples_f64(vec![1.5, -2.5])
+            .build()
+            .unwrap();
+        assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Float64);
+        assert_eq!(msr.data_samples::<f64>().unwrap(), &[1.5, -2.5]);
+
+        let msr = MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .samples_f32(vec![1.5, -2.5])
+            .build()
+            .unwrap();
+        assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Float32);
+        assert_eq!(msr.data_samples::<f32>().unwrap(), &[1.5, -2.5]);
+
+        let msr = MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .samples_text("Hello, miniSEED!")
+            .build()
+            .unwrap();
+        assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Text);
+        assert_eq!(msr.sample_type(), MSSampleType::Text);
+        assert_eq!(msr.data_samples::<u8>().unwrap(), b"Hello, miniSEED!");
+
+        let msr = MSRecordBuilder::new()
+            .sid("FDSN:XX_TEST__X_Y_Z")
+            .build()
+            .unwrap();
+        assert_eq!(msr.num_samples(), 0);
+        assert!(msr.data_samples::<i32>().is_none());
+    }
+}
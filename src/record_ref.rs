@@ -0,0 +1,719 @@
+use std::borrow::Cow;
+use std::ffi::c_double;
+use std::str;
+
+use crate::error::{MS_NOTSEED, MS_WRONGLENGTH};
+use crate::steim::{decode_steim1, decode_steim2, SteimData};
+use crate::{
+    util, MSBitFieldFlags, MSByteOrder, MSControlFlags, MSDataEncoding, MSError, MSRecord,
+    MSResult, RecordByteOrder,
+};
+
+/// Length of the miniSEED v3 fixed header.
+pub(crate) const MS3_FSDH_LEN: usize = 40;
+/// Length of the miniSEED v2 fixed section of data header.
+pub(crate) const MS2_FSDH_LEN: usize = 48;
+/// Offset at which the next miniSEED v2 record header is searched for if the record length is
+/// unknown.
+const MS2_SEARCH_STEP: usize = 64;
+
+/// A borrowed, zero-copy view of a miniSEED record.
+///
+/// In contrast to [`MSRecord`], `MSRecordRef` does not parse the record by means of libmseed.
+/// Instead, header fields of both miniSEED v2 and v3 records are decoded lazily from the
+/// underlying buffer on access, i.e. scanning records for header information is cheap.
+///
+/// If full access to the record is required, the view may be turned into an owned [`MSRecord`]
+/// by means of [`MSRecordRef::to_record()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+///
+/// use mseed::MSRecordRef;
+///
+/// let buf = fs::read("path/to/data.mseed").unwrap();
+///
+/// let mut offset = 0;
+/// while offset < buf.len() {
+///     let rec = MSRecordRef::parse(&buf[offset..]).unwrap();
+///     println!("{}: {}", rec.sid().unwrap(), rec.start_time().unwrap());
+///
+///     offset += rec.rec_len();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MSRecordRef<'a> {
+    buf: &'a [u8],
+    format_version: u8,
+    /// Whether the miniSEED v2 header is in big-endian byte order.
+    big_endian: bool,
+    /// Offset of miniSEED v2 blockette 100.
+    b100: Option<usize>,
+    /// Offset of miniSEED v2 blockette 1000.
+    b1000: Option<usize>,
+    /// Offset of miniSEED v2 blockette 1001.
+    b1001: Option<usize>,
+}
+
+impl<'a> MSRecordRef<'a> {
+    /// Creates a view of the miniSEED record at the beginning of `buf`.
+    ///
+    /// The buffer must contain the entire record. Trailing data is ignored. If the length of a
+    /// miniSEED v2 record cannot be determined, neither from blockette 1000 nor by searching the
+    /// next record header, the record is assumed to span the entire buffer.
+    pub fn parse(buf: &'a [u8]) -> MSResult<Self> {
+        if is_valid_ms3_header(buf) {
+            return Self::parse_ms3(buf);
+        }
+
+        if is_valid_ms2_header(buf) {
+            return Self::parse_ms2(buf);
+        }
+
+        Err(MSError::from_raw(MS_NOTSEED))
+    }
+
+    fn parse_ms3(buf: &'a [u8]) -> MSResult<Self> {
+        let sid_len = buf[33] as usize;
+        let extra_len = u16::from_le_bytes([buf[34], buf[35]]) as usize;
+        let data_len = u32::from_le_bytes([buf[36], buf[37], buf[38], buf[39]]) as usize;
+
+        let rec_len = MS3_FSDH_LEN + sid_len + extra_len + data_len;
+        if buf.len() < rec_len {
+            return Err(MSError::from_raw(MS_WRONGLENGTH));
+        }
+
+        Ok(Self {
+            buf: &buf[..rec_len],
+            format_version: 3,
+            big_endian: false,
+            b100: None,
+            b1000: None,
+            b1001: None,
+        })
+    }
+
+    fn parse_ms2(buf: &'a [u8]) -> MSResult<Self> {
+        // determine the header byte order by means of the year and day-of-year fields
+        let year = u16::from_be_bytes([buf[20], buf[21]]);
+        let yday = u16::from_be_bytes([buf[22], buf[23]]);
+        let big_endian = is_valid_year_day(year, yday);
+
+        let mut rv = Self {
+            buf,
+            format_version: 2,
+            big_endian,
+            b100: None,
+            b1000: None,
+            b1001: None,
+        };
+
+        // walk the blockette chain
+        let mut offset = rv.u16_at(46) as usize;
+        let mut cnt = 0;
+        while offset >= MS2_FSDH_LEN && offset + 4 <= buf.len() && cnt < buf[39] {
+            match rv.u16_at(offset) {
+                100 => rv.b100 = Some(offset),
+                1000 => rv.b1000 = Some(offset),
+                1001 => rv.b1001 = Some(offset),
+                _ => {}
+            }
+
+            let next = rv.u16_at(offset + 2) as usize;
+            if next <= offset {
+                break;
+            }
+            offset = next;
+            cnt += 1;
+        }
+
+        let rec_len = match rv.b1000 {
+            Some(b1000) if b1000 + 8 <= buf.len() => 1_usize
+                .checked_shl(buf[b1000 + 6] as u32)
+                .ok_or_else(|| MSError::from_str("invalid record length"))?,
+            _ => (MS2_SEARCH_STEP..)
+                .step_by(MS2_SEARCH_STEP)
+                .take_while(|offset| offset + MS2_FSDH_LEN <= buf.len())
+                .find(|offset| is_valid_ms2_header(&buf[*offset..]))
+                .unwrap_or(buf.len()),
+        };
+
+        if buf.len() < rec_len || rec_len < MS2_FSDH_LEN {
+            return Err(MSError::from_raw(MS_WRONGLENGTH));
+        }
+        rv.buf = &buf[..rec_len];
+
+        // drop blockettes exceeding the record, e.g. of corrupt or truncated records
+        rv.b100 = rv.b100.filter(|b100| b100 + 12 <= rec_len);
+        rv.b1000 = rv.b1000.filter(|b1000| b1000 + 8 <= rec_len);
+        rv.b1001 = rv.b1001.filter(|b1001| b1001 + 8 <= rec_len);
+
+        Ok(rv)
+    }
+
+    pub(crate) fn u16_at(&self, offset: usize) -> u16 {
+        let bytes = [self.buf[offset], self.buf[offset + 1]];
+        if self.format_version == 2 && self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    pub(crate) fn u32_at(&self, offset: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.buf[offset..offset + 4]);
+        if self.format_version == 2 && self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn i16_at(&self, offset: usize) -> i16 {
+        self.u16_at(offset) as i16
+    }
+
+    fn i32_at(&self, offset: usize) -> i32 {
+        self.u32_at(offset) as i32
+    }
+
+    /// Returns the raw miniSEED record.
+    pub fn raw(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Returns the length of the record in bytes.
+    pub fn rec_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the major format version of the underlying record.
+    pub fn format_version(&self) -> u8 {
+        self.format_version
+    }
+
+    /// Returns the [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    ///
+    /// For miniSEED v2 records the identifier is composed from the network, station, location
+    /// and channel codes of the fixed section of data header, i.e. it is not borrowed.
+    pub fn sid(&self) -> MSResult<Cow<'a, str>> {
+        match self.format_version {
+            3 => {
+                let sid_len = self.buf[33] as usize;
+                let sid = &self.buf[MS3_FSDH_LEN..MS3_FSDH_LEN + sid_len];
+                let sid = str::from_utf8(sid).map_err(|e| MSError::from_str(&e.to_string()))?;
+                Ok(Cow::Borrowed(sid))
+            }
+            _ => {
+                let net = clean(&self.buf[18..20])?;
+                let sta = clean(&self.buf[8..13])?;
+                let loc = clean(&self.buf[13..15])?;
+                let cha = clean(&self.buf[15..18])?;
+
+                let mut sid = format!("FDSN:{}_{}_{}_", net, sta, loc);
+                if cha.len() == 3 {
+                    for (i, c) in cha.chars().enumerate() {
+                        if i > 0 {
+                            sid.push('_');
+                        }
+                        sid.push(c);
+                    }
+                } else {
+                    sid.push_str(cha);
+                }
+
+                Ok(Cow::Owned(sid))
+            }
+        }
+    }
+
+    /// Returns the record level bit field flags.
+    ///
+    /// For miniSEED v2 records the flags are mapped from the activity, I/O and clock, and data
+    /// quality flags.
+    pub fn flags(&self) -> MSBitFieldFlags {
+        match self.format_version {
+            3 => MSBitFieldFlags::from_bits_retain(self.buf[3]),
+            _ => {
+                let mut flags = MSBitFieldFlags::empty();
+                if self.buf[36] & 0x01 != 0 {
+                    flags |= MSBitFieldFlags::CALIBRATION_SIGNAL_PRESENT;
+                }
+                if self.buf[38] & 0x80 != 0 {
+                    flags |= MSBitFieldFlags::TIME_TAG_QUESTIONABLE;
+                }
+                if self.buf[37] & 0x20 != 0 {
+                    flags |= MSBitFieldFlags::CLOCK_LOCKED;
+                }
+                flags
+            }
+        }
+    }
+
+    /// Returns the start time of the record as nanoseconds since the Unix epoch.
+    ///
+    /// For miniSEED v2 records the time correction is applied (unless flagged as applied,
+    /// already) as well as the microsecond offset of blockette 1001.
+    ///
+    /// Returns an error if the start time cannot be represented as nanosecond timestamp, e.g.
+    /// for corrupt year fields.
+    pub fn start_time_ns(&self) -> MSResult<i64> {
+        let nst = match self.format_version {
+            3 => util::time_fields_to_nstime(
+                self.u16_at(8).into(),
+                self.u16_at(10).into(),
+                self.buf[12].into(),
+                self.buf[13].into(),
+                self.buf[14].into(),
+                self.u32_at(4),
+            ),
+            _ => {
+                let mut nst = util::time_fields_to_nstime(
+                    self.u16_at(20).into(),
+                    self.u16_at(22).into(),
+                    self.buf[24].into(),
+                    self.buf[25].into(),
+                    self.buf[26].into(),
+                    u32::from(self.u16_at(28)) * 100_000,
+                );
+
+                let time_correction = self.i32_at(40);
+                if time_correction != 0 && self.buf[36] & 0x02 == 0 {
+                    nst = nst.and_then(|nst| nst.checked_add(i64::from(time_correction) * 100_000));
+                }
+
+                if let Some(b1001) = self.b1001 {
+                    if b1001 + 8 <= self.buf.len() {
+                        let offset = i64::from(self.buf[b1001 + 5] as i8) * 1000;
+                        nst = nst.and_then(|nst| nst.checked_add(offset));
+                    }
+                }
+
+                nst
+            }
+        };
+
+        nst.ok_or_else(|| MSError::from_str("start time out of range"))
+    }
+
+    /// Returns the start time of the record (i.e. the time of the first sample).
+    pub fn start_time(&self) -> MSResult<time::OffsetDateTime> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(self.start_time_ns()?.into())
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+
+    /// Returns the time of the last sample as nanoseconds since the Unix epoch.
+    pub fn end_time_ns(&self) -> MSResult<i64> {
+        Ok(util::sample_time(
+            self.start_time_ns()?,
+            self.sample_cnt() - 1,
+            self.sample_rate_hz(),
+        ))
+    }
+
+    /// Calculates the end time of the last sample in the record.
+    pub fn end_time(&self) -> MSResult<time::OffsetDateTime> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(self.end_time_ns()?.into())
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+
+    /// Returns the nominal sample rate as samples per second (`Hz`).
+    pub fn sample_rate_hz(&self) -> c_double {
+        match self.format_version {
+            3 => {
+                let samp_rate =
+                    f64::from_bits(u64::from_le_bytes(self.buf[16..24].try_into().unwrap()));
+                if samp_rate < 0.0 {
+                    -1.0 / samp_rate
+                } else {
+                    samp_rate
+                }
+            }
+            _ => {
+                if let Some(b100) = self.b100 {
+                    if b100 + 8 <= self.buf.len() {
+                        return f32::from_bits(self.u32_at(b100 + 4)).into();
+                    }
+                }
+
+                util::factor_multiplier_to_sample_rate(
+                    self.i16_at(32).into(),
+                    self.i16_at(34).into(),
+                )
+            }
+        }
+    }
+
+    /// Returns the data encoding format of the record.
+    pub fn encoding(&self) -> MSResult<MSDataEncoding> {
+        match self.format_version {
+            3 => MSDataEncoding::from_char(self.buf[15]),
+            _ => match self.b1000 {
+                Some(b1000) => MSDataEncoding::from_char(self.buf[b1000 + 4]),
+                None => Err(MSError::from_str(
+                    "data encoding unknown (missing blockette 1000)",
+                )),
+            },
+        }
+    }
+
+    /// Returns the record publication version.
+    ///
+    /// For miniSEED v2 records the publication version is mapped from the data quality
+    /// indicator.
+    pub fn pub_version(&self) -> u8 {
+        match self.format_version {
+            3 => self.buf[32],
+            _ => match self.buf[6] {
+                b'R' => 1,
+                b'D' => 2,
+                b'Q' => 3,
+                b'M' => 4,
+                _ => 0,
+            },
+        }
+    }
+
+    /// Returns the number of data samples as indicated by the raw record.
+    pub fn sample_cnt(&self) -> i64 {
+        match self.format_version {
+            3 => self.u32_at(24).into(),
+            _ => self.u16_at(30).into(),
+        }
+    }
+
+    /// Returns the CRC of the record.
+    ///
+    /// miniSEED v2 records do not contain a CRC, i.e. `0` is returned.
+    pub fn crc(&self) -> u32 {
+        match self.format_version {
+            3 => self.u32_at(28),
+            _ => 0,
+        }
+    }
+
+    /// Returns the records' extra headers, if available.
+    ///
+    /// Note that for miniSEED v2 records `None` is returned since extra headers are not part of
+    /// the raw record, but are created from blockettes while parsing. Use
+    /// [`MSRecordRef::to_record()`] in order to access them.
+    pub fn extra_headers(&self) -> Option<&'a [u8]> {
+        if self.format_version != 3 {
+            return None;
+        }
+
+        let sid_len = self.buf[33] as usize;
+        let extra_len = self.u16_at(34) as usize;
+        if extra_len == 0 {
+            return None;
+        }
+
+        let offset = MS3_FSDH_LEN + sid_len;
+        Some(&self.buf[offset..offset + extra_len])
+    }
+
+    /// Returns the offset of the data payload in bytes.
+    pub fn data_offset(&self) -> usize {
+        match self.format_version {
+            3 => MS3_FSDH_LEN + self.buf[33] as usize + self.u16_at(34) as usize,
+            _ => self.u16_at(44) as usize,
+        }
+    }
+
+    /// Returns the length of the data payload in bytes.
+    pub fn data_length(&self) -> u32 {
+        match self.format_version {
+            3 => self.u32_at(36),
+            _ => {
+                let data_offset = self.data_offset();
+                if data_offset == 0 || data_offset > self.buf.len() {
+                    return 0;
+                }
+
+                (self.buf.len() - data_offset) as u32
+            }
+        }
+    }
+
+    /// Returns the (encoded) data payload of the record.
+    pub fn data_payload(&self) -> &'a [u8] {
+        let len = self.data_length() as usize;
+        if len == 0 {
+            return &[];
+        }
+
+        let offset = self.data_offset();
+        &self.buf[offset..offset + len]
+    }
+
+    /// Decodes the Steim-1 or Steim-2 compressed data samples of the record without libmseed.
+    ///
+    /// Returns an error if the record is not Steim encoded. See also [`decode_steim1()`] and
+    /// [`decode_steim2()`].
+    pub fn decode_steim(&self) -> MSResult<SteimData> {
+        let sample_cnt =
+            usize::try_from(self.sample_cnt()).map_err(|e| MSError::from_str(&e.to_string()))?;
+        let big_endian = self.is_data_big_endian();
+
+        match self.encoding()? {
+            MSDataEncoding::Steim1 => decode_steim1(self.data_payload(), sample_cnt, big_endian),
+            MSDataEncoding::Steim2 => decode_steim2(self.data_payload(), sample_cnt, big_endian),
+            other => Err(MSError::from_str(&format!(
+                "invalid data encoding (not Steim encoded): {}",
+                other
+            ))),
+        }
+    }
+
+    /// Returns the byte order of the record header and the data payload.
+    ///
+    /// See also [`MSRecord::byte_order()`].
+    pub fn byte_order(&self) -> RecordByteOrder {
+        let byte_order = |big_endian: bool| {
+            if big_endian {
+                MSByteOrder::BigEndian
+            } else {
+                MSByteOrder::LittleEndian
+            }
+        };
+
+        let data_big_endian = match self.format_version {
+            3 => matches!(
+                self.encoding(),
+                Ok(MSDataEncoding::Steim1 | MSDataEncoding::Steim2)
+            ),
+            _ => self.is_data_big_endian(),
+        };
+
+        RecordByteOrder {
+            header: byte_order(self.format_version == 2 && self.big_endian),
+            data: byte_order(data_big_endian),
+        }
+    }
+
+    /// Returns whether the data payload is in big-endian word order.
+    pub(crate) fn is_data_big_endian(&self) -> bool {
+        // Steim encoded data of miniSEED v3 records is always big-endian
+        match (self.format_version, self.b1000) {
+            (3, _) => true,
+            (_, Some(b1000)) => self.buf[b1000 + 5] == 1,
+            _ => self.big_endian,
+        }
+    }
+
+    /// Returns whether the miniSEED v2 record contains blockette 1000.
+    pub(crate) fn has_b1000(&self) -> bool {
+        self.b1000.is_some()
+    }
+
+    /// Parses the record into an owned [`MSRecord`] with control flags `flags`.
+    ///
+    /// Note that the raw record of the [`MSRecord`] returned refers to the buffer borrowed.
+    pub fn to_record(&self, flags: MSControlFlags) -> MSResult<MSRecord> {
+        MSRecord::parse(self.buf, flags)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for MSRecordRef<'a> {
+    type Error = MSError;
+
+    fn try_from(buf: &'a [u8]) -> MSResult<Self> {
+        Self::parse(buf)
+    }
+}
+
+/// Checks whether `buf` starts with a valid miniSEED v3 fixed header.
+pub(crate) fn is_valid_ms3_header(buf: &[u8]) -> bool {
+    buf.len() >= MS3_FSDH_LEN
+        && buf[0] == b'M'
+        && buf[1] == b'S'
+        && buf[2] == 3
+        && buf[12] <= 23
+        && buf[13] <= 59
+        && buf[14] <= 60
+}
+
+/// Checks whether `buf` starts with a valid miniSEED v2 fixed section of data header.
+pub(crate) fn is_valid_ms2_header(buf: &[u8]) -> bool {
+    buf.len() >= MS2_FSDH_LEN
+        && buf[..6]
+            .iter()
+            .all(|c| c.is_ascii_digit() || *c == b' ' || *c == 0)
+        && matches!(buf[6], b'D' | b'R' | b'Q' | b'M')
+        && (buf[7] == b' ' || buf[7] == 0)
+        && buf[24] <= 23
+        && buf[25] <= 59
+        && buf[26] <= 60
+}
+
+/// Checks whether the year and day-of-year are in a valid range.
+fn is_valid_year_day(year: u16, yday: u16) -> bool {
+    (1900..=2100).contains(&year) && (1..=366).contains(&yday)
+}
+
+/// Returns a header code with all spaces removed.
+fn clean(code: &[u8]) -> MSResult<&str> {
+    let code = str::from_utf8(code).map_err(|e| MSError::from_str(&e.to_string()))?;
+    Ok(code.trim_matches(|c| c == ' ' || c == '\0'))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use crate::test;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    #[test]
+    fn test_record_ref_signal() {
+        for (f, format_version, crc, data_length) in [
+            ("testdata-3channel-signal.mseed3", 3, 0x4F3EAB65, 384),
+            ("testdata-3channel-signal.mseed2", 2, 0, 448),
+        ] {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let buf = fs::read(p).unwrap();
+            let rec = MSRecordRef::parse(&buf).unwrap();
+
+            assert_eq!(rec.format_version(), format_version);
+            assert_eq!(rec.sid().unwrap(), "FDSN:IU_COLA_00_L_H_1");
+            assert_eq!(
+                rec.start_time().unwrap().format(&Iso8601::DEFAULT).unwrap(),
+                "2010-02-27T06:50:00.069539000Z"
+            );
+            assert_eq!(
+                rec.end_time().unwrap().format(&Iso8601::DEFAULT).unwrap(),
+                "2010-02-27T06:52:14.069539000Z"
+            );
+            assert_eq!(rec.sample_rate_hz(), 1.0);
+            assert_eq!(rec.encoding().unwrap(), MSDataEncoding::Steim2);
+            assert_eq!(rec.pub_version(), 4);
+            assert_eq!(rec.sample_cnt(), 135);
+            assert_eq!(rec.crc(), crc);
+            assert_eq!(rec.data_length(), data_length);
+            assert_eq!(rec.data_payload().len(), data_length as usize);
+            if format_version == 3 {
+                assert_eq!(
+                    rec.extra_headers().unwrap(),
+                    b"{\"FDSN\":{\"Time\":{\"Quality\":100}}}"
+                );
+            } else {
+                assert!(rec.extra_headers().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_ref_rec_len() {
+        let test_data = vec![
+            ("reference-testdata-text.mseed2", 512),
+            ("reference-testdata-text.mseed3", 294),
+            ("reference-testdata-steim2.mseed2", 512),
+            ("reference-testdata-steim2.mseed3", 507),
+            ("testdata-detection.record.mseed2", 512),
+            ("testdata-no-blockette1000-steim1.mseed2", 4096),
+        ];
+
+        for (f, expected) in &test_data {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let buf = fs::read(p).unwrap();
+            let rec = MSRecordRef::parse(&buf).unwrap();
+            assert_eq!(rec.rec_len(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_record_ref_matches_record() {
+        let test_data = vec![
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+            "testdata-no-blockette1000-steim1.mseed2",
+            "reference-testdata-text.mseed3",
+        ];
+
+        for f in &test_data {
+            for rec in test::records(f) {
+                let rec = MSRecordRef::parse(&rec).unwrap();
+                let msr = rec.to_record(MSControlFlags::empty()).unwrap();
+
+                assert_eq!(rec.sid().unwrap(), msr.sid().unwrap());
+                assert_eq!(rec.start_time().unwrap(), msr.start_time().unwrap());
+                assert_eq!(rec.end_time().unwrap(), msr.end_time().unwrap());
+                assert_eq!(rec.sample_rate_hz(), msr.sample_rate_hz());
+                assert_eq!(rec.sample_cnt(), msr.sample_cnt());
+                assert_eq!(rec.pub_version(), msr.pub_version());
+                assert_eq!(rec.flags(), msr.flags());
+                assert_eq!(rec.crc(), msr.crc());
+                assert_eq!(rec.data_length(), msr.data_length());
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_ref_blockette_exceeding_record() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("reference-testdata-steim2.mseed2");
+
+        let buf = fs::read(p).unwrap();
+        let buf = &buf[..1024];
+
+        // place blockette 1000 at any offset, including offsets past the record length
+        for offset in (48..1024).step_by(4) {
+            let mut rec = buf.to_vec();
+            rec[39] = 1;
+            rec[46..48].copy_from_slice(&(offset as u16).to_be_bytes());
+            let b1000 = [0x03, 0xe8, 0x00, 0x00, 0x0b, 0x01, 0x09, 0x00];
+            let len = b1000.len().min(rec.len() - offset);
+            rec[offset..offset + len].copy_from_slice(&b1000[..len]);
+
+            let rec = match MSRecordRef::parse(&rec) {
+                Ok(rec) => rec,
+                Err(_) => continue,
+            };
+            if offset + 8 > rec.rec_len() {
+                assert!(!rec.has_b1000());
+                assert!(rec.encoding().is_err());
+            }
+            let _ = rec.is_data_big_endian();
+            let _ = rec.data_payload();
+            let _ = rec.start_time();
+            let _ = rec.sample_rate_hz();
+        }
+    }
+
+    #[test]
+    fn test_record_ref_year_out_of_range() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("reference-testdata-steim2.mseed3");
+
+        let mut buf = fs::read(p).unwrap();
+        buf[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        assert!(rec.start_time_ns().is_err());
+        assert!(rec.start_time().is_err());
+        assert!(rec.end_time_ns().is_err());
+    }
+
+    #[test]
+    fn test_record_ref_not_seed() {
+        let buf = [0u8; 128];
+        let err = MSRecordRef::parse(&buf).unwrap_err();
+        assert_eq!(err.raw_code(), MS_NOTSEED);
+    }
+}
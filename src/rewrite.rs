@@ -0,0 +1,573 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::ptr;
+
+use memmap2::Mmap;
+
+use crate::blockette::append_blockettes;
+use crate::pack::sid_as_cstring;
+use crate::record_ref::{MS2_FSDH_LEN, MS3_FSDH_LEN};
+use crate::swap::set_mseed2_byte_order;
+use crate::{
+    pack_header2, raw, repack_mseed3, Blockette, MSBitFieldFlags, MSControlFlags, MSError,
+    MSRecord, MSRecordRef, MSResult,
+};
+
+impl MSRecord {
+    /// Sets the [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    ///
+    /// Note that the raw record (see [`MSRecord::raw()`]) is not updated. See also
+    /// [`rewrite_header()`].
+    pub fn set_sid(&mut self, sid: &str) -> MSResult<()> {
+        let sid = sid_as_cstring(sid)?;
+        let sid = sid.as_bytes_with_nul();
+        unsafe {
+            let msr = self.get_raw_mut();
+            (*msr).sid = [0; raw::LM_SIDLEN as usize];
+            ptr::copy_nonoverlapping(sid.as_ptr(), (*msr).sid.as_mut_ptr() as *mut u8, sid.len());
+        }
+
+        Ok(())
+    }
+
+    /// Sets the publication version.
+    ///
+    /// For miniSEED v2 records the publication version is mapped to the data quality indicator.
+    pub fn set_pub_version(&mut self, pub_version: u8) {
+        unsafe { (*self.get_raw_mut()).pubversion = pub_version }
+    }
+
+    /// Sets the record level bit field flags.
+    pub fn set_flags(&mut self, flags: MSBitFieldFlags) {
+        unsafe { (*self.get_raw_mut()).flags = flags.bits() }
+    }
+
+    /// Applies the time correction `correction` to the start time of the record.
+    pub fn apply_time_correction(&mut self, correction: time::Duration) -> MSResult<()> {
+        let correction = i64::try_from(correction.whole_nanoseconds())
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        unsafe {
+            let msr = self.get_raw_mut();
+            (*msr).starttime = (*msr)
+                .starttime
+                .checked_add(correction)
+                .ok_or_else(|| MSError::from_str("start time out of range"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Rewrites the header of the record at the beginning of `rec` without re-encoding the data
+/// payload.
+///
+/// The record is parsed without unpacking the data samples and passed to `edit`, which may
+/// modify header fields, e.g. by means of [`MSRecord::set_sid()`],
+/// [`MSRecord::set_pub_version()`], [`MSRecord::set_flags()`],
+/// [`MSRecord::apply_time_correction()`] or [`MSRecord::set_extra_headers()`]. Afterwards, the
+/// header is packed by means of [`repack_mseed3()`] (miniSEED v3, including the CRC) or
+/// [`pack_header2()`] (miniSEED v2), while the encoded data payload is copied byte-for-byte.
+/// Returns the rewritten record.
+///
+/// miniSEED v2 records keep their record length, byte order and data offset. Blockettes not
+/// recreated by libmseed (e.g. blockette 2000) are re-appended to the blockette chain. Thus, an
+/// error is returned if the rewritten header does not fit in front of the data payload. Records
+/// without data payload (i.e. a data offset of zero, e.g. opaque records) keep their data offset
+/// of zero.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs;
+///
+/// use mseed::MSRecordRef;
+///
+/// let buf = fs::read("path/to/data.mseed").unwrap();
+/// let rec = MSRecordRef::parse(&buf).unwrap();
+///
+/// let rewritten = mseed::rewrite_header(rec.raw(), |msr| {
+///     msr.set_pub_version(2);
+///     Ok(())
+/// })
+/// .unwrap();
+/// ```
+pub fn rewrite_header<F>(rec: &[u8], edit: F) -> MSResult<Vec<u8>>
+where
+    F: FnOnce(&mut MSRecord) -> MSResult<()>,
+{
+    let rec = MSRecordRef::parse(rec)?;
+    let mut msr = rec.to_record(MSControlFlags::empty())?;
+    edit(&mut msr)?;
+
+    match rec.format_version() {
+        3 => {
+            let extra_len = msr.extra_headers().map_or(0, |extra| extra.len());
+            let mut buf =
+                vec![
+                    0u8;
+                    MS3_FSDH_LEN + raw::LM_SIDLEN as usize + extra_len + rec.data_payload().len()
+                ];
+            let rec_len = repack_mseed3(&msr, &mut buf)?;
+            buf.truncate(rec_len);
+
+            Ok(buf)
+        }
+        _ => {
+            // records without data payload, e.g. opaque records, have a data offset of zero
+            let data_offset = rec.data_offset();
+            if (data_offset != 0 && data_offset < MS2_FSDH_LEN) || data_offset > rec.rec_len() {
+                return Err(MSError::from_str("invalid data offset"));
+            }
+
+            let mut buf = vec![0u8; rec.rec_len()];
+            let header_len = pack_header2(&msr, &mut buf)?;
+
+            // re-append the blockettes not recreated by libmseed
+            let packed = blockette_types(&buf, header_len);
+            let blockettes = rec
+                .blockettes()?
+                .iter()
+                .filter(|b| {
+                    let blockette_type = b.blockette_type();
+                    blockette_type != 1000
+                        && blockette_type != 1001
+                        && !packed.contains(&blockette_type)
+                })
+                .map(Blockette::to_bytes)
+                .collect::<MSResult<Vec<_>>>()?;
+            let header_len = append_blockettes(&mut buf, header_len, &blockettes)?;
+
+            if data_offset != 0 && header_len > data_offset {
+                return Err(MSError::from_str(&format!(
+                    "rewritten header exceeds data offset: {} > {}",
+                    header_len, data_offset
+                )));
+            }
+            buf[44..46].copy_from_slice(&(data_offset as u16).to_be_bytes());
+
+            // convert while the data payload is still zeroed
+            set_mseed2_byte_order(&mut buf, rec.byte_order())?;
+            if data_offset != 0 {
+                buf[data_offset..].copy_from_slice(&rec.raw()[data_offset..]);
+            }
+
+            Ok(buf)
+        }
+    }
+}
+
+/// Returns the types of the blockettes of the big-endian miniSEED v2 header of `header_len`
+/// bytes at the beginning of `buf`.
+fn blockette_types(buf: &[u8], header_len: usize) -> Vec<u16> {
+    let mut rv = vec![];
+    let mut offset = u16::from_be_bytes([buf[46], buf[47]]) as usize;
+    while offset >= MS2_FSDH_LEN && offset + 4 <= header_len && rv.len() < buf[39] as usize {
+        rv.push(u16::from_be_bytes([buf[offset], buf[offset + 1]]));
+        offset = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+    }
+
+    rv
+}
+
+/// Rewrites the headers of the records of the file at `path` in place.
+///
+/// See [`rewrite_header()`] for details. Returns the number of records rewritten. An error is
+/// returned if the length of a rewritten record differs from its original length, e.g. since
+/// the length of the source identifier of a miniSEED v3 record changed. Use
+/// [`rewrite_headers()`] instead if the record length may change.
+///
+/// The records are rewritten into a temporary file next to the file at `path` which replaces
+/// the file once all records were rewritten, i.e. the file is left untouched in case of an
+/// error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::MSBitFieldFlags;
+///
+/// let cnt = mseed::rewrite_headers_in_place("path/to/data.mseed", |msr| {
+///     msr.set_flags(msr.flags() | MSBitFieldFlags::CLOCK_LOCKED);
+///     Ok(())
+/// })
+/// .unwrap();
+/// ```
+pub fn rewrite_headers_in_place<P, F>(path: P, mut edit: F) -> MSResult<usize>
+where
+    P: AsRef<Path>,
+    F: FnMut(&mut MSRecord) -> MSResult<()>,
+{
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+    let metadata = file
+        .metadata()
+        .map_err(|e| MSError::from_str(&e.to_string()))?;
+    if metadata.len() == 0 {
+        return Ok(0);
+    }
+    // SAFETY: the file must not be modified while mapped
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let rv = (|| {
+        let tmp = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp_path)
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        let mut writer = BufWriter::new(tmp);
+        let cnt = rewrite_records(&mmap, &mut writer, true, &mut edit)?;
+        let tmp = writer
+            .into_inner()
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        tmp.set_permissions(metadata.permissions())
+            .and_then(|_| tmp.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        Ok(cnt)
+    })();
+    if rv.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    rv
+}
+
+/// Rewrites the headers of the records of `buf` and writes the rewritten records to `writer`.
+///
+/// If `same_len` is set, an error is returned if the length of a rewritten record differs from
+/// its original length. Returns the number of records rewritten.
+fn rewrite_records<W, F>(buf: &[u8], writer: &mut W, same_len: bool, mut edit: F) -> MSResult<usize>
+where
+    W: Write,
+    F: FnMut(&mut MSRecord) -> MSResult<()>,
+{
+    let mut cnt = 0;
+    let mut pos = 0;
+    while pos < buf.len() {
+        let rec_len = MSRecordRef::parse(&buf[pos..])?.rec_len();
+        let rec = rewrite_header(&buf[pos..pos + rec_len], &mut edit)?;
+        if same_len && rec.len() != rec_len {
+            return Err(MSError::from_str(&format!(
+                "length of rewritten record at offset {} differs: {} != {}",
+                pos,
+                rec.len(),
+                rec_len
+            )));
+        }
+
+        writer
+            .write_all(&rec)
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        pos += rec_len;
+        cnt += 1;
+    }
+
+    Ok(cnt)
+}
+
+/// Rewrites the headers of the records of the file at `input` and writes the rewritten records
+/// to the file at `output`.
+///
+/// See [`rewrite_header()`] for details. In contrast to [`rewrite_headers_in_place()`] the
+/// length of miniSEED v3 records may change. Returns the number of records rewritten. An error
+/// is returned if `input` and `output` refer to the same file.
+pub fn rewrite_headers<P, Q, F>(input: P, output: Q, mut edit: F) -> MSResult<usize>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&mut MSRecord) -> MSResult<()>,
+{
+    // creating the output file would truncate the input file
+    if let (Ok(input), Ok(output)) = (fs::canonicalize(&input), fs::canonicalize(&output)) {
+        if input == output {
+            return Err(MSError::from_str("input and output refer to the same file"));
+        }
+    }
+
+    let file = File::open(input).map_err(|e| MSError::from_str(&e.to_string()))?;
+    let mut writer =
+        BufWriter::new(File::create(output).map_err(|e| MSError::from_str(&e.to_string()))?);
+    if file
+        .metadata()
+        .map_err(|e| MSError::from_str(&e.to_string()))?
+        .len()
+        == 0
+    {
+        return Ok(0);
+    }
+    // SAFETY: the file must not be modified while mapped
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    let cnt = rewrite_records(&mmap, &mut writer, false, &mut edit)?;
+
+    writer
+        .flush()
+        .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    Ok(cnt)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+    use time::OffsetDateTime;
+
+    use crate::{
+        pack_opaque, pack_raw, test, Blockette100, Blockette2000, Blockette500, MSExtraHeaders,
+        MSReader, OpaqueTags, PackInfo,
+    };
+
+    /// Returns the blockettes of the miniSEED v2 record `rec` other than blockettes 1000 and
+    /// 1001, ordered by blockette type.
+    fn blockettes(rec: &[u8]) -> Vec<Blockette> {
+        let mut rv: Vec<Blockette> = MSRecordRef::parse(rec)
+            .unwrap()
+            .blockettes()
+            .unwrap()
+            .into_iter()
+            .filter(|b| b.blockette_type() != 1000 && b.blockette_type() != 1001)
+            .collect();
+        rv.sort_by_key(|b| b.blockette_type());
+        rv
+    }
+
+    #[test]
+    fn test_rewrite_header() {
+        let test_data = vec![
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-steim2.mseed3",
+            "testdata-3channel-signal.mseed3",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+        ];
+
+        for f in &test_data {
+            for rec in test::records(f) {
+                let rec = MSRecordRef::parse(&rec).unwrap();
+                let orig = rec.to_record(MSControlFlags::MSF_UNPACKDATA).unwrap();
+
+                let rewritten = rewrite_header(rec.raw(), |msr| {
+                    msr.set_pub_version(3);
+                    msr.set_flags(MSBitFieldFlags::CLOCK_LOCKED);
+                    Ok(())
+                })
+                .unwrap();
+
+                let rewritten_ref = MSRecordRef::parse(&rewritten).unwrap();
+                assert_eq!(rewritten.len(), rec.rec_len());
+                assert_eq!(rewritten_ref.data_payload(), rec.data_payload());
+                assert_eq!(rewritten_ref.byte_order(), rec.byte_order());
+
+                let msr = MSRecord::parse(
+                    &rewritten,
+                    MSControlFlags::MSF_UNPACKDATA | MSControlFlags::MSF_VALIDATECRC,
+                )
+                .unwrap();
+                assert_eq!(msr.pub_version(), 3);
+                assert!(msr.flags().contains(MSBitFieldFlags::CLOCK_LOCKED));
+                assert_eq!(msr.sid().unwrap(), orig.sid().unwrap());
+                assert_eq!(msr.start_time().unwrap(), orig.start_time().unwrap());
+                assert_eq!(msr.samples().unwrap(), orig.samples().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewrite_header_sid() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("reference-testdata-steim2.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let rewritten = rewrite_header(&buf, |msr| {
+            msr.set_sid("FDSN:XX_LONGER__B_H_Z")?;
+            msr.apply_time_correction(time::Duration::milliseconds(-500))?;
+            let mut extra_headers = MSExtraHeaders::new();
+            extra_headers.set_time_quality(80)?;
+            msr.set_extra_headers(&extra_headers)
+        })
+        .unwrap();
+
+        let orig = MSRecord::parse(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        let msr = MSRecord::parse(
+            &rewritten,
+            MSControlFlags::MSF_UNPACKDATA | MSControlFlags::MSF_VALIDATECRC,
+        )
+        .unwrap();
+        assert_eq!(msr.sid().unwrap(), "FDSN:XX_LONGER__B_H_Z");
+        assert_eq!(
+            msr.start_time().unwrap(),
+            orig.start_time().unwrap() - time::Duration::milliseconds(500)
+        );
+        assert_eq!(
+            msr.get_extra_header::<i64>("/FDSN/Time/Quality").unwrap(),
+            Some(80)
+        );
+        assert_eq!(msr.samples().unwrap(), orig.samples().unwrap());
+    }
+
+    #[test]
+    fn test_rewrite_headers() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let dir = test::scratch_dir("rewrite");
+        let input = dir.join("in.mseed3");
+        let output = dir.join("out.mseed3");
+        fs::copy(&p, &input).unwrap();
+
+        // the length of the source identifier changes
+        let cnt = rewrite_headers(&input, &output, |msr| {
+            let sid = msr.sid()?.replace("IU_COLA", "XX_COLA");
+            msr.set_sid(&sid.replace("_00_", "_"))
+        })
+        .unwrap();
+        assert!(rewrite_headers_in_place(&input, |msr| {
+            let sid = msr.sid()?.replace("_00_", "_");
+            msr.set_sid(&sid)
+        })
+        .is_err());
+        // the file is left untouched, without leaving a temporary file behind
+        assert_eq!(fs::read(&input).unwrap(), fs::read(&p).unwrap());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // the output must not truncate the input
+        assert!(rewrite_headers(&input, &input, |_| Ok(())).is_err());
+        assert!(rewrite_headers(&input, dir.join(".").join("in.mseed3"), |_| Ok(())).is_err());
+        assert_eq!(fs::read(&input).unwrap(), fs::read(&p).unwrap());
+
+        let rewritten: Vec<String> = MSReader::new(output.clone())
+            .unwrap()
+            .map(|msr| msr.unwrap().sid().unwrap())
+            .collect();
+        assert_eq!(rewritten.len(), cnt);
+        assert!(rewritten.iter().all(|sid| sid.starts_with("FDSN:XX_COLA_")));
+
+        let cnt_in_place = rewrite_headers_in_place(&input, |msr| {
+            msr.set_pub_version(4);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(cnt_in_place, cnt);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(
+            fs::metadata(&input).unwrap().len(),
+            fs::metadata(&p).unwrap().len()
+        );
+        for msr in MSReader::new(input).unwrap() {
+            assert_eq!(msr.unwrap().pub_version(), 4);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_header_blockettes() {
+        let time = OffsetDateTime::parse("2012-01-01T12:30:45.123456Z", &Iso8601::DEFAULT).unwrap();
+        let mut info = PackInfo::with_sample_rate("FDSN:XX_TEST__L_H_Z", 1.0).unwrap();
+        info.rec_len = 512;
+        info.blockettes = vec![
+            Blockette::SampleRate(Blockette100 {
+                sample_rate: 1.0,
+                flags: 0,
+            }),
+            Blockette::Timing(Blockette500 {
+                vco_correction: 50.0,
+                time,
+                reception_quality: 90,
+                count: 3,
+                exception_type: "MISSING".to_string(),
+                clock_model: "Quanterra Q330".to_string(),
+                clock_status: "Locked".to_string(),
+            }),
+            Blockette::Opaque(Blockette2000 {
+                record_number: 1,
+                word_order: 1,
+                flags: 0,
+                header_fields: vec!["STATE".to_string()],
+                data: b"ok".to_vec(),
+            }),
+        ];
+
+        let mut data_samples: Vec<i32> = (0..200).map(|i| (i % 50) * 1000 - 25000).collect();
+        let mut records = vec![];
+        pack_raw(
+            &mut data_samples,
+            &time,
+            |rec: &[u8]| records.push(rec.to_vec()),
+            &info,
+            MSControlFlags::MSF_FLUSHDATA | MSControlFlags::MSF_PACKVER2,
+        )
+        .unwrap();
+
+        for rec in &records {
+            let rewritten = rewrite_header(rec, |msr| {
+                msr.set_pub_version(2);
+                Ok(())
+            })
+            .unwrap();
+
+            assert_eq!(rewritten.len(), rec.len());
+            assert_eq!(blockettes(&rewritten), blockettes(rec));
+            let rewritten_ref = MSRecordRef::parse(&rewritten).unwrap();
+            let rec_ref = MSRecordRef::parse(rec).unwrap();
+            assert_eq!(rewritten_ref.data_offset(), rec_ref.data_offset());
+            assert_eq!(rewritten_ref.data_payload(), rec_ref.data_payload());
+
+            let msr = MSRecord::parse(&rewritten, MSControlFlags::MSF_UNPACKDATA).unwrap();
+            let orig = MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+            assert_eq!(msr.pub_version(), 2);
+            assert_eq!(msr.samples().unwrap(), orig.samples().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_rewrite_header_opaque() {
+        let time = OffsetDateTime::parse("2012-01-01T00:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let mut info = PackInfo::new("FDSN:XX_TEST__S_O_H").unwrap();
+        info.format_version = 2;
+        info.rec_len = 512;
+        info.blockettes = vec![Blockette::SampleRate(Blockette100 {
+            sample_rate: 0.0,
+            flags: 0,
+        })];
+
+        let tags = OpaqueTags::HeaderFields(vec!["SOH".to_string()]);
+        let mut records = vec![];
+        pack_opaque(
+            b"state of health",
+            &tags,
+            &time,
+            |rec: &[u8]| records.push(rec.to_vec()),
+            &info,
+            MSControlFlags::empty(),
+        )
+        .unwrap();
+        let mut rec = records.remove(0);
+        // records without data payload
+        rec[44..46].copy_from_slice(&0u16.to_be_bytes());
+
+        let rewritten = rewrite_header(&rec, |msr| msr.set_sid("FDSN:XX_OTHER__S_O_H")).unwrap();
+        assert_eq!(rewritten.len(), rec.len());
+        assert_eq!(&rewritten[44..46], &[0, 0]);
+        assert_eq!(blockettes(&rewritten), blockettes(&rec));
+
+        let rewritten_ref = MSRecordRef::parse(&rewritten).unwrap();
+        assert_eq!(
+            rewritten_ref.opaque_payloads().unwrap(),
+            MSRecordRef::parse(&rec).unwrap().opaque_payloads().unwrap()
+        );
+        let msr = rewritten_ref.to_record(MSControlFlags::empty()).unwrap();
+        assert_eq!(msr.sid().unwrap(), "FDSN:XX_OTHER__S_O_H");
+    }
+}
@@ -0,0 +1,231 @@
+use std::ffi::c_void;
+#[cfg(feature = "ndarray")]
+use std::ops::Range;
+use std::slice::from_raw_parts;
+use std::str;
+
+use crate::{MSError, MSResult, MSSampleType};
+
+/// Unpacked data samples typed according to their [`MSSampleType`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSReader, Samples};
+///
+/// let reader =
+///     MSReader::new_with_flags("path/to/data.mseed", MSControlFlags::MSF_UNPACKDATA).unwrap();
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     match msr.samples().unwrap() {
+///         Samples::Int32(samples) => println!("{} integer samples", samples.len()),
+///         Samples::Text(text) => println!("{}", text),
+///         other => println!("{:?}", other.to_f64_vec()),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Samples<'a> {
+    /// 32-bit integer data samples.
+    Int32(&'a [i32]),
+    /// 32-bit floating point data samples.
+    Float32(&'a [f32]),
+    /// 64-bit floating point data samples.
+    Float64(&'a [f64]),
+    /// Text data samples.
+    Text(&'a str),
+}
+
+impl<'a> Samples<'a> {
+    /// Creates typed data samples from raw parts.
+    ///
+    /// # Safety
+    ///
+    /// `data` must point to `len` valid data samples of type `sample_type` which must remain
+    /// valid for `'a`.
+    pub(crate) unsafe fn from_raw_parts(
+        data: *const c_void,
+        len: usize,
+        sample_type: MSSampleType,
+    ) -> MSResult<Self> {
+        if data.is_null() {
+            return Err(MSError::from_str("data samples must be unpacked"));
+        }
+
+        let rv = match sample_type {
+            MSSampleType::Integer32 => Self::Int32(from_raw_parts(data as *const i32, len)),
+            MSSampleType::Float32 => Self::Float32(from_raw_parts(data as *const f32, len)),
+            MSSampleType::Float64 => Self::Float64(from_raw_parts(data as *const f64, len)),
+            MSSampleType::Text => {
+                let text = str::from_utf8(from_raw_parts(data as *const u8, len))
+                    .map_err(|e| MSError::from_str(&e.to_string()))?;
+                Self::Text(text)
+            }
+            MSSampleType::Unknown => return Err(MSError::from_str("unknown sample type")),
+        };
+
+        Ok(rv)
+    }
+
+    /// Returns the sample type of the data samples.
+    pub fn sample_type(&self) -> MSSampleType {
+        match self {
+            Self::Int32(_) => MSSampleType::Integer32,
+            Self::Float32(_) => MSSampleType::Float32,
+            Self::Float64(_) => MSSampleType::Float64,
+            Self::Text(_) => MSSampleType::Text,
+        }
+    }
+
+    /// Returns the number of data samples.
+    ///
+    /// For text data samples, the length in bytes is returned.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Int32(s) => s.len(),
+            Self::Float32(s) => s.len(),
+            Self::Float64(s) => s.len(),
+            Self::Text(s) => s.len(),
+        }
+    }
+
+    /// Returns whether there are no data samples.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the data samples within `range`, or `None` if `range` is out of bounds.
+    ///
+    /// For text data samples, `range` is in bytes.
+    #[cfg(feature = "ndarray")]
+    pub(crate) fn get(&self, range: Range<usize>) -> Option<Self> {
+        match self {
+            Self::Int32(s) => s.get(range).map(Self::Int32),
+            Self::Float32(s) => s.get(range).map(Self::Float32),
+            Self::Float64(s) => s.get(range).map(Self::Float64),
+            Self::Text(s) => s.get(range).map(Self::Text),
+        }
+    }
+
+    /// Returns the data samples if they are 32-bit integers.
+    pub fn as_i32(&self) -> Option<&'a [i32]> {
+        match self {
+            Self::Int32(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the data samples if they are 32-bit floating point numbers.
+    pub fn as_f32(&self) -> Option<&'a [f32]> {
+        match self {
+            Self::Float32(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the data samples if they are 64-bit floating point numbers.
+    pub fn as_f64(&self) -> Option<&'a [f64]> {
+        match self {
+            Self::Float64(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the data samples if they are text.
+    pub fn as_text(&self) -> Option<&'a str> {
+        match self {
+            Self::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Converts numeric data samples into 64-bit floating point numbers.
+    ///
+    /// Returns `None` for text data samples.
+    pub fn to_f64_vec(&self) -> Option<Vec<f64>> {
+        match self {
+            Self::Int32(s) => Some(s.iter().map(|&v| v as f64).collect()),
+            Self::Float32(s) => Some(s.iter().map(|&v| v as f64).collect()),
+            Self::Float64(s) => Some(s.to_vec()),
+            Self::Text(_) => None,
+        }
+    }
+
+    /// Converts numeric data samples into 32-bit floating point numbers.
+    ///
+    /// Note that the conversion may be lossy. Returns `None` for text data samples.
+    pub fn to_f32_vec(&self) -> Option<Vec<f32>> {
+        match self {
+            Self::Int32(s) => Some(s.iter().map(|&v| v as f32).collect()),
+            Self::Float32(s) => Some(s.to_vec()),
+            Self::Float64(s) => Some(s.iter().map(|&v| v as f32).collect()),
+            Self::Text(_) => None,
+        }
+    }
+
+    /// Converts numeric data samples into 32-bit integers.
+    ///
+    /// Floating point data samples are rounded to the nearest integer and saturated at the
+    /// integer bounds. Returns `None` for text data samples.
+    pub fn to_i32_vec(&self) -> Option<Vec<i32>> {
+        match self {
+            Self::Int32(s) => Some(s.to_vec()),
+            Self::Float32(s) => Some(s.iter().map(|&v| v.round() as i32).collect()),
+            Self::Float64(s) => Some(s.iter().map(|&v| v.round() as i32).collect()),
+            Self::Text(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_samples_conversion() {
+        let data = [1i32, -2, 3];
+        let samples = Samples::Int32(&data);
+        assert_eq!(samples.sample_type(), MSSampleType::Integer32);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples.as_i32(), Some(&data[..]));
+        assert!(samples.as_f64().is_none());
+        assert_eq!(samples.to_f64_vec(), Some(vec![1.0, -2.0, 3.0]));
+
+        let data = [1.4f64, -2.6];
+        let samples = Samples::Float64(&data);
+        assert_eq!(samples.to_i32_vec(), Some(vec![1, -3]));
+        assert_eq!(samples.to_f32_vec(), Some(vec![1.4, -2.6]));
+
+        let samples = Samples::Text("Hello");
+        assert_eq!(samples.len(), 5);
+        assert_eq!(samples.as_text(), Some("Hello"));
+        assert!(samples.to_f64_vec().is_none());
+    }
+
+    #[test]
+    fn test_samples_from_raw_parts() {
+        let data = [1.5f32, 2.5];
+        let samples =
+            unsafe { Samples::from_raw_parts(data.as_ptr() as *const _, 2, MSSampleType::Float32) }
+                .unwrap();
+        assert_eq!(samples, Samples::Float32(&data));
+
+        let text = b"abc\xff";
+        assert!(unsafe {
+            Samples::from_raw_parts(text.as_ptr() as *const _, 4, MSSampleType::Text)
+        }
+        .is_err());
+        assert!(
+            unsafe { Samples::from_raw_parts(std::ptr::null(), 0, MSSampleType::Integer32) }
+                .is_err()
+        );
+        assert!(unsafe {
+            Samples::from_raw_parts(data.as_ptr() as *const _, 2, MSSampleType::Unknown)
+        }
+        .is_err());
+    }
+}
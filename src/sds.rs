@@ -0,0 +1,479 @@
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::record_ref::MS3_FSDH_LEN;
+use crate::util::NetStaLocCha;
+use crate::{
+    pack_raw, pack_record, repack_mseed3, MSControlFlags, MSError, MSReader, MSRecord, MSResult,
+    MSSampleType, PackInfo,
+};
+
+/// SDS data type of waveform data files.
+const SDS_DATA_TYPE: char = 'D';
+/// Nanoseconds per day.
+const NS_PER_DAY: i128 = 86_400_000_000_000;
+
+/// A miniSEED archive organized according to the [SeisComP Data Structure
+/// (SDS)](https://www.seiscomp.de/seiscomp3/doc/applications/slarchive/SDS.html).
+///
+/// Day files are laid out as `YEAR/NET/STA/CHAN.D/NET.STA.LOC.CHAN.D.YEAR.DOY` below the
+/// archive's root directory.
+///
+/// # Examples
+///
+/// Copying records from a file into an archive and reading them back:
+///
+/// ```no_run
+/// use time::{Duration, OffsetDateTime};
+///
+/// use mseed::{MSControlFlags, MSReader, SdsArchive};
+///
+/// let archive = SdsArchive::new("path/to/sds");
+///
+/// let mut reader = MSReader::new("path/to/data.mseed").unwrap();
+/// while let Some(msr) = reader.next() {
+///     let msr = msr.unwrap();
+///     archive.write_record(&msr, MSControlFlags::empty()).unwrap();
+/// }
+///
+/// let end = OffsetDateTime::now_utc();
+/// let start = end - Duration::days(2);
+/// let reader = archive
+///     .read("FDSN:XX_TEST__B_H_Z", &start, &end, MSControlFlags::MSF_UNPACKDATA)
+///     .unwrap();
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SdsArchive {
+    root: PathBuf,
+}
+
+impl SdsArchive {
+    /// Creates a new `SdsArchive` located at the `root` directory.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the root directory of the archive.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path to the day file of `sid` for `date`.
+    ///
+    /// The file is not required to exist.
+    pub fn file_path(&self, sid: &str, date: time::Date) -> MSResult<PathBuf> {
+        let nslc = NetStaLocCha::parse(sid)?;
+        Ok(self.nslc_file_path(&nslc, date))
+    }
+
+    fn nslc_file_path(&self, nslc: &NetStaLocCha, date: time::Date) -> PathBuf {
+        let mut rv = self.root.clone();
+        rv.push(format!("{:04}", date.year()));
+        rv.push(&nslc.net);
+        rv.push(&nslc.sta);
+        rv.push(format!("{}.{}", nslc.cha, SDS_DATA_TYPE));
+        rv.push(format!(
+            "{}.{}.{}.{}.{}.{:04}.{:03}",
+            nslc.net,
+            nslc.sta,
+            nslc.loc,
+            nslc.cha,
+            SDS_DATA_TYPE,
+            date.year(),
+            date.ordinal()
+        ));
+
+        rv
+    }
+
+    /// Resolves the existing day files of `sid` covering the time window from `start` to `end`.
+    ///
+    /// Since records may start before midnight, the day file preceding `start` is included, as
+    /// well. The files returned are sorted chronologically.
+    pub fn resolve(
+        &self,
+        sid: &str,
+        start: &time::OffsetDateTime,
+        end: &time::OffsetDateTime,
+    ) -> MSResult<Vec<PathBuf>> {
+        let nslc = NetStaLocCha::parse(sid)?;
+
+        let mut rv = Vec::new();
+        let mut date = start.date().previous_day();
+        while let Some(d) = date {
+            if d > end.date() {
+                break;
+            }
+
+            let p = self.nslc_file_path(&nslc, d);
+            if p.is_file() {
+                rv.push(p);
+            }
+
+            date = d.next_day();
+        }
+
+        Ok(rv)
+    }
+
+    /// Returns a reader for the records of `sid` overlapping with the time window from `start` to
+    /// `end` (inclusive).
+    ///
+    /// The day files resolved (see [`SdsArchive::resolve()`]) are read chronologically as a
+    /// single record stream with control flags `flags`.
+    pub fn read(
+        &self,
+        sid: &str,
+        start: &time::OffsetDateTime,
+        end: &time::OffsetDateTime,
+        flags: MSControlFlags,
+    ) -> MSResult<SdsReader> {
+        let files = self.resolve(sid, start, end)?;
+
+        Ok(SdsReader {
+            files: files.into_iter(),
+            reader: None,
+            sid: sid.to_string(),
+            start: *start,
+            end: *end,
+            flags,
+        })
+    }
+
+    /// Writes `msr` to the day files of the archive.
+    ///
+    /// Records are appended to the day file selected by the record's source identifier and start
+    /// time. Directories are created as required. Records crossing midnight are split and
+    /// re-packed such that each day file contains only the samples of the corresponding day.
+    /// Otherwise, records are repacked as miniSEED v3 without unpacking the data samples.
+    ///
+    /// If `flags` has [`MSControlFlags::MSF_PACKVER2`] set records are packed as miniSEED v2.
+    ///
+    /// Returns the number of records written.
+    pub fn write_record(&self, msr: &MSRecord, flags: MSControlFlags) -> MSResult<usize> {
+        let nslc = NetStaLocCha::parse(&msr.sid()?)?;
+        let start_time = msr.start_time()?;
+        let end_time = msr.end_time()?;
+
+        if start_time.date() == end_time.date() || msr.sample_cnt() <= 1 {
+            let mut buf = Vec::new();
+            let cnt_records = if flags.contains(MSControlFlags::MSF_PACKVER2) {
+                let msr = unpacked(msr)?;
+                let (cnt_records, _) = pack_record(
+                    &msr,
+                    |rec: &[u8]| buf.extend_from_slice(rec),
+                    flags | MSControlFlags::MSF_FLUSHDATA,
+                )?;
+                cnt_records
+            } else {
+                let extra_len = msr.extra_headers().map_or(0, |extra| extra.len());
+                buf.resize(
+                    MS3_FSDH_LEN + msr.sid()?.len() + extra_len + msr.data_length() as usize,
+                    0,
+                );
+                let rec_len = repack_mseed3(msr, &mut buf)?;
+                buf.truncate(rec_len);
+                1
+            };
+
+            self.append(&nslc, start_time.date(), &buf)?;
+            return Ok(cnt_records);
+        }
+
+        let msr = unpacked(msr)?;
+        match msr.sample_type() {
+            MSSampleType::Integer32 => self.write_split::<i32>(&nslc, &msr, flags),
+            MSSampleType::Float32 => self.write_split::<f32>(&nslc, &msr, flags),
+            MSSampleType::Float64 => self.write_split::<f64>(&nslc, &msr, flags),
+            other => Err(MSError::from_str(&format!(
+                "cannot split record with sample type: {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Splits the samples of `msr` at midnight and packs each part into the corresponding day
+    /// file.
+    fn write_split<T: Copy>(
+        &self,
+        nslc: &NetStaLocCha,
+        msr: &MSRecord,
+        flags: MSControlFlags,
+    ) -> MSResult<usize> {
+        let samples = msr
+            .data_samples::<T>()
+            .ok_or_else(|| MSError::from_str("data samples must be unpacked"))?;
+        let sample_rate = msr.sample_rate_hz();
+
+        let mut info = PackInfo::with_sample_rate(msr.sid()?, sample_rate)?;
+        info.format_version = if flags.contains(MSControlFlags::MSF_PACKVER2) {
+            2
+        } else {
+            3
+        };
+        info.pub_version = msr.pub_version();
+        info.flags = msr.flags();
+        info.encoding = msr.encoding()?;
+        if let Some(extra) = msr.extra_headers() {
+            let extra: Vec<u8> = extra.iter().copied().take_while(|c| *c != 0).collect();
+            if !extra.is_empty() {
+                info.extra_headers =
+                    Some(CString::new(extra).map_err(|e| MSError::from_str(&e.to_string()))?);
+            }
+        }
+
+        let start_ns = msr.start_time()?.unix_timestamp_nanos();
+
+        let mut cnt_records = 0;
+        let mut offset = 0;
+        while offset < samples.len() {
+            let part_start = start_ns + (offset as f64 / sample_rate * 1e9 + 0.5) as i128;
+            let part_start = time::OffsetDateTime::from_unix_timestamp_nanos(part_start)
+                .map_err(|e| MSError::from_str(&e.to_string()))?;
+
+            // samples before the next midnight
+            let midnight =
+                (part_start.unix_timestamp_nanos().div_euclid(NS_PER_DAY) + 1) * NS_PER_DAY;
+            let end = ((midnight - start_ns) as f64 * sample_rate / 1e9).ceil() as usize;
+            let end = end.clamp(offset + 1, samples.len());
+
+            let mut part = samples[offset..end].to_vec();
+            let mut buf = Vec::new();
+            let (cnt, _) = pack_raw(
+                &mut part,
+                &part_start,
+                |rec: &[u8]| buf.extend_from_slice(rec),
+                &info,
+                flags | MSControlFlags::MSF_FLUSHDATA,
+            )?;
+            self.append(nslc, part_start.date(), &buf)?;
+
+            cnt_records += cnt;
+            offset = end;
+        }
+
+        Ok(cnt_records)
+    }
+
+    fn append(&self, nslc: &NetStaLocCha, date: time::Date, buf: &[u8]) -> MSResult<()> {
+        let p = self.nslc_file_path(nslc, date);
+        if let Some(dir) = p.parent() {
+            fs::create_dir_all(dir).map_err(|e| MSError::from_str(&e.to_string()))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(p)
+            .map_err(|e| MSError::from_str(&e.to_string()))?;
+        file.write_all(buf)
+            .map_err(|e| MSError::from_str(&e.to_string()))
+    }
+}
+
+/// Returns `msr` with its data samples unpacked.
+fn unpacked(msr: &MSRecord) -> MSResult<MSRecord> {
+    let mut rv = msr.try_clone()?;
+    if rv.num_samples() == 0 && rv.sample_cnt() > 0 {
+        rv.unpack_data()?;
+    }
+
+    Ok(rv)
+}
+
+/// A reader for the records of a [`SdsArchive`].
+///
+/// Created by [`SdsArchive::read()`].
+#[derive(Debug)]
+pub struct SdsReader {
+    files: std::vec::IntoIter<PathBuf>,
+    reader: Option<MSReader>,
+    sid: String,
+    start: time::OffsetDateTime,
+    end: time::OffsetDateTime,
+    flags: MSControlFlags,
+}
+
+impl SdsReader {
+    fn matches(&self, msr: &MSRecord) -> MSResult<bool> {
+        Ok(
+            msr.sid()? == self.sid
+                && msr.start_time()? <= self.end
+                && msr.end_time()? >= self.start,
+        )
+    }
+}
+
+impl Iterator for SdsReader {
+    type Item = MSResult<MSRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let reader = match self.reader {
+                Some(ref mut reader) => reader,
+                None => {
+                    let p = self.files.next()?;
+                    match MSReader::new_with_flags(p, self.flags) {
+                        Ok(reader) => self.reader.insert(reader),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            };
+
+            match reader.next() {
+                Some(Ok(msr)) => match self.matches(&msr) {
+                    Ok(true) => return Some(Ok(msr)),
+                    Ok(false) => continue,
+                    Err(e) => return Some(Err(e)),
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.reader = None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::test;
+
+    #[test]
+    fn test_sds_file_path() {
+        let archive = SdsArchive::new("/sds");
+        let date = time::Date::from_ordinal_date(2010, 58).unwrap();
+
+        assert_eq!(
+            archive.file_path("FDSN:IU_COLA_00_L_H_1", date).unwrap(),
+            PathBuf::from("/sds/2010/IU/COLA/LH1.D/IU.COLA.00.LH1.D.2010.058")
+        );
+        assert_eq!(
+            archive.file_path("FDSN:XX_TEST__B_H_Z", date).unwrap(),
+            PathBuf::from("/sds/2010/XX/TEST/BHZ.D/XX.TEST..BHZ.D.2010.058")
+        );
+    }
+
+    #[test]
+    fn test_sds_write_read() {
+        let root = test::scratch_dir("sds-write-read");
+        let archive = SdsArchive::new(&root);
+
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let sid = "FDSN:IU_COLA_00_L_H_1";
+        let mut expected = Vec::new();
+        let mut end_times = Vec::new();
+
+        let reader = MSReader::new(p.clone()).unwrap();
+        let mut cnt = 0;
+        for msr in reader {
+            let msr = msr.unwrap();
+            if msr.sid().unwrap() == sid {
+                expected.extend_from_slice(msr.raw().unwrap());
+                end_times.push(msr.end_time().unwrap());
+            }
+            cnt += archive.write_record(&msr, MSControlFlags::empty()).unwrap();
+        }
+        assert_eq!(cnt, 107);
+
+        let date = time::Date::from_ordinal_date(2010, 58).unwrap();
+        let day_file = archive.file_path(sid, date).unwrap();
+        assert_eq!(fs::read(day_file).unwrap(), expected);
+
+        let start = time::OffsetDateTime::parse("2010-02-27T06:55:00Z", &Iso8601::DEFAULT).unwrap();
+        let end = time::OffsetDateTime::parse("2010-02-28T00:00:00Z", &Iso8601::DEFAULT).unwrap();
+        let records: Vec<MSRecord> = archive
+            .read(sid, &start, &end, MSControlFlags::empty())
+            .unwrap()
+            .map(|msr| msr.unwrap())
+            .collect();
+        assert_eq!(
+            records.len(),
+            end_times.iter().filter(|t| **t >= start).count()
+        );
+        assert!(records.len() < end_times.len());
+        assert!(records.iter().all(|msr| msr.sid().unwrap() == sid));
+
+        let start = end;
+        let records = archive
+            .read(sid, &start, &end, MSControlFlags::empty())
+            .unwrap()
+            .count();
+        assert_eq!(records, 0);
+    }
+
+    #[test]
+    fn test_sds_write_split_midnight() {
+        let root = test::scratch_dir("sds-split");
+        let archive = SdsArchive::new(&root);
+
+        let sid = "FDSN:XX_TEST__B_H_Z";
+        let mut info = PackInfo::with_sample_rate(sid, 1.0).unwrap();
+        info.encoding = crate::MSDataEncoding::Integer32;
+        let start_time =
+            time::OffsetDateTime::parse("2012-01-01T23:59:50Z", &Iso8601::DEFAULT).unwrap();
+
+        let mut data_samples: Vec<i32> = (0..20).collect();
+        let mut buf = Vec::new();
+        pack_raw(
+            &mut data_samples,
+            &start_time,
+            |rec: &[u8]| buf.extend_from_slice(rec),
+            &info,
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .unwrap();
+        let msr = MSRecord::parse(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+
+        assert_eq!(
+            archive.write_record(&msr, MSControlFlags::empty()).unwrap(),
+            2
+        );
+
+        let start = start_time;
+        let end = start_time + time::Duration::minutes(1);
+        let files = archive.resolve(sid, &start, &end).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                root.join("2012/XX/TEST/BHZ.D/XX.TEST..BHZ.D.2012.001"),
+                root.join("2012/XX/TEST/BHZ.D/XX.TEST..BHZ.D.2012.002"),
+            ]
+        );
+
+        let records: Vec<MSRecord> = archive
+            .read(sid, &start, &end, MSControlFlags::MSF_UNPACKDATA)
+            .unwrap()
+            .map(|msr| msr.unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].start_time().unwrap(), start_time);
+        assert_eq!(
+            records[0].data_samples::<i32>().unwrap(),
+            &data_samples[..10]
+        );
+        assert_eq!(
+            records[1].start_time().unwrap(),
+            time::OffsetDateTime::parse("2012-01-02T00:00:00Z", &Iso8601::DEFAULT).unwrap()
+        );
+        assert_eq!(
+            records[1].data_samples::<i32>().unwrap(),
+            &data_samples[10..]
+        );
+    }
+}
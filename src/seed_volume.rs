@@ -0,0 +1,1557 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::str::{self, FromStr};
+
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::error::{MS_NOTSEED, MS_WRONGLENGTH};
+use crate::{detect, MSControlFlags, MSDataEncoding, MSError, MSRecord, MSRecordRef, MSResult};
+
+/// Logical record length assumed if the volume does not start with a blockette 10.
+const DEFAULT_LOGICAL_REC_LEN: usize = 4096;
+/// Length of the logical record header, i.e. sequence number, record type and continuation flag.
+const LOGICAL_REC_HEADER_LEN: usize = 8;
+/// Length of the blockette type and blockette length fields of control blockettes.
+const BLOCKETTE_HEADER_LEN: usize = 7;
+
+/// Blockette 10: volume identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette10 {
+    /// Version of the SEED format, e.g. `02.4`.
+    pub version: String,
+    /// Logical record length as exponent of a power of two.
+    pub rec_len_exp: u8,
+    /// Beginning time of the volume.
+    pub start_time: Option<OffsetDateTime>,
+    /// End time of the volume.
+    pub end_time: Option<OffsetDateTime>,
+    /// Time the volume was written.
+    pub volume_time: Option<OffsetDateTime>,
+    /// Originating organization.
+    pub organization: String,
+    /// Volume label.
+    pub label: String,
+}
+
+/// Blockette 11: volume station header index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette11 {
+    /// Station codes and the sequence numbers of the logical records containing the station
+    /// headers.
+    pub stations: Vec<(String, u32)>,
+}
+
+/// Blockette 30: data format dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette30 {
+    /// Short descriptive name of the data format.
+    pub name: String,
+    /// Data format identifier code, referenced by blockette 52.
+    pub code: u16,
+    /// Data family type.
+    pub family: u8,
+    /// Decoder keys.
+    pub keys: Vec<String>,
+}
+
+impl Blockette30 {
+    /// Returns the data encoding described by the data format, if known.
+    ///
+    /// Since decoder keys are rarely interpreted, the encoding is derived from the data family
+    /// type, the word length key (`W2`, `W4` or `W8`) and the name, e.g. `Steim2 Integer
+    /// Compression Format`.
+    pub fn encoding(&self) -> Option<MSDataEncoding> {
+        let name = self
+            .name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let word_len = self.keys.iter().find_map(|key| {
+            key.split_whitespace()
+                .find_map(|k| k.strip_prefix('W').and_then(|l| l.parse::<u8>().ok()))
+        });
+
+        match self.family {
+            // integer differences compression
+            50 if name.contains("STEIM1") => Some(MSDataEncoding::Steim1),
+            50 if name.contains("STEIM2") => Some(MSDataEncoding::Steim2),
+            50 => None,
+            // integer format
+            0 if name.contains("FLOAT") || name.contains("IEEE") => match word_len {
+                Some(4) => Some(MSDataEncoding::Float32),
+                Some(8) => Some(MSDataEncoding::Float64),
+                _ => None,
+            },
+            0 => match word_len {
+                Some(2) => Some(MSDataEncoding::Integer16),
+                Some(4) => Some(MSDataEncoding::Integer32),
+                _ => None,
+            },
+            // ASCII text
+            80 => Some(MSDataEncoding::Text),
+            _ => None,
+        }
+    }
+}
+
+/// Blockette 33: generic abbreviation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette33 {
+    /// Abbreviation lookup code.
+    pub code: u16,
+    /// Abbreviation description.
+    pub description: String,
+}
+
+/// Blockette 34: units abbreviation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blockette34 {
+    /// Unit lookup code.
+    pub code: u16,
+    /// Unit name, e.g. `M/S`.
+    pub name: String,
+    /// Unit description.
+    pub description: String,
+}
+
+/// Blockette 50: station identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette50 {
+    /// Station code.
+    pub station: String,
+    /// Latitude (degrees).
+    pub latitude: f64,
+    /// Longitude (degrees).
+    pub longitude: f64,
+    /// Elevation (`m`).
+    pub elevation: f64,
+    /// Site name.
+    pub site_name: String,
+    /// Network identifier code, referencing a blockette 33.
+    pub network_identifier: u16,
+    /// 32-bit word order.
+    pub word_order_32: String,
+    /// 16-bit word order.
+    pub word_order_16: String,
+    /// Start effective date.
+    pub start_date: Option<OffsetDateTime>,
+    /// End effective date.
+    pub end_date: Option<OffsetDateTime>,
+    /// Update flag.
+    pub update_flag: String,
+    /// Network code. Empty for SEED versions prior to `2.3`.
+    pub network: String,
+}
+
+/// Blockette 52: channel identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette52 {
+    /// Location identifier.
+    pub location: String,
+    /// Channel identifier.
+    pub channel: String,
+    /// Subchannel identifier.
+    pub subchannel: u16,
+    /// Instrument identifier, referencing a blockette 33.
+    pub instrument: u16,
+    /// Optional comment.
+    pub comment: String,
+    /// Units of signal response, referencing a blockette 34.
+    pub signal_units: u16,
+    /// Units of calibration input, referencing a blockette 34.
+    pub calibration_units: u16,
+    /// Latitude (degrees).
+    pub latitude: f64,
+    /// Longitude (degrees).
+    pub longitude: f64,
+    /// Elevation (`m`).
+    pub elevation: f64,
+    /// Local depth (`m`).
+    pub local_depth: f64,
+    /// Azimuth (degrees).
+    pub azimuth: f64,
+    /// Dip (degrees).
+    pub dip: f64,
+    /// Data format identifier code, referencing a blockette 30.
+    pub data_format: u16,
+    /// Data record length as exponent of a power of two.
+    pub rec_len_exp: u8,
+    /// Sample rate (`Hz`).
+    pub sample_rate: f64,
+    /// Maximum relative clock drift.
+    pub max_clock_drift: f64,
+    /// Channel flags.
+    pub flags: String,
+    /// Start date.
+    pub start_date: Option<OffsetDateTime>,
+    /// End date.
+    pub end_date: Option<OffsetDateTime>,
+    /// Update flag.
+    pub update_flag: String,
+}
+
+/// A complex value including its errors as used by blockette 53.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexValue {
+    /// Real part.
+    pub real: f64,
+    /// Imaginary part.
+    pub imaginary: f64,
+    /// Error of the real part.
+    pub real_error: f64,
+    /// Error of the imaginary part.
+    pub imaginary_error: f64,
+}
+
+/// A coefficient including its error as used by blockette 54.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficient {
+    /// Coefficient value.
+    pub value: f64,
+    /// Coefficient error.
+    pub error: f64,
+}
+
+/// Blockette 53: response (poles & zeros).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette53 {
+    /// Transfer function type, e.g. `A` for Laplace transform (`rad/s`).
+    pub transfer_function_type: String,
+    /// Stage sequence number.
+    pub stage: u8,
+    /// Stage signal input units, referencing a blockette 34.
+    pub input_units: u16,
+    /// Stage signal output units, referencing a blockette 34.
+    pub output_units: u16,
+    /// A0 normalization factor.
+    pub normalization_factor: f64,
+    /// Normalization frequency (`Hz`).
+    pub normalization_frequency: f64,
+    /// Complex zeros.
+    pub zeros: Vec<ComplexValue>,
+    /// Complex poles.
+    pub poles: Vec<ComplexValue>,
+}
+
+/// Blockette 54: response (coefficients).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette54 {
+    /// Response type, e.g. `D` for digital.
+    pub response_type: String,
+    /// Stage sequence number.
+    pub stage: u8,
+    /// Stage signal input units, referencing a blockette 34.
+    pub input_units: u16,
+    /// Stage signal output units, referencing a blockette 34.
+    pub output_units: u16,
+    /// Numerator coefficients.
+    pub numerators: Vec<Coefficient>,
+    /// Denominator coefficients.
+    pub denominators: Vec<Coefficient>,
+}
+
+/// Blockette 57: decimation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette57 {
+    /// Stage sequence number.
+    pub stage: u8,
+    /// Input sample rate (`Hz`).
+    pub input_sample_rate: f64,
+    /// Decimation factor.
+    pub factor: u32,
+    /// Decimation offset.
+    pub offset: u32,
+    /// Estimated delay (`s`).
+    pub delay: f64,
+    /// Correction applied (`s`).
+    pub correction: f64,
+}
+
+/// A calibration history entry of blockette 58.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalibrationHistory {
+    /// Sensitivity for the calibration.
+    pub sensitivity: f64,
+    /// Frequency of the calibration sensitivity (`Hz`).
+    pub frequency: f64,
+    /// Time of the calibration.
+    pub time: Option<OffsetDateTime>,
+}
+
+/// Blockette 58: channel sensitivity/gain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette58 {
+    /// Stage sequence number. `0` refers to the overall channel sensitivity.
+    pub stage: u8,
+    /// Sensitivity/gain.
+    pub gain: f64,
+    /// Frequency (`Hz`).
+    pub frequency: f64,
+    /// Calibration history.
+    pub history: Vec<CalibrationHistory>,
+}
+
+/// Blockette 61: FIR response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blockette61 {
+    /// Stage sequence number.
+    pub stage: u8,
+    /// Response name.
+    pub name: String,
+    /// Symmetry code, i.e. `A` (none), `B` (odd) or `C` (even).
+    pub symmetry: String,
+    /// Stage signal input units, referencing a blockette 34.
+    pub input_units: u16,
+    /// Stage signal output units, referencing a blockette 34.
+    pub output_units: u16,
+    /// FIR coefficients.
+    pub coefficients: Vec<f64>,
+}
+
+/// A response blockette of a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelResponse {
+    /// Blockette 53.
+    PolesZeros(Blockette53),
+    /// Blockette 54.
+    Coefficients(Blockette54),
+    /// Blockette 57.
+    Decimation(Blockette57),
+    /// Blockette 58.
+    Sensitivity(Blockette58),
+    /// Blockette 61.
+    Fir(Blockette61),
+}
+
+impl ChannelResponse {
+    /// Returns the stage sequence number of the response blockette.
+    pub fn stage(&self) -> u8 {
+        match self {
+            Self::PolesZeros(b) => b.stage,
+            Self::Coefficients(b) => b.stage,
+            Self::Decimation(b) => b.stage,
+            Self::Sensitivity(b) => b.stage,
+            Self::Fir(b) => b.stage,
+        }
+    }
+}
+
+/// Channel metadata of a station control header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedChannel {
+    /// Channel identifier blockette.
+    pub identifier: Blockette52,
+    /// Response blockettes in order of appearance.
+    pub responses: Vec<ChannelResponse>,
+}
+
+/// Station metadata of a station control header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedStation {
+    /// Station identifier blockette.
+    pub identifier: Blockette50,
+    /// Channels of the station.
+    pub channels: Vec<SeedChannel>,
+}
+
+/// A full (or dataless) SEED volume.
+///
+/// Volume (`V`), abbreviation (`A`) and station (`S`) control headers are parsed into typed
+/// blockettes, i.e. blockettes 10, 11, 30, 33, 34, 50, 52, 53, 54, 57, 58 and 61. Other control
+/// blockettes (e.g. dictionary responses or comments) are skipped. Data records are indexed and
+/// made available as [`MSRecord`]s.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{ChannelResponse, MSControlFlags, SeedVolume};
+///
+/// let volume = SeedVolume::open("path/to/volume.seed").unwrap();
+///
+/// for station in volume.stations() {
+///     for channel in &station.channels {
+///         for response in &channel.responses {
+///             if let ChannelResponse::Sensitivity(b58) = response {
+///                 println!(
+///                     "{}.{}.{}: stage {} gain {}",
+///                     station.identifier.station,
+///                     channel.identifier.location,
+///                     channel.identifier.channel,
+///                     b58.stage,
+///                     b58.gain
+///                 );
+///             }
+///         }
+///     }
+/// }
+///
+/// for msr in volume.data_records(MSControlFlags::MSF_UNPACKDATA) {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SeedVolume {
+    buf: Vec<u8>,
+    volume_identifier: Option<Blockette10>,
+    station_index: Vec<Blockette11>,
+    data_formats: Vec<Blockette30>,
+    abbreviations: Vec<Blockette33>,
+    units: Vec<Blockette34>,
+    stations: Vec<SeedStation>,
+    data_records: Vec<Range<usize>>,
+}
+
+impl SeedVolume {
+    /// Reads the SEED volume at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> MSResult<Self> {
+        let buf = fs::read(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+        Self::from_vec(buf)
+    }
+
+    /// Parses a SEED volume from `buf`.
+    pub fn from_vec(buf: Vec<u8>) -> MSResult<Self> {
+        let mut rv = Self {
+            buf: vec![],
+            volume_identifier: None,
+            station_index: vec![],
+            data_formats: vec![],
+            abbreviations: vec![],
+            units: vec![],
+            stations: vec![],
+            data_records: vec![],
+        };
+
+        let control = rv.index_logical_records(&buf)?;
+        rv.parse_control_headers(&control)?;
+        rv.buf = buf;
+
+        Ok(rv)
+    }
+
+    /// Returns the volume identifier (blockette 10), if available.
+    pub fn volume_identifier(&self) -> Option<&Blockette10> {
+        self.volume_identifier.as_ref()
+    }
+
+    /// Returns the volume station header indexes (blockette 11).
+    pub fn station_index(&self) -> &[Blockette11] {
+        &self.station_index
+    }
+
+    /// Returns the data format dictionary (blockette 30).
+    pub fn data_formats(&self) -> &[Blockette30] {
+        &self.data_formats
+    }
+
+    /// Returns the generic abbreviations (blockette 33).
+    pub fn abbreviations(&self) -> &[Blockette33] {
+        &self.abbreviations
+    }
+
+    /// Returns the units abbreviations (blockette 34).
+    pub fn units(&self) -> &[Blockette34] {
+        &self.units
+    }
+
+    /// Returns the generic abbreviation with lookup code `code`, if available.
+    pub fn abbreviation(&self, code: u16) -> Option<&Blockette33> {
+        self.abbreviations.iter().find(|b| b.code == code)
+    }
+
+    /// Returns the units abbreviation with lookup code `code`, if available.
+    pub fn unit(&self, code: u16) -> Option<&Blockette34> {
+        self.units.iter().find(|b| b.code == code)
+    }
+
+    /// Returns the station metadata.
+    pub fn stations(&self) -> &[SeedStation] {
+        &self.stations
+    }
+
+    /// Returns the station metadata of the station with station code `station`, if available.
+    pub fn station(&self, station: &str) -> Option<&SeedStation> {
+        self.stations
+            .iter()
+            .find(|s| s.identifier.station == station)
+    }
+
+    /// Returns the number of data records.
+    pub fn num_data_records(&self) -> usize {
+        self.data_records.len()
+    }
+
+    /// Returns an iterator over the raw data records.
+    pub fn raw_data_records(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.data_records.iter().map(|r| &self.buf[r.clone()])
+    }
+
+    /// Returns an iterator parsing the data records with control flags `flags`.
+    ///
+    /// The data encoding of records lacking a blockette 1000 is taken from the data format
+    /// dictionary (blockette 30) referenced by the channel identifier (blockette 52) of the
+    /// record's channel, see [`Blockette30::encoding()`].
+    pub fn data_records(
+        &self,
+        flags: MSControlFlags,
+    ) -> impl Iterator<Item = MSResult<MSRecord>> + '_ {
+        self.raw_data_records()
+            .map(move |rec| self.parse_data_record(rec, flags))
+    }
+
+    fn parse_data_record(&self, rec: &[u8], flags: MSControlFlags) -> MSResult<MSRecord> {
+        if MSRecordRef::parse(rec)?.has_b1000() {
+            return MSRecord::parse(rec, flags);
+        }
+
+        let mut msr = MSRecord::parse(rec, flags & !MSControlFlags::MSF_UNPACKDATA)?;
+        if let Some(encoding) = self.channel_encoding(&msr)? {
+            unsafe { (*msr.get_raw_mut()).encoding = encoding as _ };
+        }
+        if flags.contains(MSControlFlags::MSF_UNPACKDATA) {
+            msr.unpack_data()?;
+        }
+
+        Ok(msr)
+    }
+
+    /// Returns the data encoding of the channel of `msr` effective at the record start time.
+    fn channel_encoding(&self, msr: &MSRecord) -> MSResult<Option<MSDataEncoding>> {
+        let (station, location, channel) = (msr.station()?, msr.location()?, msr.channel()?);
+        let start_time = msr.start_time()?;
+        let is_effective = |start: Option<OffsetDateTime>, end: Option<OffsetDateTime>| {
+            start.is_none_or(|t| t <= start_time) && end.is_none_or(|t| t > start_time)
+        };
+
+        let data_format = self
+            .stations
+            .iter()
+            .filter(|s| s.identifier.station == station)
+            .flat_map(|s| &s.channels)
+            .map(|c| &c.identifier)
+            .find(|b52| {
+                b52.location == location
+                    && b52.channel == channel
+                    && is_effective(b52.start_date, b52.end_date)
+            })
+            .map(|b52| b52.data_format);
+
+        Ok(data_format
+            .and_then(|code| self.data_formats.iter().find(|b30| b30.code == code))
+            .and_then(Blockette30::encoding))
+    }
+
+    /// Walks the logical records of `buf`. Indexes the data records and returns the
+    /// concatenated control headers without logical record headers.
+    fn index_logical_records(&mut self, buf: &[u8]) -> MSResult<Vec<u8>> {
+        let log_rec_len = logical_rec_len(buf);
+
+        let mut control = Vec::new();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let rec = &buf[pos..];
+            let len = log_rec_len.min(rec.len());
+            if rec[..len].iter().all(|&b| b == b' ' || b == 0) {
+                // skip padding
+                pos += len;
+                continue;
+            }
+
+            if rec.len() < LOGICAL_REC_HEADER_LEN
+                || !rec[..6].iter().all(|&b| b.is_ascii_digit() || b == b' ')
+            {
+                return Err(MSError::from_raw(MS_NOTSEED));
+            }
+
+            match rec[6] {
+                b'V' | b'A' | b'S' | b'T' => {
+                    control.extend_from_slice(&rec[LOGICAL_REC_HEADER_LEN..len]);
+                    pos += len;
+                }
+                b'D' | b'R' | b'Q' | b'M' => {
+                    let rec_len = match detect(rec) {
+                        Ok(det) if det.format_version == 2 => {
+                            det.rec_len.map(|l| l as usize).unwrap_or(len)
+                        }
+                        _ => return Err(MSError::from_raw(MS_NOTSEED)),
+                    };
+                    if rec_len > rec.len() {
+                        return Err(MSError::from_raw(MS_WRONGLENGTH));
+                    }
+                    self.data_records.push(pos..pos + rec_len);
+                    pos += rec_len;
+                }
+                _ => return Err(MSError::from_raw(MS_NOTSEED)),
+            }
+        }
+
+        Ok(control)
+    }
+
+    /// Parses the control blockettes of `control`.
+    fn parse_control_headers(&mut self, control: &[u8]) -> MSResult<()> {
+        let mut pos = 0;
+        while pos < control.len() {
+            if control[pos] == b' ' || control[pos] == 0 {
+                pos += 1;
+                continue;
+            }
+
+            let header = control
+                .get(pos..pos + BLOCKETTE_HEADER_LEN)
+                .ok_or_else(|| MSError::from_str("truncated control blockette"))?;
+            let mut fields = Fields::new(0, header);
+            let blockette_type: u16 = fields.num(3)?;
+            let blockette_len: usize = fields.num(4)?;
+            let body = control
+                .get(pos + BLOCKETTE_HEADER_LEN..pos + blockette_len)
+                .ok_or_else(|| {
+                    MSError::from_str(&format!("invalid length of blockette {}", blockette_type))
+                })?;
+
+            self.parse_blockette(&mut Fields::new(blockette_type, body))?;
+            pos += blockette_len;
+        }
+
+        Ok(())
+    }
+
+    fn parse_blockette(&mut self, f: &mut Fields) -> MSResult<()> {
+        match f.blockette_type {
+            10 => {
+                let version = f.fixed(4)?.to_string();
+                let rec_len_exp = f.num(2)?;
+                let start_time = f.time()?;
+                let end_time = f.time()?;
+                // the remaining fields are not available prior to SEED 2.3
+                let (volume_time, organization, label) = if f.is_empty() {
+                    (None, String::new(), String::new())
+                } else {
+                    (f.time()?, f.var()?, f.var()?)
+                };
+                self.volume_identifier = Some(Blockette10 {
+                    version,
+                    rec_len_exp,
+                    start_time,
+                    end_time,
+                    volume_time,
+                    organization,
+                    label,
+                });
+            }
+            11 => {
+                let cnt: usize = f.num(3)?;
+                let stations = f.repeat(cnt, |f| Ok((f.fixed(5)?.to_string(), f.num(6)?)))?;
+                self.station_index.push(Blockette11 { stations });
+            }
+            30 => {
+                let name = f.var()?;
+                let code = f.num(4)?;
+                let family = f.num(3)?;
+                let cnt: usize = f.num(2)?;
+                let keys = f.repeat(cnt, |f| f.var())?;
+                self.data_formats.push(Blockette30 {
+                    name,
+                    code,
+                    family,
+                    keys,
+                });
+            }
+            33 => {
+                let code = f.num(3)?;
+                let description = f.var()?;
+                self.abbreviations.push(Blockette33 { code, description });
+            }
+            34 => {
+                let code = f.num(3)?;
+                let name = f.var()?;
+                let description = f.var()?;
+                self.units.push(Blockette34 {
+                    code,
+                    name,
+                    description,
+                });
+            }
+            50 => {
+                let station = f.fixed(5)?.to_string();
+                let latitude = f.num(10)?;
+                let longitude = f.num(11)?;
+                let elevation = f.num(7)?;
+                // number of channels (reserved) and number of station comments
+                f.fixed(4 + 3)?;
+                let site_name = f.var()?;
+                let network_identifier = f.num(3)?;
+                let word_order_32 = f.fixed(4)?.to_string();
+                let word_order_16 = f.fixed(2)?.to_string();
+                let start_date = f.time()?;
+                let end_date = f.time()?;
+                let update_flag = f.fixed(1)?.to_string();
+                // not available prior to SEED 2.3
+                let network = if f.is_empty() {
+                    String::new()
+                } else {
+                    f.fixed(2)?.to_string()
+                };
+                self.stations.push(SeedStation {
+                    identifier: Blockette50 {
+                        station,
+                        latitude,
+                        longitude,
+                        elevation,
+                        site_name,
+                        network_identifier,
+                        word_order_32,
+                        word_order_16,
+                        start_date,
+                        end_date,
+                        update_flag,
+                        network,
+                    },
+                    channels: vec![],
+                });
+            }
+            52 => {
+                let location = f.fixed(2)?.to_string();
+                let channel = f.fixed(3)?.to_string();
+                let subchannel = f.num(4)?;
+                let instrument = f.num(3)?;
+                let comment = f.var()?;
+                let signal_units = f.num(3)?;
+                let calibration_units = f.num(3)?;
+                let latitude = f.num(10)?;
+                let longitude = f.num(11)?;
+                let elevation = f.num(7)?;
+                let local_depth = f.num(5)?;
+                let azimuth = f.num(5)?;
+                let dip = f.num(5)?;
+                let data_format = f.num(4)?;
+                let rec_len_exp = f.num(2)?;
+                let sample_rate = f.num(10)?;
+                let max_clock_drift = f.num(10)?;
+                // number of comments
+                f.fixed(4)?;
+                let flags = f.var()?;
+                let start_date = f.time()?;
+                let end_date = f.time()?;
+                let update_flag = f.fixed(1)?.to_string();
+                let identifier = Blockette52 {
+                    location,
+                    channel,
+                    subchannel,
+                    instrument,
+                    comment,
+                    signal_units,
+                    calibration_units,
+                    latitude,
+                    longitude,
+                    elevation,
+                    local_depth,
+                    azimuth,
+                    dip,
+                    data_format,
+                    rec_len_exp,
+                    sample_rate,
+                    max_clock_drift,
+                    flags,
+                    start_date,
+                    end_date,
+                    update_flag,
+                };
+                self.current_station(f)?.channels.push(SeedChannel {
+                    identifier,
+                    responses: vec![],
+                });
+            }
+            53 => {
+                let transfer_function_type = f.fixed(1)?.to_string();
+                let stage = f.num(2)?;
+                let input_units = f.num(3)?;
+                let output_units = f.num(3)?;
+                let normalization_factor = f.num(12)?;
+                let normalization_frequency = f.num(12)?;
+                let cnt: usize = f.num(3)?;
+                let zeros = f.repeat(cnt, Fields::complex)?;
+                let cnt: usize = f.num(3)?;
+                let poles = f.repeat(cnt, Fields::complex)?;
+                self.push_response(
+                    f,
+                    ChannelResponse::PolesZeros(Blockette53 {
+                        transfer_function_type,
+                        stage,
+                        input_units,
+                        output_units,
+                        normalization_factor,
+                        normalization_frequency,
+                        zeros,
+                        poles,
+                    }),
+                )?;
+            }
+            54 => {
+                let response_type = f.fixed(1)?.to_string();
+                let stage = f.num(2)?;
+                let input_units = f.num(3)?;
+                let output_units = f.num(3)?;
+                let cnt: usize = f.num(4)?;
+                let numerators = f.repeat(cnt, Fields::coefficient)?;
+                let cnt: usize = f.num(4)?;
+                let denominators = f.repeat(cnt, Fields::coefficient)?;
+                self.push_response(
+                    f,
+                    ChannelResponse::Coefficients(Blockette54 {
+                        response_type,
+                        stage,
+                        input_units,
+                        output_units,
+                        numerators,
+                        denominators,
+                    }),
+                )?;
+            }
+            57 => {
+                let b57 = Blockette57 {
+                    stage: f.num(2)?,
+                    input_sample_rate: f.num(10)?,
+                    factor: f.num(5)?,
+                    offset: f.num(5)?,
+                    delay: f.num(11)?,
+                    correction: f.num(11)?,
+                };
+                self.push_response(f, ChannelResponse::Decimation(b57))?;
+            }
+            58 => {
+                let stage = f.num(2)?;
+                let gain = f.num(12)?;
+                let frequency = f.num(12)?;
+                let cnt: usize = f.num(2)?;
+                let history = f.repeat(cnt, |f| {
+                    Ok(CalibrationHistory {
+                        sensitivity: f.num(12)?,
+                        frequency: f.num(12)?,
+                        time: f.time()?,
+                    })
+                })?;
+                self.push_response(
+                    f,
+                    ChannelResponse::Sensitivity(Blockette58 {
+                        stage,
+                        gain,
+                        frequency,
+                        history,
+                    }),
+                )?;
+            }
+            61 => {
+                let stage = f.num(2)?;
+                let name = f.var()?;
+                let symmetry = f.fixed(1)?.to_string();
+                let input_units = f.num(3)?;
+                let output_units = f.num(3)?;
+                let cnt: usize = f.num(4)?;
+                let coefficients = f.repeat(cnt, |f| f.num(14))?;
+                self.push_response(
+                    f,
+                    ChannelResponse::Fir(Blockette61 {
+                        stage,
+                        name,
+                        symmetry,
+                        input_units,
+                        output_units,
+                        coefficients,
+                    }),
+                )?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn current_station(&mut self, f: &Fields) -> MSResult<&mut SeedStation> {
+        self.stations
+            .last_mut()
+            .ok_or_else(|| f.error("no preceding station identifier"))
+    }
+
+    fn push_response(&mut self, f: &Fields, response: ChannelResponse) -> MSResult<()> {
+        self.current_station(f)?
+            .channels
+            .last_mut()
+            .ok_or_else(|| f.error("no preceding channel identifier"))?
+            .responses
+            .push(response);
+
+        Ok(())
+    }
+}
+
+/// Returns the logical record length of the volume from blockette 10 of the first logical
+/// record.
+fn logical_rec_len(buf: &[u8]) -> usize {
+    let rec_len_exp = buf
+        .get(..LOGICAL_REC_HEADER_LEN + BLOCKETTE_HEADER_LEN + 6)
+        .filter(|rec| rec[6] == b'V' && &rec[8..11] == b"010")
+        .and_then(|rec| str::from_utf8(&rec[19..21]).ok())
+        .and_then(|exp| exp.trim().parse::<u32>().ok())
+        .filter(|exp| (8..=16).contains(exp));
+
+    match rec_len_exp {
+        Some(exp) => 1 << exp,
+        None => DEFAULT_LOGICAL_REC_LEN,
+    }
+}
+
+/// Parses a SEED time of the form `YYYY,DDD,HH:MM:SS.FFFF`, where trailing components may be
+/// omitted. Returns `None` for an empty time.
+fn parse_seed_time(s: &str) -> Option<MSResult<OffsetDateTime>> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    let invalid = || MSError::from_str(&format!("invalid SEED time: '{}'", s));
+    let parse = || -> MSResult<OffsetDateTime> {
+        let mut parts = s.splitn(3, ',');
+        let year: i32 = parts
+            .next()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(invalid)?;
+        let yday: i32 = match parts.next() {
+            Some(v) => v.parse().map_err(|_| invalid())?,
+            None => 1,
+        };
+
+        let (mut hour, mut min, mut sec, mut nsec) = (0, 0, 0, 0);
+        if let Some(hms) = parts.next().filter(|v| !v.is_empty()) {
+            let (hms, fract) = match hms.split_once('.') {
+                Some((hms, fract)) => (hms, Some(fract)),
+                None => (hms, None),
+            };
+            let mut hms = hms
+                .split(':')
+                .map(|v| v.parse::<i32>().map_err(|_| invalid()));
+            hour = hms.next().transpose()?.unwrap_or(0);
+            min = hms.next().transpose()?.unwrap_or(0);
+            sec = hms.next().transpose()?.unwrap_or(0);
+            if let Some(fract) = fract.filter(|v| !v.is_empty()) {
+                if fract.len() > 9 || !fract.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                nsec = format!("{:0<9}", fract).parse().map_err(|_| invalid())?;
+            }
+        }
+
+        if !(0..=60).contains(&sec) {
+            return Err(invalid());
+        }
+
+        // leap seconds are rolled over into the next minute
+        let date = Date::from_ordinal_date(year, u16::try_from(yday).map_err(|_| invalid())?)
+            .map_err(|_| invalid())?;
+        let time = Time::from_hms(
+            u8::try_from(hour).map_err(|_| invalid())?,
+            u8::try_from(min).map_err(|_| invalid())?,
+            0,
+        )
+        .map_err(|_| invalid())?;
+        PrimitiveDateTime::new(date, time)
+            .assume_utc()
+            .checked_add(Duration::seconds(sec.into()) + Duration::nanoseconds(nsec.into()))
+            .ok_or_else(invalid)
+    };
+
+    Some(parse())
+}
+
+/// Reads the ASCII fields of a control blockette.
+struct Fields<'a> {
+    blockette_type: u16,
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(blockette_type: u16, buf: &'a [u8]) -> Self {
+        Self {
+            blockette_type,
+            buf,
+            pos: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> MSError {
+        MSError::from_str(&format!(
+            "invalid blockette {}: {}",
+            self.blockette_type, msg
+        ))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf[self.pos..].iter().all(|&b| b == b' ')
+    }
+
+    /// Reads a fixed length field, trimmed by whitespace.
+    fn fixed(&mut self, len: usize) -> MSResult<&'a str> {
+        let field = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| self.error("unexpected end of blockette"))?;
+        self.pos += len;
+
+        str::from_utf8(field)
+            .map(str::trim)
+            .map_err(|e| self.error(&e.to_string()))
+    }
+
+    /// Reads a variable length field terminated by `~`.
+    fn var(&mut self) -> MSResult<String> {
+        let len = self.buf[self.pos..]
+            .iter()
+            .position(|&b| b == b'~')
+            .ok_or_else(|| self.error("unterminated variable length field"))?;
+        let field = self.fixed(len)?.to_string();
+        self.pos += 1;
+
+        Ok(field)
+    }
+
+    /// Reads a fixed length numeric field.
+    fn num<T: FromStr>(&mut self, len: usize) -> MSResult<T> {
+        let field = self.fixed(len)?;
+        field
+            .parse()
+            .map_err(|_| self.error(&format!("invalid numeric field: '{}'", field)))
+    }
+
+    /// Reads a variable length time field.
+    fn time(&mut self) -> MSResult<Option<OffsetDateTime>> {
+        parse_seed_time(&self.var()?).transpose()
+    }
+
+    fn complex(&mut self) -> MSResult<ComplexValue> {
+        Ok(ComplexValue {
+            real: self.num(12)?,
+            imaginary: self.num(12)?,
+            real_error: self.num(12)?,
+            imaginary_error: self.num(12)?,
+        })
+    }
+
+    fn coefficient(&mut self) -> MSResult<Coefficient> {
+        Ok(Coefficient {
+            value: self.num(12)?,
+            error: self.num(12)?,
+        })
+    }
+
+    /// Reads `cnt` repeated fields by means of `f`.
+    fn repeat<T, F>(&mut self, cnt: usize, mut f: F) -> MSResult<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> MSResult<T>,
+    {
+        (0..cnt).map(|_| f(self)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::test;
+
+    /// Formats a control blockette from its type and body.
+    fn blockette(blockette_type: u16, body: &str) -> String {
+        format!(
+            "{:03}{:04}{}",
+            blockette_type,
+            body.len() + BLOCKETTE_HEADER_LEN,
+            body
+        )
+    }
+
+    /// Splits control blockettes into logical records of `rec_len` bytes, continuing blockettes
+    /// across records.
+    fn logical_records(buf: &mut Vec<u8>, rec_type: u8, blockettes: &[String], rec_len: usize) {
+        let payload: Vec<u8> = blockettes.concat().into_bytes();
+        for (i, chunk) in payload.chunks(rec_len - LOGICAL_REC_HEADER_LEN).enumerate() {
+            let seq = buf.len() / rec_len + 1;
+            let cont = if i == 0 { b' ' } else { b'*' };
+            buf.extend_from_slice(format!("{:06}", seq).as_bytes());
+            buf.extend_from_slice(&[rec_type, cont]);
+            buf.extend_from_slice(chunk);
+            buf.resize(buf.len().div_ceil(rec_len) * rec_len, b' ');
+        }
+    }
+
+    #[test]
+    fn test_parse_seed_time() {
+        assert!(parse_seed_time("").is_none());
+        assert_eq!(
+            parse_seed_time("2012,032,12:30:45.1234").unwrap().unwrap(),
+            test::parse_time("2012-02-01T12:30:45.1234Z")
+        );
+        assert_eq!(
+            parse_seed_time("2012,032").unwrap().unwrap(),
+            test::parse_time("2012-02-01T00:00:00Z")
+        );
+        assert_eq!(
+            parse_seed_time("2012,032,06").unwrap().unwrap(),
+            test::parse_time("2012-02-01T06:00:00Z")
+        );
+        // open-ended end dates of dataless SEED
+        assert_eq!(
+            parse_seed_time("2599,365,23:59:59").unwrap().unwrap(),
+            test::parse_time("2599-12-31T23:59:59Z")
+        );
+        assert_eq!(
+            parse_seed_time("2016,366,23:59:60").unwrap().unwrap(),
+            test::parse_time("2017-01-01T00:00:00Z")
+        );
+        assert!(parse_seed_time("2012,400").unwrap().is_err());
+        assert!(parse_seed_time("2013,366").unwrap().is_err());
+        assert!(parse_seed_time("2012,032,24:00:00").unwrap().is_err());
+        assert!(parse_seed_time("foo").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_seed_volume_dataless() {
+        let rec_len = 256;
+        let mut buf = vec![];
+        logical_records(
+            &mut buf,
+            b'V',
+            &[
+                blockette(
+                    10,
+                    "02.408\
+                     2012,001,00:00:00.0000~2012,002~2012,003,12:00:00.0000~\
+                     Test Organization~Label~",
+                ),
+                blockette(11, "002XMAS 000003ABC  000004"),
+            ],
+            rec_len,
+        );
+        logical_records(
+            &mut buf,
+            b'A',
+            &[
+                blockette(
+                    30,
+                    "Steim2 Integer Compression Format~000105001F1 P0:#0,1:#0,2:#0~",
+                ),
+                blockette(33, "001Test Network~"),
+                blockette(33, "002Broadband Seismometer~"),
+                blockette(34, "001M/S~Velocity in Meters Per Second~"),
+                blockette(34, "002COUNTS~Digital Counts~"),
+            ],
+            rec_len,
+        );
+
+        let zeros = "+0.00000E+00".repeat(4);
+        let pole = "-3.70040E-02+3.70160E-02+0.00000E+00+0.00000E+00";
+        let fir: String = (0..20)
+            .map(|i| format!("{:+14.7E}", i as f64 * 0.01))
+            .collect();
+        logical_records(
+            &mut buf,
+            b'S',
+            &[
+                blockette(
+                    50,
+                    &[
+                        "XMAS ",
+                        "+34.945999",
+                        "-106.457199",
+                        "+1850.0",
+                        "0001",
+                        "000",
+                        "Test Station~",
+                        "001",
+                        "3210",
+                        "10",
+                        "2012,001~",
+                        "~",
+                        "N",
+                        "XX",
+                    ]
+                    .concat(),
+                ),
+                blockette(
+                    52,
+                    &[
+                        "00",
+                        "BHZ",
+                        "0000",
+                        "002",
+                        "~",
+                        "001",
+                        "002",
+                        "+34.945999",
+                        "-106.457199",
+                        "+1850.0",
+                        "000.0",
+                        "000.0",
+                        "-90.0",
+                        "0001",
+                        "09",
+                        "4.0000E+01",
+                        "0.0000E+00",
+                        "0000",
+                        "CG~",
+                        "2012,001~",
+                        "~",
+                        "N",
+                    ]
+                    .concat(),
+                ),
+                blockette(
+                    53,
+                    &format!(
+                        "A01001001+6.02318E+04+1.00000E+00002{}{}002{}{}",
+                        zeros, zeros, pole, pole
+                    ),
+                ),
+                blockette(54, "D020020020001+1.00000E+00+0.00000E+000000"),
+                blockette(57, "024.0000E+010000100000+0.0000E+00+0.0000E+00"),
+                blockette(
+                    58,
+                    "01+1.50000E+03+1.00000E+0001+1.40000E+03+1.00000E+002011,365~",
+                ),
+                blockette(61, &format!("03FIR_TEST~A0020020020{}", fir)),
+                blockette(58, "00+6.29145E+08+1.00000E+0000"),
+            ],
+            rec_len,
+        );
+
+        let volume = SeedVolume::from_vec(buf).unwrap();
+        assert_eq!(volume.num_data_records(), 0);
+
+        let b10 = volume.volume_identifier().unwrap();
+        assert_eq!(b10.version, "02.4");
+        assert_eq!(b10.rec_len_exp, 8);
+        assert_eq!(
+            b10.start_time,
+            Some(test::parse_time("2012-01-01T00:00:00Z"))
+        );
+        assert_eq!(b10.end_time, Some(test::parse_time("2012-01-02T00:00:00Z")));
+        assert_eq!(
+            b10.volume_time,
+            Some(test::parse_time("2012-01-03T12:00:00Z"))
+        );
+        assert_eq!(b10.organization, "Test Organization");
+        assert_eq!(b10.label, "Label");
+
+        assert_eq!(
+            volume.station_index(),
+            &[Blockette11 {
+                stations: vec![("XMAS".to_string(), 3), ("ABC".to_string(), 4)]
+            }]
+        );
+
+        assert_eq!(
+            volume.data_formats(),
+            &[Blockette30 {
+                name: "Steim2 Integer Compression Format".to_string(),
+                code: 1,
+                family: 50,
+                keys: vec!["F1 P0:#0,1:#0,2:#0".to_string()],
+            }]
+        );
+        assert_eq!(volume.abbreviations().len(), 2);
+        assert_eq!(
+            volume.abbreviation(2).unwrap().description,
+            "Broadband Seismometer"
+        );
+        assert_eq!(volume.unit(1).unwrap().name, "M/S");
+        assert_eq!(volume.unit(2).unwrap().description, "Digital Counts");
+        assert!(volume.unit(3).is_none());
+
+        assert_eq!(volume.stations().len(), 1);
+        let station = volume.station("XMAS").unwrap();
+        assert_eq!(station.identifier.network, "XX");
+        assert_eq!(station.identifier.latitude, 34.945999);
+        assert_eq!(station.identifier.elevation, 1850.0);
+        assert_eq!(station.identifier.site_name, "Test Station");
+        assert_eq!(station.identifier.word_order_32, "3210");
+        assert_eq!(
+            station.identifier.start_date,
+            Some(test::parse_time("2012-01-01T00:00:00Z"))
+        );
+        assert_eq!(station.identifier.end_date, None);
+
+        assert_eq!(station.channels.len(), 1);
+        let channel = &station.channels[0];
+        assert_eq!(channel.identifier.location, "00");
+        assert_eq!(channel.identifier.channel, "BHZ");
+        assert_eq!(channel.identifier.instrument, 2);
+        assert_eq!(channel.identifier.signal_units, 1);
+        assert_eq!(channel.identifier.calibration_units, 2);
+        assert_eq!(channel.identifier.dip, -90.0);
+        assert_eq!(channel.identifier.rec_len_exp, 9);
+        assert_eq!(channel.identifier.sample_rate, 40.0);
+        assert_eq!(channel.identifier.flags, "CG");
+
+        let stages: Vec<u8> = channel.responses.iter().map(|r| r.stage()).collect();
+        assert_eq!(stages, vec![1, 2, 2, 1, 3, 0]);
+
+        match &channel.responses[0] {
+            ChannelResponse::PolesZeros(b53) => {
+                assert_eq!(b53.transfer_function_type, "A");
+                assert_eq!(b53.normalization_factor, 6.02318e4);
+                assert_eq!(b53.zeros.len(), 2);
+                assert_eq!(b53.poles.len(), 2);
+                assert_eq!(b53.poles[0].real, -3.7004e-2);
+                assert_eq!(b53.poles[0].imaginary, 3.7016e-2);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match &channel.responses[1] {
+            ChannelResponse::Coefficients(b54) => {
+                assert_eq!(b54.response_type, "D");
+                assert_eq!(
+                    b54.numerators,
+                    vec![Coefficient {
+                        value: 1.0,
+                        error: 0.0
+                    }]
+                );
+                assert!(b54.denominators.is_empty());
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match &channel.responses[2] {
+            ChannelResponse::Decimation(b57) => {
+                assert_eq!(b57.input_sample_rate, 40.0);
+                assert_eq!(b57.factor, 1);
+                assert_eq!(b57.offset, 0);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match &channel.responses[3] {
+            ChannelResponse::Sensitivity(b58) => {
+                assert_eq!(b58.gain, 1500.0);
+                assert_eq!(
+                    b58.history,
+                    vec![CalibrationHistory {
+                        sensitivity: 1400.0,
+                        frequency: 1.0,
+                        time: Some(test::parse_time("2011-12-31T00:00:00Z")),
+                    }]
+                );
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        match &channel.responses[4] {
+            ChannelResponse::Fir(b61) => {
+                assert_eq!(b61.name, "FIR_TEST");
+                assert_eq!(b61.symmetry, "A");
+                assert_eq!(b61.coefficients.len(), 20);
+                assert_eq!(b61.coefficients[19], 0.19);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_seed_volume_data_records() {
+        let mut p = crate::test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed2");
+        let data = fs::read(p).unwrap();
+
+        let mut buf = vec![];
+        logical_records(&mut buf, b'V', &[blockette(10, "02.409~~~~~")], 512);
+        buf.extend_from_slice(&data);
+
+        let volume = SeedVolume::from_vec(buf).unwrap();
+        assert_eq!(volume.volume_identifier().unwrap().start_time, None);
+        assert!(volume.stations().is_empty());
+        assert!(volume.num_data_records() > 0);
+        assert_eq!(
+            volume
+                .raw_data_records()
+                .map(|rec| rec.len())
+                .sum::<usize>(),
+            data.len()
+        );
+
+        let msr = volume
+            .data_records(MSControlFlags::MSF_UNPACKDATA)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(msr.sample_cnt(), 135);
+    }
+
+    #[test]
+    fn test_seed_volume_multiple() {
+        let mut p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        p.push("libmseed-sys/tests/multiple.seed");
+
+        let volume = SeedVolume::open(p).unwrap();
+        assert!(volume.volume_identifier().is_none());
+        assert!(volume.stations().is_empty());
+        assert_eq!(volume.num_data_records(), 1243);
+        assert!(volume.raw_data_records().all(|rec| rec.len() == 512));
+
+        let mut sample_cnt = 0;
+        for msr in volume.data_records(MSControlFlags::empty()) {
+            let msr = msr.unwrap();
+            assert_eq!(msr.sid().unwrap(), "FDSN:IU_ANMO_00_B_H_Z");
+            assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Steim2);
+            sample_cnt += msr.sample_cnt();
+        }
+        assert_eq!(sample_cnt, 288000);
+    }
+
+    #[test]
+    fn test_seed_volume_data_records_without_b1000() {
+        let mut p = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        p.push("libmseed-sys/tests/multiple.seed");
+        let data = fs::read(p).unwrap();
+        let records = &data[..2 * 512];
+
+        let rec_len = 512;
+        let mut buf = vec![];
+        logical_records(&mut buf, b'V', &[blockette(10, "02.409~~~~~")], rec_len);
+        logical_records(
+            &mut buf,
+            b'A',
+            &[blockette(
+                30,
+                "Steim2 Integer Compression Format~000105001F1 P0:#0,1:#0,2:#0~",
+            )],
+            rec_len,
+        );
+        logical_records(
+            &mut buf,
+            b'S',
+            &[
+                blockette(
+                    50,
+                    &[
+                        "ANMO ",
+                        "+34.945999",
+                        "-106.457199",
+                        "+1850.0",
+                        "0001",
+                        "000",
+                        "Albuquerque~",
+                        "001",
+                        "3210",
+                        "10",
+                        "2010,001~",
+                        "~",
+                        "N",
+                        "IU",
+                    ]
+                    .concat(),
+                ),
+                blockette(
+                    52,
+                    &[
+                        "00",
+                        "BHZ",
+                        "0000",
+                        "000",
+                        "~",
+                        "000",
+                        "000",
+                        "+34.945999",
+                        "-106.457199",
+                        "+1850.0",
+                        "000.0",
+                        "000.0",
+                        "-90.0",
+                        "0001",
+                        "09",
+                        "2.0000E+01",
+                        "0.0000E+00",
+                        "0000",
+                        "CG~",
+                        "2010,001~",
+                        "~",
+                        "N",
+                    ]
+                    .concat(),
+                ),
+            ],
+            rec_len,
+        );
+
+        // unlink blockette 1000 from the blockette chains
+        let mut stripped = records.to_vec();
+        for rec in stripped.chunks_mut(512) {
+            assert_eq!(u16::from_be_bytes([rec[48], rec[49]]), 1000);
+            rec[39] -= 1;
+            rec.copy_within(50..52, 46);
+        }
+        buf.extend_from_slice(&stripped);
+
+        // the record length is detected from the subsequent record or falls back to the
+        // logical record length
+        let volume = SeedVolume::from_vec(buf).unwrap();
+        assert_eq!(volume.num_data_records(), 2);
+        assert!(volume.raw_data_records().all(|rec| rec.len() == 512));
+
+        for (msr, rec) in volume
+            .data_records(MSControlFlags::MSF_UNPACKDATA)
+            .zip(records.chunks(512))
+        {
+            let msr = msr.unwrap();
+            assert_eq!(msr.encoding().unwrap(), MSDataEncoding::Steim2);
+
+            let expected = MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+            assert_eq!(
+                msr.data_samples::<i32>().unwrap(),
+                expected.data_samples::<i32>().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_blockette30_encoding() {
+        let b30 = |name: &str, family: u8, keys: &[&str]| Blockette30 {
+            name: name.to_string(),
+            code: 1,
+            family,
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+        };
+
+        assert_eq!(
+            b30("Steim1 Integer Compression Format", 50, &["F1 P4 W4 D C2"]).encoding(),
+            Some(MSDataEncoding::Steim1)
+        );
+        assert_eq!(
+            b30("STEIM-2 Integer Compression", 50, &[]).encoding(),
+            Some(MSDataEncoding::Steim2)
+        );
+        assert_eq!(
+            b30("32-bit Integers", 0, &["M0", "W4 D0-31 C2"]).encoding(),
+            Some(MSDataEncoding::Integer32)
+        );
+        assert_eq!(
+            b30("16-bit Integers", 0, &["M0", "W2 D0-15 C2"]).encoding(),
+            Some(MSDataEncoding::Integer16)
+        );
+        assert_eq!(
+            b30("IEEE Float", 0, &["W8"]).encoding(),
+            Some(MSDataEncoding::Float64)
+        );
+        assert_eq!(b30("SRO Gain Ranged Format", 1, &[]).encoding(), None);
+        assert_eq!(b30("Steim3 Integer Compression", 50, &[]).encoding(), None);
+    }
+
+    #[test]
+    fn test_seed_volume_not_seed() {
+        assert!(SeedVolume::from_vec(b"not a SEED volume".to_vec()).is_err());
+        assert!(SeedVolume::from_vec(vec![]).unwrap().stations().is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+
+use raw::{MS3SelectTime, MS3Selections};
+
+use crate::error::check;
+use crate::{raw, util, MSError, MSRecord, MSResult};
+
+/// A set of data selections.
+///
+/// Selections consist of [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/)
+/// glob patterns (supporting the `*`, `?` and `[]` wildcards), optional time windows and an
+/// optional publication version. Selections are passed through to libmseed, i.e. records not
+/// matching any of the selections are skipped by readers before their data samples are unpacked.
+/// Empty selections select all records.
+///
+/// # Examples
+///
+/// Reading selected records from a file:
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSReader, MSSelections};
+///
+/// let mut selections = MSSelections::new();
+/// selections.add_sid("FDSN:IU_*_*_B_H_?").unwrap();
+///
+/// let reader = MSReader::new_with_selections(
+///     "path/to/data.mseed",
+///     MSControlFlags::MSF_UNPACKDATA,
+///     selections,
+/// )
+/// .unwrap();
+///
+/// for msr in reader {
+///     let msr = msr.unwrap();
+///     println!("{}", msr);
+/// }
+/// ```
+///
+/// Selections may be read from a selection file, as well. Each line of the file is expected to
+/// be of the form `SourceID [Starttime [Endtime [Pubversion]]]`:
+///
+/// ```no_run
+/// use mseed::MSSelections;
+///
+/// let selections = MSSelections::from_file("path/to/selections.txt").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct MSSelections {
+    inner: *mut MS3Selections,
+}
+
+impl MSSelections {
+    /// Creates a new empty set of selections.
+    pub fn new() -> Self {
+        Self {
+            inner: ptr::null_mut(),
+        }
+    }
+
+    /// Reads selections from the selection file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> MSResult<Self> {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| MSError::from_str("invalid selection file path"))?;
+        let path = CString::new(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+
+        let mut rv = Self::new();
+        unsafe {
+            check(raw::ms3_readselectionsfile(
+                (&mut rv.inner) as *mut *mut MS3Selections,
+                path.as_ptr(),
+            ))?;
+        }
+
+        Ok(rv)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const MS3Selections {
+        self.inner
+    }
+
+    /// Adds a selection for all records matching `sid_pattern`.
+    pub fn add_sid(&mut self, sid_pattern: &str) -> MSResult<()> {
+        self.add(sid_pattern, None, None, None)
+    }
+
+    /// Adds a selection.
+    ///
+    /// `sid_pattern` is a [FDSN source
+    /// identifier](https://docs.fdsn.org/projects/source-identifiers/) glob pattern. If `start`
+    /// and/or `end` are `None`, the time window is open. If `pub_version` is `None`, records of
+    /// any publication version are selected.
+    pub fn add(
+        &mut self,
+        sid_pattern: &str,
+        start: Option<&time::OffsetDateTime>,
+        end: Option<&time::OffsetDateTime>,
+        pub_version: Option<u8>,
+    ) -> MSResult<()> {
+        let sid_pattern =
+            CString::new(sid_pattern).map_err(|e| MSError::from_str(&e.to_string()))?;
+        let start = start.map_or(raw::NSTUNSET, util::time_to_nstime);
+        let end = end.map_or(raw::NSTUNSET, util::time_to_nstime);
+
+        unsafe {
+            check(raw::ms3_addselect(
+                (&mut self.inner) as *mut *mut MS3Selections,
+                sid_pattern.as_ptr(),
+                start,
+                end,
+                pub_version.unwrap_or(0),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether no selections were added.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_null()
+    }
+
+    /// Returns whether `msr` matches any of the selections.
+    ///
+    /// Empty selections match all records, consistent with readers reading all records if passed
+    /// empty selections.
+    pub fn matches(&self, msr: &MSRecord) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let mut select_time: *const MS3SelectTime = ptr::null();
+        let rv = unsafe {
+            raw::msr3_matchselect(
+                self.inner,
+                msr.get_raw(),
+                // calculated from the record
+                raw::NSTUNSET,
+                (&mut select_time) as *mut *const MS3SelectTime,
+            )
+        };
+
+        !rv.is_null()
+    }
+}
+
+impl Default for MSSelections {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for MSSelections {
+    fn drop(&mut self) {
+        if !self.inner.is_null() {
+            unsafe {
+                raw::ms3_freeselections(self.inner);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use time::format_description::well_known::Iso8601;
+
+    use crate::{test, MSControlFlags, MSReader, MSTraceList};
+
+    #[test]
+    fn test_selections_matches() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut selections = MSSelections::new();
+        assert!(selections.is_empty());
+        let reader = MSReader::new(p.clone()).unwrap();
+        for msr in reader {
+            assert!(selections.matches(&msr.unwrap()));
+        }
+        selections.add_sid("FDSN:IU_COLA_*_L_H_[1Z]").unwrap();
+        assert!(!selections.is_empty());
+
+        let mut other = MSSelections::new();
+        let start = time::OffsetDateTime::parse("2010-02-27T07:00:00Z", &Iso8601::DEFAULT).unwrap();
+        other
+            .add("FDSN:IU_COLA_00_L_H_2", Some(&start), None, Some(4))
+            .unwrap();
+
+        let reader = MSReader::new(p).unwrap();
+        for msr in reader {
+            let msr = msr.unwrap();
+            let sid = msr.sid().unwrap();
+
+            assert_eq!(selections.matches(&msr), !sid.ends_with('2'));
+            assert_eq!(
+                other.matches(&msr),
+                sid.ends_with('2') && msr.end_time().unwrap() >= start
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_selection() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut selections = MSSelections::new();
+        selections.add_sid("FDSN:IU_COLA_00_L_H_Z").unwrap();
+
+        let reader =
+            MSReader::new_with_selections(p.clone(), MSControlFlags::MSF_UNPACKDATA, selections)
+                .unwrap();
+        let mut cnt = 0;
+        for msr in reader {
+            let msr = msr.unwrap();
+            assert_eq!(msr.sid().unwrap(), "FDSN:IU_COLA_00_L_H_Z");
+            assert_eq!(msr.num_samples(), msr.sample_cnt());
+            cnt += 1;
+        }
+        assert!(cnt > 0);
+
+        let mut selections = MSSelections::new();
+        selections.add_sid("FDSN:IU_COLA_00_L_H_[12]").unwrap();
+
+        let buf = fs::read(&p).unwrap();
+        let mstl = MSTraceList::from_buffer_with_selections(
+            &buf,
+            MSControlFlags::MSF_UNPACKDATA,
+            &selections,
+        )
+        .unwrap();
+        let sids: Vec<String> = mstl.iter().map(|tid| tid.sid().unwrap()).collect();
+        assert_eq!(sids, vec!["FDSN:IU_COLA_00_L_H_1", "FDSN:IU_COLA_00_L_H_2"]);
+
+        let mstl =
+            MSTraceList::read_with_selections(p, MSControlFlags::MSF_UNPACKDATA, &selections)
+                .unwrap();
+        assert_eq!(mstl.len(), 2);
+    }
+
+    #[test]
+    fn test_selections_from_file() {
+        let dir = test::scratch_dir("selections");
+        let path = dir.join("selections.txt");
+        fs::write(
+            &path,
+            "# selections\nFDSN:IU_COLA_00_L_H_1\nFDSN:IU_COLA_00_L_H_Z 2010-02-27T00:00:00 2010-02-28T00:00:00\n",
+        )
+        .unwrap();
+
+        let selections = MSSelections::from_file(&path).unwrap();
+        assert!(!selections.is_empty());
+
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let reader = MSReader::new(p).unwrap();
+        for msr in reader {
+            let msr = msr.unwrap();
+            assert_eq!(selections.matches(&msr), !msr.sid().unwrap().ends_with('2'));
+        }
+    }
+}
@@ -0,0 +1,595 @@
+use crate::{MSError, MSResult};
+
+/// Length of a Steim frame in bytes.
+pub const STEIM_FRAME_LEN: usize = 64;
+/// Number of 32-bit words per Steim frame.
+const FRAME_WORDS: usize = STEIM_FRAME_LEN / 4;
+
+/// Data samples decoded from Steim-1 or Steim-2 compressed frames.
+///
+/// See also [`decode_steim1()`] and [`decode_steim2()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SteimData {
+    /// Decoded data samples.
+    pub samples: Vec<i32>,
+    /// The first difference stored, i.e. the difference of the first sample with respect to the
+    /// last sample of the preceding record.
+    pub d0: i32,
+    /// Forward integration constant, i.e. the value of the first sample.
+    pub x0: i32,
+    /// Reverse integration constant, i.e. the value of the last sample.
+    pub xn: i32,
+}
+
+impl SteimData {
+    /// Returns whether the reverse integration constant is consistent with the decoded samples.
+    ///
+    /// A mismatch of the last sample and the reverse integration constant [`SteimData::xn`]
+    /// indicates corrupt frames.
+    pub fn is_consistent(&self) -> bool {
+        self.samples.last().is_none_or(|&last| last == self.xn)
+    }
+}
+
+/// Decodes `sample_cnt` data samples from Steim-1 compressed `frames`.
+///
+/// `frames` is expected to contain a sequence of 64-byte frames. Trailing bytes not making up a
+/// full frame are ignored. If `big_endian` is `false` the frames are decoded in little-endian
+/// word order, which is permitted for miniSEED v2, only.
+///
+/// Returns an error if the frames do not contain `sample_cnt` samples. Note that consistency of
+/// the integration constants is not enforced, see [`SteimData::is_consistent()`].
+pub fn decode_steim1(frames: &[u8], sample_cnt: usize, big_endian: bool) -> MSResult<SteimData> {
+    steim1(frames, sample_cnt, big_endian).and_then(|data| check_sample_cnt(data, sample_cnt, 1))
+}
+
+fn steim1(frames: &[u8], max_cnt: usize, big_endian: bool) -> MSResult<SteimData> {
+    decode(frames, max_cnt, big_endian, |word, nibble, diffs| {
+        match nibble {
+            // no differences
+            0b00 => {}
+            // 4 x 8-bit differences
+            0b01 => diffs.extend(word.iter().map(|&b| i32::from(b as i8))),
+            // 2 x 16-bit differences
+            0b10 => {
+                diffs.push(i32::from(read_i16(&word[0..2], big_endian)));
+                diffs.push(i32::from(read_i16(&word[2..4], big_endian)));
+            }
+            // 1 x 32-bit difference
+            _ => diffs.push(read_u32(word, big_endian) as i32),
+        }
+
+        Ok(())
+    })
+}
+
+/// Decodes `sample_cnt` data samples from Steim-2 compressed `frames`.
+///
+/// See [`decode_steim1()`] for details.
+pub fn decode_steim2(frames: &[u8], sample_cnt: usize, big_endian: bool) -> MSResult<SteimData> {
+    steim2(frames, sample_cnt, big_endian).and_then(|data| check_sample_cnt(data, sample_cnt, 2))
+}
+
+fn steim2(frames: &[u8], max_cnt: usize, big_endian: bool) -> MSResult<SteimData> {
+    decode(frames, max_cnt, big_endian, |word, nibble, diffs| {
+        if nibble == 0b00 {
+            return Ok(());
+        }
+        if nibble == 0b01 {
+            diffs.extend(word.iter().map(|&b| i32::from(b as i8)));
+            return Ok(());
+        }
+
+        let w = read_u32(word, big_endian);
+        let dnib = w >> 30;
+        let (cnt, bits) = match (nibble, dnib) {
+            (0b10, 0b01) => (1, 30),
+            (0b10, 0b10) => (2, 15),
+            (0b10, 0b11) => (3, 10),
+            (0b11, 0b00) => (5, 6),
+            (0b11, 0b01) => (6, 5),
+            (0b11, 0b10) => (7, 4),
+            _ => {
+                return Err(MSError::from_str(&format!(
+                    "invalid Steim-2 frame (dnib={:02b} for nibble={:02b})",
+                    dnib, nibble
+                )))
+            }
+        };
+
+        for idx in (0..cnt).rev() {
+            diffs.push(sign_extend(w >> (idx * bits), bits));
+        }
+
+        Ok(())
+    })
+}
+
+/// Decodes at most `max_cnt` data samples from Steim-1 (`version = 1`) or Steim-2
+/// (`version = 2`) compressed `frames`. In contrast to [`decode_steim1()`] and
+/// [`decode_steim2()`], decoding fewer samples is not an error.
+pub(crate) fn decode_steim_frames(
+    frames: &[u8],
+    version: u8,
+    max_cnt: usize,
+    big_endian: bool,
+) -> MSResult<SteimData> {
+    match version {
+        1 => steim1(frames, max_cnt, big_endian),
+        2 => steim2(frames, max_cnt, big_endian),
+        _ => Err(MSError::from_str(&format!(
+            "invalid Steim version: {}",
+            version
+        ))),
+    }
+}
+
+/// Encodes `samples` into Steim-1 compressed frames.
+///
+/// `d0` is the difference of the first sample with respect to the last sample of the preceding
+/// record, usually `0`. At most `max_frames` frames are encoded. Returns the encoded frames and
+/// the number of samples encoded. If not all samples fit into `max_frames` frames the remaining
+/// samples are expected to be encoded into subsequent records.
+///
+/// The output is identical to the output of libmseed.
+pub fn encode_steim1(
+    samples: &[i32],
+    d0: i32,
+    max_frames: usize,
+    big_endian: bool,
+) -> MSResult<(Vec<u8>, usize)> {
+    encode(
+        samples,
+        d0,
+        max_frames,
+        big_endian,
+        4,
+        |diffs, widths, word| {
+            if diffs.len() == 4 && widths.iter().all(|&w| w <= 8) {
+                for (b, &d) in word.iter_mut().zip(diffs) {
+                    *b = d as u8;
+                }
+                Ok((0b01, 4))
+            } else if diffs.len() >= 2 && widths[..2].iter().all(|&w| w <= 16) {
+                write_i16(&mut word[0..2], diffs[0] as i16, big_endian);
+                write_i16(&mut word[2..4], diffs[1] as i16, big_endian);
+                Ok((0b10, 2))
+            } else {
+                write_u32(word, diffs[0] as u32, big_endian);
+                Ok((0b11, 1))
+            }
+        },
+    )
+}
+
+/// Encodes `samples` into Steim-2 compressed frames.
+///
+/// See [`encode_steim1()`] for details. Returns an error if a difference cannot be represented
+/// in 30 bits.
+pub fn encode_steim2(
+    samples: &[i32],
+    d0: i32,
+    max_frames: usize,
+    big_endian: bool,
+) -> MSResult<(Vec<u8>, usize)> {
+    encode(
+        samples,
+        d0,
+        max_frames,
+        big_endian,
+        7,
+        |diffs, widths, word| {
+            let fits = |cnt: usize, bits: u32| {
+                diffs.len() >= cnt && widths[..cnt].iter().all(|&w| w <= bits)
+            };
+
+            // (number of differences, bits per difference, nibble, dnib)
+            let (cnt, bits, nibble, dnib) = if diffs.len() == 7 && fits(7, 4) {
+                (7, 4, 0b11, 0b10)
+            } else if fits(6, 5) {
+                (6, 5, 0b11, 0b01)
+            } else if fits(5, 6) {
+                (5, 6, 0b11, 0b00)
+            } else if fits(4, 8) {
+                for (b, &d) in word.iter_mut().zip(diffs) {
+                    *b = d as u8;
+                }
+                return Ok((0b01, 4));
+            } else if fits(3, 10) {
+                (3, 10, 0b10, 0b11)
+            } else if fits(2, 15) {
+                (2, 15, 0b10, 0b10)
+            } else if fits(1, 30) {
+                (1, 30, 0b10, 0b01)
+            } else {
+                return Err(MSError::from_str(
+                    "unable to represent difference in <= 30 bits",
+                ));
+            };
+
+            let mask = (1u32 << bits) - 1;
+            let mut w = dnib << 30;
+            for (idx, &d) in diffs[..cnt].iter().enumerate() {
+                w |= (d as u32 & mask) << ((cnt - 1 - idx) as u32 * bits);
+            }
+            write_u32(word, w, big_endian);
+
+            Ok((nibble, cnt))
+        },
+    )
+}
+
+/// Decodes Steim frames, where `decode_word` appends the differences of a single word to the
+/// differences passed.
+///
+/// Decoding stops as soon as `max_cnt` data samples are decoded, i.e. trailing frames or
+/// differences are ignored.
+fn decode<F>(
+    frames: &[u8],
+    max_cnt: usize,
+    big_endian: bool,
+    mut decode_word: F,
+) -> MSResult<SteimData>
+where
+    F: FnMut(&[u8], u32, &mut Vec<i32>) -> MSResult<()>,
+{
+    let mut rv = SteimData {
+        samples: Vec::with_capacity(max_cnt.min(frames.len() / 4 * 7)),
+        d0: 0,
+        x0: 0,
+        xn: 0,
+    };
+    let mut diffs = Vec::with_capacity(7);
+    let mut first = true;
+
+    'frames: for (frame_idx, frame) in frames.chunks_exact(STEIM_FRAME_LEN).enumerate() {
+        let nibbles = read_u32(&frame[0..4], big_endian);
+        let start = if frame_idx == 0 {
+            rv.x0 = read_u32(&frame[4..8], big_endian) as i32;
+            rv.xn = read_u32(&frame[8..12], big_endian) as i32;
+            3
+        } else {
+            1
+        };
+
+        for widx in start..FRAME_WORDS {
+            // padding words following the last sample are not decoded
+            if rv.samples.len() >= max_cnt {
+                break 'frames;
+            }
+
+            let nibble = (nibbles >> (30 - 2 * widx)) & 0b11;
+            diffs.clear();
+            decode_word(&frame[widx * 4..widx * 4 + 4], nibble, &mut diffs)?;
+
+            for &d in &diffs {
+                if rv.samples.len() >= max_cnt {
+                    break;
+                }
+
+                if first {
+                    // the first difference refers to the preceding record
+                    rv.d0 = d;
+                    rv.samples.push(rv.x0);
+                    first = false;
+                } else {
+                    let prev = *rv.samples.last().unwrap();
+                    rv.samples.push(prev.wrapping_add(d));
+                }
+            }
+        }
+    }
+
+    Ok(rv)
+}
+
+/// Checks that exactly `sample_cnt` samples were decoded from Steim-`version` frames.
+fn check_sample_cnt(data: SteimData, sample_cnt: usize, version: u8) -> MSResult<SteimData> {
+    if data.samples.len() != sample_cnt {
+        return Err(MSError::from_str(&format!(
+            "number of Steim-{} samples decoded does not match number expected: {} != {}",
+            version,
+            data.samples.len(),
+            sample_cnt
+        )));
+    }
+
+    Ok(data)
+}
+
+/// Encodes Steim frames, where `encode_word` encodes as many of the (at most `max_diffs`)
+/// differences passed as possible into a single word and returns the nibble of the word and
+/// the number of differences encoded.
+fn encode<F>(
+    samples: &[i32],
+    d0: i32,
+    max_frames: usize,
+    big_endian: bool,
+    max_diffs: usize,
+    mut encode_word: F,
+) -> MSResult<(Vec<u8>, usize)>
+where
+    F: FnMut(&[i32], &[u32], &mut [u8]) -> MSResult<(u32, usize)>,
+{
+    let mut out = Vec::new();
+    let mut diffs: Vec<i32> = Vec::with_capacity(max_diffs);
+    let mut widths: Vec<u32> = Vec::with_capacity(max_diffs);
+    let mut idx = 0;
+
+    if !samples.is_empty() {
+        diffs.push(d0);
+        widths.push(bit_width(d0));
+    }
+
+    let mut frame_idx = 0;
+    while frame_idx < max_frames && idx < samples.len() {
+        let mut frame = [0u8; STEIM_FRAME_LEN];
+        let mut nibbles = 0u32;
+        let start = if frame_idx == 0 {
+            write_u32(&mut frame[4..8], samples[0] as u32, big_endian);
+            3
+        } else {
+            1
+        };
+
+        let mut widx = start;
+        while widx < FRAME_WORDS && idx < samples.len() {
+            // fill up the differences following the ones not encoded, yet
+            while diffs.len() < max_diffs && idx + diffs.len() < samples.len() {
+                let i = idx + diffs.len();
+                let d = samples[i].wrapping_sub(samples[i - 1]);
+                diffs.push(d);
+                widths.push(bit_width(d));
+            }
+
+            let (nibble, cnt) = encode_word(&diffs, &widths, &mut frame[widx * 4..widx * 4 + 4])?;
+            nibbles |= nibble << (30 - 2 * widx);
+            diffs.drain(..cnt);
+            widths.drain(..cnt);
+            idx += cnt;
+            widx += 1;
+        }
+
+        write_u32(&mut frame[0..4], nibbles, big_endian);
+        out.extend_from_slice(&frame);
+        frame_idx += 1;
+    }
+
+    if idx > 0 {
+        // reverse integration constant
+        write_u32(&mut out[8..12], samples[idx - 1] as u32, big_endian);
+    }
+
+    Ok((out, idx))
+}
+
+/// Returns the number of bits required to represent `d` as used by libmseed for selecting the
+/// Steim word layout.
+fn bit_width(d: i32) -> u32 {
+    match d {
+        -8..=7 => 4,
+        -16..=15 => 5,
+        -32..=31 => 6,
+        -128..=127 => 8,
+        -512..=511 => 10,
+        -16384..=16383 => 15,
+        -32768..=32767 => 16,
+        -536870912..=536870911 => 30,
+        _ => 32,
+    }
+}
+
+fn sign_extend(v: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((v << shift) as i32) >> shift
+}
+
+fn read_u32(buf: &[u8], big_endian: bool) -> u32 {
+    let bytes = [buf[0], buf[1], buf[2], buf[3]];
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+fn read_i16(buf: &[u8], big_endian: bool) -> i16 {
+    let bytes = [buf[0], buf[1]];
+    if big_endian {
+        i16::from_be_bytes(bytes)
+    } else {
+        i16::from_le_bytes(bytes)
+    }
+}
+
+fn write_u32(buf: &mut [u8], v: u32, big_endian: bool) {
+    let bytes = if big_endian {
+        v.to_be_bytes()
+    } else {
+        v.to_le_bytes()
+    };
+    buf.copy_from_slice(&bytes);
+}
+
+fn write_i16(buf: &mut [u8], v: i16, big_endian: bool) {
+    let bytes = if big_endian {
+        v.to_be_bytes()
+    } else {
+        v.to_le_bytes()
+    };
+    buf.copy_from_slice(&bytes);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{test, MSControlFlags, MSDataEncoding, MSRecordRef};
+
+    /// Returns the Steim encoded records in `buf`.
+    fn steim_records(buf: &[u8]) -> Vec<MSRecordRef<'_>> {
+        let mut rv = vec![];
+        let mut offset = 0;
+        while offset < buf.len() {
+            let rec = MSRecordRef::parse(&buf[offset..]).unwrap();
+            offset += rec.rec_len();
+            if matches!(
+                rec.encoding(),
+                Ok(MSDataEncoding::Steim1 | MSDataEncoding::Steim2)
+            ) {
+                rv.push(rec);
+            }
+        }
+        rv
+    }
+
+    #[test]
+    fn test_decode_steim2_signal() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        let data = decode_steim2(rec.data_payload(), rec.sample_cnt() as usize, true).unwrap();
+
+        assert_eq!(data.samples.len(), 135);
+        assert_eq!(&data.samples[..4], &[-502676, -504105, -507491, -506991]);
+        assert_eq!(&data.samples[131..134], &[-505212, -499533, -495590]);
+        assert_eq!(data.x0, -502676);
+        assert!(data.is_consistent());
+
+        assert!(decode_steim2(rec.data_payload(), 136, true).is_err());
+    }
+
+    #[test]
+    fn test_steim_reencode_reference() {
+        for f in [
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-steim2.mseed3",
+            "testdata-3channel-signal.mseed2",
+            "testdata-3channel-signal.mseed3",
+        ] {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            let buf = fs::read(p).unwrap();
+            let recs = steim_records(&buf);
+            assert!(!recs.is_empty(), "{}", f);
+            for rec in recs {
+                let payload = rec.data_payload();
+                let sample_cnt = rec.sample_cnt() as usize;
+                let max_frames = payload.len() / STEIM_FRAME_LEN;
+
+                let (data, (frames, cnt)) = match rec.encoding().unwrap() {
+                    MSDataEncoding::Steim1 => {
+                        let data = decode_steim1(payload, sample_cnt, true).unwrap();
+                        let encoded = encode_steim1(&data.samples, data.d0, max_frames, true);
+                        (data, encoded.unwrap())
+                    }
+                    _ => {
+                        let data = decode_steim2(payload, sample_cnt, true).unwrap();
+                        let encoded = encode_steim2(&data.samples, data.d0, max_frames, true);
+                        (data, encoded.unwrap())
+                    }
+                };
+
+                assert!(data.is_consistent(), "{}", f);
+                assert_eq!(cnt, sample_cnt, "{}", f);
+                assert_eq!(&frames[..], &payload[..frames.len()], "{}", f);
+                // unused frames of miniSEED v2 records are zero
+                assert!(payload[frames.len()..].iter().all(|&b| b == 0), "{}", f);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_steim_matches_libmseed() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        for rec in steim_records(&buf) {
+            let data = rec.decode_steim().unwrap();
+            let msr = rec.to_record(MSControlFlags::MSF_UNPACKDATA).unwrap();
+            assert_eq!(&data.samples[..], msr.data_samples::<i32>().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_steim_roundtrip() {
+        let samples: Vec<i32> = (0..1000)
+            .map(|i: i32| match i % 7 {
+                0 => i.wrapping_mul(7919),
+                1 => -i * 3,
+                2 => i << 12,
+                3 => 5,
+                _ => i - 500,
+            })
+            .collect();
+
+        for big_endian in [true, false] {
+            let (frames, cnt) = encode_steim1(&samples, 0, 100, big_endian).unwrap();
+            assert_eq!(cnt, samples.len());
+            let data = decode_steim1(&frames, cnt, big_endian).unwrap();
+            assert_eq!(data.samples, samples);
+            assert!(data.is_consistent());
+
+            let (frames, cnt) = encode_steim2(&samples, 0, 100, big_endian).unwrap();
+            assert_eq!(cnt, samples.len());
+            let data = decode_steim2(&frames, cnt, big_endian).unwrap();
+            assert_eq!(data.samples, samples);
+            assert!(data.is_consistent());
+        }
+
+        // limited number of frames
+        let (frames, cnt) = encode_steim2(&samples, 0, 2, true).unwrap();
+        assert_eq!(frames.len(), 2 * STEIM_FRAME_LEN);
+        assert!(cnt < samples.len());
+        let data = decode_steim2(&frames, cnt, true).unwrap();
+        assert_eq!(&data.samples[..], &samples[..cnt]);
+        assert!(data.is_consistent());
+
+        // differences exceeding 30 bits
+        assert!(encode_steim2(&[0, i32::MAX], 0, 1, true).is_err());
+        let (frames, _) = encode_steim1(&[0, i32::MAX], 0, 1, true).unwrap();
+        assert_eq!(
+            decode_steim1(&frames, 2, true).unwrap().samples,
+            [0, i32::MAX]
+        );
+
+        assert_eq!(encode_steim1(&[], 0, 1, true).unwrap(), (vec![], 0));
+    }
+
+    #[test]
+    fn test_steim_corrupt() {
+        let samples: Vec<i32> = (0..100).collect();
+        let (mut frames, cnt) = encode_steim2(&samples, 0, 10, true).unwrap();
+
+        // modify the reverse integration constant
+        frames[11] ^= 0x01;
+        let data = decode_steim2(&frames, cnt, true).unwrap();
+        assert!(!data.is_consistent());
+
+        // invalid dnib
+        let (mut frames, cnt) = encode_steim2(&samples, 0, 10, true).unwrap();
+        frames[12] |= 0xc0;
+        assert!(decode_steim2(&frames, cnt, true).is_err());
+
+        // an invalid dnib of a padding word following the last sample is ignored
+        let (mut frames, cnt) = encode_steim2(&[1, 2, 3], 0, 1, true).unwrap();
+        assert_eq!(cnt, 3);
+        frames[3] |= 0b10;
+        frames[60] &= 0x3f;
+        let data = decode_steim2(&frames, cnt, true).unwrap();
+        assert_eq!(data.samples, [1, 2, 3]);
+        assert!(data.is_consistent());
+    }
+}
@@ -0,0 +1,341 @@
+use crate::record_ref::MS2_FSDH_LEN;
+use crate::steim::STEIM_FRAME_LEN;
+use crate::{MSByteOrder, MSDataEncoding, MSError, MSRecordRef, MSResult, RecordByteOrder};
+
+/// Multi-byte fields of the miniSEED v2 fixed section of data header, as offset and width.
+const FSDH_FIELDS: [(usize, usize); 9] = [
+    (20, 2), // year
+    (22, 2), // day of year
+    (28, 2), // fractional seconds
+    (30, 2), // number of samples
+    (32, 2), // sample rate factor
+    (34, 2), // sample rate multiplier
+    (40, 4), // time correction
+    (44, 2), // data offset
+    (46, 2), // first blockette
+];
+
+/// Returns the multi-byte fields of the blockette body as offset and width relative to the
+/// beginning of the blockette, or `None` if the blockette type is unknown.
+///
+/// BTIME fields are split into year, day of year and fractional seconds.
+fn blockette_fields(blockette_type: u16) -> Option<&'static [(usize, usize)]> {
+    let rv: &[(usize, usize)] = match blockette_type {
+        100 => &[(4, 4)],
+        200 | 201 => &[(4, 4), (8, 4), (12, 4), (18, 2), (20, 2), (26, 2)],
+        300 | 310 => &[(4, 2), (6, 2), (12, 2), (16, 4), (20, 4), (24, 4), (32, 4)],
+        320 => &[(4, 2), (6, 2), (12, 2), (16, 4), (20, 4), (28, 4)],
+        390 => &[(4, 2), (6, 2), (12, 2), (16, 4), (20, 4)],
+        395 => &[(4, 2), (6, 2), (12, 2)],
+        400 => &[(4, 4), (8, 4), (12, 2)],
+        405 => &[(4, 2)],
+        500 => &[(4, 4), (8, 2), (10, 2), (16, 2), (20, 4)],
+        1000 | 1001 => &[],
+        2000 => &[(4, 2), (6, 2), (8, 4)],
+        _ => return None,
+    };
+
+    Some(rv)
+}
+
+/// Converts the miniSEED v2 record at the beginning of `rec` into `byte_order`.
+///
+/// The header, including blockettes, and the data payload are converted separately, where the
+/// word order of blockette 1000 is updated accordingly. Returns an error if a blockette or the
+/// data encoding is not supported, where blockettes of unknown layout are supported only if the
+/// header byte order is retained.
+pub(crate) fn set_mseed2_byte_order(rec: &mut [u8], byte_order: RecordByteOrder) -> MSResult<()> {
+    let msr = MSRecordRef::parse(rec)?;
+    if msr.format_version() != 2 {
+        return Err(MSError::from_str("not a miniSEED v2 record"));
+    }
+
+    let current = msr.byte_order();
+    let rec_len = msr.rec_len();
+    let data_offset = msr.data_offset();
+
+    // walk the blockette chain
+    let mut blockettes = Vec::new();
+    let mut offset = msr.u16_at(46) as usize;
+    while offset >= MS2_FSDH_LEN && offset + 4 <= rec_len && blockettes.len() < rec[39] as usize {
+        let blockette_type = msr.u16_at(offset);
+        let fields = match blockette_fields(blockette_type) {
+            Some(fields) => fields,
+            // blockettes of unknown layout are kept as is if the header byte order is retained
+            None if current.header == byte_order.header => &[],
+            None => {
+                return Err(MSError::from_str(&format!(
+                    "cannot convert byte order of blockette {}",
+                    blockette_type
+                )))
+            }
+        };
+        if fields
+            .iter()
+            .any(|(pos, width)| offset + pos + width > rec_len)
+        {
+            return Err(MSError::from_str(&format!(
+                "truncated blockette {} at offset {}",
+                blockette_type, offset
+            )));
+        }
+        blockettes.push((offset, blockette_type, fields));
+
+        let next = msr.u16_at(offset + 2) as usize;
+        if next <= offset {
+            break;
+        }
+        offset = next;
+    }
+
+    let data = if current.data != byte_order.data && data_offset >= MS2_FSDH_LEN {
+        let sample_cnt = usize::try_from(msr.sample_cnt()).unwrap_or(0);
+        Some((msr.encoding()?, sample_cnt))
+    } else {
+        None
+    };
+
+    if let Some((encoding, sample_cnt)) = data {
+        swap_payload(
+            &mut rec[data_offset.min(rec_len)..rec_len],
+            encoding,
+            sample_cnt,
+            current.data,
+        )?;
+    }
+
+    for &(offset, blockette_type, fields) in &blockettes {
+        if current.header != byte_order.header {
+            swap_fields(&mut rec[offset..], &[(0, 2), (2, 2)]);
+            swap_fields(&mut rec[offset..], fields);
+        }
+        if blockette_type == 1000 && offset + 6 <= rec_len {
+            rec[offset + 5] = (byte_order.data == MSByteOrder::BigEndian) as u8;
+        }
+    }
+
+    if current.header != byte_order.header {
+        swap_fields(rec, &FSDH_FIELDS);
+    }
+
+    Ok(())
+}
+
+fn swap_fields(buf: &mut [u8], fields: &[(usize, usize)]) {
+    for &(pos, width) in fields {
+        buf[pos..pos + width].reverse();
+    }
+}
+
+/// Swaps the byte order of the encoded data `payload` currently in `byte_order`.
+fn swap_payload(
+    payload: &mut [u8],
+    encoding: MSDataEncoding,
+    sample_cnt: usize,
+    byte_order: MSByteOrder,
+) -> MSResult<()> {
+    let width = match encoding {
+        MSDataEncoding::Text => return Ok(()),
+        MSDataEncoding::Integer16 => 2,
+        MSDataEncoding::Integer32 | MSDataEncoding::Float32 => 4,
+        MSDataEncoding::Float64 => 8,
+        MSDataEncoding::Steim1 | MSDataEncoding::Steim2 => {
+            let steim1 = encoding == MSDataEncoding::Steim1;
+            for frame in payload.chunks_exact_mut(STEIM_FRAME_LEN) {
+                swap_steim_frame(frame, steim1, byte_order);
+            }
+            return Ok(());
+        }
+        other => {
+            return Err(MSError::from_str(&format!(
+                "cannot convert byte order of data encoding: {}",
+                other
+            )))
+        }
+    };
+
+    let len = sample_cnt
+        .saturating_mul(width)
+        .min(payload.len() / width * width);
+    for sample in payload[..len].chunks_exact_mut(width) {
+        sample.reverse();
+    }
+
+    Ok(())
+}
+
+/// Swaps the byte order of a Steim frame currently in `byte_order`.
+///
+/// Words are swapped according to their nibble, i.e. 8-bit differences are left untouched and
+/// Steim-1 16-bit differences are swapped individually.
+fn swap_steim_frame(frame: &mut [u8], steim1: bool, byte_order: MSByteOrder) {
+    let mut nibbles = [0; 4];
+    nibbles.copy_from_slice(&frame[..4]);
+    let nibbles = match byte_order {
+        MSByteOrder::BigEndian => u32::from_be_bytes(nibbles),
+        MSByteOrder::LittleEndian => u32::from_le_bytes(nibbles),
+    };
+
+    for (i, word) in frame.chunks_exact_mut(4).enumerate() {
+        match ((nibbles >> (30 - 2 * i)) & 0b11, steim1) {
+            (0b01, _) => {}
+            (0b10, true) => {
+                word[..2].reverse();
+                word[2..].reverse();
+            }
+            _ => word.reverse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{test, MSControlFlags};
+
+    #[test]
+    fn test_set_mseed2_byte_order() {
+        let test_data = vec![
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-text.mseed2",
+            "testdata-3channel-signal.mseed2",
+            "testdata-detection.record.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+        ];
+
+        for f in &test_data {
+            for rec in test::records(f) {
+                let msr = MSRecordRef::parse(&rec).unwrap();
+                let rec = msr.raw();
+                assert_eq!(
+                    msr.byte_order(),
+                    RecordByteOrder {
+                        header: MSByteOrder::BigEndian,
+                        data: MSByteOrder::BigEndian,
+                    }
+                );
+
+                let mut swapped = rec.to_vec();
+                set_mseed2_byte_order(&mut swapped, MSByteOrder::LittleEndian.into()).unwrap();
+                assert_ne!(swapped, rec);
+
+                let swapped_msr = MSRecordRef::parse(&swapped).unwrap();
+                assert_eq!(
+                    swapped_msr.byte_order(),
+                    RecordByteOrder {
+                        header: MSByteOrder::LittleEndian,
+                        data: MSByteOrder::LittleEndian,
+                    }
+                );
+                assert_eq!(swapped_msr.sid().unwrap(), msr.sid().unwrap());
+                assert_eq!(
+                    swapped_msr.start_time_ns().unwrap(),
+                    msr.start_time_ns().unwrap()
+                );
+                assert_eq!(swapped_msr.sample_cnt(), msr.sample_cnt());
+                assert_eq!(swapped_msr.sample_rate_hz(), msr.sample_rate_hz());
+                if matches!(
+                    msr.encoding(),
+                    Ok(MSDataEncoding::Steim1 | MSDataEncoding::Steim2)
+                ) {
+                    assert_eq!(
+                        swapped_msr.decode_steim().unwrap(),
+                        msr.decode_steim().unwrap()
+                    );
+                }
+
+                // converting into the current byte order is a no-op
+                let mut unchanged = swapped.clone();
+                set_mseed2_byte_order(&mut unchanged, MSByteOrder::LittleEndian.into()).unwrap();
+                assert_eq!(unchanged, swapped);
+
+                set_mseed2_byte_order(&mut swapped, MSByteOrder::BigEndian.into()).unwrap();
+                assert_eq!(swapped, rec);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_mseed2_byte_order_not_mseed2() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("reference-testdata-steim2.mseed3");
+
+        let mut buf = fs::read(p).unwrap();
+        assert!(set_mseed2_byte_order(&mut buf, MSByteOrder::BigEndian.into()).is_err());
+    }
+
+    #[test]
+    fn test_pack_raw_byte_order() {
+        let start_time = time::OffsetDateTime::parse(
+            "2012-01-01T00:00:00Z",
+            &time::format_description::well_known::Iso8601::DEFAULT,
+        )
+        .unwrap();
+
+        for encoding in [
+            MSDataEncoding::Steim1,
+            MSDataEncoding::Steim2,
+            MSDataEncoding::Integer32,
+        ] {
+            for byte_order in [MSByteOrder::BigEndian, MSByteOrder::LittleEndian] {
+                let mut info =
+                    crate::PackInfo::with_sample_rate("FDSN:XX_TEST__L_H_Z", 1.0).unwrap();
+                info.encoding = encoding;
+                info.rec_len = 512;
+                info.byte_order = Some(byte_order);
+
+                let mut data_samples: Vec<i32> = (0..1000)
+                    .map(|i| (i % 70) * (i % 13) * 300 - 9000)
+                    .collect();
+                let mut records = vec![];
+                let (_, cnt_samples) = crate::pack_raw(
+                    &mut data_samples,
+                    &start_time,
+                    |rec: &[u8]| records.push(rec.to_vec()),
+                    &info,
+                    MSControlFlags::MSF_FLUSHDATA | MSControlFlags::MSF_PACKVER2,
+                )
+                .unwrap();
+                assert_eq!(cnt_samples, data_samples.len());
+
+                let mut unpacked = vec![];
+                for rec in &records {
+                    let msr = crate::MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap();
+                    assert_eq!(
+                        msr.byte_order(),
+                        RecordByteOrder {
+                            header: byte_order,
+                            data: byte_order,
+                        }
+                    );
+                    assert_eq!(
+                        MSRecordRef::parse(rec).unwrap().byte_order(),
+                        msr.byte_order()
+                    );
+                    unpacked.extend_from_slice(msr.data_samples::<i32>().unwrap());
+                }
+                assert_eq!(unpacked, data_samples);
+            }
+        }
+
+        // miniSEED v3 records are always little-endian
+        let mut info = crate::PackInfo::new("FDSN:XX_TEST__L_H_Z").unwrap();
+        info.byte_order = Some(MSByteOrder::BigEndian);
+        let mut data_samples = vec![1i32, 2, 3];
+        assert!(crate::pack_raw(
+            &mut data_samples,
+            &start_time,
+            |_: &[u8]| {},
+            &info,
+            MSControlFlags::MSF_FLUSHDATA,
+        )
+        .is_err());
+    }
+}
@@ -1,4 +1,13 @@
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+use crate::MSRecordRef;
 
 #[cfg(test)]
 pub fn test_data_base_dir() -> PathBuf {
@@ -7,3 +16,64 @@ pub fn test_data_base_dir() -> PathBuf {
 
     base_dir
 }
+
+/// Returns the raw records of the test data file `f`.
+#[cfg(test)]
+pub fn records(f: &str) -> Vec<Vec<u8>> {
+    let mut p = test_data_base_dir();
+    assert!(p.is_dir());
+    p.push(f);
+
+    let buf = fs::read(p).unwrap();
+    let mut rv = vec![];
+    let mut pos = 0;
+    while pos < buf.len() {
+        let rec = MSRecordRef::parse(&buf[pos..]).unwrap();
+        pos += rec.rec_len();
+        rv.push(rec.raw().to_vec());
+    }
+
+    rv
+}
+
+/// Parses the ISO 8601 time `s`.
+#[cfg(test)]
+pub fn parse_time(s: &str) -> OffsetDateTime {
+    OffsetDateTime::parse(s, &Iso8601::DEFAULT).unwrap()
+}
+
+/// A scratch directory removed when dropped, i.e. even if a test panics.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ScratchDir(PathBuf);
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Creates an empty scratch directory unique to the test `name` and the test process.
+#[cfg(test)]
+pub fn scratch_dir(name: &str) -> ScratchDir {
+    let mut path = env::temp_dir();
+    path.push(format!("mseed-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+
+    ScratchDir(path)
+}
@@ -0,0 +1,359 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_double;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::{raw, MSRecord};
+use raw::{MS3Record, MS3Tolerance};
+
+type ToleranceFn = dyn Fn(&MSRecord) -> f64 + Send + Sync;
+
+thread_local! {
+    /// The tolerance of the libmseed call currently in progress.
+    static CURRENT: Cell<*const MSTolerance> = const { Cell::new(ptr::null()) };
+    /// The payload of a tolerance closure panicking during the libmseed call in progress.
+    static PANIC: RefCell<Option<Box<dyn Any + Send>>> = const { RefCell::new(None) };
+}
+
+/// Time and sample rate tolerances applied when merging records into a [`MSTraceList`].
+///
+/// Tolerances are either constant or determined per record by a closure. Negative tolerances
+/// select the libmseed defaults, i.e. a time tolerance of half a sample period and a sample rate
+/// tolerance of `0.01` percent, respectively. If a closure panics, libmseed falls back to the
+/// default tolerance and the panic is resumed once libmseed returns.
+///
+/// Tolerances are passed by means of [`MSTraceListOptions`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSTolerance, MSTraceList, MSTraceListOptions};
+///
+/// // tolerate clock drift up to 2 sample periods for low-rate channels
+/// let tolerance = MSTolerance::new()
+///     .time_fn(|msr| match msr.sample_rate_hz() {
+///         rate if rate > 0.0 && rate <= 1.0 => 2.0 / rate,
+///         _ => -1.0,
+///     })
+///     .sample_rate(0.001);
+///
+/// let mstl = MSTraceList::read_with_options(
+///     "path/to/data.mseed",
+///     MSControlFlags::MSF_UNPACKDATA,
+///     &MSTraceListOptions::new().tolerance(&tolerance),
+/// )
+/// .unwrap();
+/// ```
+///
+/// [`MSTraceList`]: crate::MSTraceList
+/// [`MSTraceListOptions`]: crate::MSTraceListOptions
+#[derive(Default)]
+pub struct MSTolerance {
+    time: Option<Box<ToleranceFn>>,
+    sample_rate: Option<Box<ToleranceFn>>,
+}
+
+impl MSTolerance {
+    /// Creates a new `MSTolerance` applying the libmseed defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a constant time tolerance in seconds.
+    pub fn time(self, tolerance: f64) -> Self {
+        self.time_fn(move |_| tolerance)
+    }
+
+    /// Sets a closure determining the time tolerance in seconds for the record to be merged.
+    pub fn time_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&MSRecord) -> f64 + Send + Sync + 'static,
+    {
+        self.time = Some(Box::new(f));
+        self
+    }
+
+    /// Sets a constant sample rate tolerance, i.e. the absolute difference of sample rates in
+    /// `Hz`.
+    pub fn sample_rate(self, tolerance: f64) -> Self {
+        self.sample_rate_fn(move |_| tolerance)
+    }
+
+    /// Sets a closure determining the sample rate tolerance in `Hz` for the record to be merged.
+    pub fn sample_rate_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&MSRecord) -> f64 + Send + Sync + 'static,
+    {
+        self.sample_rate = Some(Box::new(f));
+        self
+    }
+
+    /// Returns the time tolerance configured for `msr`, if any.
+    pub(crate) fn eval_time(&self, msr: &MSRecord) -> Option<f64> {
+        self.time.as_ref().map(|f| f(msr))
+    }
+
+    /// Returns the sample rate tolerance configured for `msr`, if any.
+    pub(crate) fn eval_sample_rate(&self, msr: &MSRecord) -> Option<f64> {
+        self.sample_rate.as_ref().map(|f| f(msr))
+    }
+
+    /// Calls `f` with a raw tolerance whose callbacks dispatch to the tolerances configured.
+    ///
+    /// The raw tolerance is valid for the duration of `f`, only. Panics of the tolerance closures
+    /// are caught while unwinding through libmseed and resumed after `f` returns.
+    pub(crate) fn with_raw<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(*mut MS3Tolerance) -> R,
+    {
+        /// Restores the previous tolerance, even if unwinding.
+        struct Guard(*const MSTolerance);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+
+        let mut tolerance = MS3Tolerance {
+            time: self.time.as_ref().map(|_| time_tolerance as _),
+            samprate: self
+                .sample_rate
+                .as_ref()
+                .map(|_| sample_rate_tolerance as _),
+        };
+
+        let _guard = Guard(CURRENT.with(|current| current.replace(self)));
+        let rv = f(&mut tolerance);
+        if let Some(payload) = PANIC.with(|panic| panic.borrow_mut().take()) {
+            panic::resume_unwind(payload);
+        }
+
+        rv
+    }
+}
+
+impl fmt::Debug for MSTolerance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MSTolerance")
+            .field("time", &self.time.is_some())
+            .field("sample_rate", &self.sample_rate.is_some())
+            .finish()
+    }
+}
+
+/// Calls the tolerance closure selected by `select` with the record `msr`.
+fn call_current<S>(msr: *const MS3Record, select: S) -> c_double
+where
+    S: FnOnce(&MSTolerance) -> Option<&ToleranceFn>,
+{
+    CURRENT.with(|current| {
+        let tolerance = current.get();
+        if tolerance.is_null() || msr.is_null() {
+            return -1.0;
+        }
+
+        match select(unsafe { &*tolerance }) {
+            Some(f) => {
+                // the record is owned by libmseed
+                let msr = ManuallyDrop::new(unsafe { MSRecord::from_raw(msr as *mut _) });
+                // unwinding into libmseed is undefined behavior
+                panic::catch_unwind(AssertUnwindSafe(|| f(&msr))).unwrap_or_else(|payload| {
+                    PANIC.with(|panic| {
+                        panic.borrow_mut().get_or_insert(payload);
+                    });
+                    -1.0
+                })
+            }
+            None => -1.0,
+        }
+    })
+}
+
+extern "C" fn time_tolerance(msr: *const MS3Record) -> c_double {
+    call_current(msr, |tolerance| tolerance.time.as_deref())
+}
+
+extern "C" fn sample_rate_tolerance(msr: *const MS3Record) -> c_double {
+    call_current(msr, |tolerance| tolerance.sample_rate.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        rewrite_header, test, MSControlFlags, MSSelections, MSTraceList, MSTraceListOptions,
+    };
+
+    /// Returns the records of `f`, where the start time of the third record is shifted by
+    /// 700 ms.
+    fn drifting_records(f: &str) -> Vec<Vec<u8>> {
+        let mut rv = test::records(f);
+        rv[2] = rewrite_header(&rv[2], |msr| {
+            msr.apply_time_correction(time::Duration::milliseconds(700))
+        })
+        .unwrap();
+
+        rv
+    }
+
+    fn num_segments(mstl: &MSTraceList) -> u32 {
+        assert_eq!(mstl.len(), 1);
+        mstl.iter().next().unwrap().len()
+    }
+
+    #[test]
+    fn test_tolerance_from_buffer() {
+        for f in [
+            "testdata-oneseries-mixedlengths-mixedorder.mseed2",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+        ] {
+            let buf = drifting_records(f).concat();
+
+            let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+            assert!(num_segments(&mstl) > 1);
+
+            let tolerance = MSTolerance::new().time(1.0);
+            let mstl = MSTraceList::from_buffer_with_options(
+                &buf,
+                MSControlFlags::MSF_UNPACKDATA,
+                &MSTraceListOptions::new().tolerance(&tolerance),
+            )
+            .unwrap();
+            assert_eq!(num_segments(&mstl), 1);
+
+            let cnt = Arc::new(AtomicUsize::new(0));
+            let tolerance = {
+                let cnt = cnt.clone();
+                MSTolerance::new()
+                    .time_fn(move |msr| {
+                        cnt.fetch_add(1, Ordering::Relaxed);
+                        1.0 / msr.sample_rate_hz()
+                    })
+                    .sample_rate_fn(|_| -1.0)
+            };
+            let mstl = MSTraceList::from_buffer_with_options(
+                &buf,
+                MSControlFlags::MSF_UNPACKDATA,
+                &MSTraceListOptions::new().tolerance(&tolerance),
+            )
+            .unwrap();
+            assert_eq!(num_segments(&mstl), 1);
+            assert!(cnt.load(Ordering::Relaxed) > 0);
+        }
+    }
+
+    #[test]
+    fn test_tolerance_insert() {
+        let records = drifting_records("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let mut mstl = MSTraceList::new().unwrap();
+        let mut mstl_tolerant = MSTraceList::new().unwrap();
+        let tolerance = MSTolerance::new().time(1.0);
+        let options = MSTraceListOptions::new().tolerance(&tolerance);
+        for rec in &records {
+            mstl.insert(
+                MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap(),
+                true,
+            )
+            .unwrap();
+            mstl_tolerant
+                .insert_with_options(
+                    MSRecord::parse(rec, MSControlFlags::MSF_UNPACKDATA).unwrap(),
+                    true,
+                    &options,
+                )
+                .unwrap();
+        }
+
+        assert!(num_segments(&mstl) > 1);
+        assert_eq!(num_segments(&mstl_tolerant), 1);
+        let trace_id = mstl_tolerant.iter().next().unwrap();
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        assert_eq!(trace_seg.sample_cnt(), 3952);
+        assert_eq!(trace_seg.data_samples::<i32>().unwrap().len(), 3952);
+    }
+
+    #[test]
+    fn test_tolerance_read() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed2");
+
+        // a zero sample rate tolerance requires identical sample rates
+        let tolerance = MSTolerance::new().time(0.5).sample_rate(0.0);
+        let mstl = MSTraceList::read_with_options(
+            p,
+            MSControlFlags::MSF_UNPACKDATA,
+            &MSTraceListOptions::new().tolerance(&tolerance),
+        )
+        .unwrap();
+        assert_eq!(num_segments(&mstl), 1);
+        assert_eq!(
+            mstl.iter()
+                .next()
+                .unwrap()
+                .iter()
+                .next()
+                .unwrap()
+                .sample_cnt(),
+            3952
+        );
+    }
+
+    #[test]
+    fn test_tolerance_with_selections() {
+        let buf = drifting_records("testdata-oneseries-mixedlengths-mixedorder.mseed3").concat();
+        let tolerance = MSTolerance::new().time(1.0);
+
+        let mut selections = MSSelections::new();
+        selections.add_sid("FDSN:XX_TEST_00_L_H_Z").unwrap();
+        let options = MSTraceListOptions::new()
+            .selections(&selections)
+            .tolerance(&tolerance);
+        let mstl =
+            MSTraceList::from_buffer_with_options(&buf, MSControlFlags::MSF_UNPACKDATA, &options)
+                .unwrap();
+        assert_eq!(num_segments(&mstl), 1);
+
+        let mut selections = MSSelections::new();
+        selections.add_sid("FDSN:XX_OTHER_*").unwrap();
+        let options = options.selections(&selections);
+        let mstl =
+            MSTraceList::from_buffer_with_options(&buf, MSControlFlags::MSF_UNPACKDATA, &options)
+                .unwrap();
+        assert!(mstl.is_empty());
+
+        let mut mstl = MSTraceList::new().unwrap();
+        let msr = MSRecord::parse(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        mstl.insert_with_options(msr, true, &options).unwrap();
+        assert!(mstl.is_empty());
+    }
+
+    #[test]
+    fn test_tolerance_panic() {
+        let buf = drifting_records("testdata-oneseries-mixedlengths-mixedorder.mseed3").concat();
+        let tolerance = MSTolerance::new().time_fn(|_| panic!("tolerance failed"));
+        let options = MSTraceListOptions::new().tolerance(&tolerance);
+
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            MSTraceList::from_buffer_with_options(&buf, MSControlFlags::MSF_UNPACKDATA, &options)
+        }));
+        let payload = res.unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"tolerance failed"));
+
+        // the panic does not leak into subsequent calls
+        let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        assert!(num_segments(&mstl) > 1);
+    }
+}
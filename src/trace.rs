@@ -1,15 +1,20 @@
-use std::ffi::c_double;
+use std::ffi::{c_char, c_double, c_void};
 use std::fmt;
+use std::mem;
 use std::ptr;
 use std::slice::from_raw_parts;
 
+use crate::opaque::strip_opaque;
 use crate::{
-    error::check, raw, util, MSControlFlags, MSError, MSRecord, MSResult, MSSampleType,
-    MSSubSeconds, MSTimeFormat,
+    error::{check, check_eof},
+    raw, util, ConnectionInfo, IntoConnectionInfo, MSControlFlags, MSError, MSRecord, MSRecordRef,
+    MSResult, MSSampleType, MSSelections, MSSubSeconds, MSTimeFormat, MSTolerance, Samples,
 };
 use time::OffsetDateTime;
 
-use raw::{MS3TraceID, MS3TraceList, MS3TraceSeg};
+use raw::{
+    MS3FileParam, MS3Record, MS3RecordPtr, MS3Tolerance, MS3TraceID, MS3TraceList, MS3TraceSeg,
+};
 
 /// A container for a trace identifier composed by [`MSTraceSegment`]s.
 #[derive(Debug)]
@@ -94,7 +99,7 @@ impl Iterator for MSTraceIdIter {
 /// A container for a continuous trace segment.
 #[derive(Debug)]
 pub struct MSTraceSegment<'id> {
-    _trace_id: &'id MSTraceId,
+    trace_id: &'id MSTraceId,
 
     inner: *mut MS3TraceSeg,
 }
@@ -104,6 +109,11 @@ impl<'id> MSTraceSegment<'id> {
         unsafe { *self.inner }
     }
 
+    #[cfg(feature = "arrow")]
+    pub(crate) fn trace_id(&self) -> &'id MSTraceId {
+        self.trace_id
+    }
+
     /// Returns the time of the the first sample.
     pub fn start_time(&self) -> MSResult<OffsetDateTime> {
         util::nstime_to_time(self.ptr().starttime)
@@ -126,11 +136,16 @@ impl<'id> MSTraceSegment<'id> {
 
     /// Returns the data samples of the trace segment.
     ///
-    /// Note that the data samples must have been unpacked, previously. Deferred unpacking of data
-    /// samples from the internal record list is currently not implemented.
+    /// If the data samples are not unpacked, yet, but the trace segment provides a record list
+    /// (see [`MSControlFlags::MSF_RECORDLIST`]), the data samples are unpacked on demand.
+    /// Otherwise, the data samples must have been unpacked, previously.
     pub fn data_samples<T: DataSampleType>(&mut self) -> MSResult<&[T]> {
         if !self.is_data_unpacked() {
-            return Err(MSError::from_str("data samples must be unpacked"));
+            if !self.has_record_list() {
+                return Err(MSError::from_str("data samples must be unpacked"));
+            }
+
+            self.unpack_data()?;
         }
 
         let rv = unsafe {
@@ -144,6 +159,26 @@ impl<'id> MSTraceSegment<'id> {
         Ok(rv)
     }
 
+    /// Returns the data samples of the trace segment typed according to the trace segment's
+    /// [`sample_type()`](Self::sample_type).
+    ///
+    /// In contrast to [`data_samples()`](Self::data_samples), the data samples are not converted.
+    /// If the data samples are not unpacked, yet, but the trace segment provides a record list,
+    /// the data samples are unpacked on demand.
+    pub fn samples(&mut self) -> MSResult<Samples<'_>> {
+        if !self.is_data_unpacked() && self.has_record_list() {
+            self.unpack_data()?;
+        }
+
+        unsafe {
+            Samples::from_raw_parts(
+                self.ptr().datasamples,
+                self.ptr().numsamples as usize,
+                self.sample_type(),
+            )
+        }
+    }
+
     /// Returns the size of the buffer for (unpacked) data samples in bytes.
     pub fn data_size(&self) -> u64 {
         self.ptr().datasize
@@ -166,44 +201,294 @@ impl<'id> MSTraceSegment<'id> {
             && !self.ptr().datasamples.is_null()
     }
 
-    ///// Unpacks data samples of the trace segment and returns the number of unpacked samples.
-    /////
-    ///// If the data is already unpacked, the number of previously unpacked samples is returned.
-    //pub fn unpack_data(&mut self) -> MSResult<c_long> {
-    //    todo!();
+    /// Returns whether the trace segment provides a record list.
+    ///
+    /// Record lists are built when reading with [`MSControlFlags::MSF_RECORDLIST`].
+    pub fn has_record_list(&self) -> bool {
+        !self.ptr().recordlist.is_null()
+    }
+
+    /// Returns the number of records contributing to the trace segment.
+    ///
+    /// Returns `0` if the trace segment does not provide a record list.
+    pub fn record_cnt(&self) -> u64 {
+        if !self.has_record_list() {
+            return 0;
+        }
+
+        unsafe { (*self.ptr().recordlist).recordcnt }
+    }
+
+    /// Unpacks data samples of the trace segment from its record list and returns the number of
+    /// unpacked samples.
+    ///
+    /// The data samples are stored within the trace segment. If the data is already unpacked, the
+    /// number of previously unpacked samples is returned.
+    pub fn unpack_data(&mut self) -> MSResult<i64> {
+        if self.is_data_unpacked() {
+            return Ok(self.num_samples());
+        }
+
+        if !self.has_record_list() {
+            return Err(MSError::from_str("missing record list"));
+        }
+
+        unsafe {
+            check(raw::mstl3_unpack_recordlist(
+                self.trace_id.0,
+                self.inner,
+                ptr::null_mut(),
+                0,
+                0,
+            ))
+        }
+    }
+
+    /// Unpacks data samples of the trace segment from its record list into `buf` and returns the
+    /// number of unpacked samples.
+    ///
+    /// The trace segment itself is not modified, i.e. the data samples are not stored within the
+    /// trace segment. Note that no sample type conversion is performed. That is, `T` must match
+    /// the sample type of the record list's data encoding and `buf` must be large enough to hold
+    /// [`sample_cnt()`](Self::sample_cnt) samples.
+    pub fn unpack_data_into<T: DataSampleType>(&self, buf: &mut [T]) -> MSResult<usize> {
+        if !self.has_record_list() {
+            return Err(MSError::from_str("missing record list"));
+        }
+
+        let sample_type = self.record_list_sample_type()?;
+        if sample_type != T::SAMPLE_TYPE {
+            return Err(MSError::from_str(&format!(
+                "invalid sample type: {:?} (expected: {:?})",
+                T::SAMPLE_TYPE,
+                sample_type
+            )));
+        }
+
+        let rv = unsafe {
+            check(raw::mstl3_unpack_recordlist(
+                self.trace_id.0,
+                self.inner,
+                buf.as_mut_ptr() as *mut _,
+                mem::size_of_val(buf) as _,
+                0,
+            ))
+        }?;
+
+        Ok(rv as usize)
+    }
+
+    /// Splits the trace segment before the sample index `idx`.
+    ///
+    /// The trace segment is truncated such that it ends with the sample before `idx`, while the
+    /// remaining samples are moved to a new trace segment inserted after it.
+    fn split(&mut self, idx: usize) -> MSResult<()> {
+        self.unpack_data()?;
+        let seg = self.copy_range(idx, self.sample_cnt() as usize)?;
+
+        unsafe {
+            let inner = self.inner;
+            link_segment(self.trace_id.0, inner, seg);
+
+            (*inner).endtime =
+                util::sample_time((*inner).starttime, idx as i64 - 1, (*inner).samprate);
+            (*inner).samplecnt = idx as _;
+            (*inner).numsamples = idx as _;
+            free_record_list(inner);
+        }
+
+        Ok(())
+    }
+
+    /// Returns a new unlinked trace segment holding copies of the samples within the range of
+    /// sample indices `first..last`.
+    ///
+    /// The data samples must be unpacked.
+    fn copy_range(&self, first: usize, last: usize) -> MSResult<*mut MS3TraceSeg> {
+        let sample_size = sample_size(self.sample_type())?;
+        let data_size = (last - first) * sample_size;
+
+        unsafe {
+            let seg = lm_malloc(mem::size_of::<MS3TraceSeg>())? as *mut MS3TraceSeg;
+            ptr::write_bytes(seg, 0, 1);
+            let data = match lm_malloc(data_size) {
+                Ok(data) => data,
+                Err(e) => {
+                    lm_free(seg as _);
+                    return Err(e);
+                }
+            };
+            ptr::copy_nonoverlapping(
+                ((*self.inner).datasamples as *const u8).add(first * sample_size),
+                data as *mut u8,
+                data_size,
+            );
+
+            let inner = self.inner;
+            (*seg).starttime =
+                util::sample_time((*inner).starttime, first as i64, (*inner).samprate);
+            (*seg).endtime =
+                util::sample_time((*inner).starttime, last as i64 - 1, (*inner).samprate);
+            (*seg).samprate = (*inner).samprate;
+            (*seg).samplecnt = (last - first) as _;
+            (*seg).numsamples = (last - first) as _;
+            (*seg).datasamples = data;
+            (*seg).datasize = data_size as _;
+            (*seg).sampletype = (*inner).sampletype;
+
+            Ok(seg)
+        }
+    }
+
+    /// Returns the index of the first sample at or after `time`.
+    fn sample_index(&self, time: i64) -> i64 {
+        let start_time = self.ptr().starttime;
+        let samp_rate = self.sample_rate_hz();
+        let sample_cnt = self.sample_cnt();
+        if time <= start_time {
+            return 0;
+        }
+
+        let mut idx = if samp_rate > 0.0 {
+            ((time - start_time) as f64 * samp_rate / raw::NSTMODULUS as f64).ceil() as i64
+        } else {
+            0
+        }
+        .clamp(0, sample_cnt);
+        while idx > 0 && util::sample_time(start_time, idx - 1, samp_rate) >= time {
+            idx -= 1;
+        }
+        while idx < sample_cnt && util::sample_time(start_time, idx, samp_rate) < time {
+            idx += 1;
+        }
+
+        idx
+    }
+
+    /// Returns the range of sample indices within `start` and `end` (both inclusive).
+    pub(crate) fn window(&self, start: i64, end: i64) -> (i64, i64) {
+        let first = self.sample_index(start);
+        let last = match end.checked_add(1) {
+            Some(end) => self.sample_index(end),
+            None => self.sample_cnt(),
+        };
+
+        (first, last.max(first))
+    }
+
+    /// Retains the samples within the range of sample indices `first..last`.
+    fn retain(&mut self, first: i64, last: i64) -> MSResult<()> {
+        if first == 0 && last == self.sample_cnt() {
+            return Ok(());
+        }
+
+        self.unpack_data()?;
+        let sample_size = sample_size(self.sample_type())?;
+
+        unsafe {
+            let inner = self.inner;
+            let data = (*inner).datasamples as *mut u8;
+            ptr::copy(
+                data.add(first as usize * sample_size),
+                data,
+                (last - first) as usize * sample_size,
+            );
+
+            let start_time = (*inner).starttime;
+            (*inner).starttime = util::sample_time(start_time, first, (*inner).samprate);
+            (*inner).endtime = util::sample_time(start_time, last - 1, (*inner).samprate);
+            (*inner).samplecnt = (last - first) as _;
+            (*inner).numsamples = (last - first) as _;
+            free_record_list(inner);
+        }
+
+        Ok(())
+    }
+
+    /// Unlinks the trace segment from its trace identifier and releases it.
+    ///
+    /// # Safety
+    ///
+    /// The trace segment must not be accessed, afterwards.
+    unsafe fn remove(self) {
+        let (id, seg) = (self.trace_id.0, self.inner);
+        if (*seg).prev.is_null() {
+            (*id).first = (*seg).next;
+        } else {
+            (*(*seg).prev).next = (*seg).next;
+        }
+        if (*seg).next.is_null() {
+            (*id).last = (*seg).prev;
+        } else {
+            (*(*seg).next).prev = (*seg).prev;
+        }
+        (*id).numsegments -= 1;
 
-    //    if !self.ptr().datasamples.is_null() {
-    //        return Ok(self.num_samples());
-    //    }
+        free_segment(seg);
+    }
 
-    //    unsafe {
-    //        check(raw::mstl3_unpack_recordlist(
-    //            self.trace_id.get_raw_mut(),
-    //            self.inner,
-    //            ptr::null_mut(),
-    //            0,
-    //            0,
-    //        ))
-    //    }
-    //}
+    /// Returns the sample type of the first record of the record list.
+    fn record_list_sample_type(&self) -> MSResult<MSSampleType> {
+        let msr = unsafe {
+            let first = (*self.ptr().recordlist).first;
+            if first.is_null() {
+                return Err(MSError::from_str("empty record list"));
+            }
+            (*first).msr
+        };
+        if msr.is_null() {
+            return Err(MSError::from_str("missing record list header"));
+        }
+
+        let mut sample_size: u8 = 0;
+        let mut sample_type: c_char = 0;
+        unsafe {
+            check(raw::ms_encoding_sizetype(
+                (*msr).encoding as _,
+                &mut sample_size,
+                &mut sample_type,
+            ))?;
+        }
+
+        Ok(MSSampleType::from_char(sample_type as _))
+    }
 }
 
 pub trait DataSampleType {
+    /// The sample type corresponding to the data sample type.
+    const SAMPLE_TYPE: MSSampleType;
+
     /// Converts the trace segments' samples
     ///
     /// # Safety
     ///
     /// `seg` must not be a null pointer.
     unsafe fn convert_into(seg: *mut MS3TraceSeg, truncate: bool) -> MSResult<()>;
+
+    /// Copies the data samples `samples` converted into the data sample type.
+    ///
+    /// In contrast to [`convert_into()`](Self::convert_into), the data samples are left
+    /// untouched. Returns `None` if the data samples cannot be converted.
+    fn copy_from(samples: &Samples) -> Option<Vec<Self>>
+    where
+        Self: Sized;
 }
 
 impl DataSampleType for u8 {
+    const SAMPLE_TYPE: MSSampleType = MSSampleType::Text;
+
     unsafe fn convert_into(_seg: *mut MS3TraceSeg, _truncate: bool) -> MSResult<()> {
         Ok(())
     }
+
+    fn copy_from(samples: &Samples) -> Option<Vec<Self>> {
+        samples.as_text().map(|s| s.as_bytes().to_vec())
+    }
 }
 
 impl DataSampleType for i32 {
+    const SAMPLE_TYPE: MSSampleType = MSSampleType::Integer32;
+
     unsafe fn convert_into(seg: *mut MS3TraceSeg, truncate: bool) -> MSResult<()> {
         let rv = unsafe {
             check(raw::mstl3_convertsamples(
@@ -218,9 +503,15 @@ impl DataSampleType for i32 {
             Err(e) => Err(e),
         }
     }
+
+    fn copy_from(samples: &Samples) -> Option<Vec<Self>> {
+        samples.to_i32_vec()
+    }
 }
 
 impl DataSampleType for f32 {
+    const SAMPLE_TYPE: MSSampleType = MSSampleType::Float32;
+
     unsafe fn convert_into(seg: *mut MS3TraceSeg, truncate: bool) -> MSResult<()> {
         let rv = unsafe {
             check(raw::mstl3_convertsamples(
@@ -235,9 +526,15 @@ impl DataSampleType for f32 {
             Err(e) => Err(e),
         }
     }
+
+    fn copy_from(samples: &Samples) -> Option<Vec<Self>> {
+        samples.to_f32_vec()
+    }
 }
 
 impl DataSampleType for f64 {
+    const SAMPLE_TYPE: MSSampleType = MSSampleType::Float64;
+
     unsafe fn convert_into(seg: *mut MS3TraceSeg, truncate: bool) -> MSResult<()> {
         let rv = unsafe {
             check(raw::mstl3_convertsamples(
@@ -252,6 +549,10 @@ impl DataSampleType for f64 {
             Err(e) => Err(e),
         }
     }
+
+    fn copy_from(samples: &Samples) -> Option<Vec<Self>> {
+        samples.to_f64_vec()
+    }
 }
 
 /// An iterator for [`MSTraceSegment`].
@@ -272,7 +573,7 @@ impl<'id> Iterator for MSTraceSegmentIter<'id> {
         }
 
         let rv = Some(MSTraceSegment {
-            _trace_id: self.trace_id,
+            trace_id: self.trace_id,
             inner: self.next,
         });
         self.prev = self.next;
@@ -288,7 +589,7 @@ impl<'id> DoubleEndedIterator for MSTraceSegmentIter<'id> {
         }
 
         let rv = Some(MSTraceSegment {
-            _trace_id: self.trace_id,
+            trace_id: self.trace_id,
             inner: self.prev,
         });
         self.next = self.prev;
@@ -297,6 +598,58 @@ impl<'id> DoubleEndedIterator for MSTraceSegmentIter<'id> {
     }
 }
 
+/// Options applied when inserting records into a [`MSTraceList`].
+///
+/// Combines data selections (see [`MSSelections`]) and merge tolerances (see [`MSTolerance`]).
+/// By default, all records are inserted and merged according to the libmseed default
+/// tolerances.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSSelections, MSTolerance, MSTraceList, MSTraceListOptions};
+///
+/// let mut selections = MSSelections::new();
+/// selections.add_sid("FDSN:IU_COLA_*").unwrap();
+/// let tolerance = MSTolerance::new().time(1.0);
+///
+/// let mstl = MSTraceList::read_with_options(
+///     "path/to/data.mseed",
+///     MSControlFlags::MSF_UNPACKDATA,
+///     &MSTraceListOptions::new()
+///         .selections(&selections)
+///         .tolerance(&tolerance),
+/// )
+/// .unwrap();
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MSTraceListOptions<'a> {
+    selections: Option<&'a MSSelections>,
+    tolerance: Option<&'a MSTolerance>,
+}
+
+impl<'a> MSTraceListOptions<'a> {
+    /// Creates new options inserting all records according to the libmseed default tolerances.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts only records matching any of the `selections`.
+    ///
+    /// Records not matching any of the `selections` are skipped before their data samples are
+    /// unpacked. If `selections` is empty, all records are inserted.
+    pub fn selections(mut self, selections: &'a MSSelections) -> Self {
+        self.selections = Some(selections);
+        self
+    }
+
+    /// Merges records according to `tolerance`.
+    pub fn tolerance(mut self, tolerance: &'a MSTolerance) -> Self {
+        self.tolerance = Some(tolerance);
+        self
+    }
+}
+
 /// A container for [`MSTraceId`]s.
 ///
 /// # Examples
@@ -304,8 +657,7 @@ impl<'id> DoubleEndedIterator for MSTraceSegmentIter<'id> {
 /// Creating a `MSTraceList` from a file may be implemented as follows:
 ///
 /// ```no_run
-/// use std::fs::File;
-///
+/// ///
 /// use std::io::{Read, BufReader};
 ///
 /// use mseed::{MSControlFlags, MSTraceList};
@@ -323,8 +675,7 @@ impl<'id> DoubleEndedIterator for MSTraceSegmentIter<'id> {
 /// If controlling the records to be inserted is desired, using [`MSReader`] is required:
 ///
 /// ```no_run
-/// use std::fs::File;
-///
+/// ///
 /// use mseed::{MSControlFlags, MSReader, MSTraceList};
 ///
 /// let mut mstl = MSTraceList::new().unwrap();
@@ -349,10 +700,51 @@ impl<'id> DoubleEndedIterator for MSTraceSegmentIter<'id> {
 ///     }
 /// }
 /// ```
+///
+/// Building a trace list with [`MSControlFlags::MSF_RECORDLIST`] defers unpacking of data
+/// samples. Instead, each trace segment keeps a list of the records it is composed of, such that
+/// data samples may be unpacked on demand:
+///
+/// ```no_run
+/// use mseed::{MSControlFlags, MSTraceList};
+///
+/// let mstl = MSTraceList::read("path/to/data.mseed", MSControlFlags::MSF_RECORDLIST).unwrap();
+///
+/// for tid in mstl.iter() {
+///     for mut tseg in tid.iter() {
+///         // unpacks the data samples from the trace segment's record list
+///         let data_samples: &[i32] = tseg.data_samples().unwrap();
+///     }
+/// }
+/// ```
 /// [`MSReader`]: crate::MSReader
 #[derive(Debug)]
 pub struct MSTraceList {
     inner: *mut MS3TraceList,
+    // sources referenced by record lists
+    sources: Vec<RecordListSource>,
+}
+
+/// A source referenced by the record lists of a [`MSTraceList`].
+enum RecordListSource {
+    /// The buffer the records were read from, referenced by `MS3RecordPtr::bufferptr`.
+    Buffer(Vec<u8>),
+    /// The path the records were read from.
+    ///
+    /// libmseed does not copy the path but stores the pointer passed to
+    /// `ms3_readtracelist_selection()` as `MS3RecordPtr::filename`, which is used to re-open the
+    /// file when unpacking data samples from the record list. The path must therefore outlive
+    /// the trace list.
+    Path(ConnectionInfo),
+}
+
+impl fmt::Debug for RecordListSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buffer(buf) => f.debug_tuple("Buffer").field(&buf.len()).finish(),
+            Self::Path(connection_info) => f.debug_tuple("Path").field(connection_info).finish(),
+        }
+    }
 }
 
 impl MSTraceList {
@@ -378,25 +770,269 @@ impl MSTraceList {
             return Err(MSError::from_str("failed to initialize trace list"));
         }
 
-        Ok(Self { inner: mstl })
+        Ok(Self {
+            inner: mstl,
+            sources: vec![],
+        })
     }
 
     /// Creates a new [`MSTraceList`] from a buffer.
+    ///
+    /// If `flags` include [`MSControlFlags::MSF_RECORDLIST`], the trace list keeps a copy of
+    /// `buf` referenced by the record lists. Use [`MSTraceList::from_vec()`] in order to avoid
+    /// copying.
+    ///
+    /// Opaque records (see [`MSRecordRef::is_opaque()`](crate::MSRecordRef::is_opaque)) are
+    /// skipped.
     pub fn from_buffer(buf: &[u8], flags: MSControlFlags) -> MSResult<Self> {
+        Self::from_buffer_with_options(buf, flags, &MSTraceListOptions::new())
+    }
+
+    /// Creates a new [`MSTraceList`] from a buffer, inserting only records matching any of the
+    /// `selections`.
+    ///
+    /// See also [`MSTraceListOptions::selections()`].
+    pub fn from_buffer_with_selections(
+        buf: &[u8],
+        flags: MSControlFlags,
+        selections: &MSSelections,
+    ) -> MSResult<Self> {
+        Self::from_buffer_with_options(
+            buf,
+            flags,
+            &MSTraceListOptions::new().selections(selections),
+        )
+    }
+
+    /// Creates a new [`MSTraceList`] from a buffer, inserting records according to `options`.
+    ///
+    /// See also [`MSTraceList::from_buffer()`].
+    pub fn from_buffer_with_options(
+        buf: &[u8],
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<Self> {
         let mut rv = Self::new()?;
+        if flags.contains(MSControlFlags::MSF_RECORDLIST) {
+            rv.read_owned_buffer(buf.to_vec(), flags, options)?;
+        } else {
+            rv.read_buffer(buf, flags, options)?;
+        }
 
-        unsafe {
+        Ok(rv)
+    }
+
+    /// Creates a new [`MSTraceList`] from an owned buffer.
+    ///
+    /// If `flags` include [`MSControlFlags::MSF_RECORDLIST`], the trace list takes ownership of
+    /// `buf` such that data samples may be unpacked from the record lists on demand.
+    pub fn from_vec(buf: Vec<u8>, flags: MSControlFlags) -> MSResult<Self> {
+        let mut rv = Self::new()?;
+        rv.read_owned_buffer(buf, flags, &MSTraceListOptions::new())?;
+
+        Ok(rv)
+    }
+
+    fn read_owned_buffer(
+        &mut self,
+        buf: Vec<u8>,
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<()> {
+        let buf = strip_opaque(&buf).unwrap_or(buf);
+        // moving the buffer does not move its contents, i.e. the record lists remain valid
+        self.read_records(&buf, flags, options)?;
+        if flags.contains(MSControlFlags::MSF_RECORDLIST) {
+            self.sources.push(RecordListSource::Buffer(buf));
+        }
+
+        Ok(())
+    }
+
+    fn read_buffer(
+        &mut self,
+        buf: &[u8],
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<()> {
+        match strip_opaque(buf) {
+            Some(stripped) => self.read_records(&stripped, flags, options),
+            None => self.read_records(buf, flags, options),
+        }
+    }
+
+    fn read_records(
+        &mut self,
+        buf: &[u8],
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<()> {
+        let selections = options.selections.map_or(ptr::null(), |s| s.get_raw());
+
+        with_tolerance(options.tolerance, |tolerance| unsafe {
             let buf = &*(buf as *const [_] as *const [_]);
-            check(raw::mstl3_readbuffer(
-                (&mut rv.get_raw_mut()) as *mut *mut _,
+            check(raw::mstl3_readbuffer_selection(
+                (&mut self.get_raw_mut()) as *mut *mut _,
                 buf.as_ptr(),
                 buf.len() as _,
                 0,
                 flags.bits(),
-                ptr::null_mut(),
+                tolerance,
+                selections,
                 0,
             ))
-        }?;
+        })?;
+
+        Ok(())
+    }
+
+    /// Reads the records from `connection_info` record by record, inserting all but opaque
+    /// records.
+    ///
+    /// Mirrors `ms3_readtracelist_selection()`, which does not skip opaque records.
+    fn read_file(
+        &mut self,
+        connection_info: &ConnectionInfo,
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<()> {
+        let selections = options.selections.map_or(ptr::null(), |s| s.get_raw());
+        let mut msfp: *mut MS3FileParam = ptr::null_mut();
+        let mut msr: *mut MS3Record = ptr::null_mut();
+
+        let rv = with_tolerance(options.tolerance, |tolerance| loop {
+            let rv = unsafe {
+                raw::ms3_readmsr_selection(
+                    (&mut msfp) as *mut *mut MS3FileParam,
+                    (&mut msr) as *mut *mut MS3Record,
+                    connection_info.as_ptr(),
+                    flags.bits(),
+                    selections,
+                    0,
+                )
+            };
+            if check_eof(rv) {
+                break Ok(());
+            }
+            if let Err(e) = check(rv) {
+                break Err(e);
+            }
+
+            if unsafe { is_opaque(msr) } {
+                continue;
+            }
+
+            if let Err(e) =
+                unsafe { self.add_file_record(msr, msfp, connection_info, flags, tolerance) }
+            {
+                break Err(e);
+            }
+        });
+
+        // closes the file and frees the record
+        unsafe {
+            raw::ms3_readmsr_r(
+                (&mut msfp) as *mut *mut MS3FileParam,
+                (&mut msr) as *mut *mut MS3Record,
+                ptr::null(),
+                MSControlFlags::empty().bits(),
+                0,
+            );
+        }
+
+        rv
+    }
+
+    /// Inserts the record `msr` read from `connection_info` by `msfp`.
+    ///
+    /// If `flags` include [`MSControlFlags::MSF_RECORDLIST`], the record is added to the segment's
+    /// record list, referring to the record's offset within `connection_info`.
+    ///
+    /// # Safety
+    ///
+    /// `msr` must be the record most recently read by `msfp`.
+    unsafe fn add_file_record(
+        &mut self,
+        msr: *mut MS3Record,
+        msfp: *mut MS3FileParam,
+        connection_info: &ConnectionInfo,
+        flags: MSControlFlags,
+        tolerance: *mut MS3Tolerance,
+    ) -> MSResult<()> {
+        let mut recptr: *mut MS3RecordPtr = ptr::null_mut();
+        let pprecptr = if flags.contains(MSControlFlags::MSF_RECORDLIST) {
+            (&mut recptr) as *mut *mut MS3RecordPtr
+        } else {
+            ptr::null_mut()
+        };
+
+        let seg =
+            raw::mstl3_addmsr_recordptr(self.inner, msr, pprecptr, 0, 1, flags.bits(), tolerance);
+        if seg.is_null() {
+            return Err(MSError::from_str("failed to insert record"));
+        }
+
+        if !recptr.is_null() {
+            let mut data_offset = 0;
+            let mut data_size = 0;
+            check(raw::msr3_data_bounds(msr, &mut data_offset, &mut data_size))?;
+
+            (*recptr).bufferptr = ptr::null();
+            (*recptr).fileptr = ptr::null_mut();
+            (*recptr).filename = connection_info.as_ptr();
+            (*recptr).fileoffset = (*msfp).streampos - (*msr).reclen as i64;
+            (*recptr).dataoffset = data_offset;
+            (*recptr).prvtptr = ptr::null_mut();
+        }
+
+        Ok(())
+    }
+
+    /// Creates a new [`MSTraceList`] from the records read from `path_or_url`.
+    ///
+    /// If `flags` include [`MSControlFlags::MSF_RECORDLIST`], data samples may be unpacked on
+    /// demand by re-reading the records from `path_or_url`. Note that unpacking from record lists
+    /// is supported for local files, only.
+    ///
+    /// Opaque records (see [`MSRecordRef::is_opaque()`](crate::MSRecordRef::is_opaque)) are
+    /// skipped.
+    pub fn read<T: IntoConnectionInfo>(path_or_url: T, flags: MSControlFlags) -> MSResult<Self> {
+        Self::read_with_options(path_or_url, flags, &MSTraceListOptions::new())
+    }
+
+    /// Creates a new [`MSTraceList`] from the records read from `path_or_url`, inserting only
+    /// records matching any of the `selections`.
+    ///
+    /// See also [`MSTraceListOptions::selections()`].
+    pub fn read_with_selections<T: IntoConnectionInfo>(
+        path_or_url: T,
+        flags: MSControlFlags,
+        selections: &MSSelections,
+    ) -> MSResult<Self> {
+        Self::read_with_options(
+            path_or_url,
+            flags,
+            &MSTraceListOptions::new().selections(selections),
+        )
+    }
+
+    /// Creates a new [`MSTraceList`] from the records read from `path_or_url`, inserting records
+    /// according to `options`.
+    ///
+    /// See also [`MSTraceList::read()`].
+    pub fn read_with_options<T: IntoConnectionInfo>(
+        path_or_url: T,
+        flags: MSControlFlags,
+        options: &MSTraceListOptions,
+    ) -> MSResult<Self> {
+        let connection_info = path_or_url.into_connection_info()?;
+        let mut rv = Self::new()?;
+
+        rv.read_file(&connection_info, flags, options)?;
+
+        if flags.contains(MSControlFlags::MSF_RECORDLIST) {
+            rv.sources.push(RecordListSource::Path(connection_info));
+        }
 
         Ok(rv)
     }
@@ -418,13 +1054,226 @@ impl MSTraceList {
         }
     }
 
+    /// Trims the trace list to the time window from `start` to `end` (both inclusive).
+    ///
+    /// The cut points snap to sample boundaries, i.e. the trace segments are trimmed to the
+    /// samples within the time window. Trace segments and trace identifiers outside of the time
+    /// window are removed. Trimmed trace segments are unpacked on demand (see
+    /// [`MSTraceSegment::data_samples()`]), releasing their record lists.
+    pub fn trim(&mut self, start: OffsetDateTime, end: OffsetDateTime) -> MSResult<()> {
+        let (start, end) = time_window(&start, &end)?;
+
+        let ids: Vec<_> = self.iter().map(|trace_id| trace_id.0).collect();
+        for id in ids {
+            let trace_id = MSTraceId(id);
+            let segs: Vec<_> = trace_id.iter().map(|seg| seg.inner).collect();
+            for seg in segs {
+                let mut seg = MSTraceSegment {
+                    trace_id: &trace_id,
+                    inner: seg,
+                };
+                match seg.window(start, end) {
+                    (first, last) if first == last => unsafe { seg.remove() },
+                    (first, last) => seg.retain(first, last)?,
+                }
+            }
+
+            unsafe {
+                if trace_id.is_empty() {
+                    self.remove_trace_id(id);
+                } else {
+                    update_time_range(id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a new trace list holding copies of the data samples within the time window from
+    /// `start` to `end` (both inclusive).
+    ///
+    /// The cut points snap to sample boundaries. In contrast to [`MSTraceList::trim()`] the trace
+    /// list itself is not modified, except that data samples are unpacked on demand.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use time::format_description::well_known::Iso8601;
+    /// use time::OffsetDateTime;
+    ///
+    /// use mseed::{MSControlFlags, MSTraceList};
+    ///
+    /// let mstl = MSTraceList::read("path/to/data.mseed", MSControlFlags::MSF_RECORDLIST).unwrap();
+    ///
+    /// let start = OffsetDateTime::parse("2010-02-27T06:34:14Z", &Iso8601::DEFAULT).unwrap();
+    /// let end = OffsetDateTime::parse("2010-02-27T06:44:14Z", &Iso8601::DEFAULT).unwrap();
+    /// let event = mstl.slice(start, end).unwrap();
+    /// ```
+    pub fn slice(&self, start: OffsetDateTime, end: OffsetDateTime) -> MSResult<MSTraceList> {
+        let (start, end) = time_window(&start, &end)?;
+
+        let mut rv = Self::new()?;
+        for trace_id in self.iter() {
+            let mut id: *mut MS3TraceID = ptr::null_mut();
+            for mut seg in trace_id.iter() {
+                let (first, last) = seg.window(start, end);
+                if first == last {
+                    continue;
+                }
+
+                seg.unpack_data()?;
+                let rv_seg = seg.copy_range(first as usize, last as usize)?;
+                unsafe {
+                    if id.is_null() {
+                        id = match rv.push_trace_id(&trace_id) {
+                            Ok(id) => id,
+                            Err(e) => {
+                                free_segment(rv_seg);
+                                return Err(e);
+                            }
+                        };
+                    }
+                    link_segment(id, (*id).last, rv_seg);
+                }
+            }
+
+            if !id.is_null() {
+                unsafe { update_time_range(id) };
+            }
+        }
+
+        Ok(rv)
+    }
+
+    /// Splits the trace segment of the trace identifier matching the [FDSN source
+    /// identifier](https://docs.fdsn.org/projects/source-identifiers/) `sid` at `time`.
+    ///
+    /// The trace segment containing `time` is truncated such that it ends with the last sample
+    /// before `time`, while the remaining samples are moved to a new trace segment inserted after
+    /// it. `time` snaps to the next sample boundary. Returns an error if `time` does not fall
+    /// within a trace segment of `sid`, i.e. if one of the trace segments would be empty.
+    ///
+    /// The data samples are unpacked on demand (see [`MSTraceSegment::data_samples()`]). Note
+    /// that the trace segment's record list is released.
+    pub fn split_at(&mut self, sid: &str, time: OffsetDateTime) -> MSResult<()> {
+        let time = util::time_to_nstime(&time);
+        for trace_id in self
+            .iter()
+            .filter(|trace_id| trace_id.sid().is_ok_and(|s| s == sid))
+        {
+            for mut seg in trace_id.iter() {
+                let idx = seg.sample_index(time);
+                if idx > 0 && idx < seg.sample_cnt() {
+                    return seg.split(idx as usize);
+                }
+            }
+        }
+
+        Err(MSError::from_str("split time outside of trace segments"))
+    }
+
+    /// Removes the trace identifiers matching the [FDSN source
+    /// identifier](https://docs.fdsn.org/projects/source-identifiers/) `sid`, including all of
+    /// their trace segments.
+    ///
+    /// Returns whether a trace identifier was removed.
+    pub fn remove(&mut self, sid: &str) -> bool {
+        let ids: Vec<_> = self
+            .iter()
+            .filter(|trace_id| trace_id.sid().is_ok_and(|s| s == sid))
+            .map(|trace_id| trace_id.0)
+            .collect();
+
+        for id in &ids {
+            unsafe { self.remove_trace_id(*id) };
+        }
+
+        !ids.is_empty()
+    }
+
+    /// Appends an empty trace identifier with the source identifier and publication version of
+    /// `src` to the trace list.
+    ///
+    /// The trace identifier is linked on the lowest level of the trace list's skip list, only.
+    ///
+    /// # Safety
+    ///
+    /// `src` must sort after all trace identifiers of the trace list.
+    unsafe fn push_trace_id(&mut self, src: &MSTraceId) -> MSResult<*mut MS3TraceID> {
+        let id = lm_malloc(mem::size_of::<MS3TraceID>())? as *mut MS3TraceID;
+        ptr::write_bytes(id, 0, 1);
+        (*id).sid = (*src.0).sid;
+        (*id).pubversion = (*src.0).pubversion;
+        (*id).height = 1;
+
+        let head: *mut MS3TraceID = &mut (*self.inner).traces;
+        let mut node = head;
+        while !(*node).next[0].is_null() {
+            node = (*node).next[0];
+        }
+        (*node).next[0] = id;
+        (*head).height = (*head).height.max(1);
+        (*self.inner).numtraceids += 1;
+
+        Ok(id)
+    }
+
+    /// Unlinks the trace identifier `id` from the trace list and releases it.
+    ///
+    /// # Safety
+    ///
+    /// `id` must be a trace identifier of the trace list.
+    unsafe fn remove_trace_id(&mut self, id: *mut MS3TraceID) {
+        let head: *mut MS3TraceID = &mut (*self.inner).traces;
+        for level in 0..(*head).next.len() {
+            let mut node = head;
+            while !(*node).next[level].is_null() && (*node).next[level] != id {
+                node = (*node).next[level];
+            }
+            if (*node).next[level] == id {
+                (*node).next[level] = (*id).next[level];
+            }
+        }
+        (*self.inner).numtraceids -= 1;
+
+        let mut seg = (*id).first;
+        while !seg.is_null() {
+            let next = (*seg).next;
+            free_segment(seg);
+            seg = next;
+        }
+        lm_free((*id).prvtptr);
+        lm_free(id as _);
+    }
+
     /// Inserts `rec` into the trace list.
     ///
-    /// Note that currently [`MSTraceList`] does not implement deferred unpacking of data samples.
-    /// Therefore, clients need to make sure that the `rec` inserted is unpacked, beforehand. If
-    /// not doing so, the trace list will merely be a list of channels.
+    /// Note that records inserted do not contribute to record lists. Therefore, clients need to
+    /// make sure that the `rec` inserted is unpacked, beforehand. If not doing so, the trace list
+    /// will merely be a list of channels. For deferred unpacking of data samples, create the trace
+    /// list with [`MSControlFlags::MSF_RECORDLIST`], instead (see e.g. [`MSTraceList::read()`]).
+    ///
+    /// Opaque records (see [`MSRecord::is_opaque()`]) are skipped.
     pub fn insert(&mut self, rec: MSRecord, autoheal: bool) -> MSResult<()> {
-        let rv = unsafe {
+        self.insert_with_options(rec, autoheal, &MSTraceListOptions::new())
+    }
+
+    /// Inserts `rec` into the trace list according to `options`.
+    ///
+    /// Records not matching any of the selections of `options` are skipped. See also
+    /// [`MSTraceList::insert()`].
+    pub fn insert_with_options(
+        &mut self,
+        rec: MSRecord,
+        autoheal: bool,
+        options: &MSTraceListOptions,
+    ) -> MSResult<()> {
+        if rec.is_opaque() || options.selections.is_some_and(|s| !s.matches(&rec)) {
+            return Ok(());
+        }
+
+        let rv = with_tolerance(options.tolerance, |tolerance| unsafe {
             raw::mstl3_addmsr_recordptr(
                 self.inner,
                 rec.into_raw(),
@@ -432,9 +1281,9 @@ impl MSTraceList {
                 0,
                 autoheal as _,
                 MSControlFlags::empty().bits(),
-                ptr::null_mut(),
+                tolerance,
             )
-        };
+        });
 
         if rv.is_null() {
             return Err(MSError::from_str("failed to insert record"));
@@ -472,6 +1321,145 @@ impl MSTraceList {
     }
 }
 
+/// Returns whether the record `msr` read by libmseed carries opaque payloads.
+///
+/// # Safety
+///
+/// `msr` must point to a valid record whose raw record is available.
+unsafe fn is_opaque(msr: *const MS3Record) -> bool {
+    let rec = from_raw_parts((*msr).record as *const u8, (*msr).reclen as usize);
+    MSRecordRef::parse(rec).is_ok_and(|rec| rec.is_opaque())
+}
+
+/// Calls `f` with the raw `tolerance`, or with a null pointer selecting the libmseed defaults.
+fn with_tolerance<R, F>(tolerance: Option<&MSTolerance>, f: F) -> R
+where
+    F: FnOnce(*mut MS3Tolerance) -> R,
+{
+    match tolerance {
+        Some(tolerance) => tolerance.with_raw(f),
+        None => f(ptr::null_mut()),
+    }
+}
+
+/// Converts the time window from `start` to `end` into nanosecond times.
+fn time_window(start: &OffsetDateTime, end: &OffsetDateTime) -> MSResult<(i64, i64)> {
+    if start > end {
+        return Err(MSError::from_str("start time after end time"));
+    }
+
+    Ok((util::time_to_nstime(start), util::time_to_nstime(end)))
+}
+
+/// Returns the size of a sample of `sample_type` in bytes.
+fn sample_size(sample_type: MSSampleType) -> MSResult<usize> {
+    match sample_type {
+        MSSampleType::Integer32 | MSSampleType::Float32 => Ok(4),
+        MSSampleType::Float64 => Ok(8),
+        MSSampleType::Text => Ok(1),
+        MSSampleType::Unknown => Err(MSError::from_str("unknown sample type")),
+    }
+}
+
+unsafe fn lm_malloc(size: usize) -> MSResult<*mut c_void> {
+    let malloc = raw::libmseed_memory
+        .malloc
+        .ok_or_else(|| MSError::from_str("missing memory allocator"))?;
+    let rv = malloc(size);
+    if rv.is_null() {
+        return Err(MSError::from_str("failed to allocate memory"));
+    }
+
+    Ok(rv)
+}
+
+unsafe fn lm_free(ptr: *mut c_void) {
+    if let (false, Some(free)) = (ptr.is_null(), raw::libmseed_memory.free) {
+        free(ptr);
+    }
+}
+
+/// Releases the record list of `seg`.
+///
+/// # Safety
+///
+/// `seg` must be a valid trace segment.
+unsafe fn free_record_list(seg: *mut MS3TraceSeg) {
+    let list = (*seg).recordlist;
+    if list.is_null() {
+        return;
+    }
+
+    let mut rec = (*list).first;
+    while !rec.is_null() {
+        let next = (*rec).next;
+        raw::msr3_free(&mut (*rec).msr);
+        lm_free(rec as _);
+        rec = next;
+    }
+    lm_free(list as _);
+    (*seg).recordlist = ptr::null_mut();
+}
+
+/// Releases the trace segment `seg`, including its data samples and record list.
+///
+/// # Safety
+///
+/// `seg` must be a valid trace segment not linked to a trace identifier, anymore.
+unsafe fn free_segment(seg: *mut MS3TraceSeg) {
+    free_record_list(seg);
+    lm_free((*seg).datasamples);
+    lm_free((*seg).prvtptr);
+    lm_free(seg as _);
+}
+
+/// Links the unlinked trace segment `seg` into the trace identifier `id` after `prev`, or as the
+/// first trace segment if `prev` is null.
+///
+/// # Safety
+///
+/// `id` must be a valid trace identifier and `prev` either null or one of its trace segments.
+unsafe fn link_segment(id: *mut MS3TraceID, prev: *mut MS3TraceSeg, seg: *mut MS3TraceSeg) {
+    let next = if prev.is_null() {
+        (*id).first
+    } else {
+        (*prev).next
+    };
+
+    (*seg).prev = prev;
+    (*seg).next = next;
+    if next.is_null() {
+        (*id).last = seg;
+    } else {
+        (*next).prev = seg;
+    }
+    if prev.is_null() {
+        (*id).first = seg;
+    } else {
+        (*prev).next = seg;
+    }
+    (*id).numsegments += 1;
+}
+
+/// Updates the earliest and latest sample time of the trace identifier `id`.
+///
+/// # Safety
+///
+/// `id` must be a valid trace identifier.
+unsafe fn update_time_range(id: *mut MS3TraceID) {
+    let mut earliest = i64::MAX;
+    let mut latest = i64::MIN;
+    let mut seg = (*id).first;
+    while !seg.is_null() {
+        earliest = earliest.min((*seg).starttime);
+        latest = latest.max((*seg).endtime);
+        seg = (*seg).next;
+    }
+
+    (*id).earliest = earliest;
+    (*id).latest = latest;
+}
+
 impl Drop for MSTraceList {
     fn drop(&mut self) {
         unsafe { raw::mstl3_free((&mut self.inner) as *mut *mut MS3TraceList, 1) };
@@ -781,4 +1769,242 @@ mod tests {
         assert!(trace_id_iter.next().is_none());
         assert!(mstl_iter.next().is_none());
     }
+
+    #[test]
+    fn test_read_mstl_record_list() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let mstl = MSTraceList::read(p.clone(), MSControlFlags::MSF_RECORDLIST).unwrap();
+        assert_eq!(mstl.len(), 1);
+        let trace_id = mstl.iter().next().unwrap();
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        assert!(trace_seg.has_record_list());
+        assert!(trace_seg.record_cnt() > 0);
+        assert_eq!(trace_seg.sample_cnt(), 3952);
+        assert!(!trace_seg.is_data_unpacked());
+
+        // unpack into caller-provided buffer
+        let mut buf = vec![0i32; 3952];
+        assert_eq!(trace_seg.unpack_data_into(&mut buf).unwrap(), 3952);
+        assert_eq!(&buf[3948..], &[28067, -9565, -71961, -146622]);
+        assert!(!trace_seg.is_data_unpacked());
+        let mut buf = vec![0f32; 3952];
+        assert!(trace_seg.unpack_data_into(&mut buf).is_err());
+        let mut buf = vec![0i32; 10];
+        assert!(trace_seg.unpack_data_into(&mut buf).is_err());
+
+        // unpack on demand
+        let data_samples: &[i32] = trace_seg.data_samples().unwrap();
+        assert_eq!(&data_samples[3948..], &[28067, -9565, -71961, -146622]);
+        assert!(trace_seg.is_data_unpacked());
+        assert_eq!(trace_seg.unpack_data().unwrap(), 3952);
+
+        let buf = std::fs::read(&p).unwrap();
+        let mstl = MSTraceList::from_buffer(&buf, MSControlFlags::MSF_RECORDLIST).unwrap();
+        drop(buf);
+        let trace_id = mstl.iter().next().unwrap();
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        assert!(trace_seg.has_record_list());
+        assert_eq!(trace_seg.unpack_data().unwrap(), 3952);
+        let data_samples: &[i32] = trace_seg.data_samples().unwrap();
+        assert_eq!(&data_samples[3948..], &[28067, -9565, -71961, -146622]);
+    }
+
+    #[test]
+    fn test_segment_samples() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let mstl = MSTraceList::read(p, MSControlFlags::MSF_RECORDLIST).unwrap();
+        let trace_id = mstl.iter().next().unwrap();
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        let samples = trace_seg.samples().unwrap();
+        assert_eq!(samples.len(), 3952);
+        assert_eq!(
+            &samples.as_i32().unwrap()[3948..],
+            &[28067, -9565, -71961, -146622]
+        );
+        assert_eq!(samples.to_f64_vec().unwrap()[3951], -146622.0);
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let mut mstl = MSTraceList::read(p, MSControlFlags::MSF_RECORDLIST).unwrap();
+        let expected: Vec<i32> = {
+            let trace_id = mstl.iter().next().unwrap();
+            let mut trace_seg = trace_id.iter().next().unwrap();
+            trace_seg.data_samples::<i32>().unwrap()[10..=20].to_vec()
+        };
+
+        // cut points snap to sample boundaries
+        mstl.trim(
+            test::parse_time("2010-02-27T06:50:09.5Z"),
+            test::parse_time("2010-02-27T06:50:20.5Z"),
+        )
+        .unwrap();
+
+        assert_eq!(mstl.len(), 1);
+        let trace_id = mstl.iter().next().unwrap();
+        assert_eq!(trace_id.len(), 1);
+        assert_eq!(
+            trace_id.start_time().unwrap(),
+            test::parse_time("2010-02-27T06:50:10.069539Z")
+        );
+        assert_eq!(
+            trace_id.end_time().unwrap(),
+            test::parse_time("2010-02-27T06:50:20.069539Z")
+        );
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        assert!(!trace_seg.has_record_list());
+        assert_eq!(trace_seg.sample_cnt(), 11);
+        assert_eq!(trace_seg.data_samples::<i32>().unwrap(), &expected[..]);
+
+        // trimming outside of the data removes the trace identifier
+        mstl.trim(
+            test::parse_time("2011-01-01T00:00:00Z"),
+            test::parse_time("2011-01-02T00:00:00Z"),
+        )
+        .unwrap();
+        assert!(mstl.is_empty());
+    }
+
+    #[test]
+    fn test_slice_and_remove() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-3channel-signal.mseed3");
+
+        let mut mstl = MSTraceList::read(p, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        assert_eq!(mstl.len(), 3);
+
+        let start = mstl.iter().next().unwrap().start_time().unwrap();
+        let sliced = mstl
+            .slice(start, start + time::Duration::seconds(59))
+            .unwrap();
+        assert_eq!(sliced.len(), 3);
+        for (trace_id, sliced_id) in mstl.iter().zip(sliced.iter()) {
+            assert_eq!(sliced_id.sid().unwrap(), trace_id.sid().unwrap());
+            assert_eq!(sliced_id.pub_version(), trace_id.pub_version());
+
+            let mut trace_seg = trace_id.iter().next().unwrap();
+            let mut sliced_seg = sliced_id.iter().next().unwrap();
+            assert_eq!(sliced_seg.start_time().unwrap(), start);
+            assert_eq!(sliced_seg.sample_cnt(), 60);
+            assert_eq!(
+                sliced_seg.data_samples::<i32>().unwrap(),
+                &trace_seg.data_samples::<i32>().unwrap()[..60]
+            );
+        }
+
+        assert!(mstl.remove("FDSN:IU_COLA_00_L_H_1"));
+        assert!(!mstl.remove("FDSN:IU_COLA_00_L_H_1"));
+        assert_eq!(mstl.len(), 2);
+        assert!(mstl
+            .iter()
+            .all(|trace_id| trace_id.sid().unwrap() != "FDSN:IU_COLA_00_L_H_1"));
+        assert_eq!(sliced.len(), 3);
+
+        assert!(mstl
+            .slice(start + time::Duration::seconds(1), start)
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_at() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed3");
+
+        let mut mstl = MSTraceList::read(p, MSControlFlags::MSF_UNPACKDATA).unwrap();
+        let (sid, expected) = {
+            let trace_id = mstl.iter().next().unwrap();
+            let mut trace_seg = trace_id.iter().next().unwrap();
+            let expected = trace_seg.data_samples::<i32>().unwrap().to_vec();
+            (trace_id.sid().unwrap(), expected)
+        };
+
+        assert!(mstl
+            .split_at(&sid, test::parse_time("2010-02-27T06:50:00Z"))
+            .is_err());
+        assert!(mstl
+            .split_at(
+                "FDSN:XX_TEST__L_H_Z",
+                test::parse_time("2010-02-27T07:00:00Z")
+            )
+            .is_err());
+        mstl.split_at(&sid, test::parse_time("2010-02-27T07:00:00Z"))
+            .unwrap();
+        let trace_id = mstl.iter().next().unwrap();
+        assert_eq!(trace_id.len(), 2);
+
+        let mut segs = trace_id.iter();
+        let mut first = segs.next().unwrap();
+        let mut second = segs.next().unwrap();
+        assert!(segs.next().is_none());
+        assert_eq!(first.sample_cnt(), 600);
+        assert_eq!(
+            first.end_time().unwrap(),
+            test::parse_time("2010-02-27T06:59:59.069539Z")
+        );
+        assert_eq!(
+            second.start_time().unwrap(),
+            test::parse_time("2010-02-27T07:00:00.069539Z")
+        );
+        assert_eq!(second.sample_cnt(), 3352);
+        assert_eq!(first.data_samples::<i32>().unwrap(), &expected[..600]);
+        assert_eq!(second.data_samples::<i32>().unwrap(), &expected[600..]);
+
+        // slicing does not merge adjacent trace segments
+        let sliced = mstl
+            .slice(
+                test::parse_time("2010-02-27T06:59:00Z"),
+                test::parse_time("2010-02-27T07:01:00Z"),
+            )
+            .unwrap();
+        let sliced_id = sliced.iter().next().unwrap();
+        assert_eq!(sliced_id.sid().unwrap(), sid);
+        assert_eq!(sliced_id.len(), 2);
+        assert_eq!(
+            sliced_id.start_time().unwrap(),
+            test::parse_time("2010-02-27T06:59:00.069539Z")
+        );
+        let mut segs = sliced_id.iter();
+        assert_eq!(
+            segs.next().unwrap().data_samples::<i32>().unwrap(),
+            &expected[540..600]
+        );
+        assert_eq!(
+            segs.next().unwrap().data_samples::<i32>().unwrap(),
+            &expected[600..660]
+        );
+    }
+
+    #[test]
+    fn test_unpack_data_without_record_list() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+
+        p.push("testdata-oneseries-mixedlengths-mixedorder.mseed2");
+
+        let buf = std::fs::read(&p).unwrap();
+        let mstl = MSTraceList::from_vec(buf, MSControlFlags::empty()).unwrap();
+        let trace_id = mstl.iter().next().unwrap();
+        let mut trace_seg = trace_id.iter().next().unwrap();
+        assert!(!trace_seg.has_record_list());
+        assert_eq!(trace_seg.record_cnt(), 0);
+        assert!(trace_seg.unpack_data().is_err());
+        assert!(trace_seg.data_samples::<i32>().is_err());
+    }
 }
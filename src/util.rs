@@ -125,6 +125,14 @@ pub fn time_to_nstime(t: &time::OffsetDateTime) -> i64 {
     .unwrap()
 }
 
+/// Converts a time string into a nanosecond timestamp.
+///
+/// Refer to `ms_timestr2nstime()` for the supported time string formats.
+pub(crate) fn timestr_to_nstime(s: &str) -> MSResult<i64> {
+    let s = CString::new(s).map_err(|e| MSError::from_str(&e.to_string()))?;
+    unsafe { check_nst(raw::ms_timestr2nstime(s.as_ptr())) }
+}
+
 /// Utility function safely converting a slice of `c_char` values into a `String`.
 pub(crate) fn to_string(buf: &[c_char]) -> String {
     let v: Vec<u8> = buf
@@ -153,9 +161,17 @@ impl NetStaLocCha {
     /// Creates a new `NSLC` structure from a [FDSN source
     /// identifier](https://docs.fdsn.org/projects/source-identifiers/) buffer slice.
     pub fn from_sid(sid: &[c_char]) -> MSResult<Self> {
+        Self::parse(&to_string(sid))
+    }
+
+    /// Creates a new `NSLC` structure from a [FDSN source
+    /// identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    pub fn parse(sid: &str) -> MSResult<Self> {
         let s0 = "           ";
         let s1 = "                               ";
-        let sid = CString::new(to_string(sid)).unwrap().into_raw();
+        let sid = CString::new(sid)
+            .map_err(|e| MSError::from_str(&e.to_string()))?
+            .into_raw();
         let xnet = CString::new(s0).unwrap().into_raw();
         let xsta = CString::new(s0).unwrap().into_raw();
         let xloc = CString::new(s0).unwrap().into_raw();
@@ -256,6 +272,41 @@ pub fn factor_multiplier_to_sample_rate(factor: i32, multiplier: i32) -> f64 {
     return samp_rate;
 }
 
+/// Converts time fields into a nanosecond timestamp without calling into libmseed.
+///
+/// Corresponds to `ms_time2nstime()` but does not validate the ranges of the fields. Returns
+/// `None` if the time cannot be represented as nanosecond timestamp.
+pub(crate) fn time_fields_to_nstime(
+    year: i32,
+    yday: i32,
+    hour: i32,
+    min: i32,
+    sec: i32,
+    nsec: u32,
+) -> Option<i64> {
+    let leap_days =
+        |y: i64| (y - 1).div_euclid(4) - (y - 1).div_euclid(100) + (y - 1).div_euclid(400);
+
+    let year = i64::from(year);
+    let days = 365 * (year - 1970) + leap_days(year) - leap_days(1970) + i64::from(yday) - 1;
+    let secs = days * 86400 + i64::from(hour) * 3600 + i64::from(min) * 60 + i64::from(sec);
+
+    secs.checked_mul(raw::NSTMODULUS as i64)?
+        .checked_add(i64::from(nsec))
+}
+
+/// Calculates the time of the sample at `offset` relative to `nst` given the sample rate
+/// `samp_rate` (in `Hz`).
+///
+/// Corresponds to `ms_sampletime()`.
+pub(crate) fn sample_time(nst: i64, offset: i64, samp_rate: f64) -> i64 {
+    if offset <= 0 || samp_rate <= 0.0 {
+        return nst;
+    }
+
+    nst + (offset as f64 / samp_rate * raw::NSTMODULUS as f64 + 0.5) as i64
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -284,4 +335,25 @@ mod tests {
         assert_eq!(factor_multiplier_to_sample_rate(-10, 1), 0.1);
         assert_eq!(factor_multiplier_to_sample_rate(-1, -10), 0.1);
     }
+
+    #[test]
+    fn test_time_fields_to_nstime() {
+        assert_eq!(time_fields_to_nstime(1970, 1, 0, 0, 0, 0), Some(0));
+        assert_eq!(
+            time_fields_to_nstime(2010, 58, 6, 50, 0, 69539000),
+            Some(1_267_253_400_069_539_000)
+        );
+        assert_eq!(
+            time_fields_to_nstime(2000, 366, 23, 59, 59, 0),
+            Some(978_307_199_000_000_000)
+        );
+        assert_eq!(
+            time_fields_to_nstime(1969, 365, 23, 59, 59, 0),
+            Some(-1_000_000_000)
+        );
+
+        // exceeds the range of nanosecond timestamps
+        assert_eq!(time_fields_to_nstime(2263, 1, 0, 0, 0, 0), None);
+        assert_eq!(time_fields_to_nstime(65535, 366, 23, 59, 60, 0), None);
+    }
 }
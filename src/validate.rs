@@ -0,0 +1,719 @@
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::record_ref::{is_valid_ms2_header, is_valid_ms3_header, MS3_FSDH_LEN};
+use crate::steim::decode_steim_frames;
+use crate::{
+    detect, raw, MSDataEncoding, MSError, MSExtraHeaders, MSRecord, MSRecordRef, MSResult,
+};
+
+/// An issue found while validating miniSEED data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// Data that cannot be identified as miniSEED.
+    NotSeed {
+        /// Length of the data in bytes.
+        len: u64,
+    },
+    /// A record that cannot be parsed.
+    Malformed {
+        /// Error message of the parser.
+        message: String,
+    },
+    /// A record length not matching the length detected.
+    RecordLength {
+        /// Record length detected from the record header.
+        detected: u64,
+        /// Actual length of the record (or data available).
+        actual: u64,
+    },
+    /// A miniSEED v3 CRC not matching the CRC calculated.
+    Crc {
+        /// CRC stored in the record header.
+        stored: u32,
+        /// CRC calculated over the record.
+        calculated: u32,
+    },
+    /// An unknown data encoding.
+    Encoding {
+        /// Error message of the parser.
+        message: String,
+    },
+    /// Invalid Steim compressed frames.
+    SteimFrame {
+        /// Error message of the decoder.
+        message: String,
+    },
+    /// A number of samples in the record header exceeding the number of encoded samples.
+    SampleCount {
+        /// Number of samples indicated by the record header.
+        header: i64,
+        /// Number of samples encoded in the data payload.
+        encoded: i64,
+    },
+    /// A reverse integration constant not matching the last decoded sample.
+    IntegrationConstant {
+        /// Reverse integration constant stored in the first Steim frame.
+        xn: i32,
+        /// Value of the last decoded sample.
+        last_sample: i32,
+    },
+    /// An invalid sample rate.
+    SampleRate {
+        /// Sample rate in Hz.
+        sample_rate: f64,
+    },
+    /// Invalid time fields.
+    Time {
+        /// Description of the issue.
+        message: String,
+    },
+    /// Extra headers which are not a well-formed JSON object.
+    ExtraHeaders {
+        /// Error message of the parser.
+        message: String,
+    },
+    /// A syntactically invalid [FDSN source
+    /// identifier](https://docs.fdsn.org/projects/source-identifiers/).
+    Sid {
+        /// The source identifier.
+        sid: String,
+        /// Description of the issue.
+        message: String,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSeed { len } => write!(f, "{} bytes not identified as miniSEED", len),
+            Self::Malformed { message } => write!(f, "malformed record: {}", message),
+            Self::RecordLength { detected, actual } => write!(
+                f,
+                "record length mismatch: {} (detected) != {} (actual)",
+                detected, actual
+            ),
+            Self::Crc { stored, calculated } => write!(
+                f,
+                "CRC mismatch: 0x{:08X} (stored) != 0x{:08X} (calculated)",
+                stored, calculated
+            ),
+            Self::Encoding { message } => write!(f, "invalid data encoding: {}", message),
+            Self::SteimFrame { message } => write!(f, "invalid Steim frame: {}", message),
+            Self::SampleCount { header, encoded } => write!(
+                f,
+                "sample count mismatch: {} (header) != {} (encoded)",
+                header, encoded
+            ),
+            Self::IntegrationConstant { xn, last_sample } => write!(
+                f,
+                "integration constant mismatch: {} (Xn) != {} (last sample)",
+                xn, last_sample
+            ),
+            Self::SampleRate { sample_rate } => write!(f, "invalid sample rate: {}", sample_rate),
+            Self::Time { message } => write!(f, "invalid time: {}", message),
+            Self::ExtraHeaders { message } => write!(f, "invalid extra headers: {}", message),
+            Self::Sid { sid, message } => {
+                write!(f, "invalid source identifier '{}': {}", sid, message)
+            }
+        }
+    }
+}
+
+/// A finding of validating miniSEED data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationFinding {
+    /// Byte offset of the offending data.
+    ///
+    /// For [`validate_buffer()`] and [`validate_file()`] the offset refers to the beginning of
+    /// the data validated, while for [`MSRecord::validate()`] it refers to the beginning of the
+    /// record. `None` if the record validated does not provide a raw record.
+    pub offset: Option<u64>,
+    /// The issue found.
+    pub issue: ValidationIssue,
+}
+
+impl ValidationFinding {
+    fn new(offset: Option<u64>, issue: ValidationIssue) -> Self {
+        Self { offset, issue }
+    }
+}
+
+impl fmt::Display for ValidationFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => write!(f, "byte {}: {}", offset, self.issue),
+            None => write!(f, "{}", self.issue),
+        }
+    }
+}
+
+impl MSRecord {
+    /// Validates the integrity of the record.
+    ///
+    /// If the record provides a raw record (see [`MSRecord::raw()`]), the raw record is subject
+    /// to the checks described in [`validate_buffer()`]. Otherwise, the source identifier, sample rate,
+    /// start time and extra headers of the record are validated. An empty list is returned if no
+    /// issues were found.
+    pub fn validate(&self) -> Vec<ValidationFinding> {
+        let mut rv = vec![];
+
+        if let Some(raw) = self.raw() {
+            let detected = detect(raw).ok().and_then(|det| det.rec_len);
+            validate_record(raw, 0, detected, &mut rv);
+            return rv;
+        }
+
+        let sid = self.sid_lossy();
+        if let Err(message) = check_sid(&sid) {
+            rv.push(ValidationFinding::new(
+                None,
+                ValidationIssue::Sid { sid, message },
+            ));
+        }
+
+        if let Some(issue) = check_sample_rate(
+            self.sample_rate_hz(),
+            self.sample_cnt(),
+            self.encoding().ok(),
+        ) {
+            rv.push(ValidationFinding::new(None, issue));
+        }
+
+        if let Err(e) = self.start_time() {
+            rv.push(ValidationFinding::new(
+                None,
+                ValidationIssue::Time {
+                    message: e.to_string(),
+                },
+            ));
+        }
+
+        if let Some(Err(e)) = self.extra_headers().map(MSExtraHeaders::from_slice) {
+            rv.push(ValidationFinding::new(
+                None,
+                ValidationIssue::ExtraHeaders {
+                    message: e.to_string(),
+                },
+            ));
+        }
+
+        rv
+    }
+}
+
+/// Validates the integrity of the miniSEED records in `buf`.
+///
+/// For each record the following is checked:
+///
+/// - the record length versus the record length detected (see [`detect()`])
+/// - the CRC (miniSEED v3, only)
+/// - the data encoding and, for Steim compressed data, the frames, the integration constants
+///   against the last decoded sample and the sample count versus the number of encoded samples
+/// - the sample rate and the time fields
+/// - the extra headers (miniSEED v3, only) being well-formed JSON
+/// - the [FDSN source identifier](https://docs.fdsn.org/projects/source-identifiers/) syntax
+///
+/// Data that cannot be identified as miniSEED is reported and skipped. An empty list is returned
+/// if no issues were found.
+pub fn validate_buffer(buf: &[u8]) -> Vec<ValidationFinding> {
+    let mut rv = vec![];
+
+    let mut pos = 0;
+    while pos < buf.len() {
+        let avail = buf.len() - pos;
+        let det = match detect(&buf[pos..]) {
+            Ok(det) if det.format_version != 0 => det,
+            _ => {
+                // skip until the next record header
+                let start = pos;
+                pos += 1;
+                while pos < buf.len()
+                    && !is_valid_ms3_header(&buf[pos..])
+                    && !is_valid_ms2_header(&buf[pos..])
+                {
+                    pos += 1;
+                }
+
+                rv.push(ValidationFinding::new(
+                    Some(start as u64),
+                    ValidationIssue::NotSeed {
+                        len: (pos - start) as u64,
+                    },
+                ));
+                continue;
+            }
+        };
+
+        match det.rec_len {
+            Some(rec_len) if rec_len as usize > avail => {
+                rv.push(ValidationFinding::new(
+                    Some(pos as u64),
+                    ValidationIssue::RecordLength {
+                        detected: rec_len,
+                        actual: avail as u64,
+                    },
+                ));
+                break;
+            }
+            _ => {}
+        }
+
+        pos += validate_record(&buf[pos..], pos as u64, det.rec_len, &mut rv);
+    }
+
+    rv
+}
+
+/// Validates the integrity of the miniSEED records of the file at `path`.
+///
+/// See [`validate_buffer()`] for details. Returns an error if the file cannot be read.
+pub fn validate_file<P: AsRef<Path>>(path: P) -> MSResult<Vec<ValidationFinding>> {
+    let file = File::open(path).map_err(|e| MSError::from_str(&e.to_string()))?;
+    if file
+        .metadata()
+        .map_err(|e| MSError::from_str(&e.to_string()))?
+        .len()
+        == 0
+    {
+        return Ok(vec![]);
+    }
+
+    // SAFETY: the file must not be modified while mapped
+    let mmap = unsafe { Mmap::map(&file) }.map_err(|e| MSError::from_str(&e.to_string()))?;
+
+    Ok(validate_buffer(&mmap))
+}
+
+/// Validates the record at the beginning of `buf` and returns the number of bytes consumed.
+///
+/// `base` is the offset of `buf` used for reporting.
+fn validate_record(
+    buf: &[u8],
+    base: u64,
+    detected: Option<u64>,
+    findings: &mut Vec<ValidationFinding>,
+) -> usize {
+    let mut push = |offset: usize, issue| {
+        findings.push(ValidationFinding::new(Some(base + offset as u64), issue))
+    };
+
+    let rec = match MSRecordRef::parse(buf) {
+        Ok(rec) => rec,
+        Err(e) => {
+            push(
+                0,
+                ValidationIssue::Malformed {
+                    message: e.to_string(),
+                },
+            );
+            return detected
+                .map_or(buf.len(), |rec_len| rec_len as usize)
+                .max(1);
+        }
+    };
+
+    let v3 = rec.format_version() == 3;
+
+    if let Some(detected) = detected {
+        if detected != rec.rec_len() as u64 {
+            push(
+                0,
+                ValidationIssue::RecordLength {
+                    detected,
+                    actual: rec.rec_len() as u64,
+                },
+            );
+        }
+    }
+
+    if v3 {
+        let stored = rec.crc();
+        let calculated = crc(rec.raw());
+        if stored != calculated {
+            push(28, ValidationIssue::Crc { stored, calculated });
+        }
+    }
+
+    let (sid_offset, sample_cnt_offset, sample_rate_offset, time_offset) = if v3 {
+        (MS3_FSDH_LEN, 24, 16, 4)
+    } else {
+        (8, 30, 32, 20)
+    };
+
+    match rec.sid() {
+        Ok(sid) => {
+            if let Err(message) = check_sid(&sid) {
+                push(
+                    sid_offset,
+                    ValidationIssue::Sid {
+                        sid: sid.into_owned(),
+                        message,
+                    },
+                );
+            }
+        }
+        Err(e) => {
+            let sid = if v3 {
+                &rec.raw()[MS3_FSDH_LEN..MS3_FSDH_LEN + rec.raw()[33] as usize]
+            } else {
+                &rec.raw()[8..20]
+            };
+            push(
+                sid_offset,
+                ValidationIssue::Sid {
+                    sid: String::from_utf8_lossy(sid).into_owned(),
+                    message: e.to_string(),
+                },
+            )
+        }
+    }
+
+    if let Some(message) = check_time_fields(&rec) {
+        push(time_offset, ValidationIssue::Time { message });
+    }
+
+    // miniSEED v2 records lacking blockette 1000 do not indicate the data encoding
+    let encoding = match rec.encoding() {
+        Ok(encoding) => Some(encoding),
+        Err(e) => {
+            if v3 || rec.has_b1000() {
+                push(
+                    if v3 { 15 } else { rec.data_offset() },
+                    ValidationIssue::Encoding {
+                        message: e.to_string(),
+                    },
+                );
+            }
+            None
+        }
+    };
+
+    if let Some(issue) = check_sample_rate(rec.sample_rate_hz(), rec.sample_cnt(), encoding) {
+        push(sample_rate_offset, issue);
+    }
+
+    let steim_version = match encoding {
+        Some(MSDataEncoding::Steim1) => Some(1),
+        Some(MSDataEncoding::Steim2) => Some(2),
+        _ => None,
+    };
+    if let Some(version) = steim_version {
+        let data_offset = rec.data_offset();
+        let header = rec.sample_cnt();
+        // trailing frames or differences beyond the number of samples are padding
+        match decode_steim_frames(
+            rec.data_payload(),
+            version,
+            header.max(0) as usize,
+            rec.is_data_big_endian(),
+        ) {
+            Ok(data) => {
+                let encoded = data.samples.len() as i64;
+                if encoded < header {
+                    push(
+                        sample_cnt_offset,
+                        ValidationIssue::SampleCount { header, encoded },
+                    );
+                }
+
+                if header > 0 && encoded == header {
+                    let last_sample = data.samples[header as usize - 1];
+                    if last_sample != data.xn {
+                        push(
+                            data_offset + 8,
+                            ValidationIssue::IntegrationConstant {
+                                xn: data.xn,
+                                last_sample,
+                            },
+                        );
+                    }
+                }
+            }
+            Err(e) => push(
+                data_offset,
+                ValidationIssue::SteimFrame {
+                    message: e.to_string(),
+                },
+            ),
+        }
+    }
+
+    if let Some(Err(e)) = rec.extra_headers().map(MSExtraHeaders::from_slice) {
+        let sid_len = rec.raw()[33] as usize;
+        push(
+            MS3_FSDH_LEN + sid_len,
+            ValidationIssue::ExtraHeaders {
+                message: e.to_string(),
+            },
+        );
+    }
+
+    rec.rec_len()
+}
+
+/// Calculates the CRC-32C of the miniSEED v3 record `rec`.
+pub(crate) fn crc(rec: &[u8]) -> u32 {
+    // the CRC is calculated with the CRC field set to zero
+    let mut buf = rec.to_vec();
+    buf[28..32].fill(0);
+
+    unsafe { raw::ms_crc32c(buf.as_ptr(), buf.len() as _, 0) }
+}
+
+/// Checks the time fields of `rec` and returns a description of the issue, if any.
+fn check_time_fields(rec: &MSRecordRef) -> Option<String> {
+    let (year, yday, fract, max_fract) = match rec.format_version() {
+        3 => (rec.u16_at(8), rec.u16_at(10), rec.u32_at(4), 999_999_999),
+        _ => (rec.u16_at(20), rec.u16_at(22), rec.u16_at(28).into(), 9_999),
+    };
+
+    if !(1900..=2100).contains(&year) {
+        return Some(format!("implausible year: {}", year));
+    }
+
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    if yday == 0 || yday > 365 + u16::from(leap) {
+        return Some(format!("invalid day of year: {}", yday));
+    }
+
+    if fract > max_fract {
+        return Some(format!("invalid fractional seconds: {}", fract));
+    }
+
+    None
+}
+
+/// Checks the sample rate of a record and returns the issue, if any.
+fn check_sample_rate(
+    sample_rate: f64,
+    sample_cnt: i64,
+    encoding: Option<MSDataEncoding>,
+) -> Option<ValidationIssue> {
+    let valid = if !sample_rate.is_finite() || sample_rate < 0.0 {
+        false
+    } else {
+        // records containing data samples (other than text) require a sample rate
+        sample_rate > 0.0 || sample_cnt == 0 || encoding == Some(MSDataEncoding::Text)
+    };
+
+    (!valid).then_some(ValidationIssue::SampleRate { sample_rate })
+}
+
+/// Checks the syntax of the [FDSN source
+/// identifier](https://docs.fdsn.org/projects/source-identifiers/) `sid`.
+fn check_sid(sid: &str) -> Result<(), String> {
+    if sid.len() >= raw::LM_SIDLEN as usize {
+        return Err(format!("too long (> {} bytes)", raw::LM_SIDLEN - 1));
+    }
+
+    let codes = sid
+        .strip_prefix("FDSN:")
+        .ok_or_else(|| "missing 'FDSN:' prefix".to_string())?;
+    let codes: Vec<&str> = codes.split('_').collect();
+    if codes.len() != 6 {
+        return Err(format!(
+            "expected 6 codes (NET_STA_LOC_BAND_SOURCE_SUBSOURCE), found {}",
+            codes.len()
+        ));
+    }
+
+    let is_code_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit();
+    let check_code = |name: &str, code: &str, allow_empty: bool, allow_dash: bool| {
+        if code.is_empty() && !allow_empty {
+            return Err(format!("empty {} code", name));
+        }
+        if code.len() > 8 {
+            return Err(format!("{} code too long: '{}'", name, code));
+        }
+        if !code
+            .chars()
+            .all(|c| is_code_char(c) || (allow_dash && c == '-'))
+        {
+            return Err(format!("invalid characters in {} code: '{}'", name, code));
+        }
+        Ok(())
+    };
+
+    check_code("network", codes[0], false, false)?;
+    check_code("station", codes[1], false, true)?;
+    check_code("location", codes[2], true, true)?;
+    check_code("band", codes[3], true, false)?;
+    check_code("source", codes[4], false, false)?;
+    check_code("subsource", codes[5], true, false)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+
+    use crate::steim::decode_steim2;
+    use crate::{test, MSControlFlags};
+
+    #[test]
+    fn test_check_sid() {
+        assert!(check_sid("FDSN:IU_COLA_00_L_H_1").is_ok());
+        assert!(check_sid("FDSN:XX_TEST__L_H_Z").is_ok());
+        assert!(check_sid("FDSN:XX_TE-ST_--_L_H_Z").is_ok());
+        assert!(check_sid("FDSN:XX_TEST__X_HRV_Z").is_ok());
+        assert!(check_sid("IU_COLA_00_L_H_1").is_err());
+        assert!(check_sid("FDSN:IU_COLA_00_LH1").is_err());
+        assert!(check_sid("FDSN:IU__00_L_H_1").is_err());
+        assert!(check_sid("FDSN:iu_COLA_00_L_H_1").is_err());
+        assert!(check_sid("FDSN:IU_COLA_00_L__1").is_err());
+        assert!(check_sid("FDSN:IUIUIUIUI_COLA_00_L_H_1").is_err());
+    }
+
+    #[test]
+    fn test_validate_valid_files() {
+        let test_data = vec![
+            "reference-testdata-steim2.mseed2",
+            "reference-testdata-steim2.mseed3",
+            "testdata-3channel-signal.mseed2",
+            "testdata-3channel-signal.mseed3",
+            "testdata-oneseries-mixedlengths-mixedorder.mseed3",
+        ];
+
+        for f in &test_data {
+            let mut p = test::test_data_base_dir();
+            assert!(p.is_dir());
+            p.push(f);
+
+            assert_eq!(validate_file(&p).unwrap(), vec![], "{}", f);
+        }
+    }
+
+    #[test]
+    fn test_validate_corrupt() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let rec_len = MSRecordRef::parse(&buf).unwrap().rec_len();
+
+        // garbage between records
+        let mut corrupt = buf[..rec_len].to_vec();
+        corrupt.extend_from_slice(b"garbage");
+        corrupt.extend_from_slice(&buf[rec_len..2 * rec_len]);
+        let findings = validate_buffer(&corrupt);
+        assert_eq!(
+            findings,
+            vec![ValidationFinding::new(
+                Some(rec_len as u64),
+                ValidationIssue::NotSeed { len: 7 }
+            )]
+        );
+
+        // truncated record
+        let findings = validate_buffer(&buf[..rec_len - 10]);
+        assert_eq!(
+            findings,
+            vec![ValidationFinding::new(
+                Some(0),
+                ValidationIssue::RecordLength {
+                    detected: rec_len as u64,
+                    actual: rec_len as u64 - 10,
+                }
+            )]
+        );
+
+        // corrupt sample rate, CRC and data payload
+        let mut corrupt = buf[..rec_len].to_vec();
+        corrupt[16..24].copy_from_slice(&f64::NAN.to_le_bytes());
+        let rec = MSRecordRef::parse(&corrupt).unwrap();
+        let data_offset = rec.data_offset();
+        // reverse integration constant
+        corrupt[data_offset + 11] ^= 0x01;
+        let findings = validate_buffer(&corrupt);
+        let issues: Vec<&ValidationIssue> = findings.iter().map(|f| &f.issue).collect();
+        assert_eq!(issues.len(), 3);
+        assert!(matches!(issues[0], ValidationIssue::Crc { .. }));
+        assert!(matches!(issues[1], ValidationIssue::SampleRate { .. }));
+        assert!(matches!(
+            issues[2],
+            ValidationIssue::IntegrationConstant {
+                last_sample: -496168,
+                ..
+            }
+        ));
+        assert_eq!(findings[2].offset, Some(data_offset as u64 + 8));
+
+        // malformed extra headers
+        let mut corrupt = buf[..rec_len].to_vec();
+        let offset = MS3_FSDH_LEN + corrupt[33] as usize;
+        corrupt[offset] = b'[';
+        let findings = validate_buffer(&corrupt);
+        assert_eq!(findings.len(), 2);
+        assert!(matches!(findings[0].issue, ValidationIssue::Crc { .. }));
+        assert!(matches!(
+            findings[1].issue,
+            ValidationIssue::ExtraHeaders { .. }
+        ));
+        assert_eq!(findings[1].offset, Some(offset as u64));
+    }
+
+    #[test]
+    fn test_validate_padded() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let rec = MSRecordRef::parse(&buf).unwrap();
+        let (rec_len, data_offset) = (rec.rec_len(), rec.data_offset());
+        let sample_cnt = rec.sample_cnt() as usize;
+        let data = decode_steim2(rec.data_payload(), sample_cnt, true).unwrap();
+
+        // trailing differences beyond the sample count are padding
+        let mut padded = buf[..rec_len].to_vec();
+        let sample_cnt = sample_cnt - 10;
+        padded[22..26].copy_from_slice(&(sample_cnt as u32).to_le_bytes());
+        padded[data_offset + 8..data_offset + 12]
+            .copy_from_slice(&data.samples[sample_cnt - 1].to_be_bytes());
+        let padded_crc = crc(&padded);
+        padded[28..32].copy_from_slice(&padded_crc.to_le_bytes());
+        assert_eq!(validate_buffer(&padded), vec![]);
+
+        // too few samples encoded
+        let mut short = padded.clone();
+        short[22..26].copy_from_slice(&(rec.sample_cnt() as u32 + 1000).to_le_bytes());
+        let short_crc = crc(&short);
+        short[28..32].copy_from_slice(&short_crc.to_le_bytes());
+        let findings = validate_buffer(&short);
+        assert_eq!(findings.len(), 1);
+        assert!(matches!(
+            findings[0].issue,
+            ValidationIssue::SampleCount { .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_record() {
+        let mut p = test::test_data_base_dir();
+        assert!(p.is_dir());
+        p.push("testdata-3channel-signal.mseed3");
+
+        let buf = fs::read(p).unwrap();
+        let msr = MSRecord::parse(&buf, MSControlFlags::empty()).unwrap();
+        assert_eq!(msr.validate(), vec![]);
+
+        let mut corrupt = buf.clone();
+        corrupt[28] ^= 0xff;
+        let msr = MSRecord::parse(&corrupt, MSControlFlags::empty()).unwrap();
+        let findings = msr.validate();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].offset, Some(28));
+        assert!(matches!(findings[0].issue, ValidationIssue::Crc { .. }));
+    }
+}